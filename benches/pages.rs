@@ -0,0 +1,38 @@
+//! Request latency of the main rendering paths, measured against the real binary:
+//! `cargo bench --bench pages`
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+#[path = "../tests/support/mod.rs"]
+mod support;
+
+/// Routes grouped by the work they mostly do. The post pages look up the index on every request, the card
+/// fragment renders a template on every request, and the plain page runs the markdown through pulldown-cmark.
+const ROUTES: [(&str, &str); 5] = [
+    ("home page", "/"),
+    ("card fragment", "/fragment/card/test"),
+    ("post page", "/post/test"),
+    ("plain post", "/post/test/plain"),
+    ("missing post", "/post/does-not-exist"),
+];
+
+fn pages(c: &mut Criterion) {
+    let server = support::Server::start();
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let client = reqwest::Client::new();
+
+    let mut group = c.benchmark_group("pages");
+    for (name, path) in ROUTES {
+        let url = server.url(path);
+        group.bench_function(name, |b| {
+            b.to_async(&runtime).iter(|| async {
+                let response = client.get(&url).send().await.unwrap();
+                response.bytes().await.unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pages);
+criterion_main!(benches);
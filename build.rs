@@ -0,0 +1,94 @@
+//! Compiles the third-party client libraries into the binary. Each copy is checked against the hash pinned below
+//! before it's embedded, so a changed upstream file fails the build instead of being served.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use base64::Engine;
+use sha2::{Digest, Sha256, Sha384};
+
+/// Every library pages load, by the name it's served under, the upstream URL it's fetched from and the subresource
+/// integrity hash the file must have. Libraries without a pinned hash are never embedded and load from upstream.
+const LIBRARIES: [(&str, &str, Option<&str>); 9] = [
+    ("bootstrap.min.css", "https://cdn.jsdelivr.net/npm/bootstrap@5.3.0/dist/css/bootstrap.min.css", Some("sha384-9ndCyUaIbzAi2FUVXJi0CjmCapSmO7SnpJef0486qhLnuZ2cdeRhO02iuK6FUUVM")),
+    ("bootstrap.bundle.min.js", "https://cdn.jsdelivr.net/npm/bootstrap@5.3.0/dist/js/bootstrap.bundle.min.js", Some("sha384-geWF76RCwLtnZ8qwWowPQNguL3RmwHVBC9FhGdlKrxdiJJigb/j/68SIy3Te4Bkz")),
+    ("unpoly.min.css", "https://cdn.jsdelivr.net/npm/unpoly@3.9.3/unpoly.min.css", None),
+    ("unpoly.min.js", "https://cdn.jsdelivr.net/npm/unpoly@3.9.3/unpoly.min.js", None),
+    ("unpoly-bootstrap5.min.css", "https://cdn.jsdelivr.net/npm/unpoly@3.9.3/unpoly-bootstrap5.min.css", None),
+    ("unpoly-bootstrap5.min.js", "https://cdn.jsdelivr.net/npm/unpoly@3.9.3/unpoly-bootstrap5.min.js", None),
+    ("htmx.min.js", "https://cdn.jsdelivr.net/npm/htmx.org@2.0.4/dist/htmx.min.js", Some("sha384-HGfztofotfshcF7+8n44JQL2oJmowVChPTg48S+jvZoztPfvwD79OC/LTtG6dMp+")),
+    ("jquery.min.js", "https://code.jquery.com/jquery-3.5.1.min.js", Some("sha256-9/aliU8dGd2tb6OSsuzixeV4y/faTqgFtohetphbbj0=")),
+    ("markdown-tag.js", "https://cdn.jsdelivr.net/gh/MarketingPipeline/Markdown-Tag/markdown-tag.js", None),
+];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=CADEN_BLOG_VENDOR_DIR");
+    println!("cargo:rerun-if-env-changed=CADEN_BLOG_VENDOR_OFFLINE");
+
+    let out = PathBuf::from(std::env::var("OUT_DIR").expect("cargo sets OUT_DIR"));
+    let dir = out.join("vendor");
+    std::fs::create_dir_all(&dir).expect("couldn't create the vendor build directory");
+    // Copies fetched ahead of time, for building without network access
+    let local = std::env::var_os("CADEN_BLOG_VENDOR_DIR").map(PathBuf::from);
+    if let Some(local) = &local {
+        println!("cargo:rerun-if-changed={}", local.display());
+    }
+    let offline = std::env::var_os("CADEN_BLOG_VENDOR_OFFLINE").is_some();
+
+    let mut table = String::from("/// Generated by build.rs from its pinned library list\npub const LIBRARIES: [Library; 9] = [\n");
+    let mut missing = Vec::new();
+    for (name, url, integrity) in LIBRARIES {
+        let embedded = integrity.and_then(|integrity| {
+            let path = dir.join(name);
+            let copy = local.as_deref().map(|local| local.join(name)).filter(|copy| copy.is_file());
+            let bytes = match (copy, std::fs::read(&path)) {
+                (Some(copy), _) => std::fs::read(copy).ok(),
+                // Fetched by an earlier build
+                (None, Ok(cached)) if matches_pin(&cached, integrity) => Some(cached),
+                (None, _) if offline => None,
+                (None, _) => download(url, &path),
+            }?;
+            if !matches_pin(&bytes, integrity) {
+                let _ = std::fs::remove_file(&path);
+                panic!("{} from {} doesn't match its pinned hash {}", name, url, integrity);
+            }
+            std::fs::write(&path, &bytes).expect("couldn't write a vendored library");
+            Some((path, hex::encode(&Sha384::digest(&bytes)[..6])))
+        });
+        if embedded.is_none() {
+            missing.push(name);
+        }
+        let embedded = match embedded {
+            Some((path, version)) => format!("Some(Embedded {{ bytes: include_bytes!({:?}), version: {:?} }})", path, version),
+            None => "None".to_string(),
+        };
+        table.push_str(&format!("    Library {{ name: {:?}, upstream: {:?}, integrity: {:?}, embedded: {} }},\n", name, url, integrity, embedded));
+    }
+    table.push_str("];\n");
+    std::fs::write(out.join("vendored.rs"), table).expect("couldn't write the vendored library table");
+
+    if !missing.is_empty() {
+        println!("cargo:warning=Not embedding {}; pages will load them from upstream CDNs", missing.join(", "));
+    }
+}
+
+/// Whether `bytes` hash to the pinned `sha256-` or `sha384-` integrity value
+fn matches_pin(bytes: &[u8], integrity: &str) -> bool {
+    let base64 = base64::engine::general_purpose::STANDARD;
+    match integrity.split_once('-') {
+        Some(("sha256", hash)) => base64.encode(Sha256::digest(bytes)) == hash,
+        Some(("sha384", hash)) => base64.encode(Sha384::digest(bytes)) == hash,
+        _ => panic!("unsupported integrity value {}", integrity),
+    }
+}
+
+/// Fetches `url` with curl, or `None` when it can't be reached
+fn download(url: &str, path: &Path) -> Option<Vec<u8>> {
+    let status = Command::new("curl").args(["--fail", "--silent", "--location", "--max-time", "60", "--output"]).arg(path).arg(url).status().ok()?;
+    if !status.success() {
+        let _ = std::fs::remove_file(path);
+        return None;
+    }
+    std::fs::read(path).ok()
+}
@@ -0,0 +1,87 @@
+pub mod geoip;
+
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::cidr::{self, Cidr};
+use crate::extract::client::Client;
+use crate::state::AppState;
+
+/// Who may reach the site, from the `CADEN_BLOG_ALLOW_IPS`, `CADEN_BLOG_BLOCK_IPS` and `CADEN_BLOG_BLOCK_COUNTRIES`
+/// environment variables. Countries are looked up in the MaxMind database at `CADEN_BLOG_GEOIP_DB`.
+#[derive(Default)]
+pub struct AccessRules {
+    /// Let through even when a block rule matches, for your own machines and monitoring
+    allow: Vec<Cidr>,
+    block: Vec<Cidr>,
+    /// ISO 3166 country codes, uppercase
+    blocked_countries: Vec<String>,
+    geoip: Option<geoip::Reader>,
+}
+
+impl AccessRules {
+    /// Fails when the GeoIP database can't be read, or when countries are blocked without one to look them up in
+    pub fn from_env() -> Result<AccessRules, String> {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        let blocked_countries: Vec<String> = var("CADEN_BLOG_BLOCK_COUNTRIES")
+            .map(|list| list.split(',').map(|code| code.trim().to_uppercase()).filter(|code| !code.is_empty()).collect())
+            .unwrap_or_default();
+        let geoip = match var("CADEN_BLOG_GEOIP_DB") {
+            Some(path) => Some(geoip::Reader::open(&PathBuf::from(path))?),
+            None if !blocked_countries.is_empty() => return Err("CADEN_BLOG_BLOCK_COUNTRIES needs a GeoIP database in CADEN_BLOG_GEOIP_DB".to_string()),
+            None => None,
+        };
+
+        Ok(AccessRules {
+            allow: var("CADEN_BLOG_ALLOW_IPS").map(|list| cidr::parse_list(&list)).unwrap_or_default(),
+            block: var("CADEN_BLOG_BLOCK_IPS").map(|list| cidr::parse_list(&list)).unwrap_or_default(),
+            blocked_countries,
+            geoip,
+        })
+    }
+
+    /// Whether a client may be served. Clients without an address, only seen on a Unix socket with no proxy
+    /// headers, are always let through.
+    pub fn allows(&self, ip: Option<IpAddr>) -> bool {
+        let Some(ip) = ip else { return true };
+        if self.allow.iter().any(|block| block.contains(ip)) {
+            return true;
+        }
+        if self.block.iter().any(|block| block.contains(ip)) {
+            return false;
+        }
+        match (&self.geoip, self.blocked_countries.is_empty()) {
+            (Some(geoip), false) => geoip.country(ip).is_none_or(|country| !self.blocked_countries.contains(&country)),
+            _ => true,
+        }
+    }
+}
+
+/// Middleware turning away blocked clients before any handler runs
+pub async fn enforce(State(state): State<AppState>, client: Client, request: Request, next: Next) -> Response {
+    if state.access.allows(client.ip) {
+        next.run(request).await
+    } else {
+        StatusCode::FORBIDDEN.into_response()
+    }
+}
+
+#[test]
+fn allow_list_wins_over_block_rules() {
+    let rules = AccessRules {
+        allow: cidr::parse_list("203.0.113.7"),
+        block: cidr::parse_list("203.0.113.0/24, 2001:db8::/32"),
+        ..AccessRules::default()
+    };
+    let ip = |ip: &str| Some(ip.parse().unwrap());
+    assert!(rules.allows(ip("203.0.113.7")));
+    assert!(!rules.allows(ip("203.0.113.8")));
+    assert!(!rules.allows(ip("2001:db8::1")));
+    assert!(rules.allows(ip("198.51.100.1")));
+    assert!(rules.allows(None));
+}
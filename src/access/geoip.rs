@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// Marks the start of the metadata at the end of every MaxMind DB file
+const METADATA_MARKER: &[u8] = b"\xab\xcd\xefMaxMind.com";
+
+/// Zero bytes between the search tree and the data section
+const DATA_SEPARATOR: usize = 16;
+
+/// Bits walked from the root of an IPv6 tree before the IPv4 part of an address, which is stored under `::/96`
+const IPV4_IN_IPV6_BITS: usize = 96;
+
+/// A MaxMind DB file (`.mmdb`) like GeoLite2-Country, read into memory and queried for the country of an address.
+/// Only what a country lookup needs is decoded, following the format spec at https://maxmind.github.io/MaxMind-DB/.
+pub struct Reader {
+    bytes: Vec<u8>,
+    node_count: usize,
+    record_size: usize,
+    ip_version: u64,
+}
+
+/// The parts of a data section value a lookup can use, everything else is skipped over
+enum Value {
+    String(String),
+    Uint(u64),
+    Map(HashMap<String, Value>),
+    Other,
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(map) => map.get(key),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_uint(&self) -> Option<u64> {
+        match self {
+            Value::Uint(n) => Some(*n),
+            _ => None,
+        }
+    }
+}
+
+impl Reader {
+    pub fn open(path: &std::path::Path) -> Result<Reader, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("couldn't read the GeoIP database {}: {}", path.display(), e))?;
+        Reader::from_bytes(bytes).map_err(|e| format!("{} isn't a usable GeoIP database: {}", path.display(), e))
+    }
+
+    fn from_bytes(bytes: Vec<u8>) -> Result<Reader, String> {
+        let start = bytes
+            .windows(METADATA_MARKER.len())
+            .rposition(|window| window == METADATA_MARKER)
+            .ok_or("no MaxMind DB metadata")?
+            + METADATA_MARKER.len();
+        let (metadata, _) = decode(&bytes[start..], 0).ok_or("unreadable metadata")?;
+        let field = |name: &str| metadata.get(name).and_then(Value::as_uint).ok_or(format!("metadata is missing {}", name));
+
+        let (node_count, record_size, ip_version) = (field("node_count")? as usize, field("record_size")? as usize, field("ip_version")?);
+        if ![24, 28, 32].contains(&record_size) {
+            return Err(format!("unsupported record size {}", record_size));
+        }
+        let reader = Reader { bytes, node_count, record_size, ip_version };
+        if reader.data_start() > start {
+            return Err("the search tree runs past the end of the file".to_string());
+        }
+        Ok(reader)
+    }
+
+    fn tree_size(&self) -> usize {
+        self.node_count * self.record_size / 4
+    }
+
+    fn data_start(&self) -> usize {
+        self.tree_size() + DATA_SEPARATOR
+    }
+
+    /// The left (`bit` 0) or right (`bit` 1) record of a search tree node
+    fn record(&self, node: usize, bit: u8) -> Option<usize> {
+        let bytes = self.bytes.get(node * self.record_size / 4..(node + 1) * self.record_size / 4)?;
+        let be = |bytes: &[u8]| bytes.iter().fold(0usize, |n, &byte| n << 8 | byte as usize);
+        Some(match (self.record_size, bit) {
+            (24, 0) => be(&bytes[..3]),
+            (24, _) => be(&bytes[3..]),
+            // The middle byte holds the top four bits of each record
+            (28, 0) => (bytes[3] as usize & 0xf0) << 20 | be(&bytes[..3]),
+            (28, _) => (bytes[3] as usize & 0x0f) << 24 | be(&bytes[4..]),
+            (_, 0) => be(&bytes[..4]),
+            _ => be(&bytes[4..]),
+        })
+    }
+
+    /// The data record for an address, `None` when the database has nothing on it
+    fn lookup(&self, ip: IpAddr) -> Option<Value> {
+        let (bits, skip): (Vec<u8>, usize) = match (ip.to_canonical(), self.ip_version) {
+            (IpAddr::V4(ip), 6) => (ip.octets().to_vec(), IPV4_IN_IPV6_BITS),
+            (IpAddr::V4(ip), _) => (ip.octets().to_vec(), 0),
+            (IpAddr::V6(ip), 6) => (ip.octets().to_vec(), 0),
+            (IpAddr::V6(_), _) => return None,
+        };
+
+        let mut node = 0;
+        let path = std::iter::repeat_n(0, skip).chain(bits.iter().flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1)));
+        for bit in path {
+            if node >= self.node_count {
+                break;
+            }
+            node = self.record(node, bit)?;
+        }
+        // Landing exactly on the node count means no data, anything above it points into the data section
+        if node <= self.node_count {
+            return None;
+        }
+        let offset = node - self.node_count - DATA_SEPARATOR;
+        decode(self.bytes.get(self.data_start()..)?, offset).map(|(value, _)| value)
+    }
+
+    /// The ISO 3166 code of the country an address is in, or registered to when the database doesn't place it
+    pub fn country(&self, ip: IpAddr) -> Option<String> {
+        let record = self.lookup(ip)?;
+        ["country", "registered_country"]
+            .iter()
+            .find_map(|key| record.get(key)?.get("iso_code")?.as_str())
+            .map(str::to_string)
+    }
+}
+
+/// Decodes the value at `offset` in a data section, returning it and where the next value starts
+fn decode(data: &[u8], offset: usize) -> Option<(Value, usize)> {
+    let control = *data.get(offset)?;
+    let mut next = offset + 1;
+    let mut kind = control >> 5;
+
+    if kind == 1 {
+        // Pointers pack their size and the top bits of the target into the control byte
+        let size = (control >> 3 & 0x3) as usize;
+        let bytes = data.get(next..next + size + 1)?;
+        let value = bytes.iter().fold(0usize, |n, &byte| n << 8 | byte as usize);
+        let target = match size {
+            0 => (control as usize & 0x7) << 8 | value,
+            1 => ((control as usize & 0x7) << 16 | value) + 2048,
+            2 => ((control as usize & 0x7) << 24 | value) + 526336,
+            _ => value,
+        };
+        // A pointer to a pointer isn't valid, and following one could loop forever
+        if data.get(target)? >> 5 == 1 {
+            return None;
+        }
+        let (value, _) = decode(data, target)?;
+        return Some((value, next + size + 1));
+    }
+    if kind == 0 {
+        kind = 7 + *data.get(next)?;
+        next += 1;
+    }
+
+    let mut size = (control & 0x1f) as usize;
+    if size >= 29 {
+        let extra = size - 28;
+        let bytes = data.get(next..next + extra)?;
+        let value = bytes.iter().fold(0usize, |n, &byte| n << 8 | byte as usize);
+        size = match extra {
+            1 => 29 + value,
+            2 => 285 + value,
+            _ => 65821 + value,
+        };
+        next += extra;
+    }
+
+    let uint = |bytes: &[u8]| bytes.iter().fold(0u64, |n, &byte| n << 8 | byte as u64);
+    match kind {
+        2 => Some((Value::String(String::from_utf8_lossy(data.get(next..next + size)?).into_owned()), next + size)),
+        5 | 6 | 9 => Some((Value::Uint(uint(data.get(next..next + size)?)), next + size)),
+        7 => {
+            let mut map = HashMap::new();
+            for _ in 0..size {
+                let (key, after_key) = decode(data, next)?;
+                let (value, after_value) = decode(data, after_key)?;
+                if let Value::String(key) = key {
+                    map.insert(key, value);
+                }
+                next = after_value;
+            }
+            Some((Value::Map(map), next))
+        }
+        11 => {
+            for _ in 0..size {
+                next = decode(data, next)?.1;
+            }
+            Some((Value::Other, next))
+        }
+        3 => Some((Value::Other, next + 8)),
+        15 => Some((Value::Other, next + 4)),
+        // A boolean's value is its size, and there's no payload
+        14 => Some((Value::Other, next)),
+        4 | 8 | 10 => Some((Value::Other, next + size)),
+        _ => None,
+    }
+}
+
+#[test]
+fn looks_up_countries_in_a_database() {
+    // One node: addresses starting with a 0 bit are in the US, the rest aren't in the database
+    let mut bytes = vec![0, 0, 1 + 16, 0, 0, 1];
+    bytes.extend([0; DATA_SEPARATOR]);
+    bytes.extend(b"\xe1\x47country\xe1\x48iso_code\x42US");
+    bytes.extend(METADATA_MARKER);
+    bytes.extend(b"\xe3\x4anode_count\xc1\x01\x4brecord_size\xa1\x18\x4aip_version\xa1\x04");
+
+    let reader = Reader::from_bytes(bytes).unwrap();
+    assert_eq!(reader.country("1.2.3.4".parse().unwrap()).as_deref(), Some("US"));
+    assert_eq!(reader.country("::ffff:100.0.0.1".parse().unwrap()).as_deref(), Some("US"));
+    assert_eq!(reader.country("200.0.0.1".parse().unwrap()), None);
+    assert_eq!(reader.country("2001:db8::1".parse().unwrap()), None);
+
+    assert!(Reader::from_bytes(b"not a database".to_vec()).is_err());
+}
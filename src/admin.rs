@@ -0,0 +1,220 @@
+use axum::extract::{FromRequestParts, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, StatusCode};
+use axum::Json;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::assets::MissingAssets;
+use crate::config::Config;
+use crate::extract::client::Client;
+use crate::extract::preview::{self, PREVIEW_QUERY};
+use crate::feeds::Feeds;
+use crate::state::AppState;
+use crate::store::{FileCache, PageCache};
+
+/// Prefix of every admin route, which stay reachable in maintenance mode
+pub const ADMIN_PREFIX: &str = "/admin";
+
+/// For load balancers and uptime checks, also left alone by maintenance mode
+pub const HEALTH_PATH: &str = "/health";
+
+/// Bearer token the admin routes ask for, from `CADEN_BLOG_ADMIN_TOKEN`. Without one the admin routes are off.
+pub fn token_from_env() -> Option<String> {
+    std::env::var("CADEN_BLOG_ADMIN_TOKEN").ok().map(|token| token.trim().to_string()).filter(|token| !token.is_empty())
+}
+
+/// Proof that a request carries the admin token in `Authorization: Bearer <token>`
+pub struct Admin;
+
+impl Admin {
+    fn check(expected: Option<&str>, authorization: Option<&str>) -> Result<Admin, StatusCode> {
+        // Pretend the routes don't exist rather than advertise a login nobody can use
+        let expected = expected.ok_or(StatusCode::NOT_FOUND)?;
+        let given = authorization.and_then(|value| value.strip_prefix("Bearer ")).ok_or(StatusCode::UNAUTHORIZED)?;
+        // Comparing digests keeps the time taken from telling how much of the token matched
+        if Sha256::digest(given.trim()) == Sha256::digest(expected) {
+            Ok(Admin)
+        } else {
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
+
+impl Admin {
+    /// Whether the request carries the admin token, for pages that show more to the admin rather than refusing
+    /// everyone else
+    pub fn authorized(config: &Config, headers: &HeaderMap) -> bool {
+        Admin::check(config.admin_token.as_deref(), headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok())).is_ok()
+    }
+}
+
+#[async_trait::async_trait]
+impl FromRequestParts<AppState> for Admin {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        Admin::check(state.config.admin_token.as_deref(), parts.headers.get(AUTHORIZATION).and_then(|value| value.to_str().ok()))
+    }
+}
+
+pub async fn health() -> &'static str {
+    "ok"
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+}
+
+/// `GET /admin/maintenance`: whether the site is in maintenance mode
+pub async fn maintenance_status(_: Admin, State(AppState { maintenance, .. }): State<AppState>) -> Json<MaintenanceStatus> {
+    Json(MaintenanceStatus { enabled: maintenance.enabled() })
+}
+
+/// `POST /admin/maintenance` with `{"enabled": true}` or `false`: switches maintenance mode until the next restart
+pub async fn set_maintenance(_: Admin, State(AppState { maintenance, .. }): State<AppState>, Json(status): Json<MaintenanceStatus>) -> Json<MaintenanceStatus> {
+    maintenance.set(status.enabled);
+    println!("Maintenance mode {}", if status.enabled { "on" } else { "off" });
+    Json(MaintenanceStatus { enabled: maintenance.enabled() })
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CommentsStatus {
+    pub locked: bool,
+}
+
+/// `GET /admin/comments`: whether comments are locked on every post
+pub async fn comments_status(_: Admin, State(AppState { comments, .. }): State<AppState>) -> Json<CommentsStatus> {
+    Json(CommentsStatus { locked: comments.locked() })
+}
+
+/// `POST /admin/comments` with `{"locked": true}` or `false`: closes every comment form, leaving the comments
+/// there readable, or opens them again on the posts that take comments, until the next restart
+pub async fn set_comments(_: Admin, State(AppState { comments, .. }): State<AppState>, Json(status): Json<CommentsStatus>) -> Json<CommentsStatus> {
+    comments.set_locked(status.locked);
+    println!("Comments {}", if status.locked { "locked" } else { "unlocked" });
+    Json(CommentsStatus { locked: comments.locked() })
+}
+
+/// Which cache entries `POST /admin/cache/purge` drops: everything when neither is given
+#[derive(Debug, Default, Deserialize)]
+pub struct PurgeRequest {
+    /// Entries whose key starts with this
+    pub prefix: Option<String>,
+    /// The one entry with exactly this key
+    pub key: Option<String>,
+}
+
+impl PurgeRequest {
+    fn matches(&self, key: &str) -> bool {
+        match (&self.key, &self.prefix) {
+            (Some(exact), _) => key == exact,
+            (None, Some(prefix)) => key.starts_with(prefix.as_str()),
+            (None, None) => true,
+        }
+    }
+}
+
+/// How many entries were dropped from each cache
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Purged {
+    pub assets: usize,
+    /// Asset names remembered as missing
+    pub missing: usize,
+    pub pages: usize,
+    pub feeds: usize,
+}
+
+fn purge(cache: &FileCache, missing: &MissingAssets, pages: &PageCache, feeds: &Feeds, request: &PurgeRequest) -> Purged {
+    let assets = {
+        let mut cache = cache.lock().expect("cdn failed to lock the cache");
+        let before = cache.len();
+        cache.retain(|key, _| !request.matches(key));
+        before - cache.len()
+    };
+    let pages = {
+        let mut pages = pages.write().expect("failed to lock the page cache");
+        let before = pages.len();
+        pages.retain(|key, _| !request.matches(key));
+        before - pages.len()
+    };
+    Purged { assets, missing: missing.purge(|name| request.matches(name)), pages, feeds: feeds.purge(|path| request.matches(path)) }
+}
+
+/// `POST /admin/cache/purge` with `{}`, `{"prefix": "/og/"}` or `{"key": "photo.jpg"}`: drops cached assets,
+/// rendered pages and feeds so they're read and rendered afresh, say after replacing an asset. Assets are cached
+/// under their file name, generated images under their path and feeds under theirs. The assets are hashed again
+/// so pages link the replaced ones by new names.
+pub async fn purge_cache(_: Admin, State(state): State<AppState>, Json(request): Json<PurgeRequest>) -> Result<Json<Purged>, StatusCode> {
+    if request.key.is_some() && request.prefix.is_some() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let purged = purge(&state.cache, &state.missing, &state.pages, &state.feeds, &request);
+    state.refresh_manifest().await;
+    state.worker.regenerate(&state.posts, &state.icons, &state.manifest);
+    println!("Purged {} cached assets, {} missing ones, {} pages and {} feeds", purged.assets, purged.missing, purged.pages, purged.feeds);
+    Ok(Json(purged))
+}
+
+/// How long a preview link works when the request doesn't say, and the longest it can
+const DEFAULT_PREVIEW_HOURS: i64 = 72;
+const MAX_PREVIEW_HOURS: i64 = 24 * 30;
+
+#[derive(Deserialize)]
+pub struct PreviewRequest {
+    pub url_name: String,
+    pub hours: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct PreviewLink {
+    pub url: String,
+    pub expires: DateTime<Utc>,
+}
+
+/// `POST /admin/preview` with `{"url_name": "my-draft", "hours": 24}`: a link that shows the post to whoever has
+/// it until it expires, even when it's private
+pub async fn create_preview(_: Admin, State(AppState { config, posts, .. }): State<AppState>, client: Client, headers: HeaderMap, Json(request): Json<PreviewRequest>) -> Result<Json<PreviewLink>, StatusCode> {
+    if !posts.read().expect("failed to lock the post index").iter().any(|post| post.url_name == request.url_name) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let expires = Utc::now() + Duration::hours(request.hours.unwrap_or(DEFAULT_PREVIEW_HOURS).clamp(1, MAX_PREVIEW_HOURS));
+    let url = format!("{}/post/{}?{}={}", crate::share::origin(&config.site_url, &headers, &client), request.url_name, PREVIEW_QUERY, preview::token(&config.cookie_key, &request.url_name, expires));
+    println!("Made a preview link for /post/{} until {}", request.url_name, expires);
+    Ok(Json(PreviewLink { url, expires }))
+}
+
+#[test]
+fn admin_routes_need_the_token() {
+    assert_eq!(Admin::check(None, Some("Bearer anything")).err(), Some(StatusCode::NOT_FOUND));
+    assert_eq!(Admin::check(Some("secret"), None).err(), Some(StatusCode::UNAUTHORIZED));
+    assert_eq!(Admin::check(Some("secret"), Some("Bearer guess")).err(), Some(StatusCode::UNAUTHORIZED));
+    assert_eq!(Admin::check(Some("secret"), Some("Basic secret")).err(), Some(StatusCode::UNAUTHORIZED));
+    assert!(Admin::check(Some("secret"), Some("Bearer secret")).is_ok());
+}
+
+#[test]
+fn purging_drops_matching_entries_from_every_cache() {
+    let (cache, missing, pages, feeds) = (FileCache::default(), MissingAssets::default(), PageCache::default(), Feeds::default());
+    let fill = || {
+        for key in ["photo.jpg", "photos/cat.jpg", "/og/hello.png"] {
+            cache.lock().unwrap().insert(key.to_string(), Vec::new());
+        }
+        pages.write().unwrap().insert("/og/page".to_string(), String::new());
+    };
+    let purge = |request: &PurgeRequest| purge(&cache, &missing, &pages, &feeds, request);
+
+    fill();
+    let request = PurgeRequest { key: Some("photo.jpg".to_string()), prefix: None };
+    missing.remember("photo.jpg");
+    assert_eq!(purge(&request), Purged { assets: 1, missing: 1, pages: 0, feeds: 0 });
+    let request = PurgeRequest { key: None, prefix: Some("/og/".to_string()) };
+    assert_eq!(purge(&request), Purged { assets: 1, missing: 0, pages: 1, feeds: 0 });
+    assert_eq!(cache.lock().unwrap().keys().collect::<Vec<_>>(), ["photos/cat.jpg"]);
+
+    fill();
+    assert_eq!(purge(&PurgeRequest::default()), Purged { assets: 3, missing: 0, pages: 1, feeds: 0 });
+}
@@ -0,0 +1,422 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use axum::body::Body;
+use axum::http::HeaderValue;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+pub mod s3;
+
+pub const DEFAULT_MAX_CACHED_SIZE: u64 = 1024 * 1024;
+
+/// Metric counting asset requests by how the cache answered: `hit`, `miss` or `negative` for a remembered 404
+pub const ASSET_CACHE_METRIC: &str = "caden_blog_asset_cache_total";
+
+/// How long a missing asset is remembered when `CADEN_BLOG_MISSING_ASSET_TTL_SECS` isn't set
+const DEFAULT_MISSING_TTL: Duration = Duration::from_secs(60);
+
+/// Missing names remembered at once, so scanning for random paths can't grow the set without bound
+const MAX_MISSING: usize = 10_000;
+
+/// A single byte range from a `Range` request header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByteRange {
+    /// `bytes=start-`
+    From(u64),
+    /// `bytes=start-end`, inclusive
+    Between(u64, u64),
+    /// `bytes=-len`, the final `len` bytes
+    Last(u64),
+}
+
+impl ByteRange {
+    /// Parses a `Range` header, returning `None` for anything but a single byte range
+    pub fn parse(header: &str) -> Option<ByteRange> {
+        let spec = header.trim().strip_prefix("bytes=")?;
+        if spec.contains(',') {
+            return None;
+        }
+
+        let (start, end) = spec.split_once('-')?;
+        match (start.trim(), end.trim()) {
+            ("", "") => None,
+            ("", len) => len.parse().ok().map(ByteRange::Last),
+            (start, "") => start.parse().ok().map(ByteRange::From),
+            (start, end) => {
+                let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+                if start > end {
+                    return None;
+                }
+                Some(ByteRange::Between(start, end))
+            }
+        }
+    }
+
+    /// Resolves the range against an asset of `size` bytes into inclusive offsets, or `None` when unsatisfiable
+    pub fn resolve(&self, size: u64) -> Option<(u64, u64)> {
+        match *self {
+            ByteRange::From(start) if start < size => Some((start, size - 1)),
+            ByteRange::Between(start, end) if start < size => Some((start, end.min(size - 1))),
+            ByteRange::Last(len) if len > 0 && size > 0 => Some((size.saturating_sub(len), size - 1)),
+            _ => None,
+        }
+    }
+
+    pub fn header_value(&self) -> String {
+        match *self {
+            ByteRange::From(start) => format!("bytes={}-", start),
+            ByteRange::Between(start, end) => format!("bytes={}-{}", start, end),
+            ByteRange::Last(len) => format!("bytes=-{}", len),
+        }
+    }
+}
+
+/// The part of an asset a ranged response covers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContentRange {
+    pub start: u64,
+    pub end: u64,
+    pub size: u64,
+}
+
+impl ContentRange {
+    pub fn header_value(&self) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, self.size)
+    }
+}
+
+/// An asset fetched from a store, ready to be written into a response
+pub struct AssetObject {
+    pub body: Body,
+    /// Length of `body`, when the store knows it up front
+    pub len: Option<u64>,
+    /// Set when only part of the asset was requested
+    pub range: Option<ContentRange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AssetError {
+    NotFound,
+    /// The requested range lies outside an asset of this many bytes
+    RangeNotSatisfiable(u64),
+}
+
+/// Backend that asset requests are served from
+#[async_trait]
+pub trait AssetStore: Send + Sync {
+    /// Opens the asset, or just the requested range of it, as a streamed body without buffering it
+    async fn stream(&self, name: &str, range: Option<ByteRange>) -> Result<AssetObject, AssetError>;
+
+    /// Whether assets from this store should be kept in the in-memory cache
+    fn cache_in_memory(&self) -> bool {
+        true
+    }
+
+    /// A time-limited URL clients can fetch the asset from directly, if the store supports it
+    fn signed_url(&self, _name: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Serves assets from a directory on the local disk
+pub struct FilesystemStore {
+    root: std::path::PathBuf,
+}
+
+impl FilesystemStore {
+    pub fn new(root: impl Into<std::path::PathBuf>) -> FilesystemStore {
+        FilesystemStore { root: root.into() }
+    }
+}
+
+#[async_trait]
+impl AssetStore for FilesystemStore {
+    async fn stream(&self, name: &str, range: Option<ByteRange>) -> Result<AssetObject, AssetError> {
+        let path = crate::paths::contained(&self.root, name).ok_or(AssetError::NotFound)?;
+        let mut file = tokio::fs::File::open(path).await.map_err(|_| AssetError::NotFound)?;
+        let metadata = file.metadata().await.map_err(|_| AssetError::NotFound)?;
+        if !metadata.is_file() {
+            return Err(AssetError::NotFound);
+        }
+        let size = metadata.len();
+
+        let Some(range) = range else {
+            return Ok(AssetObject { body: Body::from_stream(ReaderStream::new(file)), len: Some(size), range: None });
+        };
+
+        let (start, end) = range.resolve(size).ok_or(AssetError::RangeNotSatisfiable(size))?;
+        file.seek(std::io::SeekFrom::Start(start)).await.map_err(|_| AssetError::NotFound)?;
+        let len = end - start + 1;
+
+        Ok(AssetObject {
+            body: Body::from_stream(ReaderStream::new(file.take(len))),
+            len: Some(len),
+            range: Some(ContentRange { start, end, size }),
+        })
+    }
+}
+
+/// Serves the assets compiled into the binary for anything the configured store doesn't have
+pub struct WithDefaults {
+    store: std::sync::Arc<dyn AssetStore>,
+}
+
+impl WithDefaults {
+    pub fn new(store: std::sync::Arc<dyn AssetStore>) -> WithDefaults {
+        WithDefaults { store }
+    }
+}
+
+#[async_trait]
+impl AssetStore for WithDefaults {
+    async fn stream(&self, name: &str, range: Option<ByteRange>) -> Result<AssetObject, AssetError> {
+        match self.store.stream(name, range).await {
+            Err(AssetError::NotFound) => {}
+            result => return result,
+        }
+
+        let bytes = crate::defaults::embedded(&format!("assets/{}", name)).ok_or(AssetError::NotFound)?;
+        let size = bytes.len() as u64;
+        let Some(range) = range else {
+            return Ok(AssetObject { body: Body::from(bytes), len: Some(size), range: None });
+        };
+
+        let (start, end) = range.resolve(size).ok_or(AssetError::RangeNotSatisfiable(size))?;
+        Ok(AssetObject {
+            body: Body::from(bytes[start as usize..=end as usize].to_vec()),
+            len: Some(end - start + 1),
+            range: Some(ContentRange { start, end, size }),
+        })
+    }
+
+    fn cache_in_memory(&self) -> bool {
+        self.store.cache_in_memory()
+    }
+
+    fn signed_url(&self, name: &str) -> Option<String> {
+        // A signed URL for an asset only compiled in would point at nothing
+        if crate::defaults::embedded(&format!("assets/{}", name)).is_some() {
+            return None;
+        }
+        self.store.signed_url(name)
+    }
+}
+
+/// Whether a name from a URL is a plain relative path in the assets, like `posts/my-post/diagram.png`
+pub fn valid_name(name: &str) -> bool {
+    crate::paths::contained(std::path::Path::new(""), name).is_some() && !name.contains('\\')
+}
+
+/// `Cache-Control` for directories of assets that shouldn't be cached for the usual hour, longest match first
+pub fn cache_policies_from_env() -> Vec<(String, String)> {
+    parse_cache_policies(&std::env::var("CADEN_BLOG_ASSET_CACHE_CONTROL").unwrap_or_default())
+}
+
+/// Parses `CADEN_BLOG_ASSET_CACHE_CONTROL`: `directory=policy` pairs separated by semicolons, like
+/// `drafts=no-cache; posts/wip=public, max-age=600`
+fn parse_cache_policies(spec: &str) -> Vec<(String, String)> {
+    let mut policies: Vec<(String, String)> = spec
+        .split(';')
+        .filter_map(|entry| entry.split_once('='))
+        .map(|(dir, policy)| (format!("{}/", dir.trim().trim_matches('/')), policy.trim().to_string()))
+        .filter(|(dir, policy)| dir != "/" && !policy.is_empty() && HeaderValue::from_str(policy).is_ok())
+        .collect();
+    policies.sort_by_key(|(dir, _)| std::cmp::Reverse(dir.len()));
+    policies
+}
+
+fn cache_policy<'a>(policies: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    policies.iter().find(|(dir, _)| name.starts_with(dir.as_str())).map(|(_, policy)| policy.as_str())
+}
+
+/// The `Cache-Control` an asset asked for by its plain name is served with: its directory's policy, or an hour for
+/// everything else since the file can change under the same name
+pub fn cache_control<'a>(policies: &'a [(String, String)], name: &str) -> &'a str {
+    cache_policy(policies, name).unwrap_or(crate::fingerprint::UNVERSIONED)
+}
+
+/// The MIME type for an asset's file extension, for the types posts embed
+pub fn content_type(name: &str) -> Option<&'static str> {
+    let extension = name.rsplit_once('.')?.1.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        "svg" => "image/svg+xml",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "txt" => "text/plain; charset=utf-8",
+        _ => return None,
+    })
+}
+
+/// Smaller formats a photo can also be kept in, best first. `photo.avif` or `photo.webp` next to `photo.jpg` is
+/// sent instead of it to browsers that accept them.
+const VARIANTS: [(&str, &str); 2] = [("avif", "image/avif"), ("webp", "image/webp")];
+
+/// Whether an `Accept` header takes `mime`, by name rather than through a wildcard since every browser sends
+/// `image/*` whether it can show the newer formats or not
+fn accepts(accept: &str, mime: &str) -> bool {
+    accept.split(',').any(|range| {
+        let mut parts = range.split(';').map(str::trim);
+        parts.next().is_some_and(|name| name.eq_ignore_ascii_case(mime)) && !parts.any(|param| param.strip_prefix("q=").is_some_and(|q| q.parse::<f32>().is_ok_and(|q| q == 0.0)))
+    })
+}
+
+/// Whether an asset might have smaller variants, so its responses depend on `Accept`
+pub fn negotiable(name: &str) -> bool {
+    matches!(content_type(name), Some("image/jpeg" | "image/png"))
+}
+
+/// The variants of a JPEG or PNG to try for a request's `Accept` header, with their types, best first
+pub fn variants(name: &str, accept: &str) -> Vec<(String, &'static str)> {
+    let Some((stem, _)) = name.rsplit_once('.').filter(|_| negotiable(name)) else { return Vec::new() };
+    VARIANTS.iter().filter(|(_, mime)| accepts(accept, mime)).map(|(extension, mime)| (format!("{}.{}", stem, extension), *mime)).collect()
+}
+
+/// The size of an asset in the local assets directory, which is all there is to go on without asking the store
+pub fn local_size(content: &std::path::Path, name: &str) -> Option<u64> {
+    let path = crate::paths::contained(&content.join("assets"), name)?;
+    std::fs::metadata(path).ok().filter(|metadata| metadata.is_file()).map(|metadata| metadata.len())
+}
+
+/// Files larger than this many bytes are streamed from the store on every request instead of being cached,
+/// set with `CADEN_BLOG_CACHE_MAX_FILE_SIZE`
+pub fn max_cached_size_from_env() -> u64 {
+    std::env::var("CADEN_BLOG_CACHE_MAX_FILE_SIZE")
+        .ok()
+        .and_then(|size| size.parse().ok())
+        .unwrap_or(DEFAULT_MAX_CACHED_SIZE)
+}
+
+/// Asset names the store recently said don't exist, answered with a 404 without asking it again until they expire
+pub struct MissingAssets {
+    ttl: Duration,
+    names: Mutex<HashMap<String, Instant>>,
+}
+
+impl Default for MissingAssets {
+    fn default() -> MissingAssets {
+        MissingAssets { ttl: DEFAULT_MISSING_TTL, names: Mutex::default() }
+    }
+}
+
+impl MissingAssets {
+    /// A TTL of zero turns the negative cache off
+    pub fn from_env() -> MissingAssets {
+        let ttl = std::env::var("CADEN_BLOG_MISSING_ASSET_TTL_SECS").ok().and_then(|secs| secs.trim().parse().ok()).map(Duration::from_secs).unwrap_or(DEFAULT_MISSING_TTL);
+        MissingAssets { ttl, names: Mutex::default() }
+    }
+
+    pub fn is_missing(&self, name: &str) -> bool {
+        let mut names = self.names.lock().expect("failed to lock the missing assets");
+        match names.get(name) {
+            Some(since) if since.elapsed() < self.ttl => true,
+            Some(_) => {
+                names.remove(name);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn remember(&self, name: &str) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut names = self.names.lock().expect("failed to lock the missing assets");
+        if names.len() >= MAX_MISSING {
+            names.retain(|_, since| since.elapsed() < self.ttl);
+        }
+        if names.len() < MAX_MISSING {
+            names.insert(name.to_string(), Instant::now());
+        }
+    }
+
+    /// Forgets the names that match, returning how many, for when assets are uploaded or the caches purged
+    pub fn purge(&self, matches: impl Fn(&str) -> bool) -> usize {
+        let mut names = self.names.lock().expect("failed to lock the missing assets");
+        let before = names.len();
+        names.retain(|name, _| !matches(name));
+        before - names.len()
+    }
+}
+
+/// Picks the S3 store when `CADEN_BLOG_S3_BUCKET` is set, otherwise the assets directory under `content`,
+/// either way backed by the default assets compiled into the binary
+pub fn from_env(content: &std::path::Path) -> std::sync::Arc<dyn AssetStore> {
+    let store: std::sync::Arc<dyn AssetStore> = match s3::S3Config::from_env() {
+        Some(config) => {
+            println!("Serving assets from s3 bucket {} at {}", config.bucket, config.endpoint);
+            std::sync::Arc::new(s3::S3Store::new(config))
+        }
+        None => std::sync::Arc::new(FilesystemStore::new(content.join("assets"))),
+    };
+    std::sync::Arc::new(WithDefaults::new(store))
+}
+
+#[test]
+fn byte_ranges_parse_and_resolve() {
+    assert_eq!(ByteRange::parse("bytes=0-99"), Some(ByteRange::Between(0, 99)));
+    assert_eq!(ByteRange::parse("bytes=100-"), Some(ByteRange::From(100)));
+    assert_eq!(ByteRange::parse("bytes=-50"), Some(ByteRange::Last(50)));
+    assert_eq!(ByteRange::parse("bytes=0-1,5-6"), None);
+    assert_eq!(ByteRange::parse("bytes=9-3"), None);
+    assert_eq!(ByteRange::parse("items=0-1"), None);
+
+    assert_eq!(ByteRange::Between(0, 99).resolve(50), Some((0, 49)));
+    assert_eq!(ByteRange::From(10).resolve(50), Some((10, 49)));
+    assert_eq!(ByteRange::Last(80).resolve(50), Some((0, 49)));
+    assert_eq!(ByteRange::From(50).resolve(50), None);
+    assert_eq!(ByteRange::Last(0).resolve(50), None);
+}
+
+#[test]
+fn missing_assets_are_remembered_until_they_expire() {
+    let missing = MissingAssets::default();
+    assert!(!missing.is_missing("gone.png"));
+    missing.remember("gone.png");
+    assert!(missing.is_missing("gone.png"));
+    assert_eq!(missing.purge(|name| name.starts_with("gone")), 1);
+    assert!(!missing.is_missing("gone.png"));
+
+    let expired = MissingAssets { ttl: Duration::ZERO, names: Mutex::default() };
+    expired.remember("gone.png");
+    assert!(!expired.is_missing("gone.png"));
+}
+
+#[test]
+fn directories_can_have_their_own_cache_policy() {
+    let policies = parse_cache_policies("drafts=no-cache; /posts/wip/ = public, max-age=600;posts=public, max-age=86400;=nope;bad=\n");
+    assert_eq!(cache_policy(&policies, "posts/wip/diagram.png"), Some("public, max-age=600"));
+    assert_eq!(cache_policy(&policies, "posts/hello/cat.png"), Some("public, max-age=86400"));
+    assert_eq!(cache_policy(&policies, "drafts/x.png"), Some("no-cache"));
+    assert_eq!(cache_policy(&policies, "postscript.pdf"), None);
+    assert_eq!(cache_control(&policies, "postscript.pdf"), crate::fingerprint::UNVERSIONED);
+    assert_eq!(policies.len(), 3);
+
+    assert!(valid_name("posts/my-post/diagram.png"));
+    assert!(!valid_name("posts/../../secret") && !valid_name("/etc/passwd") && !valid_name("a\\..\\b"));
+}
+
+#[test]
+fn photos_are_offered_in_the_formats_the_browser_takes() {
+    let chrome = "image/avif,image/webp,image/apng,image/svg+xml,image/*,*/*;q=0.8";
+    assert_eq!(variants("posts/cat.jpg", chrome), vec![("posts/cat.avif".to_string(), "image/avif"), ("posts/cat.webp".to_string(), "image/webp")]);
+    assert_eq!(variants("cat.PNG", "image/webp;q=0.9, image/avif;q=0"), vec![("cat.webp".to_string(), "image/webp")]);
+    assert!(variants("cat.jpg", "image/*,*/*").is_empty());
+    assert!(variants("cat.gif", chrome).is_empty());
+    assert!(variants("notes.txt", chrome).is_empty());
+}
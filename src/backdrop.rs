@@ -0,0 +1,97 @@
+use maud::{html, Markup, PreEscaped};
+
+use crate::i18n::Text;
+use crate::prefs::{BackgroundSpeed, ClientHints};
+
+/// A pattern tiled behind the pages and slowly scrolled diagonally, set with `CADEN_BLOG_BACKGROUND_PATTERN` as
+/// `none` (the default), `dots` or `grid`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    None,
+    Dots,
+    Grid,
+}
+
+impl Pattern {
+    fn parse(value: &str) -> Option<Pattern> {
+        match value {
+            "none" => Some(Pattern::None),
+            "dots" => Some(Pattern::Dots),
+            "grid" => Some(Pattern::Grid),
+            _ => None,
+        }
+    }
+
+    pub fn from_env() -> Pattern {
+        std::env::var("CADEN_BLOG_BACKGROUND_PATTERN")
+            .ok()
+            .and_then(|value| Pattern::parse(value.trim()))
+            .unwrap_or(Pattern::None)
+    }
+
+    /// The tile as a CSS background image, with the size it repeats at so scrolling by that much loops seamlessly
+    fn tile(self) -> Option<(&'static str, &'static str)> {
+        match self {
+            Pattern::None => None,
+            Pattern::Dots => Some(("radial-gradient(rgba(255, 255, 255, 0.07) 1px, transparent 1px)", "24px 24px")),
+            Pattern::Grid => Some((
+                "linear-gradient(rgba(255, 255, 255, 0.04) 1px, transparent 1px), linear-gradient(90deg, rgba(255, 255, 255, 0.04) 1px, transparent 1px)",
+                "32px 32px",
+            )),
+        }
+    }
+}
+
+/// The background for `pattern`, scrolling one tile every `--speed` unless the reader turned it off or asked for
+/// less motion. Rendered into the page rather than toggled by script, so a still background never animates at all.
+pub fn css(pattern: Pattern, speed: BackgroundSpeed, hints: ClientHints) -> Markup {
+    let Some((image, size)) = pattern.tile() else { return html! {} };
+    let animation = match speed.seconds() {
+        Some(seconds) if !hints.reduced_motion => format!(
+            "body {{ --speed: {}s; animation: backdrop-scroll var(--speed) linear infinite; }} @keyframes backdrop-scroll {{ to {{ background-position: {}; }} }}",
+            seconds, size
+        ),
+        _ => String::new(),
+    };
+    html! {
+        style { (PreEscaped(format!("body {{ background-image: {}; background-size: {}; }} {}", image, size, animation))) }
+    }
+}
+
+/// Links for changing the background speed, with the current one marked, when the site has a `pattern` to scroll
+pub fn speed_switcher(text: Text, pattern: Pattern, current: BackgroundSpeed) -> Markup {
+    let speeds = [
+        (BackgroundSpeed::Off, "background_off"),
+        (BackgroundSpeed::Slow, "background_slow"),
+        (BackgroundSpeed::Normal, "background_normal"),
+        (BackgroundSpeed::Fast, "background_fast"),
+    ];
+    html! {
+        @if pattern != Pattern::None {
+            nav class="speed-switcher small" aria-label=(text.t("background")) {
+                (text.t("background")) ": "
+                @for (speed, label) in speeds {
+                    @if speed == current {
+                        strong class="me-2" aria-current="true" { (text.t(label)) }
+                    } @else {
+                        a class="me-2" href=(format!("/background/{}", speed.name())) { (text.t(label)) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn backgrounds_scroll_at_the_readers_speed_unless_asked_to_keep_still() {
+    let normal = css(Pattern::Dots, BackgroundSpeed::Normal, ClientHints::default()).into_string();
+    assert!(normal.contains("background-size: 24px 24px;") && normal.contains("--speed: 60s;") && normal.contains("background-position: 24px 24px;"), "{}", normal);
+    assert!(css(Pattern::Grid, BackgroundSpeed::Fast, ClientHints::default()).into_string().contains("--speed: 20s;"));
+
+    let off = css(Pattern::Dots, BackgroundSpeed::Off, ClientHints::default()).into_string();
+    assert!(off.contains("background-image") && !off.contains("animation"));
+    let calm = css(Pattern::Dots, BackgroundSpeed::Fast, ClientHints { reduced_motion: true, ..ClientHints::default() }).into_string();
+    assert!(!calm.contains("animation"));
+    assert!(css(Pattern::None, BackgroundSpeed::Fast, ClientHints::default()).into_string().is_empty());
+    assert_eq!(Pattern::parse("grid"), Some(Pattern::Grid));
+}
@@ -0,0 +1,185 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::process::Command;
+
+use crate::admin::Admin;
+use crate::assets::s3::{S3Config, S3Store};
+use crate::state::AppState;
+
+/// Archives kept when `CADEN_BLOG_BACKUP_KEEP` isn't set
+const DEFAULT_KEEP: usize = 7;
+
+/// Key prefix archives are uploaded under when they go to the S3 bucket as well
+const S3_PREFIX: &str = "backups/";
+
+/// Left out of archives: the git checkout sync manages
+const EXCLUDED: [&str; 1] = [".git"];
+
+/// Where and how often the content directory is archived, read from the `CADEN_BLOG_BACKUP_*` environment variables
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    /// The content directory that gets archived
+    pub content: PathBuf,
+    /// From `CADEN_BLOG_BACKUP_DIR`, `caden-blog-backups` next to the content directory by default
+    pub dir: PathBuf,
+    /// From `CADEN_BLOG_BACKUP_INTERVAL` in seconds. Without it backups only run from the admin route.
+    pub interval: Option<Duration>,
+    /// Archives kept in `dir`, from `CADEN_BLOG_BACKUP_KEEP`. Copies in the bucket are left to its lifecycle rules.
+    pub keep: usize,
+    /// Also upload every archive to the asset bucket when `CADEN_BLOG_BACKUP_S3` is on
+    pub s3: Option<S3Config>,
+}
+
+impl BackupConfig {
+    pub fn from_env(content: &Path) -> BackupConfig {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        BackupConfig {
+            content: content.to_path_buf(),
+            dir: var("CADEN_BLOG_BACKUP_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| content.parent().unwrap_or(content).join("caden-blog-backups")),
+            interval: var("CADEN_BLOG_BACKUP_INTERVAL").and_then(|secs| secs.parse().ok()).filter(|&secs| secs > 0).map(Duration::from_secs),
+            keep: var("CADEN_BLOG_BACKUP_KEEP").and_then(|keep| keep.parse().ok()).filter(|&keep| keep > 0).unwrap_or(DEFAULT_KEEP),
+            s3: var("CADEN_BLOG_BACKUP_S3")
+                .filter(|value| matches!(value.trim(), "1" | "true" | "on"))
+                .and_then(|_| S3Config::from_env()),
+        }
+    }
+}
+
+/// Backups of the content directory, one at a time whether they come from the schedule or the admin route
+pub struct Backups {
+    config: BackupConfig,
+    running: tokio::sync::Mutex<()>,
+}
+
+impl Backups {
+    pub fn new(config: BackupConfig) -> Backups {
+        Backups { config, running: tokio::sync::Mutex::new(()) }
+    }
+
+    /// Archives the content directory, uploads the archive when configured and prunes old ones, returning its path
+    pub async fn run(&self) -> Result<PathBuf, String> {
+        let _running = self.running.lock().await;
+        let archive = create(&self.config.content, &self.config.dir, Utc::now()).await?;
+
+        if let Some(s3) = &self.config.s3 {
+            let name = archive.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            let bytes = tokio::fs::read(&archive).await.map_err(|e| format!("couldn't read {}: {}", archive.display(), e))?;
+            S3Store::new(s3.clone()).put(&format!("{}{}", S3_PREFIX, name), bytes).await?;
+        }
+        prune(&self.config.dir, self.config.keep).await;
+        Ok(archive)
+    }
+}
+
+fn archive_name(now: DateTime<Utc>) -> String {
+    format!("caden-blog-{}.tar.gz", now.format("%Y%m%dT%H%M%SZ"))
+}
+
+/// Writes `<dir>/caden-blog-<timestamp>.tar.gz` of everything in `source`, by way of a temporary file so a
+/// half-written archive never looks like a backup
+async fn create(source: &Path, dir: &Path, now: DateTime<Utc>) -> Result<PathBuf, String> {
+    tokio::fs::create_dir_all(dir).await.map_err(|e| format!("couldn't create {}: {}", dir.display(), e))?;
+    let archive = dir.join(archive_name(now));
+    let partial = archive.with_extension("partial");
+
+    let mut tar = Command::new("tar");
+    tar.arg("-czf").arg(&partial).arg("-C").arg(source);
+    for excluded in EXCLUDED {
+        tar.arg(format!("--exclude=./{}", excluded));
+    }
+    // A backup directory inside the content directory mustn't end up in its own archives
+    if let Ok(inside) = dir.strip_prefix(source) {
+        tar.arg(format!("--exclude=./{}", inside.display()));
+    }
+    let output = tar.arg(".").output().await.map_err(|e| format!("couldn't run tar: {}", e))?;
+    if !output.status.success() {
+        let _ = tokio::fs::remove_file(&partial).await;
+        return Err(format!("tar failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    tokio::fs::rename(&partial, &archive).await.map_err(|e| format!("couldn't move {} into place: {}", archive.display(), e))?;
+    Ok(archive)
+}
+
+/// Deletes all but the newest `keep` archives. Their names sort by the time they were made.
+async fn prune(dir: &Path, keep: usize) {
+    let mut archives: Vec<String> = crate::store::posts::list_files_in_directory(dir)
+        .into_iter()
+        .filter(|name| name.starts_with("caden-blog-") && name.ends_with(".tar.gz"))
+        .collect();
+    archives.sort();
+    let stale = archives.len().saturating_sub(keep);
+    for name in &archives[..stale] {
+        if let Err(e) = tokio::fs::remove_file(dir.join(name)).await {
+            println!("Couldn't remove the old backup {}: {}", name, e);
+        }
+    }
+}
+
+/// Backs up on the configured interval, starting one interval after startup
+pub async fn run(backups: Arc<Backups>) {
+    let Some(every) = backups.config.interval else { return };
+    println!("Backing up content to {} every {}s", backups.config.dir.display(), every.as_secs());
+
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + every, every);
+    loop {
+        interval.tick().await;
+        match backups.run().await {
+            Ok(archive) => println!("Backed up content to {}", archive.display()),
+            Err(e) => println!("Backup failed: {}", e),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BackupResult {
+    pub archive: String,
+}
+
+/// `POST /admin/backup`: makes a backup right away
+pub async fn backup_now(_: Admin, State(AppState { backups, .. }): State<AppState>) -> Result<Json<BackupResult>, (StatusCode, String)> {
+    match backups.run().await {
+        Ok(archive) => Ok(Json(BackupResult { archive: archive.display().to_string() })),
+        Err(e) => {
+            println!("Backup failed: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+#[tokio::test]
+async fn backups_archive_the_content_and_keep_the_newest() {
+    let root = std::env::temp_dir().join(format!("caden-blog-backup-{}", std::process::id()));
+    let content = root.join("content");
+    let backups = content.join("backups");
+    std::fs::create_dir_all(content.join("posts")).unwrap();
+    std::fs::create_dir_all(content.join(".git")).unwrap();
+    std::fs::write(content.join("posts/hello.json"), "{}").unwrap();
+    std::fs::write(content.join(".git/HEAD"), "").unwrap();
+
+    let start: DateTime<Utc> = "2026-01-01T00:00:00Z".parse().unwrap();
+    for day in 0..3 {
+        create(&content, &backups, start + chrono::Duration::days(day)).await.unwrap();
+    }
+    prune(&backups, 2).await;
+
+    let mut kept = crate::store::posts::list_files_in_directory(&backups);
+    kept.sort();
+    assert_eq!(kept, vec![archive_name(start + chrono::Duration::days(1)), archive_name(start + chrono::Duration::days(2))]);
+
+    let listing = std::process::Command::new("tar").arg("-tzf").arg(backups.join(&kept[1])).output().unwrap();
+    let listing = String::from_utf8_lossy(&listing.stdout);
+    assert!(listing.contains("./posts/hello.json"), "{}", listing);
+    assert!(!listing.contains(".git") && !listing.contains("backups"), "{}", listing);
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use axum::extract::{Request, State};
+use axum::http::header::{CONTENT_TYPE, RETRY_AFTER, USER_AGENT};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::extract::client::Client;
+use crate::state::AppState;
+
+/// Metric counting the requests turned away, labelled with why
+pub const BOT_REQUESTS_METRIC: &str = "caden_blog_bot_requests_total";
+
+/// User agents of crawlers known to ignore crawl limits, matched case-insensitively anywhere in the header.
+/// `CADEN_BLOG_BAD_BOTS` replaces the list.
+const DEFAULT_BAD_BOTS: [&str; 9] = ["ahrefsbot", "semrushbot", "mj12bot", "dotbot", "petalbot", "bytespider", "blexbot", "dataforseobot", "megaindex"];
+
+/// Page views a reader can make in a second, from `CADEN_BLOG_BOT_MAX_PAGES_PER_SECOND`, before they're taken for a bot
+const DEFAULT_MAX_PAGES_PER_SECOND: u32 = 8;
+
+/// How long a client caught in a honeypot or clicking too fast is turned away for
+const FLAG_DURATION: Duration = Duration::from_secs(600);
+
+/// Clients remembered before the ones that went quiet are forgotten
+const MAX_TRACKED: usize = 10_000;
+
+/// Why a request was taken for a bot's
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    UserAgent,
+    /// Asked for a path robots.txt disallows
+    Robots,
+    /// Opened pages faster than anyone could read them
+    Speed,
+    /// Was caught by one of the above a little earlier
+    Flagged,
+}
+
+impl Reason {
+    fn label(self) -> &'static str {
+        match self {
+            Reason::UserAgent => "user_agent",
+            Reason::Robots => "robots",
+            Reason::Speed => "speed",
+            Reason::Flagged => "flagged",
+        }
+    }
+}
+
+struct Visits {
+    window_start: Instant,
+    pages: u32,
+    flagged_until: Option<Instant>,
+}
+
+/// Spots obvious bad bots: known crawler user agents, anything ignoring robots.txt, and page views at inhuman speed
+#[derive(Default)]
+pub struct BotGuard {
+    user_agents: Vec<String>,
+    /// `Disallow` prefixes from robots.txt for every user agent
+    disallowed: Vec<String>,
+    /// Zero turns the speed check off
+    max_pages_per_second: u32,
+    clients: Mutex<HashMap<IpAddr, Visits>>,
+}
+
+impl BotGuard {
+    pub fn from_env(content: &Path) -> BotGuard {
+        let user_agents = match std::env::var("CADEN_BLOG_BAD_BOTS") {
+            Ok(list) => list.split(',').map(|ua| ua.trim().to_lowercase()).filter(|ua| !ua.is_empty()).collect(),
+            Err(_) => DEFAULT_BAD_BOTS.iter().map(|ua| ua.to_string()).collect(),
+        };
+        let robots = crate::defaults::read(content, "robots.txt").map(|robots| String::from_utf8_lossy(&robots).into_owned()).unwrap_or_default();
+        BotGuard {
+            user_agents,
+            disallowed: disallowed_paths(&robots),
+            max_pages_per_second: std::env::var("CADEN_BLOG_BOT_MAX_PAGES_PER_SECOND")
+                .ok()
+                .and_then(|max| max.parse().ok())
+                .unwrap_or(DEFAULT_MAX_PAGES_PER_SECOND),
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Why a request looks like a bot's, or `None` to serve it
+    pub fn check(&self, ip: Option<IpAddr>, user_agent: &str, path: &str, now: Instant) -> Option<Reason> {
+        let user_agent = user_agent.to_lowercase();
+        if self.user_agents.iter().any(|bad| user_agent.contains(bad)) {
+            return Some(Reason::UserAgent);
+        }
+        // Without an address there's nobody to remember, so only the user agent can give a bot away
+        let ip = ip?;
+
+        let mut clients = self.clients.lock().expect("failed to lock the bot guard");
+        if clients.len() >= MAX_TRACKED {
+            clients.retain(|_, visits| visits.flagged_until.is_some_and(|until| until > now) || now.duration_since(visits.window_start) < Duration::from_secs(1));
+        }
+        let visits = clients.entry(ip).or_insert(Visits { window_start: now, pages: 0, flagged_until: None });
+        if visits.flagged_until.is_some_and(|until| until > now) {
+            return Some(Reason::Flagged);
+        }
+
+        let reason = if self.disallowed.iter().any(|prefix| path.starts_with(prefix.as_str())) {
+            Some(Reason::Robots)
+        } else if self.max_pages_per_second > 0 && is_page(path) {
+            if now.duration_since(visits.window_start) >= Duration::from_secs(1) {
+                visits.window_start = now;
+                visits.pages = 0;
+            }
+            visits.pages += 1;
+            (visits.pages > self.max_pages_per_second).then_some(Reason::Speed)
+        } else {
+            None
+        };
+        if reason.is_some() {
+            visits.flagged_until = Some(now + FLAG_DURATION);
+        }
+        reason
+    }
+}
+
+/// Paths a reader navigates to, as opposed to the assets, fragments and event streams the pages load themselves
+fn is_page(path: &str) -> bool {
+    match path.strip_prefix("/post/") {
+        Some(rest) => !rest.contains('/') || rest.ends_with("/plain"),
+        None => path == "/" || path == "/contact",
+    }
+}
+
+/// The `Disallow` rules in robots.txt groups that apply to every user agent
+fn disallowed_paths(robots: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut agents: Vec<String> = Vec::new();
+    let mut in_rules = false;
+    for line in robots.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let Some((field, value)) = line.split_once(':') else { continue };
+        let value = value.trim();
+        match field.trim().to_ascii_lowercase().as_str() {
+            "user-agent" => {
+                // A user-agent line after rules starts the next group
+                if in_rules {
+                    agents.clear();
+                    in_rules = false;
+                }
+                agents.push(value.to_string());
+            }
+            "disallow" => {
+                in_rules = true;
+                if !value.is_empty() && agents.iter().any(|agent| agent == "*") {
+                    paths.push(value.to_string());
+                }
+            }
+            _ => in_rules = true,
+        }
+    }
+    paths
+}
+
+/// Serves the site's robots.txt, or the default one disallowing the honeypot
+pub async fn serve_robots(State(state): State<AppState>) -> Response {
+    match crate::defaults::read_async(&state.config.content_dir, "robots.txt").await {
+        Some(robots) => ([(CONTENT_TYPE, "text/plain; charset=utf-8")], robots).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Middleware answering requests that look like a bad bot's with a bare `429`, counting them by reason
+pub async fn guard(State(state): State<AppState>, client: Client, request: Request, next: Next) -> Response {
+    let user_agent = request.headers().get(USER_AGENT).and_then(|ua| ua.to_str().ok()).unwrap_or_default();
+    match state.bots.check(client.ip, user_agent, request.uri().path(), Instant::now()) {
+        None => next.run(request).await,
+        Some(reason) => {
+            state.metrics.incr(BOT_REQUESTS_METRIC, &[("reason", reason.label())]);
+            (StatusCode::TOO_MANY_REQUESTS, [(RETRY_AFTER, FLAG_DURATION.as_secs().to_string())]).into_response()
+        }
+    }
+}
+
+#[test]
+fn robots_rules_only_count_for_every_agent() {
+    let robots = "User-agent: Googlebot\nDisallow: /drafts/\n\nUser-agent: *\nUser-agent: Bingbot\nDisallow: /private/ # honeypot\nDisallow:\nAllow: /\n\nUser-agent: other\nDisallow: /tmp/";
+    assert_eq!(disallowed_paths(robots), vec!["/private/"]);
+}
+
+#[test]
+fn bots_are_caught_and_remembered() {
+    let guard = BotGuard {
+        user_agents: vec!["semrushbot".to_string()],
+        disallowed: vec!["/private/".to_string()],
+        max_pages_per_second: 3,
+        ..BotGuard::default()
+    };
+    let now = Instant::now();
+    let reader = Some("198.51.100.1".parse().unwrap());
+    let crawler = Some("198.51.100.2".parse().unwrap());
+    let browser = "Mozilla/5.0 (X11; Linux x86_64)";
+
+    assert_eq!(guard.check(reader, "Mozilla/5.0 (compatible; SemrushBot/7)", "/", now), Some(Reason::UserAgent));
+    assert_eq!(guard.check(None, browser, "/private/", now), None);
+
+    // Assets loaded along with a page don't count towards the speed limit
+    for path in ["/", "/asset/cat.png", "/post/hello", "/post/hello/react", "/post/hello/plain"] {
+        assert_eq!(guard.check(reader, browser, path, now), None, "{}", path);
+    }
+    assert_eq!(guard.check(reader, browser, "/contact", now), Some(Reason::Speed));
+    assert_eq!(guard.check(reader, browser, "/", now + Duration::from_secs(2)), Some(Reason::Flagged));
+    assert_eq!(guard.check(reader, browser, "/", now + FLAG_DURATION + Duration::from_secs(1)), None);
+
+    assert_eq!(guard.check(crawler, browser, "/private/admin.php", now), Some(Reason::Robots));
+    assert_eq!(guard.check(crawler, browser, "/", now), Some(Reason::Flagged));
+}
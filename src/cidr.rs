@@ -0,0 +1,78 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// A block of addresses like `10.0.0.0/8` or `2001:db8::/32`. A bare address is a block of one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // IPv4 clients reaching a dual-stack socket show up as `::ffff:a.b.c.d`
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(block), IpAddr::V4(ip)) => masked(u32::from(block).into(), self.prefix, 32) == masked(u32::from(ip).into(), self.prefix, 32),
+            (IpAddr::V6(block), IpAddr::V6(ip)) => masked(block.into(), self.prefix, 128) == masked(ip.into(), self.prefix, 128),
+            _ => false,
+        }
+    }
+}
+
+/// The top `prefix` bits of a `width`-bit address
+fn masked(bits: u128, prefix: u8, width: u8) -> u128 {
+    match width - prefix {
+        0 => bits,
+        host if host >= 128 => 0,
+        host => bits >> host,
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Cidr, String> {
+        let s = s.trim();
+        let (addr, prefix) = s.split_once('/').map_or((s, None), |(addr, prefix)| (addr, Some(prefix)));
+        let addr: IpAddr = addr.parse().map_err(|_| format!("{} isn't an address or a CIDR block", s))?;
+        let width = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().ok().filter(|&prefix| prefix <= width).ok_or_else(|| format!("{} has an invalid prefix length", s))?,
+            None => width,
+        };
+        Ok(Cidr { addr, prefix })
+    }
+}
+
+/// Comma-separated blocks, as the `CADEN_BLOG_*` variables list them, skipping and reporting the ones that don't parse
+pub fn parse_list(list: &str) -> Vec<Cidr> {
+    list.split(',')
+        .filter(|block| !block.trim().is_empty())
+        .filter_map(|block| block.parse().map_err(|e| println!("Ignoring {}", e)).ok())
+        .collect()
+}
+
+#[test]
+fn cidr_blocks_match_their_addresses() {
+    let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+    let private: Cidr = "10.0.0.0/8".parse().unwrap();
+    assert!(private.contains(ip("10.20.30.40")));
+    assert!(private.contains(ip("::ffff:10.0.0.1")));
+    assert!(!private.contains(ip("11.0.0.1")));
+    assert!(!private.contains(ip("::1")));
+
+    let single: Cidr = "192.168.1.5".parse().unwrap();
+    assert!(single.contains(ip("192.168.1.5")));
+    assert!(!single.contains(ip("192.168.1.6")));
+
+    let v6: Cidr = "2001:db8::/32".parse().unwrap();
+    assert!(v6.contains(ip("2001:db8:1::1")));
+    assert!(!v6.contains(ip("2001:db9::1")));
+
+    let everything: Cidr = "0.0.0.0/0".parse().unwrap();
+    assert!(everything.contains(ip("203.0.113.9")));
+
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    assert!("example.com".parse::<Cidr>().is_err());
+    assert_eq!(parse_list("127.0.0.1, ::1,,nope"), vec!["127.0.0.1".parse().unwrap(), "::1".parse().unwrap()]);
+}
@@ -0,0 +1,121 @@
+use std::fs;
+
+use chrono::Utc;
+
+use crate::assets::{self, AssetError, AssetStore};
+use crate::model::post::{find_slug_conflicts, serialize_post, Post, RenderedBody};
+use crate::config::Config;
+use crate::slug;
+use crate::store::posts::read_posts;
+
+/// `caden-blog new-post <title>`: scaffolds an empty post named after the slugified title
+pub fn new_post(config: &Config, title: &str) -> std::process::ExitCode {
+    if title.trim().is_empty() {
+        println!("usage: caden-blog new-post <title>");
+        return std::process::ExitCode::FAILURE;
+    }
+
+    let slug = slug::slugify(title);
+    let mut file_name = format!("{}.json", slug);
+    let mut n = 2;
+    let dir = config.path("posts");
+    while dir.join(&file_name).exists() {
+        file_name = format!("{}-{}.json", slug, n);
+        n += 1;
+    }
+
+    let post = Post {
+        title: title.trim().to_string(),
+        body: String::new(),
+        image_url: String::new(),
+        image_alt: None,
+        image_poster: None,
+        summary: String::new(),
+        timestamp: Utc::now(),
+        updated: None,
+        lang: None,
+        slug: None,
+        tags: Vec::new(),
+        visibility: Default::default(),
+        comments_enabled: None,
+        audio: None,
+        video: None,
+        attachments: Vec::new(),
+        accent_color: None,
+        hero: None,
+        url_name: String::new(),
+        source_file: String::new(),
+        rendered: RenderedBody::default(),
+    };
+    let path = dir.join(&file_name);
+    match fs::create_dir_all(&dir).and_then(|_| fs::write(&path, serialize_post(&post))) {
+        Ok(()) => {
+            println!("Created {}", path.display());
+            std::process::ExitCode::SUCCESS
+        }
+        Err(e) => {
+            println!("Couldn't write {}: {}", path.display(), e);
+            std::process::ExitCode::FAILURE
+        }
+    }
+}
+
+/// `caden-blog validate`: checks the posts directory and exits non-zero when something would not be served
+pub async fn validate(config: &Config) -> std::process::ExitCode {
+    let posts = match read_posts(&config.content_dir).await {
+        Ok(posts) => posts,
+        Err(e) => {
+            println!("error: {}", e);
+            return std::process::ExitCode::FAILURE;
+        }
+    };
+    let conflicts = find_slug_conflicts(&posts);
+
+    for conflict in &conflicts {
+        println!("error: {}", conflict);
+    }
+
+    // Broken images only get a warning since the cards fall back to a placeholder
+    let store = assets::from_env(&config.content_dir);
+    let client = reqwest::Client::new();
+    let mut broken_images = 0;
+    for post in &posts {
+        if let Some(problem) = check_image(&post.image_url, store.as_ref(), &client).await {
+            println!("warning: {} has an unreachable image {}: {}", post.source_file, post.image_url, problem);
+            broken_images += 1;
+        }
+    }
+
+    println!("Checked {} posts, found {} slug conflicts and {} unreachable images", posts.len(), conflicts.len(), broken_images);
+
+    if conflicts.is_empty() {
+        std::process::ExitCode::SUCCESS
+    } else {
+        std::process::ExitCode::FAILURE
+    }
+}
+
+/// Why a post's image can't be loaded, or `None` when it's fine or there is no image at all
+pub async fn check_image(image_url: &str, store: &dyn AssetStore, client: &reqwest::Client) -> Option<String> {
+    if image_url.trim().is_empty() {
+        return None;
+    }
+
+    if let Some(name) = image_url.strip_prefix("/asset/") {
+        return match store.stream(name, None).await {
+            Ok(_) => None,
+            Err(AssetError::NotFound) => Some("asset not found".to_string()),
+            Err(AssetError::RangeNotSatisfiable(_)) => None,
+        };
+    }
+
+    if image_url.starts_with("http://") || image_url.starts_with("https://") {
+        return match client.head(image_url).send().await {
+            Ok(response) if response.status().is_success() => None,
+            Ok(response) => Some(format!("server returned {}", response.status())),
+            Err(e) => Some(e.to_string()),
+        };
+    }
+
+    Some("expected an /asset/ path or an http(s) URL".to_string())
+}
@@ -0,0 +1,790 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+use axum::body::Body;
+use axum::extract::{Form, Path, Query, State};
+use axum::http::{header, HeaderMap, Response, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{Html, IntoResponse};
+use axum::Json;
+use chrono::{DateTime, Duration, Utc};
+use maud::{html, Markup, PreEscaped};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::admin::Admin;
+use crate::extract::client::Client;
+use crate::extract::fragment::Partial;
+use crate::extract::tz::UserTz;
+use crate::i18n::Text;
+use crate::model::post::Post;
+use crate::notify::Notification;
+use crate::prefs::{self, TimeFormatter};
+use crate::signed::Key;
+use crate::{icons, placeholder, vendor};
+use crate::render::timestamp;
+use crate::spam::{Submission, Verdict, SPAM_METRIC};
+use crate::state::AppState;
+
+const STATE_FILE: &str = "state/comments.json";
+
+/// Levels of replies shown nested. A reply to a comment at the deepest level joins that comment's thread as its
+/// sibling, so long back-and-forths don't shrink to a sliver.
+pub const MAX_DEPTH: usize = 4;
+
+const MAX_AUTHOR_CHARS: usize = 80;
+const MAX_EMAIL_CHARS: usize = 254;
+const MAX_BODY_CHARS: usize = 5000;
+
+/// Where the avatar for an email hash is generated
+pub const AVATAR_PATH: &str = "/avatar/:file";
+
+/// Where a post's page hears about new comments on it
+pub const LIVE_PATH: &str = "/post/:url_name/comments/events";
+
+/// Signed list of the comments a visitor wrote, so they can change them while the edit window is open
+const COOKIE: &str = "comments";
+
+/// Only the most recent comments are remembered, older ones are past their edit window anyway
+const MAX_REMEMBERED: usize = 20;
+
+/// How long the moderation links in notification emails work
+const MODERATION_DAYS: i64 = 30;
+
+/// Marks the signed value as a moderation grant, so no other signed value can pass for one
+const MODERATION_PREFIX: &str = "moderate";
+
+/// Minutes authors can edit or delete a comment for when `CADEN_BLOG_COMMENT_EDIT_MINUTES` isn't set
+const DEFAULT_EDIT_MINUTES: i64 = 15;
+
+/// The ids of the comments a visitor wrote, from their signed cookie
+pub fn written(key: &Key, headers: &HeaderMap) -> Vec<u64> {
+    key.read_list(headers, COOKIE).iter().filter_map(|id| id.parse().ok()).collect()
+}
+
+/// A reader's comment on a post, a reply when it has a parent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: u64,
+    /// `url_name` of the post it's on
+    pub post: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<u64>,
+    pub author: String,
+    /// Hex SHA-256 of the commenter's lowercased email, when they gave one, for their avatar. The email itself is
+    /// never kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_hash: Option<String>,
+    pub body: String,
+    pub created: DateTime<Utc>,
+    /// When the author last changed the text
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited: Option<DateTime<Utc>>,
+    /// Left in place of a deleted comment that has replies, without its author or text
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+}
+
+/// The hash an email is remembered by, the same one Gravatar uses
+pub fn email_hash(email: &str) -> String {
+    hex::encode(Sha256::digest(email.trim().to_lowercase()))
+}
+
+/// What the state file holds. Ids count up from `last_id` rather than from the comments left, so a deleted
+/// comment's id is never handed to someone else while its author's cookie still lists it.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Saved {
+    #[serde(default)]
+    last_id: u64,
+    comments: Vec<Comment>,
+}
+
+/// The state file as it is now, or as a bare list of comments from before it kept `last_id`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedFile {
+    Saved(Saved),
+    List(Vec<Comment>),
+}
+
+/// Every comment on every post, oldest first, saved as json in the state directory after every change
+pub struct Comments {
+    path: PathBuf,
+    saved: RwLock<Saved>,
+    /// Closes every post to comments at once, starting from `CADEN_BLOG_COMMENTS_LOCKED` and switched at runtime
+    /// from the admin routes
+    locked: AtomicBool,
+    /// Whether posts that don't say take comments, from `CADEN_BLOG_COMMENTS_DEFAULT`
+    open_by_default: bool,
+    /// How long after posting a comment its author can still edit or delete it, from `CADEN_BLOG_COMMENT_EDIT_MINUTES`
+    edit_window: Duration,
+    /// Every comment as it's added, for the pages of the posts they're on
+    added: broadcast::Sender<Comment>,
+}
+
+pub type CommentStore = Arc<Comments>;
+
+pub fn load(content: &std::path::Path) -> Comments {
+    let mut comments = Comments::load(content.join(STATE_FILE));
+    let var = |name: &str| std::env::var(name).ok().map(|value| value.trim().to_string());
+    comments.open_by_default = !matches!(var("CADEN_BLOG_COMMENTS_DEFAULT").as_deref(), Some("0" | "false" | "off"));
+    if let Some(minutes) = var("CADEN_BLOG_COMMENT_EDIT_MINUTES").and_then(|minutes| minutes.parse::<i64>().ok()) {
+        comments.edit_window = Duration::minutes(minutes.max(0));
+    }
+    if matches!(var("CADEN_BLOG_COMMENTS_LOCKED").as_deref(), Some("1" | "true" | "on")) {
+        println!("Starting with comments locked");
+        comments.set_locked(true);
+    }
+    comments
+}
+
+impl Comments {
+    pub fn load(path: impl Into<PathBuf>) -> Comments {
+        let path = path.into();
+        let mut saved = match std::fs::read_to_string(&path).map(|json| serde_json::from_str(&json)) {
+            Ok(Ok(SavedFile::Saved(saved))) => saved,
+            Ok(Ok(SavedFile::List(comments))) => Saved { last_id: 0, comments },
+            Ok(Err(e)) => {
+                println!("Couldn't parse {}, starting without comments: {}", path.display(), e);
+                Saved::default()
+            }
+            Err(_) => Saved::default(),
+        };
+        saved.last_id = saved.comments.iter().map(|comment| comment.id).fold(saved.last_id, u64::max);
+        Comments { path, saved: RwLock::new(saved), locked: AtomicBool::new(false), open_by_default: true, edit_window: Duration::minutes(DEFAULT_EDIT_MINUTES), added: broadcast::channel(16).0 }
+    }
+
+    pub fn locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    pub fn set_locked(&self, locked: bool) {
+        self.locked.store(locked, Ordering::Relaxed);
+    }
+
+    /// Whether readers can comment on a post right now, rather than only read what's there
+    pub fn open_on(&self, post: &Post) -> bool {
+        !self.locked() && post.comments_enabled.unwrap_or(self.open_by_default)
+    }
+
+    /// Whether the visitor who wrote `written` may still edit or delete the comment
+    fn can_change(&self, comment: &Comment, written: &[u64], now: DateTime<Utc>) -> bool {
+        !comment.deleted && written.contains(&comment.id) && now < comment.created + self.edit_window
+    }
+
+    /// The comments in `written` their author can still edit or delete
+    pub fn changeable(&self, written: &[u64], now: DateTime<Utc>) -> Vec<u64> {
+        let saved = self.saved.read().expect("failed to lock the comments");
+        saved.comments.iter().filter(|comment| self.can_change(comment, written, now)).map(|comment| comment.id).collect()
+    }
+
+    /// The comments on a post, oldest first
+    pub fn on(&self, post: &str) -> Vec<Comment> {
+        self.saved.read().expect("failed to lock the comments").comments.iter().filter(|comment| comment.post == post).cloned().collect()
+    }
+
+    /// Hears about every comment added from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Comment> {
+        self.added.subscribe()
+    }
+
+    pub fn get(&self, id: u64) -> Option<Comment> {
+        self.saved.read().expect("failed to lock the comments").comments.iter().find(|comment| comment.id == id).cloned()
+    }
+
+    /// Applies a change and saves, unless the change gives up with `None`
+    async fn change<T>(&self, change: impl FnOnce(&mut Saved) -> Option<T>) -> Option<T> {
+        let (changed, json) = {
+            let mut saved = self.saved.write().expect("failed to lock the comments");
+            let changed = change(&mut saved)?;
+            (changed, serde_json::to_string_pretty(&*saved).expect("comments serialize"))
+        };
+
+        let path = self.path.as_path();
+        let saved = match path.parent() {
+            Some(dir) => tokio::fs::create_dir_all(dir).await,
+            None => Ok(()),
+        };
+        if let Err(e) = saved.and(tokio::fs::write(path, json).await) {
+            println!("Couldn't save {}: {}", path.display(), e);
+        }
+        Some(changed)
+    }
+
+    /// Adds a comment, moving a reply up to the deepest level shown, and saves. `None` when the parent isn't a
+    /// comment on the same post.
+    pub async fn add(&self, post: &str, parent: Option<u64>, author: &str, email: Option<&str>, body: &str) -> Option<Comment> {
+        self.change(|Saved { last_id, comments }| {
+            let parent = match parent {
+                Some(id) => {
+                    let on_post: Vec<&Comment> = comments.iter().filter(|comment| comment.post == post).collect();
+                    let mut parent = on_post.iter().find(|comment| comment.id == id)?;
+                    while depth(&on_post, parent) + 1 >= MAX_DEPTH {
+                        parent = on_post.iter().find(|comment| Some(comment.id) == parent.parent)?;
+                    }
+                    Some(parent.id)
+                }
+                None => None,
+            };
+            *last_id += 1;
+            let comment = Comment {
+                id: *last_id,
+                post: post.to_string(),
+                parent,
+                author: author.trim().to_string(),
+                email_hash: email.map(str::trim).filter(|email| !email.is_empty()).map(email_hash),
+                body: body.trim().to_string(),
+                created: Utc::now(),
+                edited: None,
+                deleted: false,
+            };
+            comments.push(comment.clone());
+            Some(comment)
+        })
+        .await
+        .inspect(|comment| {
+            // Nobody listening just means no page of the post is open
+            let _ = self.added.send(comment.clone());
+        })
+    }
+
+    /// Replaces a comment's text and saves
+    pub async fn edit(&self, id: u64, body: &str) -> Option<Comment> {
+        self.change(|Saved { comments, .. }| {
+            let comment = comments.iter_mut().find(|comment| comment.id == id && !comment.deleted)?;
+            comment.body = body.trim().to_string();
+            comment.edited = Some(Utc::now());
+            Some(comment.clone())
+        })
+        .await
+    }
+
+    /// Removes a comment and saves. One with replies leaves a placeholder behind so they keep their thread.
+    pub async fn delete(&self, id: u64) -> Option<Comment> {
+        self.change(|Saved { comments, .. }| {
+            let index = comments.iter().position(|comment| comment.id == id && !comment.deleted)?;
+            let removed = comments[index].clone();
+            if comments.iter().any(|reply| reply.parent == Some(id)) {
+                let placeholder = &mut comments[index];
+                placeholder.author.clear();
+                placeholder.email_hash = None;
+                placeholder.body.clear();
+                placeholder.deleted = true;
+            } else {
+                comments.remove(index);
+            }
+            Some(removed)
+        })
+        .await
+    }
+}
+
+/// How many ancestors a comment has, top-level comments being at depth 0
+fn depth(comments: &[&Comment], comment: &Comment) -> usize {
+    let mut depth = 0;
+    let mut parent = comment.parent;
+    while let Some(id) = parent {
+        depth += 1;
+        parent = comments.iter().find(|comment| comment.id == id).and_then(|comment| comment.parent);
+    }
+    depth
+}
+
+/// The comment count, each thread with its replies nested under it, and a form for a new comment. The comments
+/// in `changeable`, those [`Comments::changeable`] still lets the visitor change, get edit and delete buttons.
+/// Unless the post is `open` the comments are only shown, without any of the forms.
+pub fn widget(url_name: &str, comments: &[Comment], changeable: &[u64], open: bool, text: Text) -> Markup {
+    let changeable = if open { changeable } else { &[] };
+    let count = comments.iter().filter(|comment| !comment.deleted).count();
+    html! {
+        section id="comments" class="comments mt-4" aria-labelledby="comments-heading" {
+            h3 id="comments-heading" class="h5" { (text.t("comments_count").replace("{n}", &count.to_string())) }
+            @if comments.is_empty() {
+                p class="comments-empty text-muted" { (text.t("comments_empty")) }
+            }
+            (thread(url_name, comments, None, changeable, open, text))
+            @if open {
+                (form(url_name, None, text))
+            } @else {
+                p class="text-muted small mt-3" { (text.t("comments_closed")) }
+            }
+        }
+    }
+}
+
+fn thread(url_name: &str, comments: &[Comment], parent: Option<u64>, changeable: &[u64], open: bool, text: Text) -> Markup {
+    html! {
+        @for comment in comments.iter().filter(|comment| comment.parent == parent) {
+            @let replies = comments.iter().filter(|reply| reply.parent == Some(comment.id)).count();
+            article id=(format!("comment-{}", comment.id)) class="comment mt-3" data-parent=[comment.parent] {
+                @if comment.deleted {
+                    p class="small text-muted fst-italic mb-1" { (text.t("comment_deleted")) }
+                } @else {
+                    p class="small text-muted mb-1 d-flex align-items-center" {
+                        (avatar(comment))
+                        strong class="text-reset ms-2" { (comment.author) }
+                        span class="ms-1" {
+                            " · " (timestamp(&comment.created))
+                            @if comment.edited.is_some() { " · " (text.t("comment_edited")) }
+                        }
+                    }
+                    @for paragraph in comment.body.split("\n\n").map(str::trim).filter(|paragraph| !paragraph.is_empty()) {
+                        p class="mb-1" style="white-space: pre-line" { (paragraph) }
+                    }
+                    @if open {
+                        a class="small" href=(format!("/post/{}/comments/{}/reply", url_name, comment.id)) hx-get=(format!("/post/{}/comments/{}/reply", url_name, comment.id)) hx-target=(format!("#reply-{}", comment.id)) {
+                            (text.t("comment_reply"))
+                        }
+                    }
+                    @if !comment.deleted && changeable.contains(&comment.id) {
+                        (changes(comment, text))
+                    }
+                    div id=(format!("reply-{}", comment.id)) {}
+                }
+                @if replies > 0 {
+                    details class="comment-replies ms-3 ps-3 border-start" open {
+                        summary class="small text-muted" { (text.t("comment_replies").replace("{n}", &replies.to_string())) }
+                        (thread(url_name, comments, Some(comment.id), changeable, open, text))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Edit and delete buttons for a comment's author
+fn changes(comment: &Comment, text: Text) -> Markup {
+    let path = format!("/comments/{}", comment.id);
+    html! {
+        button type="button" class="btn btn-link btn-sm text-danger p-0 ms-2 align-baseline" hx-delete=(path) hx-target="#comments" hx-swap="outerHTML" hx-confirm=(text.t("comment_delete_confirm")) {
+            (text.t("comment_delete"))
+        }
+        details class="comment-edit mt-1" {
+            summary class="small" { (text.t("comment_edit")) }
+            form hx-patch=(path) hx-target="#comments" hx-swap="outerHTML" class="mt-1" {
+                textarea class="form-control form-control-sm mb-1" name="body" rows="3" required maxlength=(MAX_BODY_CHARS) aria-label=(text.t("comment_body")) { (comment.body) }
+                button type="submit" class="btn btn-sm btn-primary" { (text.t("comment_save")) }
+            }
+        }
+    }
+}
+
+/// The commenter's identicon when they left an email, otherwise their initials on a color of their name
+fn avatar(comment: &Comment) -> Markup {
+    html! {
+        @if let Some(hash) = &comment.email_hash {
+            img class="comment-avatar rounded-circle" src=(AVATAR_PATH.replace(":file", &format!("{}.svg", hash))) width="32" height="32" alt="" loading="lazy";
+        } @else {
+            span class="comment-avatar rounded-circle d-inline-flex align-items-center justify-content-center text-white fw-bold"
+                style=(format!("width: 32px; height: 32px; font-size: 0.8rem; background: hsl({}, 45%, 35%);", placeholder::hue(&comment.author)))
+                aria-hidden="true" {
+                (placeholder::initials(&comment.author))
+            }
+        }
+    }
+}
+
+fn form(url_name: &str, parent: Option<u64>, text: Text) -> Markup {
+    let action = format!("/post/{}/comments", url_name);
+    let id = |name: &str| match parent {
+        Some(parent) => format!("comment-{}-{}", name, parent),
+        None => format!("comment-{}", name),
+    };
+    html! {
+        form method="post" action=(action) hx-post=(action) hx-target="#comments" hx-swap="outerHTML" class="comment-form mt-3" {
+            @if let Some(parent) = parent {
+                input type="hidden" name="parent" value=(parent);
+            }
+            div class="mb-2" {
+                label class="form-label small" for=(id("author")) { (text.t("comment_author")) }
+                input type="text" class="form-control form-control-sm" id=(id("author")) name="author" required maxlength=(MAX_AUTHOR_CHARS);
+            }
+            div class="mb-2" {
+                label class="form-label small" for=(id("email")) { (text.t("comment_email")) }
+                input type="email" class="form-control form-control-sm" id=(id("email")) name="email" maxlength=(MAX_EMAIL_CHARS) aria-describedby=(id("email-help"));
+                div id=(id("email-help")) class="form-text" { (text.t("comment_email_help")) }
+            }
+            div class="mb-2" {
+                label class="form-label small" for=(id("body")) { (text.t("comment_body")) }
+                textarea class="form-control form-control-sm" id=(id("body")) name="body" rows="3" required maxlength=(MAX_BODY_CHARS) {}
+            }
+            button type="submit" class="btn btn-sm btn-primary" { (text.t(if parent.is_some() { "comment_reply" } else { "comment_submit" })) }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CommentForm {
+    author: String,
+    #[serde(default)]
+    email: String,
+    body: String,
+    parent: Option<u64>,
+}
+
+/// The public or unlisted post comments are going to, if there is one
+fn commentable(state: &AppState, url_name: &str) -> Option<Post> {
+    state.posts.read().expect("failed to lock the post index").iter().find(|post| post.url_name == url_name && post.visible_to(false)).cloned()
+}
+
+/// The post at `url_name` as long as it takes comments, a 403 when it's closed to them
+fn open_post(state: &AppState, url_name: &str) -> Result<Post, StatusCode> {
+    let post = commentable(state, url_name).ok_or(StatusCode::NOT_FOUND)?;
+    if state.comments.open_on(&post) {
+        Ok(post)
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// `POST /post/:url_name/comments`: adds a comment or reply and answers with the updated comments for htmx, or a
+/// redirect to the new comment when the form was submitted without javascript. Spam is turned away before it's kept.
+pub async fn submit(Partial(partial): Partial, State(state): State<AppState>, Path(url_name): Path<String>, client: Client, headers: HeaderMap, Form(form): Form<CommentForm>) -> Result<Response<Body>, StatusCode> {
+    let post = open_post(&state, &url_name)?;
+    let (author, email, body) = (form.author.trim(), form.email.trim(), form.body.trim());
+    if author.is_empty() || body.is_empty() || author.chars().count() > MAX_AUTHOR_CHARS || body.chars().count() > MAX_BODY_CHARS {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    if !email.is_empty() && (email.len() > MAX_EMAIL_CHARS || !email.contains('@')) {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let header = |name: header::HeaderName| headers.get(name).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    let permalink = format!("/post/{}", url_name);
+    let submission = Submission { author, email, body, ip: client.ip, user_agent: header(header::USER_AGENT), referrer: header(header::REFERER), permalink: &permalink };
+    if state.spam.check(&submission).await == Verdict::Spam {
+        state.metrics.incr(SPAM_METRIC, &[("checker", state.spam.name())]);
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let comment = state.comments.add(&url_name, form.parent, author, Some(email), body).await.ok_or(StatusCode::UNPROCESSABLE_ENTITY)?;
+    // Links only ever start with the configured site url, never the host the commenter's request named
+    if let Some(site_url) = state.notifier.site_url() {
+        state.notifier.push(Notification {
+            title: format!("New comment on {}", post.title),
+            text: format!(
+                "{} wrote:\n\n{}\n\nSee it: {}/post/{}#comment-{}\nModerate it: {}",
+                comment.author,
+                comment.body,
+                site_url,
+                url_name,
+                comment.id,
+                moderation_url(&state.config.cookie_key, site_url, comment.id, Utc::now())
+            ),
+        });
+    }
+
+    let mut written = written(&state.config.cookie_key, &headers);
+    written.push(comment.id);
+    let cookie = state.config.cookie_key.list_cookie(COOKIE, &written.iter().map(u64::to_string).collect::<Vec<_>>(), MAX_REMEMBERED);
+    let response = Response::builder().header(header::SET_COOKIE, cookie);
+    if partial {
+        let lang = state.locales.negotiate(&headers);
+        let html = widget(&url_name, &state.comments.on(&url_name), &state.comments.changeable(&written, Utc::now()), true, state.locales.text(&lang)).into_string();
+        Ok(response.header(header::CONTENT_TYPE, "text/html; charset=utf-8").body(Body::from(html)).unwrap())
+    } else {
+        Ok(response
+            .status(StatusCode::SEE_OTHER)
+            .header(header::LOCATION, format!("/post/{}#comment-{}", url_name, comment.id))
+            .body(Body::empty())
+            .unwrap())
+    }
+}
+
+/// The comment at `id` if the visitor wrote it and can still change it
+fn own_comment(state: &AppState, id: u64, headers: &HeaderMap) -> Result<Comment, StatusCode> {
+    let comment = state.comments.get(id).filter(|comment| !comment.deleted).ok_or(StatusCode::NOT_FOUND)?;
+    open_post(state, &comment.post)?;
+    if state.comments.can_change(&comment, &written(&state.config.cookie_key, headers), Utc::now()) {
+        Ok(comment)
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// The post's comments for htmx to swap in after a change
+fn updated_widget(state: &AppState, url_name: &str, headers: &HeaderMap) -> Response<Body> {
+    let lang = state.locales.negotiate(headers);
+    let open = commentable(state, url_name).is_some_and(|post| state.comments.open_on(&post));
+    let changeable = state.comments.changeable(&written(&state.config.cookie_key, headers), Utc::now());
+    let html = widget(url_name, &state.comments.on(url_name), &changeable, open, state.locales.text(&lang)).into_string();
+    Response::builder().header(header::CONTENT_TYPE, "text/html; charset=utf-8").body(Body::from(html)).unwrap()
+}
+
+#[derive(Deserialize)]
+pub struct EditForm {
+    body: String,
+}
+
+/// `PATCH /comments/:id`: replaces the text of a comment the visitor wrote, while the edit window is open.
+/// Answers with the updated comments for htmx, otherwise with the comment as JSON.
+pub async fn edit(Partial(partial): Partial, State(state): State<AppState>, Path(id): Path<u64>, headers: HeaderMap, Form(form): Form<EditForm>) -> Result<Response<Body>, StatusCode> {
+    own_comment(&state, id, &headers)?;
+    let body = form.body.trim();
+    if body.is_empty() || body.chars().count() > MAX_BODY_CHARS {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let comment = state.comments.edit(id, body).await.ok_or(StatusCode::NOT_FOUND)?;
+    if partial {
+        Ok(updated_widget(&state, &comment.post, &headers))
+    } else {
+        Ok(Json(comment).into_response())
+    }
+}
+
+/// `DELETE /comments/:id`: deletes a comment the visitor wrote, while the edit window is open
+pub async fn delete(Partial(partial): Partial, State(state): State<AppState>, Path(id): Path<u64>, headers: HeaderMap) -> Result<Response<Body>, StatusCode> {
+    own_comment(&state, id, &headers)?;
+    let comment = state.comments.delete(id).await.ok_or(StatusCode::NOT_FOUND)?;
+    if partial {
+        Ok(updated_widget(&state, &comment.post, &headers))
+    } else {
+        Ok(StatusCode::NO_CONTENT.into_response())
+    }
+}
+
+/// `GET /post/:url_name/comments/:id/reply`: the reply form htmx opens under a comment. Followed without
+/// javascript it leads back to the comment.
+pub async fn reply_form(Partial(partial): Partial, State(state): State<AppState>, Path((url_name, id)): Path<(String, u64)>, headers: HeaderMap) -> Result<Response<Body>, StatusCode> {
+    open_post(&state, &url_name)?;
+    if !state.comments.on(&url_name).iter().any(|comment| comment.id == id) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !partial {
+        return Ok(Response::builder()
+            .status(StatusCode::SEE_OTHER)
+            .header(header::LOCATION, format!("/post/{}#comment-{}", url_name, id))
+            .body(Body::empty())
+            .unwrap());
+    }
+    let lang = state.locales.negotiate(&headers);
+    let html = form(&url_name, Some(id), state.locales.text(&lang)).into_string();
+    Ok(Response::builder().header(header::CONTENT_TYPE, "text/html; charset=utf-8").body(Body::from(html)).unwrap())
+}
+
+/// `GET /post/:url_name/comments/events`: each new comment on the post as it's added, rendered for someone who
+/// didn't write it, in a `comment_added` event with the comment's id
+pub async fn live(State(state): State<AppState>, Path(url_name): Path<String>, user_tz: UserTz, headers: HeaderMap) -> Result<Sse<impl Stream<Item = Result<Event, std::convert::Infallible>>>, StatusCode> {
+    commentable(&state, &url_name).ok_or(StatusCode::NOT_FOUND)?;
+    let lang = state.locales.negotiate(&headers);
+    let stream = BroadcastStream::new(state.comments.subscribe())
+        .filter_map(move |comment| comment.ok().filter(|comment| comment.post == url_name))
+        .map(move |comment| {
+            let text = state.locales.text(&lang);
+            let html = thread(&comment.post, std::slice::from_ref(&comment), comment.parent, &[], true, text).into_string();
+            let html = prefs::localize_times(&html, &TimeFormatter::new(user_tz, &headers, text, state.config.time_display));
+            Ok(Event::default().event("comment_added").id(comment.id.to_string()).data(html))
+        });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Adds comments to the page as they come in, replies under their parent and the rest before the form. Ones
+/// already there, like the reader's own, are left alone.
+pub fn live_script(url_name: &str) -> Markup {
+    html! {
+        script data-events=(LIVE_PATH.replace(":url_name", url_name)) { (PreEscaped(r#"
+            new EventSource(document.currentScript.dataset.events).addEventListener('comment_added', (event) => {
+                const comments = document.getElementById('comments');
+                if (!comments || document.getElementById('comment-' + event.lastEventId)) return;
+                const template = document.createElement('template');
+                template.innerHTML = event.data;
+                const comment = template.content.firstElementChild;
+                const parent = comment.dataset.parent && document.getElementById('comment-' + comment.dataset.parent);
+                if (parent) {
+                    (parent.querySelector(':scope > .comment-replies') || parent).appendChild(comment);
+                } else {
+                    comments.querySelector(':scope > .comments-empty')?.remove();
+                    comments.insertBefore(comment, comments.querySelector(':scope > .comment-form'));
+                }
+                if (window.htmx) htmx.process(comment);
+            });
+        "#)) }
+    }
+}
+
+/// A link deleting a comment for a while from `now`, for the admin's notification email
+pub fn moderation_url(key: &Key, origin: &str, id: u64, now: DateTime<Utc>) -> String {
+    let expires = now + Duration::days(MODERATION_DAYS);
+    format!("{}/admin/comments/{}?token={}", origin, id, key.sign(&format!("{}\n{}\n{}", MODERATION_PREFIX, id, expires.timestamp())))
+}
+
+/// Whether a token from [`moderation_url`] is ours, for comment `id` and not expired by `now`
+fn moderation_grants(key: &Key, token: &str, id: u64, now: DateTime<Utc>) -> bool {
+    let Some(value) = key.verify(token) else { return false };
+    let mut lines = value.lines();
+    lines.next() == Some(MODERATION_PREFIX) && lines.next() == Some(id.to_string().as_str()) && lines.next().and_then(|expires| expires.parse::<i64>().ok()).is_some_and(|expires| now.timestamp() < expires)
+}
+
+#[derive(Deserialize)]
+pub struct ModerationToken {
+    #[serde(default)]
+    token: String,
+}
+
+/// The comment at `id`, if the request has the admin token or a moderation link for it
+fn moderated(state: &AppState, id: u64, token: &str, headers: &HeaderMap) -> Result<Comment, StatusCode> {
+    if !Admin::authorized(&state.config, headers) && !moderation_grants(&state.config.cookie_key, token, id, Utc::now()) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    state.comments.get(id).filter(|comment| !comment.deleted).ok_or(StatusCode::NOT_FOUND)
+}
+
+fn moderation_html(comment: &Comment, token: &str, deleted: bool, theme_color: &str, text: Text) -> Html<String> {
+    Html(html! {
+        (maud::DOCTYPE)
+        html lang=(text.lang) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                meta name="robots" content="noindex";
+                meta name="referrer" content="no-referrer";
+                (icons::icon_links(theme_color))
+                title { (text.t("comment_moderate")) }
+                (vendor::stylesheet("bootstrap.min.css"))
+            }
+            body class="container my-4" style="max-width: 800px" {
+                h1 class="h4" { (text.t("comment_moderate")) }
+                @if deleted {
+                    p { (text.t("comment_moderate_deleted")) }
+                } @else {
+                    article class="comment border rounded p-3 my-3" {
+                        p class="small text-muted mb-1" { strong { (comment.author) } " · " (timestamp(&comment.created)) }
+                        p class="mb-0" style="white-space: pre-line" { (comment.body) }
+                    }
+                    form method="post" {
+                        input type="hidden" name="token" value=(token);
+                        button type="submit" class="btn btn-danger" { (text.t("comment_delete")) }
+                    }
+                }
+                p class="mt-3" { a href=(format!("/post/{}#comments", comment.post)) { (text.t("comment_moderate_view")) } }
+            }
+        }
+    }.into_string())
+}
+
+/// `GET /admin/comments/:id?token=`: where the moderation link in a notification email leads, the comment with a
+/// button deleting it. Only the button's `POST` deletes, so mail scanners following links can't.
+pub async fn moderation_page(State(state): State<AppState>, Path(id): Path<u64>, Query(query): Query<ModerationToken>, headers: HeaderMap) -> Result<Html<String>, StatusCode> {
+    let comment = moderated(&state, id, &query.token, &headers)?;
+    let lang = state.locales.negotiate(&headers);
+    Ok(moderation_html(&comment, &query.token, false, &state.config.theme_color, state.locales.text(&lang)))
+}
+
+/// `POST /admin/comments/:id`: deletes the comment, for the admin or whoever has its moderation link
+pub async fn moderate(State(state): State<AppState>, Path(id): Path<u64>, headers: HeaderMap, Form(form): Form<ModerationToken>) -> Result<Html<String>, StatusCode> {
+    moderated(&state, id, &form.token, &headers)?;
+    let comment = state.comments.delete(id).await.ok_or(StatusCode::NOT_FOUND)?;
+    println!("Deleted comment {} on /post/{}", id, comment.post);
+    let lang = state.locales.negotiate(&headers);
+    Ok(moderation_html(&comment, &form.token, true, &state.config.theme_color, state.locales.text(&lang)))
+}
+
+/// `GET /avatar/:hash.svg`: the identicon for an email hash, made here and cached with the assets so readers'
+/// browsers never ask anyone else for it
+pub async fn serve_avatar(State(AppState { cache, .. }): State<AppState>, Path(file): Path<String>) -> Result<Response<Body>, StatusCode> {
+    let hash = file.strip_suffix(".svg").and_then(|hash| hex::decode(hash).ok()).filter(|hash| hash.len() == 32).ok_or(StatusCode::NOT_FOUND)?;
+    let key = format!("/avatar/{}", file);
+    let cached = cache.lock().expect("cdn failed to lock the cache").get(&key).cloned();
+    let svg = match cached {
+        Some(svg) => svg,
+        None => {
+            let svg = placeholder::identicon(&hash).into_bytes();
+            cache.lock().expect("cdn failed to lock the cache").insert(key, svg.clone());
+            svg
+        }
+    };
+
+    // The same hash always makes the same picture
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "image/svg+xml")
+        .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+        .body(Body::from(svg))
+        .unwrap())
+}
+
+#[tokio::test]
+async fn replies_nest_up_to_the_depth_limit() {
+    let dir = std::env::temp_dir().join(format!("caden-blog-comments-{}", std::process::id()));
+    let comments = Comments::load(dir.join("comments.json"));
+
+    let mut parent = None;
+    for level in 0..MAX_DEPTH + 2 {
+        let comment = comments.add("post", parent, "Ann", None, &format!("Level {}", level)).await.unwrap();
+        parent = Some(comment.id);
+    }
+    let parents: Vec<Option<u64>> = comments.on("post").iter().map(|comment| comment.parent).collect();
+    // The two replies past the limit both answer the deepest comment's parent
+    assert_eq!(parents, vec![None, Some(1), Some(2), Some(3), Some(3), Some(3)]);
+    assert!(comments.add("other", Some(1), "Bob", None, "Wrong post").await.is_none());
+    let with_email = comments.add("other", None, "Bob", Some(" Bob@Example.com "), "Hi").await.unwrap();
+    assert_eq!(with_email.email_hash.as_deref(), Some(email_hash("bob@example.com").as_str()));
+    assert!(!std::fs::read_to_string(dir.join("comments.json")).unwrap().contains("example.com"));
+
+    let reloaded = Comments::load(dir.join("comments.json"));
+    assert_eq!(reloaded.on("post").len(), MAX_DEPTH + 2);
+
+    let locales = crate::i18n::Locales::load(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR));
+    let html = widget("post", &reloaded.on("post"), &[], true, locales.text("en")).into_string();
+    assert_eq!(html.matches("<details").count(), 3);
+    assert!(html.contains(r#"hx-get="/post/post/comments/4/reply""#));
+    assert!(widget("post", &[], &[], true, locales.text("en")).into_string().contains("No comments yet."));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn authors_change_their_comments_within_the_window() {
+    let dir = std::env::temp_dir().join(format!("caden-blog-comment-edits-{}", std::process::id()));
+    let comments = Comments::load(dir.join("comments.json"));
+    let first = comments.add("post", None, "Ann", Some("ann@example.com"), "First").await.unwrap();
+    let reply = comments.add("post", Some(first.id), "Bob", None, "Reply").await.unwrap();
+
+    let now = Utc::now();
+    assert!(comments.can_change(&first, &[first.id], now));
+    assert!(!comments.can_change(&first, &[reply.id], now));
+    assert!(!comments.can_change(&first, &[first.id], now + Duration::minutes(DEFAULT_EDIT_MINUTES)));
+    assert_eq!(comments.changeable(&[first.id], now), [first.id]);
+
+    let edited = comments.edit(first.id, " Changed ").await.unwrap();
+    assert_eq!(edited.body, "Changed");
+    assert!(edited.edited.is_some());
+
+    // With a reply it leaves a placeholder, without one it's gone
+    comments.delete(first.id).await.unwrap();
+    comments.delete(reply.id).await.unwrap();
+    let left = Comments::load(dir.join("comments.json")).on("post");
+    assert_eq!(left.len(), 1);
+    assert!(left[0].deleted && left[0].author.is_empty() && left[0].email_hash.is_none() && left[0].body.is_empty());
+    assert!(comments.edit(first.id, "Again").await.is_none() && comments.delete(first.id).await.is_none());
+
+    let locales = crate::i18n::Locales::load(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR));
+    let html = widget("post", &left, &[first.id], true, locales.text("en")).into_string();
+    assert!(html.contains("This comment was deleted.") && html.contains("0 comments") && !html.contains("hx-delete"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn deleted_comments_ids_are_never_handed_out_again() {
+    let dir = std::env::temp_dir().join(format!("caden-blog-comment-ids-{}", std::process::id()));
+    let comments = Comments::load(dir.join("comments.json"));
+    comments.add("post", None, "Ann", None, "Kept").await.unwrap();
+    let deleted = comments.add("post", None, "Ann", None, "Regretted").await.unwrap();
+    comments.delete(deleted.id).await.unwrap();
+
+    // Ann's cookie still lists the deleted comment, which mustn't let her change Bob's
+    let bob = comments.add("post", None, "Bob", None, "Mine").await.unwrap();
+    assert_ne!(bob.id, deleted.id);
+    assert!(!comments.can_change(&bob, &[deleted.id], Utc::now()));
+    comments.delete(bob.id).await.unwrap();
+    let reloaded = Comments::load(dir.join("comments.json"));
+    assert_eq!(reloaded.add("post", None, "Cat", None, "After a restart").await.unwrap().id, bob.id + 1);
+
+    // Files from before the counter was saved start counting after their newest comment
+    std::fs::write(dir.join("old.json"), r#"[{"id": 7, "post": "post", "author": "Ann", "body": "Old", "created": "2024-01-01T00:00:00Z"}]"#).unwrap();
+    assert_eq!(Comments::load(dir.join("old.json")).add("post", None, "Bob", None, "New").await.unwrap().id, 8);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
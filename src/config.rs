@@ -0,0 +1,166 @@
+use std::path::{Path, PathBuf};
+
+use chrono_tz::Tz;
+
+use crate::extract::tz;
+use crate::listen::ServerOptions;
+use crate::outbound::LinkStyle;
+use crate::backdrop::Pattern;
+use crate::prefs::{self, BackgroundSpeed, LayoutMode, TimeDisplay};
+use crate::render::sidebar::Sidebar;
+use crate::{admin, assets, dev, excerpt, exif, icons, og, share, signed, sync, theme};
+
+/// Content directory used when neither `--content-dir` nor `CADEN_BLOG_CONTENT_DIR` names one
+pub const DEFAULT_CONTENT_DIR: &str = "./caden-blog";
+
+/// How the app is served, read from the command line and environment by the binary
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Address to listen on, `CADEN_BLOG_ADDR` for running several instances or the benchmarks next to a dev server.
+    /// `unix:<path>` listens on a Unix domain socket instead, and a socket passed in by systemd takes precedence over both.
+    pub addr: String,
+    /// Live reload and no caching, from `--dev`
+    pub dev: bool,
+    /// Assets up to this many bytes are kept in memory, from `CADEN_BLOG_CACHE_MAX_FILE_SIZE`
+    pub max_cached_size: u64,
+    /// Whether photos are sent without their EXIF and other metadata, from `CADEN_BLOG_STRIP_METADATA`
+    pub strip_metadata: bool,
+    /// `Cache-Control` for directories of assets, from `CADEN_BLOG_ASSET_CACHE_CONTROL`, longest match first
+    pub asset_cache_policies: Vec<(String, String)>,
+    /// Posts, assets, locales and state, from `--content-dir`, then `CADEN_BLOG_CONTENT_DIR`, then `./caden-blog`
+    pub content_dir: PathBuf,
+    /// TLS, HTTP/2, keep-alive and connection limits
+    pub server: ServerOptions,
+    /// Public origin of the site without a trailing slash, from `CADEN_BLOG_SITE_URL`, empty when unset
+    pub site_url: String,
+    /// Zone timestamps are shown in for readers who don't send one, from `CADEN_BLOG_DEFAULT_TZ`
+    pub default_tz: Tz,
+    /// Whether timestamps read as dates or as "2 hours ago" unless a reader picks, from `CADEN_BLOG_TIME_DISPLAY`
+    pub time_display: TimeDisplay,
+    /// How the home page lists posts unless a reader picks, from `CADEN_BLOG_LAYOUT`
+    pub layout: LayoutMode,
+    /// Cards per row in the grid layout on wide screens, from `CADEN_BLOG_POSTS_PER_ROW`
+    pub posts_per_row: usize,
+    /// The pattern behind the pages, from `CADEN_BLOG_BACKGROUND_PATTERN`
+    pub background_pattern: Pattern,
+    /// How fast the pattern scrolls unless a reader picks, from `CADEN_BLOG_BACKGROUND_SPEED`
+    pub background_speed: BackgroundSpeed,
+    /// Code block theme, from `CADEN_BLOG_CODE_THEME`
+    pub code_theme: String,
+    /// Where the Mastodon share button sends readers, from `CADEN_BLOG_MASTODON_SHARE`
+    pub mastodon_share: String,
+    /// Color browsers tint their bars with and social cards are drawn in, from `CADEN_BLOG_THEME_COLOR`
+    pub theme_color: String,
+    /// Characters of a post's summary shown on its card, from `CADEN_BLOG_SUMMARY_CHARS`
+    pub summary_chars: usize,
+    /// How links to other sites are written
+    pub link_style: LinkStyle,
+    /// Bearer token the admin routes ask for, from `CADEN_BLOG_ADMIN_TOKEN`. Without one the admin routes are off.
+    pub admin_token: Option<String>,
+    /// Signs visitor cookies, preview links and outbound redirects
+    pub cookie_key: signed::Key,
+    /// The home page sidebar's widgets, social links and tag cloud size
+    pub sidebar: Sidebar,
+}
+
+impl Config {
+    /// Reads the config and settles on the content directory every path is resolved against. Fails when that
+    /// directory doesn't exist, rather than serving an empty site.
+    pub fn from_env() -> Result<Config, String> {
+        let dir = split_args().0
+            .or_else(|| std::env::var("CADEN_BLOG_CONTENT_DIR").ok())
+            .unwrap_or_else(|| DEFAULT_CONTENT_DIR.to_string());
+
+        // Syncing fills an empty directory from the remote, so there is nothing to check yet
+        if sync::SyncConfig::from_env(Path::new(&dir)).is_some() {
+            std::fs::create_dir_all(&dir).map_err(|e| format!("couldn't create the content directory {}: {}", dir, e))?;
+        }
+        let content_dir = std::fs::canonicalize(&dir)
+            .ok()
+            .filter(|path| path.is_dir())
+            .ok_or_else(|| format!("content directory {} doesn't exist; point --content-dir or CADEN_BLOG_CONTENT_DIR at the directory holding posts/", dir))?;
+
+        Ok(Config {
+            addr: std::env::var("CADEN_BLOG_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_string()),
+            dev: dev::from_args(),
+            max_cached_size: assets::max_cached_size_from_env(),
+            strip_metadata: exif::strip_metadata_from_env(),
+            asset_cache_policies: assets::cache_policies_from_env(),
+            content_dir,
+            server: ServerOptions::from_env(),
+            site_url: og::site_url_from_env(),
+            default_tz: tz::default_tz_from_env(),
+            time_display: TimeDisplay::from_env(),
+            layout: LayoutMode::from_env(),
+            posts_per_row: prefs::posts_per_row_from_env(),
+            background_pattern: Pattern::from_env(),
+            background_speed: BackgroundSpeed::from_env(),
+            code_theme: theme::code_theme_from_env(),
+            mastodon_share: share::mastodon_share_from_env(),
+            theme_color: icons::theme_color_from_env(),
+            summary_chars: excerpt::summary_chars_from_env(),
+            link_style: LinkStyle::from_env(),
+            admin_token: admin::token_from_env(),
+            cookie_key: signed::Key::from_env(),
+            sidebar: Sidebar::from_env(),
+        })
+    }
+
+    /// A file or directory inside the content directory, like `posts` or `state/polls.json`
+    pub fn path(&self, relative: &str) -> PathBuf {
+        self.content_dir.join(relative)
+    }
+
+    /// The command line without the config flags, leaving the subcommand and its arguments
+    pub fn args() -> Vec<String> {
+        split_args().1
+    }
+}
+
+/// Serving `./caden-blog` on the default address with everything else off, the default sidebar and a fresh cookie key,
+/// as tests start from
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            addr: "0.0.0.0:8080".to_string(),
+            dev: false,
+            max_cached_size: assets::DEFAULT_MAX_CACHED_SIZE,
+            strip_metadata: true,
+            asset_cache_policies: Vec::new(),
+            content_dir: PathBuf::from(DEFAULT_CONTENT_DIR),
+            server: ServerOptions::default(),
+            site_url: String::new(),
+            default_tz: Tz::UTC,
+            time_display: TimeDisplay::Absolute,
+            layout: LayoutMode::List,
+            posts_per_row: prefs::DEFAULT_POSTS_PER_ROW,
+            background_pattern: Pattern::None,
+            background_speed: BackgroundSpeed::Normal,
+            code_theme: theme::DEFAULT_THEME.to_string(),
+            mastodon_share: share::DEFAULT_MASTODON_SHARE.to_string(),
+            theme_color: icons::DEFAULT_THEME_COLOR.to_string(),
+            summary_chars: excerpt::EXCERPT_LEN,
+            link_style: LinkStyle::default(),
+            admin_token: None,
+            cookie_key: signed::Key::random(),
+            sidebar: Sidebar::default(),
+        }
+    }
+}
+
+/// Takes `--content-dir <dir>` or `--content-dir=<dir>` out of the command line, wherever it appears
+fn split_args() -> (Option<String>, Vec<String>) {
+    let mut args = std::env::args().skip(1);
+    let mut content_dir = None;
+    let mut rest = Vec::new();
+    while let Some(arg) = args.next() {
+        if arg == "--content-dir" {
+            content_dir = args.next();
+        } else if let Some(dir) = arg.strip_prefix("--content-dir=") {
+            content_dir = Some(dir.to_string());
+        } else {
+            rest.push(arg);
+        }
+    }
+    (content_dir, rest)
+}
@@ -0,0 +1,36 @@
+use std::path::Path;
+
+use rust_embed::Embed;
+
+/// Files compiled into the binary so a fresh deploy works with an empty content directory.
+/// `favicon.ico`, `robots.txt`, the `templates/` scripts and `assets/<name>`, which holds the code block themes, are
+/// used whenever the same path is missing on disk. The client libraries are compiled in separately, by `build.rs`.
+#[derive(Embed)]
+#[folder = "src/defaults/"]
+pub struct Defaults;
+
+/// Reads a file from the `content` directory, falling back to the copy compiled into the binary
+pub fn read(content: &Path, path: &str) -> Option<Vec<u8>> {
+    std::fs::read(content.join(path))
+        .ok()
+        .or_else(|| embedded(path))
+}
+
+/// [`read`] on the blocking pool, for the request path
+pub async fn read_async(content: &Path, path: &str) -> Option<Vec<u8>> {
+    match tokio::fs::read(content.join(path)).await {
+        Ok(contents) => Some(contents),
+        Err(_) => embedded(path),
+    }
+}
+
+/// The compiled in copy of a file, ignoring anything on disk
+pub fn embedded(path: &str) -> Option<Vec<u8>> {
+    Defaults::get(path).map(|file| file.data.into_owned())
+}
+
+#[test]
+fn defaults_include_a_favicon() {
+    assert!(embedded("favicon.ico").is_some_and(|icon| image::load_from_memory(&icon).is_ok()));
+    assert!(embedded("missing.txt").is_none());
+}
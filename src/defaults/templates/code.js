@@ -0,0 +1,34 @@
+// Adds a copy button to every code block, including the ones rendered in the browser after the page loads
+(() => {
+    const labels = document.currentScript.dataset;
+    const addButtons = () => {
+        for (const code of document.querySelectorAll('pre > code')) {
+            const pre = code.parentElement;
+            if (pre.querySelector('.code-copy')) continue;
+            const button = document.createElement('button');
+            button.type = 'button';
+            button.className = 'code-copy';
+            button.textContent = labels.copy;
+            button.addEventListener('click', async () => {
+                try {
+                    await navigator.clipboard.writeText(code.innerText);
+                    button.textContent = labels.copied;
+                    setTimeout(() => { button.textContent = labels.copy; }, 2000);
+                } catch (e) {
+                    console.error('Couldn\'t copy the code', e);
+                }
+            });
+            pre.classList.add('code-block');
+            pre.append(button);
+        }
+    };
+    const start = () => {
+        addButtons();
+        new MutationObserver(addButtons).observe(document.body, { childList: true, subtree: true });
+    };
+    if (document.readyState === 'loading') {
+        document.addEventListener('DOMContentLoaded', start);
+    } else {
+        start();
+    }
+})();
@@ -0,0 +1,81 @@
+// Generated by the server, see pwa.rs. The version changes whenever the precached content or any asset does.
+const VERSION = "__VERSION__";
+const CACHE = "caden-blog-" + VERSION;
+const PRECACHE = __PRECACHE__;
+
+// Pages and assets read along the way, kept across versions for offline reading but only this many of them
+const RUNTIME = "caden-blog-runtime";
+const MAX_RUNTIME_ENTRIES = 100;
+
+// The public pages and assets worth keeping a copy of. Admin pages, previews and everything else are never stored.
+const PUBLIC_PAGES = [/^\/$/, /^\/post\/[^/]+(\/plain)?$/, /^\/(notes|projects|archive|tags|stats)$/];
+const PUBLIC_ASSETS = ["/asset/", "/assets/vendor/", "/theme/", "/thumb/"];
+
+// Fingerprinted names like `code.0123456789ab.css` never change, unlike plain names which can
+const FINGERPRINTED = /\.[0-9a-f]{12}\.[^/.]+$/;
+
+self.addEventListener("install", (event) => {
+    event.waitUntil(
+        caches.open(CACHE)
+            .then((cache) => cache.addAll(PRECACHE))
+            .then(() => self.skipWaiting())
+    );
+});
+
+self.addEventListener("activate", (event) => {
+    event.waitUntil(
+        caches.keys()
+            .then((keys) => Promise.all(keys.filter((key) => key !== CACHE && key !== RUNTIME).map((key) => caches.delete(key))))
+            .then(() => self.clients.claim())
+    );
+});
+
+self.addEventListener("fetch", (event) => {
+    const request = event.request;
+    const url = new URL(request.url);
+    if (request.method !== "GET" || url.origin !== self.location.origin) {
+        return;
+    }
+
+    // Event streams like the new comments never end, so there is nothing to keep a copy of
+    if (request.headers.get("Accept") === "text/event-stream") {
+        return;
+    }
+
+    // Preview and moderation links carry a token, and whatever they show isn't public
+    const asset = PUBLIC_ASSETS.some((prefix) => url.pathname.startsWith(prefix));
+    if (url.searchParams.has("token") || !(asset || PUBLIC_PAGES.some((page) => page.test(url.pathname)))) {
+        return;
+    }
+
+    if (asset && FINGERPRINTED.test(url.pathname)) {
+        event.respondWith(
+            caches.match(request).then((cached) => cached || fetch(request).then((response) => remember(request, response)))
+        );
+        return;
+    }
+
+    // Pages and plainly named assets go to the network first, so a changed one is picked up right away
+    event.respondWith(
+        fetch(request)
+            .then((response) => remember(request, response))
+            .catch(() => caches.match(request)
+                .then((cached) => cached || (request.mode === "navigate" ? caches.match("/") : undefined))
+                .then((cached) => cached || Response.error()))
+    );
+});
+
+function remember(request, response) {
+    const cacheControl = response.headers.get("Cache-Control") || "";
+    if (response.ok && response.type === "basic" && !/private|no-store/.test(cacheControl)) {
+        const copy = response.clone();
+        caches.open(RUNTIME)
+            .then((cache) => cache.delete(request).then(() => cache.put(request, copy)).then(() => trim(cache)));
+    }
+    return response;
+}
+
+// Drops the entries stored longest ago once there are too many, since keys come back in the order they were put
+function trim(cache) {
+    return cache.keys().then((keys) => Promise.all(keys.slice(0, Math.max(0, keys.length - MAX_RUNTIME_ENTRIES)).map((key) => cache.delete(key))));
+}
@@ -0,0 +1,105 @@
+use std::convert::Infallible;
+use std::time::{Duration, SystemTime};
+
+use axum::extract::{Request, State};
+use axum::http::header::CACHE_CONTROL;
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::Response;
+use maud::{html, Markup, PreEscaped};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::state::AppState;
+
+/// Where the reload script listens for changes
+pub const RELOAD_PATH: &str = "/dev/reload";
+
+/// How often the content directory is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Whether the server was started with `--dev`, read once into [`Config::dev`](crate::Config::dev)
+pub fn from_args() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--dev")
+}
+
+/// Reloads the browser whenever the server reports a change, only included in dev mode
+pub fn reload_script(dev: bool) -> Markup {
+    html! {
+        @if dev {
+            script { (PreEscaped(format!("new EventSource('{}').addEventListener('reload', () => location.reload());", RELOAD_PATH))) }
+        }
+    }
+}
+
+/// Reloads the posts and notes and drops every cache before each request, keeping the previous content when they fail
+/// to load, then stops the browser caching the response
+pub async fn reload(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if request.uri().path() != RELOAD_PATH && request.uri().path() != crate::events::EVENTS_PATH {
+        // Half-saved edits are common while writing, so the last content that loaded keeps serving until they're fixed
+        if let Err(e) = state.reload_index().await {
+            println!("Couldn't reload the content, still serving what loaded last: {}", e);
+        }
+    }
+
+    let mut response = next.run(request).await;
+    response.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    response
+}
+
+/// The newest modification time and number of files under a directory, which changes whenever anything in it does
+fn fingerprint(dir: &std::path::Path) -> (Option<SystemTime>, usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return (None, 0) };
+
+    entries.flatten().fold((None, 0), |(newest, count), entry| {
+        let (modified, files) = match entry.file_type() {
+            Ok(kind) if kind.is_dir() => fingerprint(&entry.path()),
+            _ => (entry.metadata().and_then(|metadata| metadata.modified()).ok(), 1),
+        };
+        (newest.max(modified), count + files)
+    })
+}
+
+/// [`fingerprint`] on the blocking pool
+async fn fingerprint_async(dir: &std::path::Path) -> Option<(Option<SystemTime>, usize)> {
+    let dir = dir.to_path_buf();
+    tokio::task::spawn_blocking(move || fingerprint(&dir)).await.ok()
+}
+
+/// Polls the content directory `dir` and announces every change to the connected browsers
+pub async fn watch(changes: broadcast::Sender<()>, dir: std::path::PathBuf) {
+    let mut last = fingerprint_async(&dir).await;
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let current = fingerprint_async(&dir).await;
+        if current != last {
+            last = current;
+            println!("Content changed, reloading browsers");
+            // Nobody listening just means no tab is open
+            let _ = changes.send(());
+        }
+    }
+}
+
+pub async fn events(changes: broadcast::Sender<()>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(changes.subscribe()).map(|_| Ok(Event::default().event("reload").data("changed")));
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+#[test]
+fn fingerprint_counts_nested_files() {
+    let dir = std::env::temp_dir().join(format!("caden-blog-dev-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    std::fs::write(dir.join("a.css"), "a").unwrap();
+    std::fs::write(dir.join("nested").join("b.json"), "b").unwrap();
+
+    let (newest, count) = fingerprint(&dir);
+    assert_eq!(count, 2);
+    assert!(newest.is_some());
+    assert_eq!(fingerprint(&dir.join("missing")), (None, 0));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+/// Shortcode names and their emoji, bundled so posts written in editors that keep `:rocket:` as text render the
+/// same as ones that insert 🚀
+fn table() -> &'static HashMap<String, String> {
+    static TABLE: OnceLock<HashMap<String, String>> = OnceLock::new();
+    TABLE.get_or_init(|| toml::from_str(include_str!("emoji.toml")).expect("the emoji table is valid toml"))
+}
+
+pub fn lookup(name: &str) -> Option<&'static str> {
+    table().get(name).map(String::as_str)
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '+' | '-'))
+}
+
+/// Where the known `:shortcodes:` are in some text and the emoji each stands for. A colon that doesn't open a
+/// known one can still close the one before or open the next, so times like `10:30` stay as they are.
+pub fn shortcodes(text: &str) -> Vec<(Range<usize>, &'static str)> {
+    let mut found = Vec::new();
+    let mut search = 0;
+    while let Some(open) = text[search..].find(':').map(|at| search + at) {
+        let Some(close) = text[open + 1..].find(':').map(|at| open + 1 + at) else { break };
+        match Some(&text[open + 1..close]).filter(|name| valid_name(name)).and_then(lookup) {
+            Some(emoji) => {
+                found.push((open..close + 1, emoji));
+                search = close + 1;
+            }
+            None => search = close,
+        }
+    }
+    found
+}
+
+#[test]
+fn known_shortcodes_become_emoji() {
+    assert_eq!(lookup("rocket"), Some("🚀"));
+    assert_eq!(lookup("+1"), Some("👍"));
+    assert_eq!(shortcodes("Launch :rocket::tada: at 10:30:fire: :not_an_emoji: :Rocket:"), vec![(7..15, "🚀"), (15..21, "🎉"), (30..36, "🔥")]);
+    assert!(shortcodes("no colons, or one: here").is_empty());
+}
@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+use std::convert::Infallible;
+
+use axum::extract::State;
+use axum::response::sse::{Event, KeepAlive, Sse};
+use maud::{html, Markup, PreEscaped};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+
+use crate::model::post::{keep_renderings, Post};
+use crate::state::AppState;
+use crate::store::PostIndex;
+
+/// Where pages subscribe to site events
+pub const EVENTS_PATH: &str = "/events";
+
+/// Sends the `url_name` of every newly published post to the subscribed readers
+pub type Publisher = broadcast::Sender<String>;
+
+pub fn publisher() -> Publisher {
+    broadcast::channel(16).0
+}
+
+/// Swaps in a freshly loaded post index, keeping the renderings of unchanged posts, and announces every post that
+/// wasn't in the old one
+pub fn replace_posts(posts: &PostIndex, mut loaded: Vec<Post>, publisher: &Publisher, content: &std::path::Path) {
+    let mut posts = posts.write().expect("failed to lock the post index");
+    keep_renderings(&posts, &mut loaded, content);
+    let known: HashSet<&str> = posts.iter().map(|post| post.url_name.as_str()).collect();
+    let published: Vec<String> = new_posts(&known, &loaded);
+    *posts = loaded;
+    drop(posts);
+
+    for url_name in published {
+        println!("Published /post/{}", url_name);
+        // Nobody listening just means no tab is open
+        let _ = publisher.send(url_name);
+    }
+}
+
+/// Names of the listed posts missing from `known`, oldest first so the newest ends up on top when prepended
+fn new_posts(known: &HashSet<&str>, loaded: &[Post]) -> Vec<String> {
+    let mut published: Vec<&Post> = loaded.iter().filter(|post| post.listed() && !known.contains(post.url_name.as_str())).collect();
+    published.sort_by_key(|post| post.timestamp);
+    published.dedup_by(|a, b| a.url_name == b.url_name);
+    published.into_iter().map(|post| post.url_name.clone()).collect()
+}
+
+pub async fn events(State(AppState { publisher, .. }): State<AppState>) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(publisher.subscribe())
+        .filter_map(|url_name| url_name.ok())
+        .map(|url_name| Ok(Event::default().event("post_published").data(url_name)));
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Prepends the card of each newly published post to the home page listing
+pub fn subscribe_script() -> Markup {
+    html! {
+        script { (PreEscaped(r#"
+            new EventSource('/events').addEventListener('post_published', (event) => {
+                const posts = document.getElementById('posts');
+                if (!posts || posts.querySelector(`[data-post="${CSS.escape(event.data)}"]`)) return;
+                fetch('/fragment/card/' + encodeURIComponent(event.data), { headers: { 'X-Up-Target': '#posts' } })
+                    .then((response) => response.ok ? response.text() : '')
+                    .then((card) => {
+                        if (!card) return;
+                        posts.insertAdjacentHTML('afterbegin', card);
+                        if (window.up) up.hello(posts.firstElementChild);
+                    });
+            });
+        "#)) }
+    }
+}
+
+#[test]
+fn only_unknown_posts_are_announced() {
+    let post = |url_name: &str, day: u32| {
+        let mut post: Post = serde_json::from_str(&format!(
+            r#"{{"title":"","body":"","image_url":"","summary":"","timestamp":"2024-01-{:02}T00:00:00Z"}}"#,
+            day
+        ))
+        .unwrap();
+        post.url_name = url_name.to_string();
+        post
+    };
+
+    let known: HashSet<&str> = ["old"].into_iter().collect();
+    let loaded = vec![post("newest", 3), post("old", 1), post("newer", 2), post("newer", 2)];
+    assert_eq!(new_posts(&known, &loaded), vec!["newer", "newest"]);
+}
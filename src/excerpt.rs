@@ -0,0 +1,75 @@
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+
+/// Length budget for generated summaries, in characters
+pub const EXCERPT_LEN: usize = 200;
+
+/// Length budget for summaries shown on cards, from `CADEN_BLOG_SUMMARY_CHARS`, defaulting to [`EXCERPT_LEN`].
+/// Longer summaries are cut with an ellipsis and keep their full text in a tooltip.
+pub fn summary_chars_from_env() -> usize {
+    std::env::var("CADEN_BLOG_SUMMARY_CHARS")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|chars| *chars > 0)
+        .unwrap_or(EXCERPT_LEN)
+}
+
+/// Plain text of the first paragraph of a markdown document, truncated at a word boundary with an ellipsis
+pub fn excerpt(markdown: &str, max_chars: usize) -> String {
+    let mut text = String::new();
+    let mut in_paragraph = false;
+
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Start(Tag::Paragraph) => in_paragraph = true,
+            Event::End(TagEnd::Paragraph) if !text.trim().is_empty() => break,
+            Event::End(TagEnd::Paragraph) => in_paragraph = false,
+            Event::Text(content) | Event::Code(content) if in_paragraph => text.push_str(&content),
+            Event::SoftBreak | Event::HardBreak if in_paragraph => text.push(' '),
+            _ => {}
+        }
+    }
+
+    truncate_words(text.split_whitespace().collect::<Vec<_>>().join(" ").as_str(), max_chars)
+}
+
+/// Plain text of a whole markdown document with its whitespace collapsed, for searching
+pub fn plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(content) | Event::Code(content) => text.push_str(&content),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            // Blocks end with a space so words either side don't run together, inline markup doesn't
+            Event::End(end) if !matches!(end, TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough | TagEnd::Link | TagEnd::Image) => text.push(' '),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Cuts text down to `max_chars`, backing up to the last whole word and adding an ellipsis when anything was cut
+pub fn truncate_words(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let cut: String = text.chars().take(max_chars).collect();
+    let cut = match cut.rfind(' ') {
+        Some(space) if space > 0 => &cut[..space],
+        _ => cut.as_str(),
+    };
+    format!("{}…", cut.trim_end_matches(|c: char| c.is_ascii_punctuation() || c.is_whitespace()))
+}
+
+#[test]
+fn excerpt_uses_first_paragraph_as_plain_text() {
+    let markdown = "# Title\n\n```\ncode\n```\n\nSome *emphasis* and `code` with a [link](https://example.com).\nSame paragraph.\n\nSecond paragraph.";
+    assert_eq!(excerpt(markdown, 200), "Some emphasis and code with a link. Same paragraph.");
+    assert_eq!(excerpt(markdown, 20), "Some emphasis and…");
+    assert_eq!(excerpt("# Only a heading", 200), "");
+    assert_eq!(plain_text(markdown), "Title code Some emphasis and code with a link. Same paragraph. Second paragraph.");
+
+    let long = "word ".repeat(100);
+    assert_eq!(truncate_words(&long, 12), "word word…");
+    assert_eq!(truncate_words("short summary", 200), "short summary");
+}
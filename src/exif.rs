@@ -0,0 +1,371 @@
+/// Whether photos lose their metadata on the way out, on unless `CADEN_BLOG_STRIP_METADATA` is `off`. Phones
+/// write where a photo was taken into it, which nobody means to publish with a picture of their workbench.
+pub fn strip_metadata_from_env() -> bool {
+    !matches!(std::env::var("CADEN_BLOG_STRIP_METADATA").unwrap_or_default().trim(), "off" | "false" | "0")
+}
+
+/// Biggest photo read into memory to be cleaned, anything larger is sent as it is
+pub const MAX_SIZE: usize = 64 * 1024 * 1024;
+
+/// Whether the asset is a photo to clean before it's sent
+pub fn applies(name: &str) -> bool {
+    matches!(crate::assets::content_type(name), Some("image/jpeg" | "image/png" | "image/webp" | "image/avif"))
+}
+
+/// The asset's contents, without the metadata when it's a photo [`applies`] to
+pub fn clean(name: &str, contents: Vec<u8>) -> Vec<u8> {
+    if !applies(name) {
+        return contents;
+    }
+    strip(&contents).unwrap_or(contents)
+}
+
+/// The image without its EXIF, XMP, IPTC and text metadata, or `None` when it isn't a JPEG, PNG, WebP or AVIF we
+/// can take apart. A JPEG keeps its orientation, since phones store sideways photos with a note to turn them.
+pub fn strip(image: &[u8]) -> Option<Vec<u8>> {
+    if image.starts_with(b"\xFF\xD8") {
+        strip_jpeg(image)
+    } else if image.starts_with(b"\x89PNG\r\n\x1a\n") {
+        strip_png(image)
+    } else if image.starts_with(b"RIFF") && image.get(8..12) == Some(b"WEBP") {
+        strip_webp(image)
+    } else if image.get(4..8) == Some(b"ftyp") {
+        strip_avif(image)
+    } else {
+        None
+    }
+}
+
+const APP1: u8 = 0xE1;
+const APP13: u8 = 0xED;
+const COMMENT: u8 = 0xFE;
+/// Start of scan, after which comes the compressed image and nothing we want to look at
+const SOS: u8 = 0xDA;
+
+fn strip_jpeg(image: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = image[..2].to_vec();
+    let mut at = 2;
+    loop {
+        if *image.get(at)? != 0xFF {
+            return None;
+        }
+        let marker = *image.get(at + 1)?;
+        match marker {
+            // Padding before a marker
+            0xFF => {
+                at += 1;
+                continue;
+            }
+            SOS | 0xD9 => {
+                stripped.extend_from_slice(&image[at..]);
+                return Some(stripped);
+            }
+            // Markers without a length
+            0x01 | 0xD0..=0xD7 => {
+                stripped.extend_from_slice(&image[at..at + 2]);
+                at += 2;
+                continue;
+            }
+            _ => {}
+        }
+        // The length counts its own two bytes, so anything shorter is broken
+        let len = u16::from_be_bytes([*image.get(at + 2)?, *image.get(at + 3)?]) as usize;
+        let segment = image.get(at..at + 2 + len).filter(|_| len >= 2)?;
+        match marker {
+            APP1 => {
+                if let Some(orientation) = segment[4..].strip_prefix(b"Exif\0\0").and_then(orientation).filter(|orientation| *orientation != 1) {
+                    stripped.extend_from_slice(&orientation_segment(orientation));
+                }
+            }
+            APP13 | COMMENT => {}
+            _ => stripped.extend_from_slice(segment),
+        }
+        at += 2 + len;
+    }
+}
+
+/// The orientation tag from the first directory of an EXIF block
+fn orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let u16_at = |at: usize| tiff.get(at..at + 2).map(|bytes| if big_endian { u16::from_be_bytes([bytes[0], bytes[1]]) } else { u16::from_le_bytes([bytes[0], bytes[1]]) });
+    let u32_at = |at: usize| tiff.get(at..at + 4).map(|bytes| {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+    });
+
+    let directory = u32_at(4)? as usize;
+    (0..u16_at(directory)? as usize).map(|entry| directory + 2 + entry * 12).find(|entry| u16_at(*entry) == Some(0x0112)).and_then(|entry| u16_at(entry + 8)).filter(|orientation| (1..=8).contains(orientation))
+}
+
+/// An EXIF block holding nothing but the orientation
+fn orientation_segment(orientation: u16) -> Vec<u8> {
+    let mut segment = vec![0xFF, APP1, 0, 34];
+    segment.extend_from_slice(b"Exif\0\0MM\0\x2A\0\0\0\x08");
+    // One entry: tag 0x0112, type SHORT, one value, then no next directory
+    segment.extend_from_slice(&[0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1]);
+    segment.extend_from_slice(&orientation.to_be_bytes());
+    segment.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    segment
+}
+
+/// PNG chunks that carry metadata rather than pixels
+const PNG_METADATA: [&[u8; 4]; 4] = [b"eXIf", b"tEXt", b"zTXt", b"iTXt"];
+
+fn strip_png(image: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = image[..8].to_vec();
+    let mut at = 8;
+    while at < image.len() {
+        let len = u32::from_be_bytes(image.get(at..at + 4)?.try_into().ok()?) as usize;
+        // Length, type, data and checksum
+        let chunk = image.get(at..at + 12 + len)?;
+        if !PNG_METADATA.iter().any(|kind| &chunk[4..8] == *kind) {
+            stripped.extend_from_slice(chunk);
+        }
+        at += 12 + len;
+    }
+    Some(stripped)
+}
+
+/// RIFF chunks of a WebP that carry metadata rather than pixels
+const WEBP_METADATA: [&[u8; 4]; 2] = [b"EXIF", b"XMP "];
+/// The bits of the `VP8X` header's flags saying EXIF and XMP chunks follow
+const WEBP_METADATA_FLAGS: u8 = 0x08 | 0x04;
+
+fn strip_webp(image: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = image[..12].to_vec();
+    let mut at = 12;
+    while at < image.len() {
+        let len = u32::from_le_bytes(image.get(at + 4..at + 8)?.try_into().ok()?) as usize;
+        // Type, length and data, padded to an even length
+        let end = at.checked_add(8 + len)?;
+        let chunk = image.get(at..(end + len % 2).min(image.len())).filter(|_| end <= image.len())?;
+        if !WEBP_METADATA.iter().any(|kind| &chunk[..4] == *kind) {
+            let start = stripped.len();
+            stripped.extend_from_slice(chunk);
+            if &chunk[..4] == b"VP8X" && chunk.len() > 8 {
+                stripped[start + 8] &= !WEBP_METADATA_FLAGS;
+            }
+        }
+        at += chunk.len();
+    }
+    // The RIFF header counts everything after its own eight bytes
+    let size = u32::try_from(stripped.len() - 8).ok()?;
+    stripped[4..8].copy_from_slice(&size.to_le_bytes());
+    Some(stripped)
+}
+
+/// A box of an ISO media file like AVIF, with where its contents start and end in the file
+struct IsoBox<'a> {
+    kind: &'a [u8],
+    start: usize,
+    end: usize,
+}
+
+/// A big endian number of `size` bytes, where no bytes is zero
+fn uint(data: &[u8], at: usize, size: usize) -> Option<u64> {
+    data.get(at..at.checked_add(size)?).map(|bytes| bytes.iter().fold(0, |number, byte| number << 8 | *byte as u64))
+}
+
+/// The boxes one after another from `at` to `end`
+fn iso_boxes(data: &[u8], mut at: usize, end: usize) -> Option<Vec<IsoBox<'_>>> {
+    let mut boxes = Vec::new();
+    while at < end {
+        let kind = data.get(at + 4..at + 8)?;
+        let (header, size) = match uint(data, at, 4)? {
+            0 => (8, end - at),
+            1 => (16, usize::try_from(uint(data, at + 8, 8)?).ok()?),
+            size => (8, size as usize),
+        };
+        if size < header || at.checked_add(size)? > end {
+            return None;
+        }
+        boxes.push(IsoBox { kind, start: at + header, end: at + size });
+        at += size;
+    }
+    Some(boxes)
+}
+
+/// An AVIF keeps its EXIF and XMP as items of the `meta` box, found through tables of offsets that would all need
+/// rewriting if the items were cut out. They're blanked with zeros instead, leaving everything where it was.
+fn strip_avif(image: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = image.to_vec();
+    let Some(meta) = iso_boxes(image, 0, image.len())?.into_iter().find(|found| found.kind == b"meta") else { return Some(stripped) };
+    // A full box, with a version and flags before its children
+    let children = iso_boxes(image, meta.start + 4, meta.end)?;
+    let child = |kind: &[u8]| children.iter().find(|found| found.kind == kind);
+    let items = match child(b"iinf") {
+        Some(iinf) => metadata_items(image, iinf)?,
+        None => Vec::new(),
+    };
+    if items.is_empty() {
+        return Some(stripped);
+    }
+    for (start, end) in item_extents(image, child(b"iloc")?, child(b"idat").map(|idat| idat.start), &items)? {
+        stripped.get_mut(start..end.min(image.len()))?.fill(0);
+    }
+    Some(stripped)
+}
+
+/// The ids of the EXIF and XMP items an `iinf` box lists
+fn metadata_items(image: &[u8], iinf: &IsoBox) -> Option<Vec<u64>> {
+    let count_size = if *image.get(iinf.start)? == 0 { 2 } else { 4 };
+    let mut items = Vec::new();
+    for infe in iso_boxes(image, iinf.start + 4 + count_size, iinf.end)?.iter().filter(|found| found.kind == b"infe") {
+        // Entries before version 2 don't say what type their item is
+        let version = *image.get(infe.start)?;
+        if version < 2 {
+            continue;
+        }
+        let id_size = if version == 2 { 2 } else { 4 };
+        let id = uint(image, infe.start + 4, id_size)?;
+        // After the id comes the protection index, then the type
+        let at = infe.start + 4 + id_size + 2;
+        let kind = image.get(at..at + 4)?;
+        // A mime item is named and then typed, each ending in a zero
+        let xmp = kind == b"mime" && image.get(at + 4..infe.end)?.split(|byte| *byte == 0).nth(1) == Some(b"application/rdf+xml");
+        if kind == b"Exif" || xmp {
+            items.push(id);
+        }
+    }
+    Some(items)
+}
+
+/// Where the data of `items` is in the file, from an `iloc` box and the start of the `idat` box's contents
+fn item_extents(image: &[u8], iloc: &IsoBox, idat: Option<usize>, items: &[u64]) -> Option<Vec<(usize, usize)>> {
+    let version = *image.get(iloc.start)?;
+    let sizes = *image.get(iloc.start + 4)?;
+    let more_sizes = *image.get(iloc.start + 5)?;
+    let (offset_size, length_size, base_size) = ((sizes >> 4) as usize, (sizes & 0xF) as usize, (more_sizes >> 4) as usize);
+    let index_size = if version > 0 { (more_sizes & 0xF) as usize } else { 0 };
+    let id_size = if version < 2 { 2 } else { 4 };
+
+    let mut at = iloc.start + 6;
+    let count = uint(image, at, id_size)?;
+    at += id_size;
+    let mut extents = Vec::new();
+    for _ in 0..count {
+        let id = uint(image, at, id_size)?;
+        at += id_size;
+        // 0 for offsets into the file, 1 for offsets into `idat`
+        let method = if version > 0 { uint(image, at, 2)? & 0xF } else { 0 };
+        if version > 0 {
+            at += 2;
+        }
+        // Skipping the data reference index
+        let base = uint(image, at + 2, base_size)?;
+        at += 2 + base_size;
+        let extent_count = uint(image, at, 2)?;
+        at += 2;
+        for _ in 0..extent_count {
+            at += index_size;
+            let offset = uint(image, at, offset_size)?;
+            let length = uint(image, at + offset_size, length_size)?;
+            at += offset_size + length_size;
+            let origin = match method {
+                0 => 0,
+                1 => idat? as u64,
+                _ => continue,
+            };
+            if items.contains(&id) {
+                let start = usize::try_from(origin.checked_add(base)?.checked_add(offset)?).ok()?;
+                // No length means the rest of the file
+                let end = if length == 0 { image.len() } else { start.checked_add(usize::try_from(length).ok()?)? };
+                extents.push((start, end));
+            }
+        }
+    }
+    Some(extents)
+}
+
+#[test]
+fn photos_lose_their_metadata_but_keep_their_orientation() {
+    use std::io::Cursor;
+
+    let mut jpeg = Vec::new();
+    image::DynamicImage::new_rgb8(4, 2).write_to(&mut Cursor::new(&mut jpeg), image::ImageFormat::Jpeg).unwrap();
+    // An EXIF block in little endian with the orientation and a GPS pointer, then a comment
+    let mut tiff = b"II\x2A\0\x08\0\0\0\x02\0".to_vec();
+    tiff.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0]);
+    tiff.extend_from_slice(&[0x25, 0x88, 4, 0, 1, 0, 0, 0, 0x26, 0, 0, 0]);
+    tiff.extend_from_slice(b"\0\0\0\0GPS 51.5N 0.1W");
+    let mut exif = vec![0xFF, APP1];
+    exif.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+    exif.extend_from_slice(b"Exif\0\0");
+    exif.extend_from_slice(&tiff);
+    exif.extend_from_slice(b"\xFF\xFE\0\x08secret");
+    let tagged: Vec<u8> = [&jpeg[..2], &exif, &jpeg[2..]].concat();
+    assert_eq!(orientation(&tiff), Some(6));
+
+    let stripped = strip(&tagged).unwrap();
+    let text = String::from_utf8_lossy(&stripped);
+    assert!(!text.contains("GPS") && !text.contains("secret"));
+    assert_eq!(stripped.len(), jpeg.len() + orientation_segment(6).len());
+    assert_eq!(orientation(&orientation_segment(6)[10..]), Some(6));
+    let decoded = image::load_from_memory(&stripped).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (4, 2));
+    // Nothing to keep from an upright photo's metadata
+    assert_eq!(strip(&jpeg).unwrap(), jpeg);
+
+    let mut png = Vec::new();
+    image::DynamicImage::new_rgba8(2, 2).write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+    let text_chunk = b"\0\0\0\x0atEXtComment\0hi\0\0\0\0";
+    let tagged: Vec<u8> = [&png[..33], &text_chunk[..], &png[33..]].concat();
+    assert_eq!(strip(&tagged).unwrap(), png);
+    assert_eq!(strip(b"GIF89a"), None);
+    assert_eq!(strip(&tagged[..40]), None);
+}
+
+#[test]
+fn webp_and_avif_photos_lose_their_metadata_too() {
+    let riff = |chunks: &[&[u8]]| {
+        let body: Vec<u8> = [&b"WEBP"[..], &chunks.concat()].concat();
+        [&b"RIFF"[..], &(body.len() as u32).to_le_bytes(), &body].concat()
+    };
+    let chunk = |kind: &[u8; 4], data: &[u8]| {
+        let padding: &[u8] = if data.len() % 2 == 1 { b"\0" } else { b"" };
+        [&kind[..], &(data.len() as u32).to_le_bytes(), data, padding].concat()
+    };
+    // A one pixel photo, as it was encoded and then with EXIF carrying GPS and XMP added by a phone
+    let plain = std::fs::read("tests/fixtures/assets/photo.webp").unwrap();
+    let pixels = &plain[12..];
+    assert_eq!(strip(&plain).unwrap(), plain);
+    let header = chunk(b"VP8X", &[0x08 | 0x04 | 0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let exif = chunk(b"EXIF", b"MM\0\x2A\0\0\0\x08GPS 51.5N 0.1W");
+    let xmp = chunk(b"XMP ", b"<x:xmpmeta>secret</x:xmpmeta>");
+    let tagged = riff(&[&header, pixels, &exif, &xmp]);
+
+    let stripped = strip(&tagged).unwrap();
+    let text = String::from_utf8_lossy(&stripped);
+    assert!(!text.contains("GPS") && !text.contains("secret"));
+    // Only the alpha flag is left, and the RIFF size counts what's left
+    assert_eq!(stripped, riff(&[&chunk(b"VP8X", &[0x10, 0, 0, 0, 0, 0, 0, 0, 0, 0]), pixels]));
+    assert_eq!(strip(&tagged[..tagged.len() - 4]), None);
+
+    // An AVIF with the picture and its EXIF both in `mdat`, found through `iinf` and `iloc`
+    let iso_box = |kind: &[u8; 4], contents: &[u8]| [&((contents.len() + 8) as u32).to_be_bytes()[..], kind, contents].concat();
+    let full_box = |kind: &[u8; 4], version: u8, contents: &[u8]| iso_box(kind, &[&[version, 0, 0, 0][..], contents].concat());
+    let infe = |id: u8, kind: &[u8; 4]| full_box(b"infe", 2, &[&[0, id, 0, 0][..], kind, b"\0"].concat());
+    let iinf = full_box(b"iinf", 0, &[&[0, 2][..], &infe(1, b"av01"), &infe(2, b"Exif")].concat());
+    let picture = b"AV1 pixels";
+    let gps = b"\0\0\0\0MM\0\x2A\0\0\0\x08GPS 51.5N 0.1W";
+    let build = |mdat_at: u32| {
+        // Four byte offsets and lengths, no base offset, two items of one extent each
+        let iloc = full_box(b"iloc", 0, &[&[0x44, 0x00, 0, 2][..], &[0, 1, 0, 0, 0, 1], &mdat_at.to_be_bytes(), &(picture.len() as u32).to_be_bytes(), &[0, 2, 0, 0, 0, 1], &(mdat_at + picture.len() as u32).to_be_bytes(), &(gps.len() as u32).to_be_bytes()].concat());
+        let meta = full_box(b"meta", 0, &[full_box(b"hdlr", 0, b"\0\0\0\0pict\0\0\0\0\0\0\0\0\0\0\0\0\0"), iinf.clone(), iloc].concat());
+        [iso_box(b"ftyp", b"avifmif1"), meta, iso_box(b"mdat", &[&picture[..], gps].concat())].concat()
+    };
+    let mdat_at = build(0).len() as u32 - (picture.len() + gps.len()) as u32;
+    let avif = build(mdat_at);
+    assert_eq!(&avif[mdat_at as usize..][..picture.len()], picture);
+
+    let stripped = strip(&avif).unwrap();
+    assert_eq!(stripped.len(), avif.len());
+    assert!(!String::from_utf8_lossy(&stripped).contains("GPS"));
+    assert_eq!(&stripped[..avif.len() - gps.len()], &avif[..avif.len() - gps.len()]);
+    assert!(stripped[avif.len() - gps.len()..].iter().all(|byte| *byte == 0));
+    assert!(applies("photo.avif") && applies("posts/photo.webp"));
+}
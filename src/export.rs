@@ -0,0 +1,180 @@
+use std::path::Path;
+
+use crate::assets::{self, AssetStore};
+use crate::model::post::{split_translation, Post};
+use crate::store::posts::list_files_in_directory;
+use crate::config::Config;
+use crate::paths;
+
+/// Characters that end an `/asset/<name>` reference in markdown, HTML or a shortcode
+const REFERENCE_END: [char; 9] = [')', '"', '\'', ' ', '\n', '>', '?', '#', ']'];
+
+/// `caden-blog export-content <dir>`: writes every post out as `<dir>/posts/<slug>/index.md` with YAML front
+/// matter, next to copies of the assets it uses, so `caden-blog import`, Hugo or Jekyll can read it back
+pub async fn export(config: &Config, target: &str) -> std::process::ExitCode {
+    if target.trim().is_empty() {
+        println!("usage: caden-blog export-content <directory>");
+        return std::process::ExitCode::FAILURE;
+    }
+
+    let target = Path::new(target).join("posts");
+    let store = assets::from_env(&config.content_dir);
+    let source = config.path("posts");
+    let mut files: Vec<String> = list_files_in_directory(&source).into_iter().filter(|name| name.ends_with(".json")).collect();
+    files.sort();
+
+    let (mut exported, mut failed) = (0, 0);
+    for file in files {
+        // Read the files as stored rather than through the index, which fills in a missing summary
+        let post = match std::fs::read_to_string(source.join(&file)).map_err(|e| e.to_string()).and_then(|json| serde_json::from_str::<Post>(&json).map_err(|e| e.to_string())) {
+            Ok(post) => post,
+            Err(e) => {
+                println!("Skipping {}: {}", file, e);
+                failed += 1;
+                continue;
+            }
+        };
+        let (url_name, file_lang) = split_translation(file.trim_end_matches(".json"));
+        let bundle = target.join(post.slug.as_deref().unwrap_or(url_name));
+        let index = match file_lang {
+            Some(lang) => format!("index.{}.md", lang),
+            None => "index.md".to_string(),
+        };
+
+        match write_bundle(&bundle, &index, &post, store.as_ref()).await {
+            Ok(()) => exported += 1,
+            Err(e) => {
+                println!("Couldn't export {}: {}", file, e);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("Exported {} posts to {}, {} failed", exported, target.display(), failed);
+    if failed == 0 {
+        std::process::ExitCode::SUCCESS
+    } else {
+        std::process::ExitCode::FAILURE
+    }
+}
+
+/// Copies the assets a post uses into its bundle and writes the markdown pointing at the copies
+async fn write_bundle(bundle: &Path, index: &str, post: &Post, store: &dyn AssetStore) -> Result<(), String> {
+    tokio::fs::create_dir_all(bundle).await.map_err(|e| format!("couldn't create {}: {}", bundle.display(), e))?;
+
+    let mut copied = Vec::new();
+    let media: Vec<String> = post.audio.iter().chain(&post.video).flat_map(|media| std::iter::once(media.url("")).chain(media.poster_url(""))).chain(post.image_poster.clone()).collect();
+    for name in asset_references(&format!("{}\n{}\n{}", post.image_url, media.join("\n"), post.body)) {
+        let Some(path) = paths::contained(bundle, &name) else { continue };
+        let bytes = match store.stream(&name, None).await {
+            Ok(asset) => axum::body::to_bytes(asset.body, usize::MAX).await.map_err(|e| format!("couldn't read the asset {}: {}", name, e))?,
+            Err(_) => {
+                println!("warning: {} uses the missing asset {}, leaving the link as it is", post.title, name);
+                continue;
+            }
+        };
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| format!("couldn't create {}: {}", parent.display(), e))?;
+        }
+        tokio::fs::write(&path, bytes).await.map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+        copied.push(name);
+    }
+
+    let path = bundle.join(index);
+    tokio::fs::write(&path, to_markdown(post, &copied)).await.map_err(|e| format!("couldn't write {}: {}", path.display(), e))
+}
+
+/// The names of the assets `text` links to as `/asset/<name>`, each once
+fn asset_references(text: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (at, _) in text.match_indices("/asset/") {
+        let rest = &text[at + "/asset/".len()..];
+        let name = &rest[..rest.find(REFERENCE_END).unwrap_or(rest.len())];
+        if !name.is_empty() && !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// The post as markdown with YAML front matter, with links to the `copied` assets made relative to the bundle
+fn to_markdown(post: &Post, copied: &[String]) -> String {
+    let relative = |text: &str| copied.iter().fold(text.to_string(), |text, name| text.replace(&format!("/asset/{}", name), name));
+
+    let mut front = vec![format!("title: {}", yaml_string(&post.title)), format!("date: {}", post.timestamp.to_rfc3339())];
+    if let Some(updated) = post.updated {
+        front.push(format!("lastmod: {}", updated.to_rfc3339()));
+    }
+    if let Some(slug) = &post.slug {
+        front.push(format!("slug: {}", yaml_string(slug)));
+    }
+    if let Some(lang) = &post.lang {
+        front.push(format!("lang: {}", yaml_string(lang)));
+    }
+    if !post.summary.trim().is_empty() {
+        front.push(format!("description: {}", yaml_string(&post.summary)));
+    }
+    if !post.image_url.trim().is_empty() {
+        front.push(format!("image: {}", yaml_string(&relative(&post.image_url))));
+    }
+    if let Some(alt) = &post.image_alt {
+        front.push(format!("image_alt: {}", yaml_string(alt)));
+    }
+    if let Some(poster) = &post.image_poster {
+        front.push(format!("image_poster: {}", yaml_string(&relative(poster))));
+    }
+    if !post.listed() {
+        front.push(format!("visibility: {}", serde_json::to_string(&post.visibility).expect("failed to serialize the visibility").trim_matches('"')));
+    }
+    if let Some(enabled) = post.comments_enabled {
+        front.push(format!("comments: {}", enabled));
+    }
+    if let Some(audio) = &post.audio {
+        front.push(format!("audio: {}", yaml_string(&relative(&audio.url("")))));
+    }
+    if !post.attachments.is_empty() {
+        front.push("attachments:".to_string());
+        front.extend(post.attachments.iter().map(|file| format!("  - {}", yaml_string(file))));
+    }
+    if let Some(video) = &post.video {
+        front.push(format!("video: {}", yaml_string(&relative(&video.url("")))));
+        if let Some(poster) = video.poster_url("") {
+            front.push(format!("video_poster: {}", yaml_string(&relative(&poster))));
+        }
+    }
+    if !post.tags.is_empty() {
+        front.push("tags:".to_string());
+        front.extend(post.tags.iter().map(|tag| format!("  - {}", yaml_string(tag))));
+    }
+
+    format!("---\n{}\n---\n\n{}\n", front.join("\n"), relative(&post.body).trim_end())
+}
+
+/// A double-quoted YAML scalar, which is safe for any text
+fn yaml_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n").replace('\t', "\\t"))
+}
+
+#[test]
+fn exported_markdown_imports_back() {
+    let post: Post = serde_json::from_str(
+        r#"{"title":"Say \"hi\": a post","body":"![cat](/asset/img/cat.png) and [notes](/asset/notes.txt)","image_url":"/asset/img/cat.png",
+            "summary":"Line one\nline two","timestamp":"2024-02-03T04:05:06Z","lang":"es","tags":["rust","a, b"],"visibility":"unlisted","comments_enabled":false}"#,
+    )
+    .unwrap();
+    assert_eq!(asset_references(&format!("{}\n{}", post.image_url, post.body)), vec!["img/cat.png", "notes.txt"]);
+
+    let markdown = to_markdown(&post, &["img/cat.png".to_string()]);
+    assert!(markdown.contains("![cat](img/cat.png) and [notes](/asset/notes.txt)"), "{}", markdown);
+
+    let imported = crate::import::from_markdown(Path::new("out/posts/greeting/index.es.md"), &markdown).unwrap().unwrap();
+    assert_eq!(imported.file_name, "greeting.es.json");
+    assert_eq!(imported.post.title, post.title);
+    assert_eq!(imported.post.summary, post.summary);
+    assert_eq!(imported.post.timestamp, post.timestamp);
+    assert_eq!(imported.post.lang.as_deref(), Some("es"));
+    assert_eq!(imported.post.tags, post.tags);
+    assert_eq!(imported.post.visibility, post.visibility);
+    assert_eq!(imported.post.comments_enabled, Some(false));
+    assert_eq!(imported.post.image_url, "img/cat.png");
+}
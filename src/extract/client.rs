@@ -0,0 +1,96 @@
+use std::net::IpAddr;
+
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::HeaderMap;
+
+use crate::cidr::Cidr;
+use crate::listen::Peer;
+use crate::state::AppState;
+
+pub const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+pub const FORWARDED_PROTO_HEADER: &str = "x-forwarded-proto";
+
+/// Who is on the other end of a request. Behind a proxy listed in `CADEN_BLOG_TRUSTED_PROXIES`, or one on the
+/// Unix socket, that's read from `X-Forwarded-For` and `X-Forwarded-Proto`; otherwise it's the connection itself,
+/// since anyone can send those headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Client {
+    /// `None` when the connection has no IP address, as on a Unix socket without forwarding headers
+    pub ip: Option<IpAddr>,
+    pub https: bool,
+}
+
+impl Client {
+    pub fn resolve(peer: Option<&Peer>, headers: &HeaderMap, trusted: &[Cidr]) -> Client {
+        let direct = Client { ip: peer.and_then(|peer| peer.ip), https: peer.is_some_and(|peer| peer.tls) };
+        let is_trusted = |ip: Option<IpAddr>| match ip {
+            Some(ip) => trusted.iter().any(|block| block.contains(ip)),
+            // A Unix socket is only reachable from this machine, so whoever is on it is the proxy
+            None => peer.is_some(),
+        };
+        if !is_trusted(direct.ip) {
+            return direct;
+        }
+
+        // Each proxy appends the address it saw, so the client is the last one not added by a trusted proxy
+        let forwarded: Vec<IpAddr> = headers
+            .get_all(FORWARDED_FOR_HEADER)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .filter_map(|ip| ip.trim().parse().ok())
+            .collect();
+        let ip = forwarded
+            .iter()
+            .rev()
+            .find(|&&ip| !is_trusted(Some(ip)))
+            .or(forwarded.first())
+            .copied()
+            .or(direct.ip);
+        let https = match headers.get(FORWARDED_PROTO_HEADER).and_then(|proto| proto.to_str().ok()) {
+            Some(proto) => proto.split(',').next().is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https")),
+            None => direct.https,
+        };
+        Client { ip, https }
+    }
+
+    pub fn scheme(&self) -> &'static str {
+        if self.https {
+            "https"
+        } else {
+            "http"
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl FromRequestParts<AppState> for Client {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        Ok(Client::resolve(parts.extensions.get::<Peer>(), &parts.headers, &state.config.server.trusted_proxies))
+    }
+}
+
+#[test]
+fn forwarded_headers_only_count_from_trusted_proxies() {
+    let trusted = crate::cidr::parse_list("10.0.0.0/8");
+    let mut headers = HeaderMap::new();
+    headers.insert(FORWARDED_FOR_HEADER, "198.51.100.1, 203.0.113.7, 10.0.0.3".parse().unwrap());
+    headers.insert(FORWARDED_PROTO_HEADER, "https".parse().unwrap());
+
+    let proxy = Peer { ip: Some("10.0.0.2".parse().unwrap()), tls: false };
+    let client = Client::resolve(Some(&proxy), &headers, &trusted);
+    assert_eq!(client, Client { ip: Some("203.0.113.7".parse().unwrap()), https: true });
+    assert_eq!(client.scheme(), "https");
+
+    // Straight from the internet the headers are made up
+    let stranger = Peer { ip: Some("192.0.2.5".parse().unwrap()), tls: true };
+    assert_eq!(Client::resolve(Some(&stranger), &headers, &trusted), Client { ip: stranger.ip, https: true });
+
+    let unix = Peer { ip: None, tls: false };
+    assert_eq!(Client::resolve(Some(&unix), &headers, &[]).ip, Some("10.0.0.3".parse().unwrap()));
+
+    assert_eq!(Client::resolve(None, &headers, &trusted), Client { ip: None, https: false });
+}
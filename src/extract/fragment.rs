@@ -0,0 +1,32 @@
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::HeaderMap;
+
+/// Sent by htmx with every request it makes
+pub const HX_REQUEST_HEADER: &str = "hx-request";
+/// Sent by unpoly, which the site's own pages use, with the selector it's about to swap
+pub const UP_TARGET_HEADER: &str = "x-up-target";
+
+/// For the `Vary` header of anything answered differently to [`Partial`] requests, so caches keep both versions
+pub const VARY: &str = "HX-Request, X-Up-Target";
+
+/// Whether a request comes from htmx or unpoly wanting a bare fragment to swap into the page it's already showing.
+/// A browser visiting a fragment endpoint directly gets a whole page, or is sent to one, instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Partial(pub bool);
+
+impl Partial {
+    pub fn detect(headers: &HeaderMap) -> Partial {
+        let htmx = headers.get(HX_REQUEST_HEADER).is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"true"));
+        Partial(htmx || headers.contains_key(UP_TARGET_HEADER))
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Partial {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        Ok(Partial::detect(&parts.headers))
+    }
+}
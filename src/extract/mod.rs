@@ -0,0 +1,12 @@
+pub mod client;
+pub mod fragment;
+pub mod preview;
+pub mod tz;
+
+use serde::Deserialize;
+
+/// Explicit `?lang=` choice of translation on post pages
+#[derive(Debug, Deserialize)]
+pub struct LangQuery {
+    pub lang: Option<String>,
+}
@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use axum::extract::{FromRequestParts, Query};
+use axum::http::request::Parts;
+use chrono::{DateTime, Utc};
+
+use crate::signed::Key;
+use crate::state::AppState;
+
+/// Query parameter preview links carry their token in
+pub const PREVIEW_QUERY: &str = "token";
+
+/// Marks the signed value as a preview grant, so no other signed value can pass for one
+const PREVIEW_PREFIX: &str = "preview";
+
+/// A token letting whoever holds it see one private post until it expires, signed with the cookie key
+pub fn token(key: &Key, url_name: &str, expires: DateTime<Utc>) -> String {
+    key.sign(&format!("{}\n{}\n{}", PREVIEW_PREFIX, url_name, expires.timestamp()))
+}
+
+/// The post a token grants, when it's ours and hasn't expired by `now`
+fn verify(key: &Key, token: &str, now: DateTime<Utc>) -> Option<String> {
+    let value = key.verify(token)?;
+    let mut lines = value.lines();
+    if lines.next() != Some(PREVIEW_PREFIX) {
+        return None;
+    }
+    let url_name = lines.next()?;
+    let expires: i64 = lines.next()?.parse().ok()?;
+    (now.timestamp() < expires).then(|| url_name.to_string())
+}
+
+/// The post a `?token=` preview link grants access to, if it has a valid one. Requests without one, or with one
+/// that's expired or forged, get through with nothing granted rather than an error.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Preview(pub Option<String>);
+
+impl Preview {
+    pub fn grants(&self, url_name: &str) -> bool {
+        self.0.as_deref() == Some(url_name)
+    }
+}
+
+#[async_trait::async_trait]
+impl FromRequestParts<AppState> for Preview {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let query = Query::<HashMap<String, String>>::try_from_uri(&parts.uri).map(|Query(query)| query).unwrap_or_default();
+        Ok(Preview(query.get(PREVIEW_QUERY).and_then(|token| verify(&state.config.cookie_key, token, Utc::now()))))
+    }
+}
+
+#[test]
+fn preview_tokens_expire_and_only_grant_their_post() {
+    let (now, key) = (Utc::now(), Key::random());
+    let token = token(&key, "draft", now + chrono::Duration::hours(1));
+    assert_eq!(verify(&key, &token, now).as_deref(), Some("draft"));
+    assert_eq!(verify(&key, &token, now + chrono::Duration::hours(2)), None);
+    assert_eq!(verify(&Key::random(), &token, now), None);
+    assert_eq!(verify(&key, &key.sign("draft:like"), now), None);
+    assert_eq!(verify(&key, "garbage", now), None);
+    assert!(Preview(Some("draft".to_string())).grants("draft") && !Preview(Some("draft".to_string())).grants("other"));
+}
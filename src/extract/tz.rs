@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use axum::extract::{FromRequestParts, Query, Request};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderValue, Uri};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+use crate::prefs::{cookie, TIMESTAMP_FORMAT};
+use crate::state::AppState;
+
+/// Header clients can send their IANA timezone in, and that every response echoes the resolved zone back in
+pub const TIME_ZONE_HEADER: &str = "x-time-zone";
+pub const TIME_ZONE_COOKIE: &str = "tz";
+pub const TIME_ZONE_QUERY: &str = "tz";
+
+/// Where a reader's timezone came from, in order of precedence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TzSource {
+    Header,
+    Cookie,
+    Query,
+    Default,
+}
+
+/// The reader's timezone, resolved from the `X-Time-Zone` header, then the `tz` cookie, then the `?tz=` query
+/// parameter, then the configured default zone. Values that aren't IANA zone names are skipped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UserTz {
+    pub tz: Tz,
+    pub source: TzSource,
+}
+
+/// Zone for readers who don't send one, from `CADEN_BLOG_DEFAULT_TZ`, UTC when unset
+pub fn default_tz_from_env() -> Tz {
+    std::env::var("CADEN_BLOG_DEFAULT_TZ")
+        .ok()
+        .and_then(|tz| tz.trim().parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+impl UserTz {
+    pub fn resolve(headers: &HeaderMap, uri: &Uri, default: Tz) -> UserTz {
+        let parse = |value: Option<&str>| value.and_then(|tz| tz.trim().parse::<Tz>().ok());
+
+        if let Some(tz) = parse(headers.get(TIME_ZONE_HEADER).and_then(|value| value.to_str().ok())) {
+            return UserTz { tz, source: TzSource::Header };
+        }
+        // Cookies can't hold a raw slash in every browser, so the zone may arrive percent-encoded
+        let cookie_tz = cookie(headers, TIME_ZONE_COOKIE).map(|tz| tz.replace("%2F", "/").replace("%2f", "/"));
+        if let Some(tz) = parse(cookie_tz.as_deref()) {
+            return UserTz { tz, source: TzSource::Cookie };
+        }
+        let query = Query::<HashMap<String, String>>::try_from_uri(uri).map(|Query(query)| query).unwrap_or_default();
+        if let Some(tz) = parse(query.get(TIME_ZONE_QUERY).map(String::as_str)) {
+            return UserTz { tz, source: TzSource::Query };
+        }
+
+        UserTz { tz: default, source: TzSource::Default }
+    }
+
+    pub fn format(&self, timestamp: &DateTime<Utc>) -> String {
+        timestamp.with_timezone(&self.tz).format(TIMESTAMP_FORMAT).to_string()
+    }
+}
+
+#[async_trait::async_trait]
+impl FromRequestParts<AppState> for UserTz {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        Ok(UserTz::resolve(&parts.headers, &parts.uri, state.config.default_tz))
+    }
+}
+
+/// Middleware echoing the timezone each response was rendered for in `X-Time-Zone`
+pub async fn echo_time_zone(user_tz: UserTz, request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(user_tz.tz.name()) {
+        response.headers_mut().insert(TIME_ZONE_HEADER, value);
+    }
+    response
+}
+
+#[test]
+fn time_zone_precedence_is_header_cookie_query_default() {
+    let uri: Uri = "/?tz=Asia/Tokyo".parse().unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert("cookie", "lang=en; tz=Europe%2FBerlin".parse().unwrap());
+    headers.insert(TIME_ZONE_HEADER, "America/New_York".parse().unwrap());
+
+    let resolved = UserTz::resolve(&headers, &uri, Tz::UTC);
+    assert_eq!((resolved.tz, resolved.source), (Tz::America__New_York, TzSource::Header));
+
+    headers.insert(TIME_ZONE_HEADER, "Not/AZone".parse().unwrap());
+    let resolved = UserTz::resolve(&headers, &uri, Tz::UTC);
+    assert_eq!((resolved.tz, resolved.source), (Tz::Europe__Berlin, TzSource::Cookie));
+
+    headers.remove("cookie");
+    let resolved = UserTz::resolve(&headers, &uri, Tz::UTC);
+    assert_eq!((resolved.tz, resolved.source), (Tz::Asia__Tokyo, TzSource::Query));
+
+    let resolved = UserTz::resolve(&HeaderMap::new(), &"/".parse().unwrap(), Tz::Europe__Paris);
+    assert_eq!((resolved.tz, resolved.source), (Tz::Europe__Paris, TzSource::Default));
+}
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, Response, StatusCode};
+use chrono::{DateTime, Utc};
+
+use crate::extract::client::Client;
+use crate::i18n::{Locales, DEFAULT_LANG};
+use crate::model::note::Note;
+use crate::model::post::{content_hash, Post};
+use crate::routes::notes::NOTES_PATH;
+use crate::share::{note_url, post_url};
+use crate::state::AppState;
+use crate::store::posts::localized_listing;
+use crate::store::{NoteIndex, PostIndex};
+
+pub const ATOM_PATH: &str = "/feed.xml";
+pub const RSS_PATH: &str = "/rss.xml";
+pub const SITEMAP_PATH: &str = "/sitemap.xml";
+
+/// Newest posts and notes listed in the feeds. The sitemap lists every post.
+const FEED_LEN: usize = 20;
+
+/// Feed readers poll on their own schedule, the validators make most of those polls a 304
+const CACHE_CONTROL: &str = "public, max-age=300";
+
+/// `Last-Modified` and `If-Modified-Since` dates
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Document {
+    Atom,
+    Rss,
+    Sitemap,
+}
+
+impl Document {
+    const ALL: [Document; 3] = [Document::Atom, Document::Rss, Document::Sitemap];
+
+    fn path(self) -> &'static str {
+        match self {
+            Document::Atom => ATOM_PATH,
+            Document::Rss => RSS_PATH,
+            Document::Sitemap => SITEMAP_PATH,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Document::Atom => "application/atom+xml; charset=utf-8",
+            Document::Rss => "application/rss+xml; charset=utf-8",
+            Document::Sitemap => "application/xml; charset=utf-8",
+        }
+    }
+}
+
+/// The category notes are filed under in the feeds, so readers can tell them from posts
+const NOTE_CATEGORY: &str = "note";
+
+/// A post or note as the feeds list it
+struct Entry {
+    title: String,
+    url: String,
+    published: DateTime<Utc>,
+    updated: DateTime<Utc>,
+    summary: String,
+    categories: Vec<String>,
+    /// A podcast episode, or failing that a video, as `(url, length, type)`
+    enclosure: Option<(String, u64, String)>,
+}
+
+impl Entry {
+    fn post(origin: &str, post: &Post, content: &Path) -> Entry {
+        Entry {
+            title: post.title.clone(),
+            url: post_url(origin, post),
+            published: post.timestamp,
+            updated: post.updated.unwrap_or(post.timestamp),
+            summary: post.summary.clone(),
+            categories: post.tags.clone(),
+            // Podcast apps want a length, zero when it's unknown is the convention
+            enclosure: post.audio.as_ref().or(post.video.as_ref()).map(|media| (media.url(origin), media.length(content).unwrap_or(0), media.mime().to_string())),
+        }
+    }
+
+    fn note(origin: &str, note: &Note) -> Entry {
+        Entry {
+            title: note.heading(),
+            url: note_url(origin, note),
+            published: note.timestamp,
+            updated: note.timestamp,
+            summary: crate::excerpt::plain_text(&note.body),
+            categories: std::iter::once(NOTE_CATEGORY.to_string()).chain(note.tags.iter().cloned()).collect(),
+            enclosure: None,
+        }
+    }
+}
+
+/// A feed or sitemap as generated for one origin, with the validators it's served with
+pub struct Generated {
+    pub body: String,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+}
+
+/// The feeds and sitemap, generated once per content change rather than per request. With `CADEN_BLOG_FEED_DIR`
+/// set, the copies for `CADEN_BLOG_SITE_URL` are also written there so a proxy can serve them as static files.
+#[derive(Default)]
+pub struct Feeds {
+    generated: RwLock<HashMap<(Document, String), Arc<Generated>>>,
+    dir: Option<PathBuf>,
+    /// The content directory, where the sizes of podcast episodes are looked up
+    content: PathBuf,
+    /// The origin the copies on disk are generated for
+    site_url: String,
+}
+
+impl Feeds {
+    pub fn from_env(content: &Path, site_url: &str) -> Feeds {
+        Feeds {
+            generated: RwLock::default(),
+            dir: std::env::var("CADEN_BLOG_FEED_DIR").ok().filter(|dir| !dir.trim().is_empty()).map(PathBuf::from),
+            content: content.to_path_buf(),
+            site_url: site_url.to_string(),
+        }
+    }
+
+    /// Drops every generated document, for when the posts change
+    pub fn clear(&self) {
+        self.generated.write().expect("failed to lock the feeds").clear();
+    }
+
+    /// Drops the generated documents whose path (`/feed.xml`, `/rss.xml` or `/sitemap.xml`) matches, returning how many
+    pub fn purge(&self, matches: impl Fn(&str) -> bool) -> usize {
+        let mut generated = self.generated.write().expect("failed to lock the feeds");
+        let before = generated.len();
+        generated.retain(|(document, _), _| !matches(document.path()));
+        before - generated.len()
+    }
+
+    /// The document for `origin`, generating it when the posts changed since it was last asked for
+    pub fn get(&self, document: Document, origin: &str, posts: &PostIndex, notes: &NoteIndex, locales: &Locales) -> Arc<Generated> {
+        let key = (document, origin.to_string());
+        if let Some(generated) = self.generated.read().expect("failed to lock the feeds").get(&key) {
+            return generated.clone();
+        }
+
+        let generated = Arc::new(generate(document, origin, &posts.read().expect("failed to lock the post index"), &notes.read().expect("failed to lock the notes"), locales, &self.content));
+        self.generated.write().expect("failed to lock the feeds").insert(key, generated.clone());
+        generated
+    }
+
+    /// Regenerates everything for the configured site URL after the posts or notes change, writing the copies on disk
+    pub fn regenerate(&self, posts: &PostIndex, notes: &NoteIndex, locales: &Locales) {
+        self.clear();
+        let origin = self.site_url.as_str();
+        if origin.is_empty() {
+            return;
+        }
+        for document in Document::ALL {
+            let generated = self.get(document, origin, posts, notes, locales);
+            if let Some(dir) = &self.dir {
+                let path = dir.join(document.path().trim_start_matches('/'));
+                if let Err(e) = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&path, &generated.body)) {
+                    println!("Couldn't write {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+fn generate(document: Document, origin: &str, posts: &[Post], notes: &[Note], locales: &Locales, content: &Path) -> Generated {
+    let last_modified = posts.iter().map(|post| post.updated.unwrap_or(post.timestamp)).chain(notes.iter().map(|note| note.timestamp)).max().unwrap_or_default();
+    let body = match document {
+        Document::Atom => atom(origin, &newest(origin, posts, notes, content), locales, last_modified),
+        Document::Rss => rss(origin, &newest(origin, posts, notes, content), locales),
+        Document::Sitemap => sitemap(origin, posts, !notes.is_empty()),
+    };
+    Generated { etag: format!("\"{:016x}\"", content_hash(&body)), body, last_modified }
+}
+
+/// The [`FEED_LEN`] newest posts and notes, the posts in the default language or whichever translation there is
+fn newest(origin: &str, posts: &[Post], notes: &[Note], content: &Path) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = localized_listing(posts, DEFAULT_LANG).iter().map(|post| Entry::post(origin, post, content)).chain(notes.iter().map(|note| Entry::note(origin, note))).collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.published));
+    entries.truncate(FEED_LEN);
+    entries
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn atom(origin: &str, entries: &[Entry], locales: &Locales, updated: DateTime<Utc>) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n<title>{}</title>\n<id>{}/</id>\n<link href=\"{}/\"/>\n<link rel=\"self\" href=\"{}{}\"/>\n<updated>{}</updated>\n",
+        escape(locales.get(DEFAULT_LANG, "site_title")),
+        escape(origin),
+        escape(origin),
+        escape(origin),
+        ATOM_PATH,
+        updated.to_rfc3339(),
+    );
+    for entry in entries {
+        let url = escape(&entry.url);
+        xml.push_str(&format!(
+            "<entry>\n<title>{}</title>\n<id>{}</id>\n<link href=\"{}\"/>\n<published>{}</published>\n<updated>{}</updated>\n<author><name>{}</name></author>\n<summary>{}</summary>\n",
+            escape(&entry.title),
+            url,
+            url,
+            entry.published.to_rfc3339(),
+            entry.updated.to_rfc3339(),
+            escape(locales.get(DEFAULT_LANG, "site_title")),
+            escape(&entry.summary),
+        ));
+        for category in &entry.categories {
+            xml.push_str(&format!("<category term=\"{}\"/>\n", escape(category)));
+        }
+        if let Some((url, length, mime)) = &entry.enclosure {
+            xml.push_str(&format!("<link rel=\"enclosure\" href=\"{}\" length=\"{}\" type=\"{}\"/>\n", escape(url), length, escape(mime)));
+        }
+        xml.push_str("</entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn rss(origin: &str, entries: &[Entry], locales: &Locales) -> String {
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<rss version=\"2.0\">\n<channel>\n<title>{}</title>\n<link>{}/</link>\n<description>{}</description>\n",
+        escape(locales.get(DEFAULT_LANG, "site_title")),
+        escape(origin),
+        escape(locales.get(DEFAULT_LANG, "site_title")),
+    );
+    for entry in entries {
+        let url = escape(&entry.url);
+        xml.push_str(&format!(
+            "<item>\n<title>{}</title>\n<link>{}</link>\n<guid>{}</guid>\n<pubDate>{}</pubDate>\n<description>{}</description>\n",
+            escape(&entry.title),
+            url,
+            url,
+            entry.published.to_rfc2822(),
+            escape(&entry.summary),
+        ));
+        for category in &entry.categories {
+            xml.push_str(&format!("<category>{}</category>\n", escape(category)));
+        }
+        if let Some((url, length, mime)) = &entry.enclosure {
+            xml.push_str(&format!("<enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\n", escape(url), length, escape(mime)));
+        }
+        xml.push_str("</item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+/// Every post in every language, plus the pages that aren't posts and the notes timeline when there are notes
+fn sitemap(origin: &str, posts: &[Post], notes: bool) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    let pages: &[&str] = if notes { &["/", "/contact", NOTES_PATH] } else { &["/", "/contact"] };
+    for page in pages {
+        xml.push_str(&format!("<url><loc>{}{}</loc></url>\n", escape(origin), page));
+    }
+    for post in posts.iter().filter(|post| post.listed()) {
+        xml.push_str(&format!(
+            "<url><loc>{}</loc><lastmod>{}</lastmod></url>\n",
+            escape(&post_url(origin, post)),
+            post.updated.unwrap_or(post.timestamp).format("%Y-%m-%d"),
+        ));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+/// Whether the client's copy is still the current one, by `If-None-Match` or failing that `If-Modified-Since`
+fn not_modified(headers: &HeaderMap, generated: &Generated) -> bool {
+    if let Some(tags) = headers.get(header::IF_NONE_MATCH).and_then(|tags| tags.to_str().ok()) {
+        return tags.split(',').map(str::trim).any(|tag| tag == "*" || tag == generated.etag);
+    }
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|since| since.to_str().ok())
+        .and_then(|since| chrono::NaiveDateTime::parse_from_str(since, HTTP_DATE).ok())
+        .is_some_and(|since| generated.last_modified.timestamp() <= since.and_utc().timestamp())
+}
+
+fn serve(document: Document, state: &AppState, headers: &HeaderMap, client: &Client) -> Response<Body> {
+    let generated = state.feeds.get(document, &crate::share::origin(&state.config.site_url, headers, client), &state.posts, &state.notes, &state.locales);
+    let response = Response::builder()
+        .header(header::ETAG, &generated.etag)
+        .header(header::LAST_MODIFIED, generated.last_modified.format(HTTP_DATE).to_string())
+        .header(header::CACHE_CONTROL, CACHE_CONTROL);
+    if not_modified(headers, &generated) {
+        return response.status(StatusCode::NOT_MODIFIED).body(Body::empty()).unwrap();
+    }
+    response.header(header::CONTENT_TYPE, document.content_type()).body(Body::from(generated.body.clone())).unwrap()
+}
+
+pub async fn serve_atom(State(state): State<AppState>, headers: HeaderMap, client: Client) -> Response<Body> {
+    serve(Document::Atom, &state, &headers, &client)
+}
+
+pub async fn serve_rss(State(state): State<AppState>, headers: HeaderMap, client: Client) -> Response<Body> {
+    serve(Document::Rss, &state, &headers, &client)
+}
+
+pub async fn serve_sitemap(State(state): State<AppState>, headers: HeaderMap, client: Client) -> Response<Body> {
+    serve(Document::Sitemap, &state, &headers, &client)
+}
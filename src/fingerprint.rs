@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::RwLock;
+
+use axum::body::Body;
+use axum::http::{Response, StatusCode};
+use sha2::{Digest, Sha256};
+
+/// `Cache-Control` for a file asked for by its current fingerprinted name, whose contents never change
+pub const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// `Cache-Control` for a file asked for by its plain name, which browsers check again after an hour
+pub const UNVERSIONED: &str = "public, max-age=3600";
+
+/// How many hex digits of the content hash go in a file name
+const VERSION_LEN: usize = 12;
+
+/// The start of the content hash that's put in a file's name
+pub fn version(contents: &[u8]) -> String {
+    hex::encode(&Sha256::digest(contents)[..VERSION_LEN / 2])
+}
+
+/// The file name with the version before the extension, so `htmx.min.js` is linked as `htmx.min.<version>.js` and
+/// a new copy gets a new URL rather than waiting for browsers' caches to expire
+pub fn name(name: &str, version: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{}.{}.{}", stem, version, extension),
+        None => format!("{}.{}", name, version),
+    }
+}
+
+/// The plain name and version of a fingerprinted name, `htmx.min.js` and the version for `htmx.min.<version>.js`
+pub fn parse(name: &str) -> Option<(String, &str)> {
+    let (rest, extension) = name.rsplit_once('.')?;
+    let (stem, version) = rest.rsplit_once('.')?;
+    if version.len() != VERSION_LEN || !version.bytes().all(|byte| byte.is_ascii_hexdigit()) || extension.contains('/') {
+        return None;
+    }
+    Some((format!("{}.{}", stem, extension), version))
+}
+
+/// The `Cache-Control` for a file asked for with the `requested` version in its name while `current` is being
+/// served, or `None` when the name is for an older copy
+pub fn cache_control(requested: Option<&str>, current: &str) -> Option<&'static str> {
+    match requested {
+        Some(requested) if requested == current => Some(IMMUTABLE),
+        Some(_) => None,
+        None => Some(UNVERSIONED),
+    }
+}
+
+/// Sends a request for an older copy, from a page cached before the file changed, on to the current one
+pub fn redirect(location: &str) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::TEMPORARY_REDIRECT)
+        .header("Location", location)
+        .header("Cache-Control", "no-cache")
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// What a request path is for, going by the manifest
+#[derive(Debug, PartialEq)]
+pub enum Resolved {
+    /// Not a fingerprinted name, so the path is served as it is
+    Plain,
+    /// The current fingerprinted name of the file at this plain path
+    Current(String),
+    /// An older fingerprinted name, to redirect to this current URL
+    Stale(String),
+}
+
+/// The current version of each of the site's own files by the path they're served at, like `/theme/code.css` or
+/// `/asset/posts/diagram.png`, so pages link them by fingerprinted URLs that can be cached for good
+#[derive(Default)]
+pub struct AssetManifest {
+    versions: RwLock<HashMap<String, String>>,
+}
+
+impl AssetManifest {
+    /// Notes the contents now served at `path`
+    pub fn record(&self, path: &str, contents: &[u8]) {
+        self.versions.write().expect("failed to lock the asset manifest").insert(path.to_string(), version(contents));
+    }
+
+    /// Swaps in the versions of the files under `/asset/`, keeping the theme's
+    pub fn replace_assets(&self, assets: HashMap<String, String>) {
+        let mut versions = self.versions.write().expect("failed to lock the asset manifest");
+        versions.retain(|path, _| !path.starts_with("/asset/"));
+        versions.extend(assets);
+    }
+
+    /// The URL to link `path` by: its fingerprinted name when the manifest has it, otherwise `path` unchanged.
+    /// Files without an extension have nowhere to put the version, so they're always linked by their plain names.
+    pub fn url(&self, path: &str) -> String {
+        let file_name = path.rsplit('/').next().unwrap_or_default();
+        match self.versions.read().expect("failed to lock the asset manifest").get(path) {
+            Some(version) if file_name.contains('.') => name(path, version),
+            _ => path.to_string(),
+        }
+    }
+
+    pub fn resolve(&self, path: &str) -> Resolved {
+        let versions = self.versions.read().expect("failed to lock the asset manifest");
+        // A file that happens to be named like a fingerprint is served as it is
+        if versions.contains_key(path) {
+            return Resolved::Plain;
+        }
+        let Some((plain, requested)) = parse(path) else { return Resolved::Plain };
+        match versions.get(&plain) {
+            Some(current) if current == requested => Resolved::Current(plain),
+            Some(current) => Resolved::Stale(name(&plain, current)),
+            None => Resolved::Plain,
+        }
+    }
+
+    /// Every path and its version, sorted by path
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let mut entries: Vec<(String, String)> = self.versions.read().expect("failed to lock the asset manifest").iter().map(|(path, version)| (path.clone(), version.clone())).collect();
+        entries.sort();
+        entries
+    }
+}
+
+/// Versions of the files in the assets directory `dir` no bigger than `max_size`, and of the default assets
+/// compiled into the binary that aren't overridden there, by their `/asset/` paths. Bigger files keep their plain
+/// names rather than being read through at every reload.
+pub fn scan_assets(dir: &Path, max_size: u64) -> HashMap<String, String> {
+    let mut versions = HashMap::new();
+    scan_dir(dir, "", max_size, &mut versions);
+    for name in crate::defaults::Defaults::iter() {
+        if let Some(asset) = name.strip_prefix("assets/") {
+            let path = format!("/asset/{}", asset);
+            if !versions.contains_key(&path) && !dir.join(asset).is_file() {
+                if let Some(contents) = crate::defaults::embedded(&name) {
+                    versions.insert(path, version(&contents));
+                }
+            }
+        }
+    }
+    versions
+}
+
+fn scan_dir(dir: &Path, prefix: &str, max_size: u64, versions: &mut HashMap<String, String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let Some(file_name) = entry.file_name().to_str().map(String::from) else { continue };
+        let name = format!("{}{}", prefix, file_name);
+        match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => scan_dir(&entry.path(), &format!("{}/", name), max_size, versions),
+            Ok(metadata) if metadata.len() <= max_size => {
+                if let Ok(contents) = std::fs::read(entry.path()) {
+                    versions.insert(format!("/asset/{}", name), version(&contents));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn fingerprints_go_before_the_extension() {
+    assert_eq!(name("htmx.min.js", "ab12cd34ef56"), "htmx.min.ab12cd34ef56.js");
+    assert_eq!(parse("htmx.min.ab12cd34ef56.js"), Some(("htmx.min.js".to_string(), "ab12cd34ef56")));
+    assert_eq!(parse("/asset/posts/diagram.0123456789ab.png"), Some(("/asset/posts/diagram.png".to_string(), "0123456789ab")));
+    // Not hex, too short to be a version, or not a file name
+    assert_eq!(parse("htmx.min.latest.js"), None);
+    assert_eq!(parse("diagram.dark.png"), None);
+    assert_eq!(parse("photo.cafe.png"), None);
+    assert_eq!(parse("/asset/a.0123456789ab/notes"), None);
+}
+
+#[test]
+fn the_manifest_links_current_copies_and_redirects_old_ones() {
+    let manifest = AssetManifest::default();
+    manifest.record("/theme/code.css", b"pre {}");
+    let current = manifest.url("/theme/code.css");
+    assert_eq!(current, name("/theme/code.css", &version(b"pre {}")));
+    assert_eq!(manifest.resolve(&current), Resolved::Current("/theme/code.css".to_string()));
+    assert_eq!(manifest.resolve("/theme/code.css"), Resolved::Plain);
+    assert_eq!(manifest.url("/asset/unknown.png"), "/asset/unknown.png");
+    manifest.record("/asset/posts.d/README", b"read me");
+    assert_eq!(manifest.url("/asset/posts.d/README"), "/asset/posts.d/README");
+
+    manifest.record("/theme/code.css", b"pre { color: red }");
+    assert_eq!(manifest.resolve(&current), Resolved::Stale(manifest.url("/theme/code.css")));
+
+    // Replacing the assets leaves the theme alone, and a file really named like a fingerprint stays plain
+    let literal = name("/asset/photo.png", "0123456789ab");
+    manifest.replace_assets(HashMap::from([(literal.clone(), "ffffffffffff".to_string()), ("/asset/photo.png".to_string(), "0123456789ab".to_string())]));
+    assert_eq!(manifest.resolve(&literal), Resolved::Plain);
+    assert_eq!(manifest.resolve(&manifest.url("/theme/code.css")), Resolved::Current("/theme/code.css".to_string()));
+    assert_eq!(manifest.url("/asset/posts.d/README"), "/asset/posts.d/README");
+
+    assert_eq!(cache_control(None, "0123456789ab"), Some(UNVERSIONED));
+    assert_eq!(cache_control(Some("0123456789ab"), "0123456789ab"), Some(IMMUTABLE));
+    assert_eq!(cache_control(Some("ffffffffffff"), "0123456789ab"), None);
+}
@@ -0,0 +1,108 @@
+use maud::{html, Markup, PreEscaped};
+
+use crate::i18n::Text;
+use crate::thumbnail;
+
+/// Width of the thumbnails in the grid, big enough for two columns on a phone and three on a desktop
+const GRID_WIDTH: u32 = 480;
+
+/// The grid and the lightbox, included once on pages with a gallery
+pub const GALLERY_CSS: &str = r#"
+    .gallery {
+        display: grid;
+        grid-template-columns: repeat(auto-fill, minmax(160px, 1fr));
+        gap: 8px;
+        margin: 1rem 0;
+    }
+    .gallery img {
+        width: 100%;
+        aspect-ratio: 4 / 3;
+        object-fit: cover;
+        border-radius: 4px;
+    }
+    .lightbox {
+        max-width: 95vw;
+        max-height: 95vh;
+        padding: 0;
+        border: none;
+        background: transparent;
+    }
+    .lightbox::backdrop {
+        background: rgba(0, 0, 0, 0.85);
+    }
+    .lightbox img {
+        max-width: 95vw;
+        max-height: 85vh;
+        display: block;
+        margin: 0 auto;
+    }
+    .lightbox-controls {
+        display: flex;
+        justify-content: center;
+        gap: 8px;
+        margin-top: 8px;
+    }
+"#;
+
+/// Opens the full-size photo in a dialog when a thumbnail is clicked, with buttons and the arrow keys going through
+/// the rest of its gallery. Without javascript the thumbnails are links to the photos.
+pub const GALLERY_SCRIPT: &str = r#"
+(() => {
+    const dialog = document.querySelector('.lightbox');
+    if (!dialog || !dialog.showModal) return;
+    const image = dialog.querySelector('img');
+    let links = [], index = 0;
+    const show = (i) => {
+        index = (i + links.length) % links.length;
+        image.src = links[index].href;
+        image.alt = links[index].querySelector('img').alt;
+    };
+    document.addEventListener('click', (event) => {
+        const link = event.target.closest('.gallery a');
+        if (!link) return;
+        event.preventDefault();
+        links = [...link.closest('.gallery').querySelectorAll('a')];
+        show(links.indexOf(link));
+        dialog.showModal();
+    });
+    dialog.querySelector('[data-step="-1"]').addEventListener('click', () => show(index - 1));
+    dialog.querySelector('[data-step="1"]').addEventListener('click', () => show(index + 1));
+    dialog.addEventListener('keydown', (event) => {
+        if (event.key === 'ArrowLeft') show(index - 1);
+        if (event.key === 'ArrowRight') show(index + 1);
+    });
+    dialog.addEventListener('click', (event) => { if (event.target === dialog) dialog.close(); });
+})();
+"#;
+
+/// A grid of thumbnails from the resizing endpoint, each linking to the full-size asset
+pub fn widget(images: &[&str], text: Text) -> Markup {
+    html! {
+        div class="gallery" {
+            @for (index, image) in images.iter().enumerate() {
+                a href=(format!("/asset/{}", image)) {
+                    img src=(thumbnail::url(image, GRID_WIDTH)) loading="lazy"
+                        alt=(text.t("gallery_photo").replace("{n}", &(index + 1).to_string()).replace("{total}", &images.len().to_string()));
+                }
+            }
+        }
+    }
+}
+
+/// The styles, the lightbox dialog and its script, for the end of a page with galleries on it
+pub fn lightbox(text: Text) -> Markup {
+    html! {
+        style { (PreEscaped(GALLERY_CSS)) }
+        dialog class="lightbox" aria-label=(text.t("gallery")) {
+            img alt="";
+            div class="lightbox-controls" {
+                button type="button" class="btn btn-secondary btn-sm" data-step="-1" { (text.t("gallery_previous")) }
+                form method="dialog" {
+                    button class="btn btn-secondary btn-sm" { (text.t("gallery_close")) }
+                }
+                button type="button" class="btn btn-secondary btn-sm" data-step="1" { (text.t("gallery_next")) }
+            }
+        }
+        script { (PreEscaped(GALLERY_SCRIPT)) }
+    }
+}
@@ -0,0 +1,127 @@
+use axum::body::Body;
+use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE, HOST, REFERER, VARY};
+use axum::http::{HeaderMap, HeaderValue, Response, StatusCode};
+
+/// What an image asked for from another site's page gets instead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    /// `403 Forbidden`
+    Forbid,
+    /// A picture saying where the image really lives
+    Placeholder,
+}
+
+/// Keeps other sites from embedding the images: requests referred by a page on a host that isn't this site or one
+/// of `CADEN_BLOG_HOTLINK_ALLOW` are refused. Requests without a referrer are let through, since browsers and
+/// privacy settings often leave it out. Off unless the allowlist is set.
+#[derive(Debug, Default)]
+pub struct Hotlinks {
+    /// Hosts allowed to embed, each also allowing its subdomains
+    allowed: Vec<String>,
+    /// `None` when protection is off
+    refusal: Option<Refusal>,
+    /// The host of the configured site URL, which can always embed
+    site: Option<String>,
+}
+
+impl Hotlinks {
+    /// `CADEN_BLOG_HOTLINK_ALLOW` is a comma-separated list of hosts, which can be empty to allow only this site.
+    /// `CADEN_BLOG_HOTLINK_RESPONSE` is `forbid` (the default) or `placeholder`.
+    pub fn from_env(site_url: &str) -> Hotlinks {
+        let Ok(allowed) = std::env::var("CADEN_BLOG_HOTLINK_ALLOW") else { return Hotlinks::default() };
+        let refusal = match std::env::var("CADEN_BLOG_HOTLINK_RESPONSE").unwrap_or_default().trim() {
+            "placeholder" => Refusal::Placeholder,
+            "" | "forbid" => Refusal::Forbid,
+            other => {
+                println!("Unknown hotlink response {}, forbidding instead", other);
+                Refusal::Forbid
+            }
+        };
+        Hotlinks { site: host_of(site_url), ..Hotlinks::new(allowed.split(',').map(str::to_string).collect(), refusal) }
+    }
+
+    pub fn new(allowed: Vec<String>, refusal: Refusal) -> Hotlinks {
+        let allowed = allowed.iter().map(|host| host.trim().trim_start_matches("*.").to_lowercase()).filter(|host| !host.is_empty()).collect();
+        Hotlinks { allowed, refusal: Some(refusal), site: None }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.refusal.is_some()
+    }
+
+    /// How to refuse an image request, or `None` to serve it
+    pub fn check(&self, headers: &HeaderMap) -> Option<Refusal> {
+        self.refusal?;
+        let referrer = headers.get(REFERER).and_then(|value| value.to_str().ok()).and_then(host_of)?;
+        let request_host = headers.get(HOST).and_then(|value| value.to_str().ok()).map(|host| strip_port(host).to_lowercase());
+        let own = request_host.as_deref() == Some(referrer.as_str()) || self.site.as_deref() == Some(referrer.as_str());
+        let allowed = self.allowed.iter().any(|host| referrer == *host || referrer.strip_suffix(host.as_str()).is_some_and(|rest| rest.ends_with('.')));
+        if own || allowed {
+            None
+        } else {
+            self.refusal
+        }
+    }
+
+    /// The response for a refused request
+    pub fn refuse(&self, refusal: Refusal, headers: &HeaderMap) -> Response<Body> {
+        let response = Response::builder().header(CACHE_CONTROL, "no-store").header(VARY, "Referer");
+        match refusal {
+            Refusal::Forbid => response.status(StatusCode::FORBIDDEN).body(Body::empty()).unwrap(),
+            Refusal::Placeholder => {
+                let site = self.site.clone().or_else(|| headers.get(HOST).and_then(|value| value.to_str().ok()).map(str::to_string)).unwrap_or_default();
+                response.header(CONTENT_TYPE, "image/svg+xml").body(Body::from(placeholder(&site))).unwrap()
+            }
+        }
+    }
+}
+
+fn strip_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.is_empty() && port.bytes().all(|byte| byte.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+/// The host of an absolute URL, lowercased and without the port
+pub fn host_of(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = strip_port(authority.rsplit_once('@').map_or(authority, |(_, host)| host));
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+/// A grey card pointing at the site the image is from
+fn placeholder(site: &str) -> String {
+    let site = site.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"640\" height=\"360\" viewBox=\"0 0 640 360\"><rect width=\"640\" height=\"360\" fill=\"#343a40\"/><g fill=\"#e0e0e0\" font-family=\"Arial, sans-serif\" text-anchor=\"middle\"><text x=\"320\" y=\"170\" font-size=\"24\">Image hosted at</text><text x=\"320\" y=\"210\" font-size=\"32\" font-weight=\"bold\">{}</text></g></svg>",
+        site
+    )
+}
+
+/// Marks a served image as depending on the referrer, so shared caches don't hand one site's copy to another
+pub fn vary(response: &mut Response<Body>) {
+    response.headers_mut().append(VARY, HeaderValue::from_static("Referer"));
+}
+
+#[test]
+fn only_allowed_sites_can_embed_images() {
+    let headers = |referrer: Option<&str>| {
+        let mut headers = HeaderMap::new();
+        headers.insert(HOST, HeaderValue::from_static("blog.example:8080"));
+        if let Some(referrer) = referrer {
+            headers.insert(REFERER, HeaderValue::from_str(referrer).unwrap());
+        }
+        headers
+    };
+    let hotlinks = Hotlinks::new(vec![" friend.example".to_string(), "*.Pals.example".to_string(), String::new()], Refusal::Placeholder);
+    for allowed in [None, Some("http://blog.example:8080/post/hello"), Some("https://friend.example/"), Some("https://www.friend.example/x"), Some("https://a.pals.example"), Some("not a url")] {
+        assert_eq!(hotlinks.check(&headers(allowed)), None, "{:?}", allowed);
+    }
+    for refused in ["https://evil.example/", "https://notfriend.example/", "https://friend.example.evil.example/", "https://friend.example@evil.example/"] {
+        assert_eq!(hotlinks.check(&headers(Some(refused))), Some(Refusal::Placeholder), "{}", refused);
+    }
+    assert_eq!(Hotlinks::default().check(&headers(Some("https://evil.example/"))), None);
+    assert!(placeholder("<b>").contains("&lt;b&gt;"));
+}
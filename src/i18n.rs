@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use axum::http::HeaderMap;
+
+pub const DEFAULT_LANG: &str = "en";
+
+/// Locales compiled into the binary; files in the content directory's `locales` override or extend them
+const BUILTIN: [(&str, &str); 2] = [
+    ("en", include_str!("locales/en.toml")),
+    ("es", include_str!("locales/es.toml")),
+];
+
+/// UI strings for every available language, keyed by language code and then string key
+pub struct Locales {
+    strings: HashMap<String, HashMap<String, String>>,
+}
+
+impl Locales {
+    pub fn load(content: &Path) -> Locales {
+        let mut strings: HashMap<String, HashMap<String, String>> = HashMap::new();
+        for (lang, source) in BUILTIN {
+            let table = toml::from_str(source).expect("builtin locale files are valid toml");
+            strings.insert(lang.to_string(), table);
+        }
+
+        let dir = content.join("locales");
+        for file in crate::store::posts::list_files_in_directory(&dir) {
+            let Some(lang) = file.strip_suffix(".toml") else { continue };
+            let table: HashMap<String, String> = match std::fs::read_to_string(dir.join(&file))
+                .map_err(|e| e.to_string())
+                .and_then(|source| toml::from_str(&source).map_err(|e| e.to_string()))
+            {
+                Ok(table) => table,
+                Err(e) => {
+                    println!("Couldn't load locale {}: {}", file, e);
+                    continue;
+                }
+            };
+            strings.entry(lang.to_lowercase()).or_default().extend(table);
+        }
+
+        Locales { strings }
+    }
+
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        self.strings.keys().map(String::as_str)
+    }
+
+    /// Matches a requested language tag like `es-MX` against the available locales
+    pub fn find(&self, tag: &str) -> Option<&str> {
+        let tag = tag.trim().to_lowercase();
+        let primary = tag.split('-').next().unwrap_or_default();
+        self.strings
+            .get_key_value(&tag)
+            .or_else(|| self.strings.get_key_value(primary))
+            .map(|(lang, _)| lang.as_str())
+    }
+
+    /// Picks the reader's language from the `lang` cookie, then `Accept-Language`, then the default
+    pub fn negotiate(&self, headers: &HeaderMap) -> String {
+        if let Some(lang) = crate::prefs::cookie(headers, "lang").and_then(|lang| self.find(lang)) {
+            return lang.to_string();
+        }
+
+        let mut accepted: Vec<(&str, f32)> = headers
+            .get(axum::http::header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.split(';');
+                let tag = parts.next()?.trim();
+                let quality = parts
+                    .find_map(|param| param.trim().strip_prefix("q="))
+                    .and_then(|q| q.parse().ok())
+                    .unwrap_or(1.0);
+                Some((tag, quality))
+            })
+            .collect();
+        accepted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        accepted
+            .iter()
+            .find_map(|(tag, _)| self.find(tag))
+            .unwrap_or(DEFAULT_LANG)
+            .to_string()
+    }
+
+    /// Looks up a string, falling back to the default language and then the key itself
+    pub fn get<'a>(&'a self, lang: &str, key: &'a str) -> &'a str {
+        self.strings
+            .get(lang)
+            .and_then(|table| table.get(key))
+            .or_else(|| self.strings.get(DEFAULT_LANG).and_then(|table| table.get(key)))
+            .map_or(key, String::as_str)
+    }
+
+    /// A language's name in that language, or just its code when there is no locale for it
+    pub fn language_name<'a>(&'a self, lang: &'a str) -> &'a str {
+        self.strings
+            .get(lang)
+            .and_then(|table| table.get("language_name"))
+            .map_or(lang, String::as_str)
+    }
+
+    pub fn text<'a>(&'a self, lang: &'a str) -> Text<'a> {
+        Text { locales: self, lang }
+    }
+}
+
+/// The strings for one language, handed to the templates
+#[derive(Clone, Copy)]
+pub struct Text<'a> {
+    locales: &'a Locales,
+    pub lang: &'a str,
+}
+
+impl<'a> Text<'a> {
+    pub fn t(&self, key: &'a str) -> &'a str {
+        self.locales.get(self.lang, key)
+    }
+}
+
+#[test]
+fn negotiates_cookie_then_accept_language() {
+    let locales = Locales::load(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR));
+    let mut headers = HeaderMap::new();
+    assert_eq!(locales.negotiate(&headers), "en");
+
+    headers.insert("accept-language", "fr-CH, es-MX;q=0.9, en;q=0.8".parse().unwrap());
+    assert_eq!(locales.negotiate(&headers), "es");
+
+    headers.insert("cookie", "theme=dark; lang=en".parse().unwrap());
+    assert_eq!(locales.negotiate(&headers), "en");
+
+    assert_eq!(locales.text("es").t("read_more"), "Leer más");
+    assert_eq!(locales.text("es").t("missing_key"), "missing_key");
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::{Response, StatusCode};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use maud::{html, Markup};
+
+use crate::state::AppState;
+
+/// Generated PNG icons, by the path they are served at, and the square size each is resized to
+pub const ICON_SIZES: [(&str, u32); 3] = [
+    ("apple-touch-icon.png", 180),
+    ("icon-192.png", 192),
+    ("icon-512.png", 512),
+];
+
+/// Icons resized from the configured source image, plus the web manifest pointing at them
+pub struct IconSet {
+    pngs: HashMap<&'static str, Vec<u8>>,
+    manifest: String,
+}
+
+/// Page theme color when `CADEN_BLOG_THEME_COLOR` isn't set
+pub const DEFAULT_THEME_COLOR: &str = "#121212";
+
+/// Page theme color, set with `CADEN_BLOG_THEME_COLOR`
+pub fn theme_color_from_env() -> String {
+    std::env::var("CADEN_BLOG_THEME_COLOR").unwrap_or_else(|_| DEFAULT_THEME_COLOR.to_string())
+}
+
+/// The `<head>` tags pointing browsers at the icon set and manifest, and tinting the browser in `theme_color`
+pub fn icon_links(theme_color: &str) -> Markup {
+    html! {
+        link rel="apple-touch-icon" sizes="180x180" href="/apple-touch-icon.png";
+        link rel="icon" type="image/png" sizes="192x192" href="/icon-192.png";
+        link rel="manifest" href="/site.webmanifest";
+        meta name="theme-color" content=(theme_color);
+    }
+}
+
+impl IconSet {
+    /// Resizes the source image from `CADEN_BLOG_ICON`, relative to the content directory (the favicon by
+    /// default), into every icon size it's big enough for, listed in a manifest tinted `theme_color`
+    pub fn from_env(content: &Path, theme_color: &str) -> IconSet {
+        let (source, image) = match std::env::var("CADEN_BLOG_ICON") {
+            Ok(source) => {
+                let image = image::open(content.join(&source)).map_err(|e| e.to_string());
+                (source, image)
+            }
+            Err(_) => {
+                let image = crate::defaults::read(content, "favicon.ico")
+                    .ok_or_else(|| "no favicon".to_string())
+                    .and_then(|icon| image::load_from_memory(&icon).map_err(|e| e.to_string()));
+                ("favicon.ico".to_string(), image)
+            }
+        };
+
+        match image {
+            Ok(image) => IconSet::from_image(&image, theme_color),
+            Err(e) => {
+                println!("Couldn't load icon source {}: {}", source, e);
+                IconSet::from_pngs(HashMap::new(), theme_color)
+            }
+        }
+    }
+
+    /// Resizes `image` into the icon sizes it's at least as big as, since upscaling only blurs it
+    fn from_image(image: &DynamicImage, theme_color: &str) -> IconSet {
+        let pngs = ICON_SIZES
+            .iter()
+            .filter(|(_, size)| *size <= image.width().min(image.height()))
+            .filter_map(|(name, size)| {
+                let mut png = Vec::new();
+                image
+                    .resize_exact(*size, *size, FilterType::Lanczos3)
+                    .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                    .ok()?;
+                Some((*name, png))
+            })
+            .collect();
+        IconSet::from_pngs(pngs, theme_color)
+    }
+
+    fn from_pngs(pngs: HashMap<&'static str, Vec<u8>>, theme_color: &str) -> IconSet {
+        let manifest = manifest(&pngs, theme_color);
+        IconSet { pngs, manifest }
+    }
+
+    pub fn png(&self, name: &str) -> Option<&Vec<u8>> {
+        self.pngs.get(name)
+    }
+
+    /// The names of the icons there are, in [`ICON_SIZES`] order
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        ICON_SIZES.iter().map(|(name, _)| *name).filter(|name| self.pngs.contains_key(name))
+    }
+}
+
+fn manifest(pngs: &HashMap<&'static str, Vec<u8>>, theme_color: &str) -> String {
+    let icons: Vec<serde_json::Value> = ICON_SIZES
+        .iter()
+        .filter(|(name, _)| name.starts_with("icon-") && pngs.contains_key(name))
+        .map(|(name, size)| serde_json::json!({
+            "src": format!("/{}", name),
+            "sizes": format!("{}x{}", size, size),
+            "type": "image/png",
+        }))
+        .collect();
+
+    serde_json::json!({
+        "name": "The Caden Times",
+        "short_name": "Caden Times",
+        "start_url": "/",
+        "display": "standalone",
+        "background_color": "#121212",
+        "theme_color": theme_color,
+        "icons": icons,
+    })
+    .to_string()
+}
+
+pub async fn serve_icon(name: &'static str, icons: std::sync::Arc<IconSet>) -> Result<Response<Body>, StatusCode> {
+    let png = icons.png(name).ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Response::builder()
+        .header("Content-Type", "image/png")
+        .header("Cache-Control", crate::fingerprint::UNVERSIONED)
+        .body(Body::from(png.clone()))
+        .unwrap())
+}
+
+pub async fn serve_manifest(State(AppState { icons, .. }): State<AppState>) -> Response<Body> {
+    Response::builder()
+        .header("Content-Type", "application/manifest+json")
+        .header("Cache-Control", "public, max-age=86400")
+        .body(Body::from(icons.manifest.clone()))
+        .unwrap()
+}
+
+#[test]
+fn icons_are_never_upscaled() {
+    let icons = IconSet::from_image(&DynamicImage::new_rgb8(200, 300), "#336699");
+    assert_eq!(icons.names().collect::<Vec<_>>(), ["apple-touch-icon.png", "icon-192.png"]);
+    assert!(icons.png("icon-512.png").is_none());
+    assert!(icons.manifest.contains("/icon-192.png") && !icons.manifest.contains("/icon-512.png"), "{}", icons.manifest);
+    assert!(icons.manifest.contains(r##""theme_color":"#336699""##), "{}", icons.manifest);
+}
@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+use crate::model::post::{serialize_post, split_translation, Media, Post, RenderedBody, Visibility};
+use crate::config::Config;
+use crate::slug;
+
+/// A post read from another blog engine, before it's written out as `posts/<file_name>`
+#[derive(Debug)]
+pub(crate) struct Imported {
+    pub file_name: String,
+    pub post: Post,
+}
+
+/// A front matter value, which is all this importer needs out of YAML or TOML
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    List(Vec<String>),
+}
+
+type FrontMatter = HashMap<String, Value>;
+
+/// `caden-blog import <dir|export.xml>`: converts a Jekyll or Hugo site's markdown posts, or a WordPress WXR
+/// export, into posts in the content directory. Existing posts are never overwritten.
+pub fn import(config: &Config, source: &str) -> std::process::ExitCode {
+    if source.trim().is_empty() {
+        println!("usage: caden-blog import <directory of markdown posts | WordPress export.xml>");
+        return std::process::ExitCode::FAILURE;
+    }
+
+    let source = Path::new(source);
+    let (imported, mut skipped) = if source.is_dir() {
+        from_markdown_dir(source)
+    } else {
+        match fs::read_to_string(source) {
+            Ok(xml) => from_wxr(&xml),
+            Err(e) => {
+                println!("Couldn't read {}: {}", source.display(), e);
+                return std::process::ExitCode::FAILURE;
+            }
+        }
+    };
+
+    let dir = config.path("posts");
+    if let Err(e) = fs::create_dir_all(&dir) {
+        println!("Couldn't create {}: {}", dir.display(), e);
+        return std::process::ExitCode::FAILURE;
+    }
+    let mut written = 0;
+    for Imported { file_name, post } in &imported {
+        let path = dir.join(file_name);
+        if path.exists() {
+            println!("Skipping {}: {} already exists", post.title, path.display());
+            skipped += 1;
+            continue;
+        }
+        match fs::write(&path, serialize_post(post)) {
+            Ok(()) => written += 1,
+            Err(e) => {
+                println!("Couldn't write {}: {}", path.display(), e);
+                skipped += 1;
+            }
+        }
+    }
+
+    println!("Imported {} posts into {}, skipped {}", written, dir.display(), skipped);
+    std::process::ExitCode::SUCCESS
+}
+
+/// Every `.md`/`.markdown` file under `dir`, as Jekyll keeps them in `_posts` and Hugo in `content/posts`
+fn from_markdown_dir(dir: &Path) -> (Vec<Imported>, usize) {
+    let mut files = Vec::new();
+    collect_markdown(dir, &mut files);
+    files.sort();
+
+    let mut imported = Vec::new();
+    let mut skipped = 0;
+    for file in files {
+        let converted = fs::read_to_string(&file)
+            .map_err(|e| e.to_string())
+            .and_then(|text| from_markdown(&file, &text));
+        match converted {
+            Ok(Some(post)) => imported.push(post),
+            Ok(None) => {
+                println!("Skipping the draft {}", file.display());
+                skipped += 1;
+            }
+            Err(e) => {
+                println!("Skipping {}: {}", file.display(), e);
+                skipped += 1;
+            }
+        }
+    }
+    (imported, skipped)
+}
+
+fn collect_markdown(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.is_dir() {
+            collect_markdown(&path, files);
+        } else if path.extension().is_some_and(|ext| ext == "md" || ext == "markdown") {
+            files.push(path);
+        }
+    }
+}
+
+/// A markdown post with YAML (`---`) or TOML (`+++`) front matter, `None` for drafts
+pub(crate) fn from_markdown(file: &Path, text: &str) -> Result<Option<Imported>, String> {
+    let (front, body) = split_front_matter(text)?;
+    if front.get("draft").is_some_and(|draft| text_of(draft) == "true") || front.get("published").is_some_and(|published| text_of(published) == "false") {
+        return Ok(None);
+    }
+
+    // Hugo names translations `<slug>.<lang>.md` or `<slug>/index.<lang>.md`
+    let (stem, file_lang) = split_translation(file.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default());
+    // Hugo page bundles are `<slug>/index.md`, Jekyll posts are `YYYY-MM-DD-<slug>.md`
+    let stem = match stem {
+        "index" | "_index" => file.parent().and_then(|dir| dir.file_name()).and_then(|name| name.to_str()).unwrap_or(stem),
+        stem => stem,
+    };
+    let (file_date, file_slug) = match stem.get(..11).and_then(|prefix| NaiveDate::parse_from_str(&prefix[..10], "%Y-%m-%d").ok()) {
+        Some(date) if stem.as_bytes()[10] == b'-' => (Some(date.and_hms_opt(0, 0, 0).unwrap().and_utc()), &stem[11..]),
+        _ => (None, stem),
+    };
+
+    let get = |keys: &[&str]| keys.iter().find_map(|key| front.get(*key)).map(text_of).filter(|value| !value.is_empty());
+    let title = get(&["title"]).unwrap_or_else(|| file_slug.replace('-', " "));
+    let timestamp = get(&["date"]).and_then(|date| parse_date(&date)).or(file_date).ok_or("no date in the front matter or the file name")?;
+    let slug = get(&["slug"]).unwrap_or_else(|| file_slug.to_string());
+    let mut tags = Vec::new();
+    for key in ["tags", "categories"] {
+        for tag in front.get(key).map(list_of).unwrap_or_default() {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+
+    let post = Post {
+        title,
+        body: body.trim().to_string(),
+        image_url: get(&["image", "cover", "featured_image"]).unwrap_or_default(),
+        image_alt: get(&["image_alt"]),
+        image_poster: get(&["image_poster", "cover_poster"]),
+        summary: get(&["description", "summary", "excerpt"]).unwrap_or_default(),
+        timestamp,
+        updated: get(&["lastmod", "last_modified_at"]).and_then(|date| parse_date(&date)),
+        lang: get(&["lang", "language"]).or(file_lang.map(str::to_string)),
+        slug: None,
+        tags,
+        visibility: match get(&["visibility"]).as_deref() {
+            Some("unlisted") => Visibility::Unlisted,
+            Some("private") => Visibility::Private,
+            _ => Visibility::Public,
+        },
+        comments_enabled: match get(&["comments"]).as_deref() {
+            Some("true") => Some(true),
+            Some("false") => Some(false),
+            _ => None,
+        },
+        audio: get(&["audio"]).map(|src| Media::new(&src)),
+        attachments: front.get("attachments").map(list_of).unwrap_or_default(),
+        video: get(&["video"]).map(|src| Media { poster: get(&["video_poster", "poster"]), ..Media::new(&src) }),
+        accent_color: get(&["accent_color", "accent"]),
+        hero: get(&["hero", "banner"]),
+        url_name: String::new(),
+        source_file: String::new(),
+        rendered: RenderedBody::default(),
+    };
+    let file_name = match file_lang {
+        Some(lang) => format!("{}.{}.json", slug::slugify(&slug), lang),
+        None => format!("{}.json", slug::slugify(&slug)),
+    };
+    Ok(Some(Imported { file_name, post }))
+}
+
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::Text(text) => text.clone(),
+        Value::List(items) => items.join(", "),
+    }
+}
+
+/// Jekyll also takes a single tag or a space-separated string in place of a list
+fn list_of(value: &Value) -> Vec<String> {
+    match value {
+        Value::Text(text) => text.split_whitespace().map(str::to_string).collect(),
+        Value::List(items) => items.clone(),
+    }
+}
+
+fn split_front_matter(text: &str) -> Result<(FrontMatter, &str), String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let fence = match text.lines().next().map(str::trim_end) {
+        Some(fence @ ("---" | "+++")) => fence,
+        _ => return Err("no front matter".to_string()),
+    };
+    let rest = &text[text.find('\n').ok_or("no front matter")? + 1..];
+    let end = rest
+        .match_indices(fence)
+        .find(|&(at, _)| (at == 0 || rest.as_bytes()[at - 1] == b'\n') && rest[at + 3..].lines().next().is_none_or(|line| line.trim().is_empty()))
+        .map(|(at, _)| at)
+        .ok_or("the front matter is never closed")?;
+    let body = rest[end + 3..].strip_prefix('\n').or_else(|| rest[end + 3..].strip_prefix("\r\n")).unwrap_or(&rest[end + 3..]);
+
+    let front = if fence == "+++" { parse_toml(&rest[..end])? } else { parse_yaml(&rest[..end]) };
+    Ok((front, body))
+}
+
+fn parse_toml(front: &str) -> Result<FrontMatter, String> {
+    let table: toml::Table = front.parse().map_err(|e| format!("bad TOML front matter: {}", e))?;
+    let scalar = |value: &toml::Value| match value {
+        toml::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    Ok(table
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                toml::Value::Array(items) => Value::List(items.iter().map(scalar).collect()),
+                value => Value::Text(scalar(value)),
+            };
+            (key.clone(), value)
+        })
+        .collect())
+}
+
+/// The flat subset of YAML front matter uses: `key: value`, inline `[a, b]` lists and `- item` lists below a
+/// key. Nested maps are skipped since no field the importer maps lives in one.
+fn parse_yaml(front: &str) -> FrontMatter {
+    let mut map = FrontMatter::new();
+    let mut list_key: Option<String> = None;
+    for line in front.lines() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        if line.starts_with([' ', '\t', '-']) {
+            if let (Some(key), Some(item)) = (&list_key, line.trim().strip_prefix('-')) {
+                if let Some(Value::List(items)) = map.get_mut(key) {
+                    items.push(unquote(item));
+                }
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else { continue };
+        let (key, value) = (key.trim().to_string(), value.trim());
+        list_key = None;
+        if value.is_empty() {
+            map.insert(key.clone(), Value::List(Vec::new()));
+            list_key = Some(key);
+        } else if let Some(items) = value.strip_prefix('[').and_then(|value| value.strip_suffix(']')) {
+            map.insert(key, Value::List(items.split(',').map(unquote).filter(|item| !item.is_empty()).collect()));
+        } else {
+            map.insert(key, Value::Text(unquote(value)));
+        }
+    }
+    map
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if let Some(inner) = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')) {
+        return unescape(inner);
+    }
+    if let Some(inner) = value.strip_prefix('\'').and_then(|value| value.strip_suffix('\'')) {
+        return inner.replace("''", "'");
+    }
+    // A trailing comment only counts after whitespace, `#` can be part of a value
+    value.split(" #").next().unwrap_or(value).trim().to_string()
+}
+
+/// The escapes of a double-quoted YAML string that show up in titles and summaries
+fn unescape(inner: &str) -> String {
+    let mut text = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => text.push('\n'),
+            Some('t') => text.push('\t'),
+            Some(other) => text.push(other),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/// The date formats Jekyll, Hugo and WordPress write, read as UTC when they carry no offset
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.to_utc());
+    }
+    for format in ["%Y-%m-%d %H:%M:%S %z", "%Y-%m-%d %H:%M %z"] {
+        if let Ok(date) = DateTime::parse_from_str(date, format) {
+            return Some(date.to_utc());
+        }
+    }
+    for format in ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(date) = NaiveDateTime::parse_from_str(date, format) {
+            return Some(date.and_utc());
+        }
+    }
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok().map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc())
+}
+
+/// The published and private posts of a WordPress export. Pages, attachments and drafts are counted as skipped.
+/// Bodies stay HTML, which markdown passes through.
+fn from_wxr(xml: &str) -> (Vec<Imported>, usize) {
+    let mut imported = Vec::new();
+    let mut skipped = 0;
+    for item in elements(xml, "item") {
+        let field = |name: &str| elements(item, name).next().map(text).unwrap_or_default();
+        let visibility = match field("wp:status").as_str() {
+            _ if field("wp:post_type") != "post" => None,
+            "publish" => Some(Visibility::Public),
+            "private" => Some(Visibility::Private),
+            _ => None,
+        };
+        let Some(visibility) = visibility else {
+            skipped += 1;
+            continue;
+        };
+        let title = field("title");
+        let Some(timestamp) = parse_date(&field("wp:post_date_gmt")).or_else(|| parse_date(&field("wp:post_date"))) else {
+            println!("Skipping {}: no publish date", title);
+            skipped += 1;
+            continue;
+        };
+
+        let mut tags = Vec::new();
+        for category in elements_with_attributes(item, "category") {
+            let tag = text(category.1);
+            if (category.0.contains("domain=\"post_tag\"") || category.0.contains("domain=\"category\"")) && !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        let name = field("wp:post_name");
+        let post = Post {
+            body: field("content:encoded").trim().to_string(),
+            image_url: String::new(),
+            image_alt: None,
+            image_poster: None,
+            summary: field("excerpt:encoded").trim().to_string(),
+            timestamp,
+            updated: parse_date(&field("wp:post_modified_gmt")).filter(|&updated| updated > timestamp),
+            lang: None,
+            slug: None,
+            tags,
+            visibility,
+            comments_enabled: (field("wp:comment_status") == "closed").then_some(false),
+            audio: None,
+            video: None,
+            attachments: Vec::new(),
+            accent_color: None,
+            hero: None,
+            url_name: String::new(),
+            source_file: String::new(),
+            rendered: RenderedBody::default(),
+            title,
+        };
+        imported.push(Imported { file_name: format!("{}.json", slug::slugify(if name.is_empty() { &post.title } else { &name })), post });
+    }
+    (imported, skipped)
+}
+
+/// The contents of each `<name>` element in `xml`, without nesting of the same element
+fn elements<'a>(xml: &'a str, name: &str) -> impl Iterator<Item = &'a str> {
+    elements_with_attributes(xml, name).map(|(_, inner)| inner)
+}
+
+/// The attributes and contents of each `<name ...>` element in `xml`
+fn elements_with_attributes<'a>(xml: &'a str, name: &str) -> impl Iterator<Item = (&'a str, &'a str)> {
+    let (open, close) = (format!("<{}", name), format!("</{}>", name));
+    let mut rest = xml;
+    std::iter::from_fn(move || loop {
+        let start = rest.find(&open)?;
+        let after = &rest[start + open.len()..];
+        // `<category` mustn't match `<categoryfoo`
+        if !after.starts_with(['>', ' ', '/', '\t', '\n', '\r']) {
+            rest = after;
+            continue;
+        }
+        let tag_end = after.find('>')?;
+        let attributes = &after[..tag_end];
+        if attributes.ends_with('/') {
+            rest = &after[tag_end + 1..];
+            return Some((attributes, ""));
+        }
+        let inner = &after[tag_end + 1..];
+        let end = find_outside_cdata(inner, &close)?;
+        rest = &inner[end + close.len()..];
+        return Some((attributes, &inner[..end]));
+    })
+}
+
+/// Where `needle` first shows up in `xml` outside any CDATA section, since post bodies can contain anything
+fn find_outside_cdata(xml: &str, needle: &str) -> Option<usize> {
+    let mut offset = 0;
+    loop {
+        let rest = &xml[offset..];
+        let found = rest.find(needle)?;
+        match rest.find("<![CDATA[") {
+            Some(cdata) if cdata < found => offset += cdata + rest[cdata..].find("]]>")? + 3,
+            _ => return Some(offset + found),
+        }
+    }
+}
+
+/// An element's text, from CDATA sections as-is and everything else with entities decoded
+fn text(inner: &str) -> String {
+    let mut text = String::new();
+    let mut rest = inner;
+    while let Some(start) = rest.find("<![CDATA[") {
+        text.push_str(&decode_entities(&rest[..start]));
+        let cdata = &rest[start + 9..];
+        let end = cdata.find("]]>").unwrap_or(cdata.len());
+        text.push_str(&cdata[..end]);
+        rest = cdata.get(end + 3..).unwrap_or_default();
+    }
+    text.push_str(&decode_entities(rest));
+    text.trim().to_string()
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        let after = &rest[amp + 1..];
+        let entity = after.find(';').map(|end| (&after[..end], end));
+        let character = entity.and_then(|(entity, _)| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity.strip_prefix('#').and_then(|n| n.parse().ok()).and_then(char::from_u32),
+            },
+        });
+        match (character, entity) {
+            (Some(character), Some((_, end))) => {
+                decoded.push(character);
+                rest = &after[end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = after;
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[test]
+fn imports_jekyll_and_hugo_posts() {
+    let jekyll = "---\nlayout: post\ntitle: \"Hello: World\"\ndate: 2020-03-04 10:30:00 +0100\ntags: [rust, web]\ncategories:\n  - notes\n  - rust\nexcerpt: A first post\n---\nSome *markdown*.\n";
+    let post = from_markdown(Path::new("_posts/2020-03-04-hello-world.md"), jekyll).unwrap().unwrap();
+    assert_eq!(post.file_name, "hello-world.json");
+    assert_eq!(post.post.title, "Hello: World");
+    assert_eq!(post.post.timestamp, "2020-03-04T09:30:00Z".parse::<DateTime<Utc>>().unwrap());
+    assert_eq!(post.post.tags, vec!["rust", "web", "notes"]);
+    assert_eq!(post.post.summary, "A first post");
+    assert_eq!(post.post.body, "Some *markdown*.");
+
+    let hugo = "+++\ntitle = \"Bundled\"\ndate = 2021-05-06T07:08:09Z\nlastmod = 2021-06-01\nslug = \"My Slug\"\ntags = [\"go\"]\n+++\n\nBody\n";
+    let post = from_markdown(Path::new("content/posts/bundled/index.md"), hugo).unwrap().unwrap();
+    assert_eq!(post.file_name, "my-slug.json");
+    assert_eq!(post.post.timestamp, "2021-05-06T07:08:09Z".parse::<DateTime<Utc>>().unwrap());
+    assert_eq!(post.post.updated, Some("2021-06-01T00:00:00Z".parse().unwrap()));
+    assert_eq!(post.post.tags, vec!["go"]);
+
+    let draft = "---\ntitle: Later\ndate: 2022-01-01\ndraft: true\n---\n";
+    assert!(from_markdown(Path::new("later.md"), draft).unwrap().is_none());
+    assert!(from_markdown(Path::new("undated.md"), "---\ntitle: Undated\n---\n").is_err());
+}
+
+#[test]
+fn imports_published_wordpress_posts() {
+    let xml = r#"<?xml version="1.0"?><rss><channel><title>Blog</title>
+<item><title>Fish &amp; Chips</title>
+<content:encoded><![CDATA[<p>Tasty</item></p>]]></content:encoded>
+<excerpt:encoded><![CDATA[]]></excerpt:encoded>
+<wp:post_date_gmt><![CDATA[2019-07-08 12:00:00]]></wp:post_date_gmt>
+<wp:post_name><![CDATA[fish-and-chips]]></wp:post_name>
+<wp:status><![CDATA[publish]]></wp:status>
+<wp:post_type><![CDATA[post]]></wp:post_type>
+<category domain="category" nicename="food"><![CDATA[Food]]></category>
+<category domain="post_tag" nicename="uk"><![CDATA[UK]]></category>
+</item>
+<item><title>About</title><wp:status>publish</wp:status><wp:post_type>page</wp:post_type></item>
+<item><title>Draft</title><wp:status>draft</wp:status><wp:post_type>post</wp:post_type></item>
+<item><title>Diary</title><wp:status>private</wp:status><wp:comment_status>closed</wp:comment_status><wp:post_type>post</wp:post_type><wp:post_date_gmt>2019-07-09 12:00:00</wp:post_date_gmt></item>
+</channel></rss>"#;
+    let (imported, skipped) = from_wxr(xml);
+    assert_eq!(skipped, 2);
+    assert_eq!(imported.len(), 2);
+    assert_eq!(imported[1].post.visibility, Visibility::Private);
+    assert_eq!((imported[0].post.comments_enabled, imported[1].post.comments_enabled), (None, Some(false)));
+    let Imported { file_name, post } = &imported[0];
+    assert_eq!(file_name, "fish-and-chips.json");
+    assert_eq!(post.title, "Fish & Chips");
+    assert_eq!(post.body, "<p>Tasty</item></p>");
+    assert_eq!(post.tags, vec!["Food", "UK"]);
+    assert_eq!(post.timestamp, "2019-07-08T12:00:00Z".parse::<DateTime<Utc>>().unwrap());
+}
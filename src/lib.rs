@@ -0,0 +1,67 @@
+mod access;
+mod admin;
+mod assets;
+mod backdrop;
+mod backup;
+mod bots;
+mod cidr;
+mod commands;
+mod comments;
+mod config;
+mod defaults;
+mod dev;
+mod emoji;
+mod events;
+mod excerpt;
+mod exif;
+mod export;
+mod extract;
+mod feeds;
+mod fingerprint;
+mod gallery;
+mod hotlink;
+mod i18n;
+mod icons;
+mod import;
+mod listen;
+mod mail;
+mod maintenance;
+mod metrics;
+mod model;
+mod notify;
+mod og;
+mod outbound;
+mod paths;
+mod placeholder;
+mod polls;
+mod prefs;
+mod previews;
+mod reactions;
+mod render;
+mod routes;
+mod search;
+mod slug;
+mod spam;
+mod pwa;
+/// Requests against the full router with the posts and assets under `tests/fixtures`
+#[cfg(test)]
+mod route_tests;
+mod share;
+mod shortcode;
+mod signed;
+mod state;
+mod store;
+mod sync;
+mod tally;
+mod theme;
+mod thumbnail;
+pub mod vendor;
+mod warm;
+
+pub use commands::{new_post, validate};
+pub use config::Config;
+pub use export::export;
+pub use import::import;
+pub use listen::{serve, Listener, ServerOptions};
+pub use routes::build_app;
+pub use state::AppState;
@@ -0,0 +1,275 @@
+use std::net::IpAddr;
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::Request;
+use axum::Router;
+use hyper::body::Incoming;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio_rustls::rustls;
+use tokio_rustls::TlsAcceptor;
+use tower::Service;
+
+use crate::cidr::{self, Cidr};
+
+/// Prefix of a `CADEN_BLOG_ADDR` that names a Unix domain socket path instead of a TCP address
+const UNIX_PREFIX: &str = "unix:";
+
+/// First descriptor systemd hands over with socket activation, per `sd_listen_fds(3)`
+const LISTEN_FDS_START: i32 = 3;
+
+/// How long a connection may sit without sending a request when `CADEN_BLOG_KEEP_ALIVE_SECS` isn't set
+const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(30);
+
+/// Server tuning for running straight on the public internet, read from the `CADEN_BLOG_*` environment variables
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// Certificate chain and private key, both PEM, from `CADEN_BLOG_TLS_CERT` and `CADEN_BLOG_TLS_KEY`
+    pub tls: Option<(PathBuf, PathBuf)>,
+    /// Offer HTTP/2 as well, through ALPN over TLS or by prior knowledge without it, from `CADEN_BLOG_HTTP2`
+    pub http2: bool,
+    /// How long an idle connection is kept open waiting for its next request, from `CADEN_BLOG_KEEP_ALIVE_SECS`.
+    /// Zero closes HTTP/1 connections after every response.
+    pub keep_alive: Duration,
+    /// Connections served at once, from `CADEN_BLOG_MAX_CONNECTIONS`; the rest wait in the listen backlog
+    pub max_connections: Option<usize>,
+    /// Proxies whose forwarding headers are believed, from `CADEN_BLOG_TRUSTED_PROXIES`, see [`crate::extract::client::Client`]
+    pub trusted_proxies: Vec<Cidr>,
+}
+
+/// The connection a request arrived on, added to every request's extensions
+#[derive(Debug, Clone, Copy)]
+pub struct Peer {
+    /// `None` on a Unix socket
+    pub ip: Option<IpAddr>,
+    pub tls: bool,
+}
+
+impl Default for ServerOptions {
+    fn default() -> ServerOptions {
+        ServerOptions { tls: None, http2: false, keep_alive: DEFAULT_KEEP_ALIVE, max_connections: None, trusted_proxies: Vec::new() }
+    }
+}
+
+impl ServerOptions {
+    pub fn from_env() -> ServerOptions {
+        let var = |name: &str| std::env::var(name).ok().map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+        ServerOptions {
+            tls: var("CADEN_BLOG_TLS_CERT").zip(var("CADEN_BLOG_TLS_KEY")).map(|(cert, key)| (cert.into(), key.into())),
+            http2: var("CADEN_BLOG_HTTP2").is_some_and(|value| matches!(value.as_str(), "1" | "true" | "on")),
+            keep_alive: var("CADEN_BLOG_KEEP_ALIVE_SECS")
+                .and_then(|secs| secs.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_KEEP_ALIVE),
+            max_connections: var("CADEN_BLOG_MAX_CONNECTIONS").and_then(|max| max.parse().ok()).filter(|&max| max > 0),
+            trusted_proxies: var("CADEN_BLOG_TRUSTED_PROXIES").map(|list| cidr::parse_list(&list)).unwrap_or_default(),
+        }
+    }
+
+    /// Reads the certificate and key, advertising HTTP/2 to clients when it's on
+    fn tls_acceptor(&self) -> Result<Option<TlsAcceptor>, String> {
+        use rustls::pki_types::pem::PemObject;
+        use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+        let Some((cert, key)) = &self.tls else { return Ok(None) };
+        let certs = CertificateDer::pem_file_iter(cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("couldn't read the certificate {}: {}", cert.display(), e))?;
+        let key = PrivateKeyDer::from_pem_file(key).map_err(|e| format!("couldn't read the private key {}: {}", key.display(), e))?;
+
+        let mut config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .map_err(|e| format!("couldn't use the certificate {}: {}", cert.display(), e))?;
+        config.alpn_protocols = if self.http2 { vec![b"h2".to_vec(), b"http/1.1".to_vec()] } else { vec![b"http/1.1".to_vec()] };
+        Ok(Some(TlsAcceptor::from(Arc::new(config))))
+    }
+
+    fn builder(&self) -> auto::Builder<TokioExecutor> {
+        let mut builder = auto::Builder::new(TokioExecutor::new());
+        builder
+            .http1()
+            .timer(TokioTimer::new())
+            .keep_alive(!self.keep_alive.is_zero())
+            .header_read_timeout(Some(self.keep_alive).filter(|timeout| !timeout.is_zero()));
+        builder.http2().timer(TokioTimer::new()).keep_alive_interval(Some(self.keep_alive).filter(|interval| !interval.is_zero()));
+        if self.http2 {
+            builder
+        } else {
+            builder.http1_only()
+        }
+    }
+}
+
+/// Where connections come from: a TCP port, or a Unix domain socket for sitting behind a local reverse proxy
+pub enum Listener {
+    Tcp(tokio::net::TcpListener),
+    Unix(tokio::net::UnixListener),
+}
+
+impl Listener {
+    /// The socket systemd passed in through `LISTEN_FDS`, otherwise `addr` bound as `unix:<path>` or a TCP address
+    pub async fn bind(addr: &str) -> Result<Listener, String> {
+        if let Some(listener) = inherited()? {
+            return Ok(listener);
+        }
+
+        match addr.strip_prefix(UNIX_PREFIX) {
+            Some(path) => {
+                // A socket file left behind by an earlier run would make the bind fail
+                if std::fs::metadata(path).is_ok_and(|meta| std::os::unix::fs::FileTypeExt::is_socket(&meta.file_type())) {
+                    std::fs::remove_file(path).map_err(|e| format!("couldn't remove the stale socket {}: {}", path, e))?;
+                }
+                tokio::net::UnixListener::bind(path)
+                    .map(Listener::Unix)
+                    .map_err(|e| format!("couldn't listen on {}: {}", path, e))
+            }
+            None => tokio::net::TcpListener::bind(addr)
+                .await
+                .map(Listener::Tcp)
+                .map_err(|e| format!("couldn't listen on {}: {}", addr, e)),
+        }
+    }
+
+    /// Where the listener is reachable, for the startup log
+    pub fn describe(&self) -> String {
+        match self {
+            Listener::Tcp(listener) => listener.local_addr().map(|addr| addr.to_string()),
+            Listener::Unix(listener) => listener
+                .local_addr()
+                .map(|addr| addr.as_pathname().map(|path| format!("{}{}", UNIX_PREFIX, path.display())).unwrap_or_else(|| "an unnamed unix socket".to_string())),
+        }
+        .unwrap_or_else(|e| format!("an unknown address ({})", e))
+    }
+}
+
+/// The first socket from systemd socket activation, when `LISTEN_PID` says the descriptors are meant for this process
+fn inherited() -> Result<Option<Listener>, String> {
+    let for_us = std::env::var("LISTEN_PID").ok().and_then(|pid| pid.parse::<u32>().ok()) == Some(std::process::id());
+    let count = std::env::var("LISTEN_FDS").ok().and_then(|count| count.parse::<i32>().ok()).unwrap_or(0);
+    if !for_us || count < 1 {
+        return Ok(None);
+    }
+    if count > 1 {
+        println!("systemd passed {} sockets, only listening on the first", count);
+    }
+
+    // Safety: systemd keeps descriptors from LISTEN_FDS_START open for us, and nothing else in the process takes them
+    let fd = unsafe { OwnedFd::from_raw_fd(LISTEN_FDS_START) };
+    // The address family tells the two kinds apart: reading a TCP address off a Unix socket fails
+    let tcp = std::net::TcpListener::from(fd);
+    let listener = if tcp.local_addr().is_ok() {
+        tcp.set_nonblocking(true).and_then(|_| tokio::net::TcpListener::from_std(tcp)).map(Listener::Tcp)
+    } else {
+        let unix = std::os::unix::net::UnixListener::from(OwnedFd::from(tcp));
+        unix.set_nonblocking(true).and_then(|_| tokio::net::UnixListener::from_std(unix)).map(Listener::Unix)
+    };
+    listener.map(Some).map_err(|e| format!("couldn't use the socket from systemd: {}", e))
+}
+
+/// Serves the app on the listener until the process exits. Fails before accepting anything when the TLS files can't be used.
+pub async fn serve(listener: Listener, app: Router, options: &ServerOptions) -> Result<(), String> {
+    let tls = options.tls_acceptor()?;
+    let limit = options.max_connections.map(|max| Arc::new(Semaphore::new(max)));
+    loop {
+        let permit = match &limit {
+            Some(limit) => Some(limit.clone().acquire_owned().await.expect("the connection limit is never closed")),
+            None => None,
+        };
+        let accepted = match &listener {
+            Listener::Tcp(listener) => listener.accept().await.map(|(socket, addr)| {
+                let _ = socket.set_nodelay(true);
+                let peer = Peer { ip: Some(addr.ip()), tls: tls.is_some() };
+                spawn_connection(socket, peer, app.clone(), options.clone(), tls.clone(), permit)
+            }),
+            Listener::Unix(listener) => listener.accept().await.map(|(socket, _)| {
+                let peer = Peer { ip: None, tls: tls.is_some() };
+                spawn_connection(socket, peer, app.clone(), options.clone(), tls.clone(), permit)
+            }),
+        };
+        // Running out of file descriptors shouldn't spin the loop, so back off for a moment
+        if let Err(e) = accepted {
+            println!("Couldn't accept a connection: {}", e);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+}
+
+/// Serves one connection on its own task, holding on to its slot under the connection limit until it closes
+fn spawn_connection<S>(socket: S, peer: Peer, app: Router, options: ServerOptions, tls: Option<TlsAcceptor>, permit: Option<OwnedSemaphorePermit>)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let _permit = permit;
+        let service = hyper::service::service_fn(move |mut request: Request<Incoming>| {
+            request.extensions_mut().insert(peer);
+            app.clone().call(request)
+        });
+        let builder = options.builder();
+        let result = match tls {
+            // Failed handshakes are mostly scanners and not worth logging
+            Some(tls) => match tls.accept(socket).await {
+                Ok(stream) => builder.serve_connection_with_upgrades(TokioIo::new(stream), service).await,
+                Err(_) => return,
+            },
+            None => builder.serve_connection_with_upgrades(TokioIo::new(socket), service).await,
+        };
+        if let Err(e) = result {
+            println!("Connection failed: {}", e);
+        }
+    });
+}
+
+#[tokio::test]
+async fn unix_socket_serves_the_app() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let path = std::env::temp_dir().join(format!("caden-blog-listen-{}.sock", std::process::id()));
+    let listener = Listener::bind(&format!("{}{}", UNIX_PREFIX, path.display())).await.unwrap();
+    assert_eq!(listener.describe(), format!("{}{}", UNIX_PREFIX, path.display()));
+    let app = Router::new().route("/", axum::routing::get(|| async { "hello" }));
+    tokio::spawn(async move { serve(listener, app, &ServerOptions::default()).await });
+
+    let mut stream = tokio::net::UnixStream::connect(&path).await.unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("hello"), "{}", response);
+
+    // Binding again replaces the socket file the first listener left behind
+    Listener::bind(&format!("{}{}", UNIX_PREFIX, path.display())).await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn connection_limit_holds_back_further_connections() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let Listener::Tcp(tcp) = &listener else { unreachable!() };
+    let addr = tcp.local_addr().unwrap();
+    let options = ServerOptions { max_connections: Some(1), ..ServerOptions::default() };
+    let app = Router::new().route("/", axum::routing::get(|| async { "hello" }));
+    tokio::spawn(async move { serve(listener, app, &options).await });
+
+    let request = b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n";
+    let mut first = tokio::net::TcpStream::connect(addr).await.unwrap();
+    first.write_all(request).await.unwrap();
+    let mut buf = [0; 512];
+    assert!(first.read(&mut buf).await.unwrap() > 0);
+
+    // The first connection is kept alive, so the second isn't served until it goes away
+    let mut second = tokio::net::TcpStream::connect(addr).await.unwrap();
+    second.write_all(request).await.unwrap();
+    assert!(tokio::time::timeout(Duration::from_millis(200), second.read(&mut buf)).await.is_err());
+    drop(first);
+    let read = tokio::time::timeout(Duration::from_secs(5), second.read(&mut buf)).await.unwrap().unwrap();
+    assert!(String::from_utf8_lossy(&buf[..read]).starts_with("HTTP/1.1 200"));
+}
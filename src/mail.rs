@@ -0,0 +1,245 @@
+use std::io;
+use std::sync::Arc;
+
+use base64::Engine;
+use chrono::Utc;
+use rustls_platform_verifier::ConfigVerifierExt;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufStream};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::ClientConfig;
+use tokio_rustls::TlsConnector;
+
+/// Longest line in an SMTP reply we'll read, anything longer isn't a mail server
+const MAX_REPLY_LINE: usize = 4096;
+
+/// How the connection to the mail server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Security {
+    /// TLS from the start, usually on port 465
+    Tls,
+    /// Upgraded with `STARTTLS` after connecting, usually on port 587
+    StartTls,
+    /// Plain text, only for a relay on the same machine
+    None,
+}
+
+impl Security {
+    fn default_port(self) -> u16 {
+        match self {
+            Security::Tls => 465,
+            Security::StartTls => 587,
+            Security::None => 25,
+        }
+    }
+}
+
+/// The mail server emails go out through, read from the `CADEN_BLOG_SMTP_*` environment variables
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// From `CADEN_BLOG_SMTP_PORT`, the usual port for `security` by default
+    pub port: u16,
+    /// From `CADEN_BLOG_SMTP_SECURITY`: `tls`, `starttls` (the default) or `none`
+    pub security: Security,
+    /// `CADEN_BLOG_SMTP_USER` and `CADEN_BLOG_SMTP_PASSWORD`, sent with `AUTH PLAIN` when both are set
+    pub credentials: Option<(String, String)>,
+    /// `CADEN_BLOG_SMTP_FROM`, the address emails are sent from
+    pub from: String,
+    /// `CADEN_BLOG_SMTP_TO`, the admin's address
+    pub to: String,
+}
+
+impl SmtpConfig {
+    /// `None` unless a host and both addresses are set
+    pub fn from_env() -> Option<SmtpConfig> {
+        let var = |name: &str| std::env::var(name).ok().map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+        let security = match var("CADEN_BLOG_SMTP_SECURITY").as_deref() {
+            Some("tls") => Security::Tls,
+            Some("none") => Security::None,
+            Some("starttls") | None => Security::StartTls,
+            Some(other) => {
+                println!("Unknown SMTP security {}, using starttls", other);
+                Security::StartTls
+            }
+        };
+        Some(SmtpConfig {
+            host: var("CADEN_BLOG_SMTP_HOST")?,
+            port: var("CADEN_BLOG_SMTP_PORT").and_then(|port| port.parse().ok()).unwrap_or(security.default_port()),
+            security,
+            credentials: var("CADEN_BLOG_SMTP_USER").zip(var("CADEN_BLOG_SMTP_PASSWORD")),
+            from: var("CADEN_BLOG_SMTP_FROM")?,
+            to: var("CADEN_BLOG_SMTP_TO")?,
+        })
+    }
+}
+
+/// A plain text email to the admin
+#[derive(Debug, Clone, PartialEq)]
+pub struct Email {
+    pub subject: String,
+    pub body: String,
+}
+
+trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Stream for S {}
+
+/// Sends an email to the admin through the configured server
+pub async fn send(config: &SmtpConfig, email: &Email) -> io::Result<()> {
+    let tcp = TcpStream::connect((config.host.as_str(), config.port)).await?;
+    let stream: Box<dyn Stream> = match config.security {
+        Security::Tls => Box::new(tls(&config.host, Box::new(tcp)).await?),
+        Security::StartTls | Security::None => Box::new(tcp),
+    };
+    deliver(stream, config, email).await
+}
+
+async fn tls(host: &str, stream: Box<dyn Stream>) -> io::Result<impl Stream> {
+    let config = ClientConfig::with_platform_verifier().map_err(io::Error::other)?;
+    let name = ServerName::try_from(host.to_string()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    TlsConnector::from(Arc::new(config)).connect(name, stream).await
+}
+
+/// Reads a reply, joining the lines of a multiline one, and fails unless its code is `expected`
+async fn reply(stream: &mut BufStream<Box<dyn Stream>>, expected: u16) -> io::Result<String> {
+    let mut text = String::new();
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 || line.len() > MAX_REPLY_LINE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the mail server hung up or sent garbage"));
+        }
+        let line = line.trim_end();
+        let code: u16 = line.get(..3).and_then(|code| code.parse().ok()).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("not an SMTP reply: {:?}", line)))?;
+        text.push_str(line.get(4..).unwrap_or_default());
+        text.push('\n');
+        // `250-` continues, `250 ` is the last line
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return if code == expected { Ok(text) } else { Err(io::Error::other(format!("the mail server answered {} {}", code, text.trim_end()))) };
+        }
+    }
+}
+
+async fn command(stream: &mut BufStream<Box<dyn Stream>>, line: &str, expected: u16) -> io::Result<String> {
+    stream.write_all(format!("{}\r\n", line).as_bytes()).await?;
+    stream.flush().await?;
+    reply(stream, expected).await
+}
+
+/// Speaks SMTP over a connected stream, upgrading it first when it's `STARTTLS`
+async fn deliver(stream: Box<dyn Stream>, config: &SmtpConfig, email: &Email) -> io::Result<()> {
+    let mut stream = BufStream::new(stream);
+    reply(&mut stream, 220).await?;
+    let ehlo = format!("EHLO {}", hostname(&config.from));
+    let mut extensions = command(&mut stream, &ehlo, 250).await?;
+
+    if config.security == Security::StartTls {
+        if !extensions.lines().any(|line| line.eq_ignore_ascii_case("STARTTLS")) {
+            return Err(io::Error::other("the mail server doesn't offer STARTTLS"));
+        }
+        command(&mut stream, "STARTTLS", 220).await?;
+        stream = BufStream::new(Box::new(tls(&config.host, stream.into_inner()).await?));
+        extensions = command(&mut stream, &ehlo, 250).await?;
+    }
+
+    if let Some((user, password)) = &config.credentials {
+        if !extensions.lines().any(|line| line.to_uppercase().starts_with("AUTH") && line.to_uppercase().contains("PLAIN")) {
+            return Err(io::Error::other("the mail server doesn't offer AUTH PLAIN"));
+        }
+        let plain = base64::engine::general_purpose::STANDARD.encode(format!("\0{}\0{}", user, password));
+        command(&mut stream, &format!("AUTH PLAIN {}", plain), 235).await?;
+    }
+
+    command(&mut stream, &format!("MAIL FROM:<{}>", config.from), 250).await?;
+    command(&mut stream, &format!("RCPT TO:<{}>", config.to), 250).await?;
+    command(&mut stream, "DATA", 354).await?;
+    command(&mut stream, &format!("{}\r\n.", message(config, email)), 250).await?;
+    // The email is accepted by now, so a server that hangs up without a goodbye doesn't matter
+    let _ = command(&mut stream, "QUIT", 221).await;
+    Ok(())
+}
+
+/// The domain part of an address, to greet the server with
+fn hostname(address: &str) -> &str {
+    address.rsplit_once('@').map_or("localhost", |(_, domain)| domain)
+}
+
+/// Headers and a base64 body, so no line of the text can be taken for the end of the data
+fn message(config: &SmtpConfig, email: &Email) -> String {
+    let engine = base64::engine::general_purpose::STANDARD;
+    // A line break in a post title mustn't start a header of its own
+    let subject = email.subject.replace(['\r', '\n'], " ");
+    let subject = if subject.is_ascii() { subject } else { format!("=?UTF-8?B?{}?=", engine.encode(&subject)) };
+    let body = engine.encode(email.body.replace("\r\n", "\n").replace('\n', "\r\n"));
+    let lines: Vec<&str> = body.as_bytes().chunks(76).map(|chunk| std::str::from_utf8(chunk).expect("base64 is ascii")).collect();
+    format!(
+        "From: <{from}>\r\nTo: <{to}>\r\nSubject: {subject}\r\nDate: {date}\r\nMessage-ID: <{id}@{host}>\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{body}",
+        from = config.from,
+        to = config.to,
+        date = Utc::now().to_rfc2822(),
+        id = uuid::Uuid::new_v4(),
+        host = hostname(&config.from),
+        body = lines.join("\r\n"),
+    )
+}
+
+#[tokio::test]
+async fn emails_are_delivered_over_smtp() {
+    use tokio::io::AsyncReadExt;
+
+    let (client, server) = tokio::io::duplex(64 * 1024);
+    let server = tokio::spawn(async move {
+        let mut server = BufStream::new(server);
+        let mut said = Vec::new();
+        server.write_all(b"220 mail.example\r\n").await.unwrap();
+        server.flush().await.unwrap();
+        loop {
+            let mut line = String::new();
+            if server.read_line(&mut line).await.unwrap() == 0 {
+                break;
+            }
+            let verb = line.split([' ', '\r']).next().unwrap_or_default().to_string();
+            said.push(line);
+            let answer = match verb.as_str() {
+                "EHLO" => "250-mail.example\r\n250 AUTH LOGIN PLAIN",
+                "AUTH" => "235 ok",
+                "DATA" => {
+                    server.write_all(b"354 go on\r\n").await.unwrap();
+                    server.flush().await.unwrap();
+                    let mut data = Vec::new();
+                    while !data.ends_with(b"\r\n.\r\n") {
+                        data.push(server.read_u8().await.unwrap());
+                    }
+                    said.push(String::from_utf8(data).unwrap());
+                    "250 queued"
+                }
+                "QUIT" => "221 bye",
+                _ => "250 ok",
+            };
+            server.write_all(format!("{}\r\n", answer).as_bytes()).await.unwrap();
+            server.flush().await.unwrap();
+        }
+        said
+    });
+
+    let config = SmtpConfig {
+        host: "mail.example".to_string(),
+        port: 25,
+        security: Security::None,
+        credentials: Some(("ann".to_string(), "secret".to_string())),
+        from: "blog@example.com".to_string(),
+        to: "ann@example.com".to_string(),
+    };
+    let email = Email { subject: "Nuevo comentario ✓".to_string(), body: "Hi\n.\nthere".to_string() };
+    deliver(Box::new(client), &config, &email).await.unwrap();
+
+    let said = server.await.unwrap();
+    assert_eq!(said[0], "EHLO example.com\r\n");
+    assert_eq!(said[1], format!("AUTH PLAIN {}\r\n", base64::engine::general_purpose::STANDARD.encode("\0ann\0secret")));
+    assert_eq!(said[2], "MAIL FROM:<blog@example.com>\r\n");
+    assert_eq!(said[3], "RCPT TO:<ann@example.com>\r\n");
+    let data = &said[5];
+    assert!(data.contains("Subject: =?UTF-8?B?"), "{}", data);
+    assert!(data.contains(&base64::engine::general_purpose::STANDARD.encode("Hi\r\n.\r\nthere")), "{}", data);
+    assert_eq!(said.last().map(String::as_str), Some("QUIT\r\n"));
+}
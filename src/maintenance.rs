@@ -0,0 +1,138 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use axum::extract::{Request, State};
+use axum::http::header::RETRY_AFTER;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{Html, IntoResponse, Response};
+use maud::{html, PreEscaped};
+
+use crate::admin::{ADMIN_PREFIX, HEALTH_PATH};
+use crate::i18n::Text;
+use crate::render::{skip_link, FOCUS_CSS};
+use crate::state::AppState;
+use crate::{icons, metrics, vendor};
+
+/// Seconds clients are told to wait when `CADEN_BLOG_MAINTENANCE_RETRY_AFTER` isn't set
+const DEFAULT_RETRY_AFTER: u64 = 600;
+
+/// Whether public pages are swapped for a "Back soon" page, starting from `CADEN_BLOG_MAINTENANCE` and switched at
+/// runtime from the admin routes
+pub struct Maintenance {
+    enabled: AtomicBool,
+    /// Sent as `Retry-After`, from `CADEN_BLOG_MAINTENANCE_RETRY_AFTER`
+    retry_after: u64,
+}
+
+impl Default for Maintenance {
+    fn default() -> Maintenance {
+        Maintenance { enabled: AtomicBool::new(false), retry_after: DEFAULT_RETRY_AFTER }
+    }
+}
+
+impl Maintenance {
+    pub fn from_env() -> Maintenance {
+        let enabled = std::env::var("CADEN_BLOG_MAINTENANCE").is_ok_and(|value| matches!(value.trim(), "1" | "true" | "on"));
+        if enabled {
+            println!("Starting in maintenance mode");
+        }
+        Maintenance {
+            enabled: AtomicBool::new(enabled),
+            retry_after: std::env::var("CADEN_BLOG_MAINTENANCE_RETRY_AFTER")
+                .ok()
+                .and_then(|secs| secs.parse().ok())
+                .unwrap_or(DEFAULT_RETRY_AFTER),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// Routes that keep working in maintenance mode: the admin and health checks, and what the "Back soon" page loads
+fn exempt(path: &str) -> bool {
+    path == HEALTH_PATH
+        || path == metrics::METRICS_PATH
+        || path == "/favicon.ico"
+        || path.strip_prefix(ADMIN_PREFIX).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        || path.starts_with("/assets/vendor/")
+        || icons::ICON_SIZES.iter().any(|(name, _)| path.strip_prefix('/') == Some(*name))
+}
+
+fn page(theme_color: &str, text: Text) -> maud::Markup {
+    html! {
+        (maud::DOCTYPE)
+        html lang=(text.lang) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                (icons::icon_links(theme_color))
+                title { (text.t("maintenance_title")) }
+                (vendor::stylesheet("bootstrap.min.css"))
+                style { r#"
+                    body {
+                        font-family: Arial, sans-serif;
+                        background-color: #121212;
+                        color: #e0e0e0;
+                        padding: 20px;
+                    }
+                    .container {
+                        max-width: 800px;
+                        margin: 0 auto;
+                        text-align: center;
+                    }
+                    .header {
+                        text-align: center;
+                        background-color: #343a40;
+                        color: #f0f0f0;
+                        padding: 20px;
+                    }
+                    .maintenance-message {
+                        background-color: #1e1e1e;
+                        padding: 20px;
+                        border-radius: 8px;
+                        box-shadow: 0 4px 8px rgba(0, 0, 0, 0.3);
+                    }
+                "# }
+                style { (PreEscaped(FOCUS_CSS)) }
+            }
+            body {
+                (skip_link(text))
+                header class="header" {
+                    h1 { "The Caden Times" }
+                }
+                main id="main" class="container mt-4" {
+                    div class="maintenance-message" {
+                        h2 { (text.t("maintenance_title")) }
+                        p { (text.t("maintenance_text")) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Middleware answering public routes with a `503` "Back soon" page while maintenance mode is on
+pub async fn gate(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !state.maintenance.enabled() || exempt(request.uri().path()) {
+        return next.run(request).await;
+    }
+    let lang = state.locales.negotiate(request.headers());
+    let text = state.locales.text(&lang);
+    (StatusCode::SERVICE_UNAVAILABLE, [(RETRY_AFTER, state.maintenance.retry_after.to_string())], Html(page(&state.config.theme_color, text).into_string())).into_response()
+}
+
+#[test]
+fn maintenance_leaves_admin_and_health_alone() {
+    for path in ["/health", "/admin", "/admin/maintenance", "/metrics", "/assets/vendor/bootstrap.min.css", "/icon-192.png"] {
+        assert!(exempt(path), "{}", path);
+    }
+    for path in ["/", "/post/hello-world", "/administrator", "/asset/cat.png"] {
+        assert!(!exempt(path), "{}", path);
+    }
+}
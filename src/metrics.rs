@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+
+use crate::state::AppState;
+
+/// Where the counters are exposed, in the Prometheus text format
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Counters for the subsystems to report into, keyed by metric name and then by the rendered label set
+#[derive(Default)]
+pub struct Metrics {
+    counters: Mutex<BTreeMap<&'static str, BTreeMap<String, u64>>>,
+}
+
+impl Metrics {
+    pub fn incr(&self, name: &'static str, labels: &[(&str, &str)]) {
+        let labels = labels.iter().map(|(key, value)| format!("{}=\"{}\"", key, value.replace('\\', "\\\\").replace('"', "\\\""))).collect::<Vec<_>>().join(",");
+        *self.counters.lock().expect("failed to lock the metrics").entry(name).or_default().entry(labels).or_default() += 1;
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, series) in self.counters.lock().expect("failed to lock the metrics").iter() {
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for (labels, value) in series {
+                let _ = match labels.as_str() {
+                    "" => writeln!(out, "{} {}", name, value),
+                    labels => writeln!(out, "{}{{{}}} {}", name, labels, value),
+                };
+            }
+        }
+        out
+    }
+}
+
+pub async fn serve_metrics(State(AppState { metrics, .. }): State<AppState>) -> impl IntoResponse {
+    ([(CONTENT_TYPE, "text/plain; version=0.0.4")], metrics.render())
+}
+
+#[test]
+fn metrics_render_as_prometheus_text() {
+    let metrics = Metrics::default();
+    metrics.incr("caden_blog_bot_requests_total", &[("reason", "user_agent")]);
+    metrics.incr("caden_blog_bot_requests_total", &[("reason", "user_agent")]);
+    metrics.incr("caden_blog_bot_requests_total", &[("reason", "speed")]);
+    metrics.incr("caden_blog_events_total", &[]);
+    assert_eq!(
+        metrics.render(),
+        "# TYPE caden_blog_bot_requests_total counter\n\
+         caden_blog_bot_requests_total{reason=\"speed\"} 1\n\
+         caden_blog_bot_requests_total{reason=\"user_agent\"} 2\n\
+         # TYPE caden_blog_events_total counter\n\
+         caden_blog_events_total 1\n"
+    );
+}
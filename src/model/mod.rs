@@ -0,0 +1,3 @@
+pub mod note;
+pub mod post;
+pub mod project;
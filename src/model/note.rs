@@ -0,0 +1,50 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::excerpt;
+
+/// Characters of the body a note without a title is headed with in feeds
+const HEADING_LEN: usize = 60;
+
+/// A short update stored in `./caden-blog/notes`, one json file per note. Notes have no image or summary, and a
+/// title only when the author gives them one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Note {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub body: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// The file name without `.json`, the note's anchor on `/notes`
+    #[serde(skip)]
+    pub id: String,
+}
+
+impl Note {
+    /// The title, or the start of the body for a note without one
+    pub fn heading(&self) -> String {
+        match &self.title {
+            Some(title) if !title.trim().is_empty() => title.clone(),
+            _ => excerpt::truncate_words(&excerpt::plain_text(&self.body), HEADING_LEN),
+        }
+    }
+}
+
+/// A note from the contents of its file, with its id taken from the file name
+pub fn parse_note(file_name: &str, contents: &str) -> Result<Note, String> {
+    let mut note: Note = serde_json::from_str(contents).map_err(|e| format!("{}: {}", file_name, e))?;
+    note.id = file_name.trim_end_matches(".json").to_string();
+    Ok(note)
+}
+
+#[test]
+fn notes_without_a_title_are_headed_by_their_body() {
+    let note = parse_note("shipped.json", r#"{"body": "Shipped **dark mode** today, more on that in a post soon enough, promise", "timestamp": "2024-01-01T00:00:00Z"}"#).unwrap();
+    assert_eq!(note.id, "shipped");
+    assert_eq!(note.heading(), "Shipped dark mode today, more on that in a post soon…");
+
+    let titled = parse_note("v2.json", r#"{"title": "v2", "body": "Out now", "timestamp": "2024-01-01T00:00:00Z"}"#).unwrap();
+    assert_eq!(titled.heading(), "v2");
+    assert!(parse_note("broken.json", "{}").unwrap_err().starts_with("broken.json: "));
+}
@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, OnceLock};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::render::markdown::dark_variants;
+use crate::{excerpt, i18n};
+
+/// A post as stored in `./caden-blog/posts`, one json file per post and translation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Post {
+    pub title: String,
+    pub body: String,
+    pub image_url: String,
+    /// Description of the image for screen readers, the cards fall back to the title without one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_alt: Option<String>,
+    /// A still of `image_url` when that's a video or animated image, shown to readers saving data and shared in
+    /// its place, since link previews can't play it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_poster: Option<String>,
+    pub summary: String,
+    pub timestamp: DateTime<Utc>,
+    /// When the post was last edited after publishing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<DateTime<Utc>>,
+    /// Language the post is written in, used for the page's `lang` attribute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    /// URL name to publish under instead of the one derived from the file name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slug: Option<String>,
+    /// Topics the post is filed under
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Who gets to see the post, everyone by default
+    #[serde(default, skip_serializing_if = "Visibility::is_public")]
+    pub visibility: Visibility,
+    /// Whether readers can comment, the site's default from `CADEN_BLOG_COMMENTS_DEFAULT` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comments_enabled: Option<bool>,
+    /// An episode played on the post page and enclosed in the feeds, so the blog doubles as a podcast
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio: Option<Media>,
+    /// A video played on the post page, served from the assets so it streams with range requests
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video: Option<Media>,
+    /// Files offered for download under the post, stored with the assets in a directory named after the post
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<String>,
+    /// A hex color like `#e06c75` tinting the post's card and title, so a series or topic stands out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accent_color: Option<String>,
+    /// A banner image across the top of the post page, named like `image_url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hero: Option<String>,
+    #[serde(skip)]
+    pub url_name: String,
+    /// File the post was loaded from, for error reports
+    #[serde(skip)]
+    pub source_file: String,
+    /// The body's markdown rendered on first use, see [`crate::render::markdown::render_body`]
+    #[serde(skip)]
+    pub rendered: RenderedBody,
+}
+
+/// A file that goes with a post, like a podcast episode or a video
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Media {
+    /// A name from the assets directory, or a full URL for a file hosted elsewhere
+    pub src: String,
+    /// Size in bytes, looked up in the assets directory when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<u64>,
+    /// MIME type, guessed from the file extension when left out
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+    /// For a video, the still shown before it plays, named like `src`. The post's card image when left out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poster: Option<String>,
+}
+
+/// Where an asset name or full URL is served from on `origin`
+fn asset_url(origin: &str, src: &str) -> String {
+    if src.contains("://") {
+        src.to_string()
+    } else {
+        format!("{}/asset/{}", origin, src)
+    }
+}
+
+impl Media {
+    pub fn new(src: &str) -> Media {
+        Media { src: src.to_string(), length: None, mime: None, poster: None }
+    }
+
+    fn remote(&self) -> bool {
+        self.src.contains("://")
+    }
+
+    /// Where the file is served from on `origin`, which remote files ignore
+    pub fn url(&self, origin: &str) -> String {
+        asset_url(origin, &self.src)
+    }
+
+    /// Where the poster is served from on `origin`, when one is set
+    pub fn poster_url(&self, origin: &str) -> Option<String> {
+        self.poster.as_deref().filter(|poster| !poster.trim().is_empty()).map(|poster| asset_url(origin, poster))
+    }
+
+    pub fn mime(&self) -> &str {
+        self.mime.as_deref().or_else(|| crate::assets::content_type(&self.src)).unwrap_or("application/octet-stream")
+    }
+
+    /// The size in bytes, when it's given or the file is in the assets directory under `content`
+    pub fn length(&self, content: &std::path::Path) -> Option<u64> {
+        if self.length.is_some() || self.remote() {
+            return self.length;
+        }
+        crate::assets::local_size(content, &self.src)
+    }
+}
+
+/// Where a post shows up. Unlisted posts are served to anyone with the link but left out of the listings, feeds,
+/// search and sitemap. Private ones are left out too and only served to the admin and through preview links.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    Public,
+    Unlisted,
+    Private,
+}
+
+impl Visibility {
+    pub fn is_public(&self) -> bool {
+        *self == Visibility::Public
+    }
+}
+
+impl Post {
+    /// Whether the post belongs in listings, feeds, search and the sitemap
+    pub fn listed(&self) -> bool {
+        self.visibility.is_public()
+    }
+
+    /// The accent color, when it's a `#rgb` or `#rrggbb` hex color that's safe to put in a style attribute
+    pub fn accent(&self) -> Option<&str> {
+        self.accent_color.as_deref().map(str::trim).filter(|color| {
+            color.strip_prefix('#').is_some_and(|hex| matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        })
+    }
+
+    /// Where the hero banner is served from, when the post has one
+    pub fn hero_url(&self) -> Option<String> {
+        self.hero.as_deref().map(str::trim).filter(|hero| !hero.is_empty()).map(|hero| asset_url("", hero))
+    }
+
+    /// Where one of the post's attachments is kept in the asset store
+    pub fn attachment_asset(&self, file: &str) -> String {
+        format!("{}/{}", self.url_name, file)
+    }
+
+    /// Whether the post can be served to a reader, `privileged` when they've shown the admin token or a preview
+    /// link for it
+    pub fn visible_to(&self, privileged: bool) -> bool {
+        self.visibility != Visibility::Private || privileged
+    }
+}
+
+/// What's made from a post body on first use, shared by every copy of the post so it's only done once
+#[derive(Debug, Default)]
+pub struct Renderings {
+    /// The dark mode copies of the body's images that were in the assets, which the output depends on as well
+    pub assets: OnceLock<Vec<String>>,
+    /// The HTML of each markdown segment
+    pub html: OnceLock<Vec<String>>,
+    /// Each markdown segment preprocessed for the post page, for readers not saving data and for those who are
+    pub preprocessed: [OnceLock<Vec<String>>; 2],
+}
+
+pub type RenderedBody = Arc<Renderings>;
+
+pub fn content_hash(body: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Hands the renderings of the current posts over to freshly loaded ones with the same body, so a reload only
+/// re-renders the posts that changed. A rendering is left behind when a dark copy of one of its images has since
+/// been added to or removed from the assets.
+pub fn keep_renderings(current: &[Post], loaded: &mut [Post], content: &std::path::Path) {
+    let rendered: HashMap<u64, (&Vec<String>, &RenderedBody)> = current
+        .iter()
+        .filter_map(|post| post.rendered.assets.get().map(|assets| (content_hash(&post.body), (assets, &post.rendered))))
+        .collect();
+    for post in loaded {
+        if let Some((assets, existing)) = rendered.get(&content_hash(&post.body)) {
+            if **assets == dark_variants(&post.body, content) {
+                post.rendered = Arc::clone(existing);
+            }
+        }
+    }
+}
+
+pub fn serialize_post(post: &Post) -> String {
+    serde_json::to_string(post).expect("Failed to serialize Post")
+}
+
+pub fn deserialize_post(json_data: &str,url_name: &str) -> Post {
+    let mut post: Post = serde_json::from_str(json_data).expect("Failed to deserialize Post");
+    post.url_name = post.slug.clone().unwrap_or_else(|| url_name.to_string());
+    post
+}
+
+/// A post from the contents of its file, with the URL name and language taken from the file name
+/// when the post doesn't set them
+pub fn parse_post(file_name: &str, contents: &str) -> Post {
+    let (url_name, lang) = split_translation(file_name.trim_end_matches(".json"));
+    let mut post = deserialize_post(contents, url_name);
+    if post.lang.is_none() {
+        post.lang = lang.map(str::to_string);
+    }
+    post.source_file = file_name.to_string();
+    if post.summary.trim().is_empty() {
+        post.summary = excerpt::excerpt(&post.body, excerpt::EXCERPT_LEN);
+    }
+    post
+}
+
+/// Splits a translated post's file stem like `my-post.es` into its slug and language code
+pub fn split_translation(stem: &str) -> (&str, Option<&str>) {
+    match stem.rsplit_once('.') {
+        Some((slug, lang))
+            if (2..=3).contains(&lang.split('-').next().unwrap_or_default().len())
+                && lang.chars().all(|c| c.is_ascii_alphabetic() || c == '-') =>
+        {
+            (slug, Some(lang))
+        }
+        _ => (stem, None),
+    }
+}
+
+/// The language a post is written in, posts without one are taken to be in the default language
+pub fn post_lang(post: &Post) -> &str {
+    post.lang.as_deref().unwrap_or(i18n::DEFAULT_LANG)
+}
+
+/// Picks the translation of a post closest to the reader's language
+pub fn pick_translation<'a>(translations: impl Iterator<Item = &'a Post>, lang: &str) -> Option<&'a Post> {
+    let primary = lang.split('-').next().unwrap_or_default();
+    translations
+        .map(|post| {
+            let rank = if post_lang(post).eq_ignore_ascii_case(lang) {
+                0
+            } else if post_lang(post).split('-').next().unwrap_or_default().eq_ignore_ascii_case(primary) {
+                1
+            } else if post.lang.is_none() {
+                2
+            } else {
+                3
+            };
+            (rank, post)
+        })
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, post)| post)
+}
+
+/// Files that would all be served at the same URL in the same language
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlugConflict {
+    pub url_name: String,
+    pub lang: String,
+    /// The oldest post at the URL, by timestamp and then file name, which keeps it
+    pub kept: String,
+    /// The files that claimed the URL after it, oldest first
+    pub dropped: Vec<String>,
+}
+
+impl std::fmt::Display for SlugConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "/post/{} ({}) belongs to {} but is also claimed by {}", self.url_name, self.lang, self.kept, self.dropped.join(", "))
+    }
+}
+
+pub fn find_slug_conflicts(posts: &[Post]) -> Vec<SlugConflict> {
+    let mut by_key: HashMap<(String, String), Vec<&Post>> = HashMap::new();
+    for post in posts {
+        by_key.entry((post.url_name.clone(), post_lang(post).to_string()))
+            .or_default()
+            .push(post);
+    }
+
+    let mut conflicts: Vec<SlugConflict> = by_key.into_iter()
+        .filter(|(_, claims)| claims.len() > 1)
+        .map(|((url_name, lang), mut claims)| {
+            claims.sort_by(|a, b| a.timestamp.cmp(&b.timestamp).then_with(|| a.source_file.cmp(&b.source_file)));
+            let kept = claims[0].source_file.clone();
+            let dropped = claims[1..].iter().map(|post| post.source_file.clone()).collect();
+            SlugConflict { url_name, lang, kept, dropped }
+        })
+        .collect();
+    conflicts.sort_by(|a, b| a.url_name.cmp(&b.url_name));
+    conflicts
+}
+
+#[test]
+fn slug_conflicts_ignore_translations() {
+    let post = |file: &str, url_name: &str, lang: Option<&str>, day: u32| Post {
+        title: file.to_string(),
+        body: String::new(),
+        image_url: String::new(),
+        image_alt: None,
+        image_poster: None,
+        summary: String::new(),
+        timestamp: chrono::TimeZone::with_ymd_and_hms(&Utc, 2024, 1, day, 0, 0, 0).unwrap(),
+        updated: None,
+        lang: lang.map(str::to_string),
+        slug: None,
+        tags: Vec::new(),
+        visibility: Visibility::Public,
+        comments_enabled: None,
+        audio: None,
+        video: None,
+        attachments: Vec::new(),
+        accent_color: None,
+        hero: None,
+        url_name: url_name.to_string(),
+        source_file: file.to_string(),
+        rendered: RenderedBody::default(),
+    };
+    let posts = vec![
+        post("hello.json", "hello", None, 1),
+        post("hello.es.json", "hello", Some("es"), 1),
+        post("greeting.json", "hello", Some("en"), 5),
+        post("aloha.json", "hello", None, 9),
+    ];
+
+    // The post that had the URL first keeps it, however the newer files sort
+    let conflicts = find_slug_conflicts(&posts);
+    assert_eq!(conflicts, vec![SlugConflict {
+        url_name: "hello".to_string(),
+        lang: "en".to_string(),
+        kept: "hello.json".to_string(),
+        dropped: vec!["greeting.json".to_string(), "aloha.json".to_string()],
+    }]);
+    assert_eq!(conflicts[0].to_string(), "/post/hello (en) belongs to hello.json but is also claimed by greeting.json, aloha.json");
+}
+
+#[test]
+fn media_in_the_assets_directory_is_measured() {
+    let content = std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR);
+    let local = Media::new("maxresdefault.jpg");
+    assert_eq!(local.url("https://blog.example"), "https://blog.example/asset/maxresdefault.jpg");
+    assert_eq!(local.mime(), "image/jpeg");
+    assert_eq!(local.length(content), std::fs::metadata("caden-blog/assets/maxresdefault.jpg").ok().map(|metadata| metadata.len()));
+
+    let remote = Media { src: "https://cdn.example/episode-1.mp3".to_string(), length: None, mime: Some("audio/x-custom".to_string()), poster: Some("still.jpg".to_string()) };
+    assert_eq!(remote.url("https://blog.example"), "https://cdn.example/episode-1.mp3");
+    assert_eq!((remote.mime(), remote.length(content)), ("audio/x-custom", None));
+    assert_eq!(remote.poster_url(""), Some("/asset/still.jpg".to_string()));
+    assert_eq!(local.poster_url(""), None);
+    assert_eq!(Media::new("../posts/hello.json").length(content), None);
+}
+
+#[test]
+fn only_hex_accents_are_used() {
+    let post = |accent: &str, hero: &str| {
+        serde_json::from_value::<Post>(serde_json::json!({
+            "title": "", "body": "", "image_url": "", "summary": "", "timestamp": "2024-01-01T00:00:00Z", "accent_color": accent, "hero": hero
+        }))
+        .unwrap()
+    };
+    assert_eq!(post(" #E06c75", "").accent(), Some("#E06c75"));
+    assert_eq!(post("#abc", "").accent(), Some("#abc"));
+    for invalid in ["red", "#abcd", "#ggg", "#fff; background: url(x)", ""] {
+        assert_eq!(post(invalid, "").accent(), None, "{}", invalid);
+    }
+    assert_eq!(post("", "banners/rust.jpg").hero_url().as_deref(), Some("/asset/banners/rust.jpg"));
+    assert_eq!(post("", "https://cdn.example/a.jpg").hero_url().as_deref(), Some("https://cdn.example/a.jpg"));
+    assert_eq!(post("", " ").hero_url(), None);
+}
@@ -0,0 +1,76 @@
+use serde::Deserialize;
+
+/// A project from `./caden-blog/projects.toml`, one `[[project]]` table each, shown on `/projects` in file order
+#[derive(Debug, Clone, Deserialize)]
+pub struct Project {
+    pub name: String,
+    /// Markdown, usually a sentence or two
+    #[serde(default)]
+    pub description: String,
+    /// Where the code lives
+    #[serde(default)]
+    pub repo: Option<String>,
+    /// Where the project itself can be seen, like a demo or its own site
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Asset names from the assets directory or full URLs, the first one goes on the card
+    #[serde(default)]
+    pub screenshots: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ProjectsFile {
+    #[serde(default)]
+    project: Vec<Project>,
+}
+
+impl Project {
+    /// The screenshots as URLs, with bare asset names served from `/asset/`
+    pub fn screenshot_urls(&self) -> Vec<String> {
+        self.screenshots
+            .iter()
+            .filter(|screenshot| !screenshot.trim().is_empty())
+            .map(|screenshot| if screenshot.starts_with('/') || screenshot.contains("://") { screenshot.clone() } else { format!("/asset/{}", screenshot) })
+            .collect()
+    }
+}
+
+pub fn parse_projects(contents: &str) -> Result<Vec<Project>, String> {
+    toml::from_str::<ProjectsFile>(contents).map(|file| file.project).map_err(|e| e.to_string())
+}
+
+/// The projects in the content directory, read on every request so an edit shows up straight away. Without a
+/// `projects.toml` there are none, and a broken one is reported and shown as none.
+pub async fn load_projects(content: &std::path::Path) -> Vec<Project> {
+    let path = content.join("projects.toml");
+    let Ok(contents) = tokio::fs::read_to_string(&path).await else { return Vec::new() };
+    parse_projects(&contents).unwrap_or_else(|e| {
+        println!("Couldn't read {}: {}", path.display(), e);
+        Vec::new()
+    })
+}
+
+#[test]
+fn projects_are_read_in_file_order_with_asset_screenshots() {
+    let projects = parse_projects(
+        r#"
+        [[project]]
+        name = "Line follower"
+        description = "A robot that follows tape"
+        screenshots = ["robot.jpg", "https://img.example/robot-2.jpg", "/og/robot.png"]
+        tags = ["robotics"]
+
+        [[project]]
+        name = "This blog"
+        repo = "https://github.com/cadenthecreator/caden-blog"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(projects.iter().map(|project| project.name.as_str()).collect::<Vec<_>>(), ["Line follower", "This blog"]);
+    assert_eq!(projects[0].screenshot_urls(), ["/asset/robot.jpg", "https://img.example/robot-2.jpg", "/og/robot.png"]);
+    assert!(projects[1].screenshot_urls().is_empty() && projects[1].url.is_none());
+    assert_eq!(parse_projects("").unwrap().len(), 0);
+    assert!(parse_projects("[[project]]\ndescription = \"no name\"").is_err());
+}
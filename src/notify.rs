@@ -0,0 +1,132 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+
+use crate::mail::{self, Email, SmtpConfig};
+
+/// How long notifications are gathered into one email when `CADEN_BLOG_NOTIFY_BATCH_SECS` isn't set
+const DEFAULT_BATCH: Duration = Duration::from_secs(300);
+
+/// Notifications waiting for the next email before new ones are dropped, so a flood can't grow without bound
+const MAX_QUEUED: usize = 100;
+
+/// Something the admin should hear about, like a new comment
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    /// One line saying what happened
+    pub title: String,
+    /// The details, with links to see it and to moderate it
+    pub text: String,
+}
+
+/// Emails the admin what happened on the blog, gathering everything from one batch period into one email so a
+/// burst of comments is one email rather than dozens. Without an SMTP server configured it does nothing.
+#[derive(Default)]
+pub struct Notifier {
+    smtp: Option<SmtpConfig>,
+    /// What the links in emails start with. It has to be configured: the host a commenter's request names is
+    /// theirs to choose, and would let them point the moderation link at their own site.
+    site_url: String,
+    batch: Duration,
+    queue: Mutex<Vec<Notification>>,
+    /// Dropped since the last email because the queue was full
+    dropped: Mutex<usize>,
+    wake: Notify,
+}
+
+impl Notifier {
+    pub fn from_env(site_url: &str) -> Notifier {
+        let batch = std::env::var("CADEN_BLOG_NOTIFY_BATCH_SECS").ok().and_then(|secs| secs.trim().parse().ok()).map(Duration::from_secs).unwrap_or(DEFAULT_BATCH);
+        Notifier::new(SmtpConfig::from_env(), site_url, batch)
+    }
+
+    /// Off without a site url for the links, even with a mail server
+    pub fn new(smtp: Option<SmtpConfig>, site_url: &str, batch: Duration) -> Notifier {
+        if smtp.is_some() && site_url.is_empty() {
+            println!("Not emailing notifications: set CADEN_BLOG_SITE_URL for the links in them");
+            return Notifier { batch, ..Notifier::default() };
+        }
+        Notifier { smtp, site_url: site_url.to_string(), batch, ..Notifier::default() }
+    }
+
+    /// Where links in notifications start, `None` while notifications are off
+    pub fn site_url(&self) -> Option<&str> {
+        self.smtp.as_ref().map(|_| self.site_url.as_str())
+    }
+
+    /// Queues a notification for the next email, returning straight away
+    pub fn push(&self, notification: Notification) {
+        if self.smtp.is_none() {
+            return;
+        }
+        let mut queue = self.queue.lock().expect("failed to lock the notifications");
+        if queue.len() < MAX_QUEUED {
+            queue.push(notification);
+        } else {
+            *self.dropped.lock().expect("failed to lock the notifications") += 1;
+        }
+        self.wake.notify_one();
+    }
+
+    /// Everything queued, as one email
+    fn take(&self) -> Option<Email> {
+        let batch = std::mem::take(&mut *self.queue.lock().expect("failed to lock the notifications"));
+        let dropped = std::mem::take(&mut *self.dropped.lock().expect("failed to lock the notifications"));
+        digest(&batch, dropped)
+    }
+}
+
+fn digest(batch: &[Notification], dropped: usize) -> Option<Email> {
+    let subject = match batch {
+        [] => return None,
+        [only] if dropped == 0 => only.title.clone(),
+        _ => format!("{} new notifications", batch.len() + dropped),
+    };
+    let mut body = batch.iter().map(|notification| format!("{}\n\n{}", notification.title, notification.text.trim_end())).collect::<Vec<_>>().join("\n\n---\n\n");
+    if dropped > 0 {
+        body.push_str(&format!("\n\n---\n\n{} more arrived too quickly to list.", dropped));
+    }
+    Some(Email { subject, body })
+}
+
+/// Sends the queued notifications as they come, at most one email per batch period
+pub async fn run(notifier: Arc<Notifier>) {
+    let Some(smtp) = &notifier.smtp else { return };
+    println!("Emailing notifications to {} through {}", smtp.to, smtp.host);
+
+    loop {
+        notifier.wake.notified().await;
+        // Anything else arriving meanwhile goes in the same email
+        tokio::time::sleep(notifier.batch).await;
+        let Some(email) = notifier.take() else { continue };
+        if let Err(e) = mail::send(smtp, &email).await {
+            println!("Couldn't email notifications: {}", e);
+        }
+    }
+}
+
+#[test]
+fn notifications_are_batched_into_one_email() {
+    let notification = |title: &str| Notification { title: title.to_string(), text: format!("About {}\n", title) };
+    assert_eq!(digest(&[], 0), None);
+    assert_eq!(digest(&[notification("New comment on Hello")], 0), Some(Email { subject: "New comment on Hello".to_string(), body: "New comment on Hello\n\nAbout New comment on Hello".to_string() }));
+
+    let email = digest(&[notification("One"), notification("Two")], 3).unwrap();
+    assert_eq!(email.subject, "5 new notifications");
+    assert!(email.body.starts_with("One\n\nAbout One\n\n---\n\nTwo"));
+    assert!(email.body.ends_with("3 more arrived too quickly to list."));
+
+    // Without a mail server nothing queues up
+    let notifier = Notifier::default();
+    notifier.push(notification("Ignored"));
+    assert_eq!(notifier.take(), None);
+
+    // Nor without a site url to link to
+    let smtp = SmtpConfig { host: "mail.example".to_string(), port: 587, security: mail::Security::StartTls, credentials: None, from: "blog@example.com".to_string(), to: "admin@example.com".to_string() };
+    assert_eq!(Notifier::new(Some(smtp.clone()), "", DEFAULT_BATCH).site_url(), None);
+    let notifier = Notifier::new(Some(smtp), "https://blog.example", DEFAULT_BATCH);
+    assert_eq!(notifier.site_url(), Some("https://blog.example"));
+    notifier.push(notification("Kept"));
+    assert_eq!(notifier.take().unwrap().subject, "Kept");
+}
@@ -0,0 +1,236 @@
+use std::io::Cursor;
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::{Response, StatusCode};
+use image::{ImageFormat, Rgba, RgbaImage};
+
+use crate::admin::Admin;
+use crate::extract::LangQuery;
+use crate::model::post::{post_lang, Post};
+use crate::state::AppState;
+use crate::store::posts::find_post;
+
+/// Size recommended for Open Graph and Twitter cards
+pub const OG_WIDTH: u32 = 1200;
+pub const OG_HEIGHT: u32 = 630;
+
+const MARGIN: f32 = 80.0;
+const MAX_TITLE_LINES: usize = 4;
+
+const FONT: &[u8] = include_bytes!("fonts/DejaVuSans-Bold.ttf");
+
+/// Public origin of the site from `CADEN_BLOG_SITE_URL`, used to make share URLs absolute
+pub fn site_url_from_env() -> String {
+    std::env::var("CADEN_BLOG_SITE_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_default()
+}
+
+/// The image shared for a post: its own image when it has one, or its still when that's a video, otherwise the
+/// generated card, made absolute on `site_url`
+pub fn image_for(post: &Post, site_url: &str) -> String {
+    let poster = post.image_poster.as_deref().map(str::trim).filter(|poster| !poster.is_empty());
+    let path = if let Some(poster) = poster.filter(|_| crate::placeholder::is_video(&post.image_url)) {
+        poster.to_string()
+    } else if !post.image_url.trim().is_empty() && !crate::placeholder::is_video(&post.image_url) {
+        post.image_url.clone()
+    } else if let Some(lang) = &post.lang {
+        format!("/og/{}.png?lang={}", post.url_name, lang)
+    } else {
+        format!("/og/{}.png", post.url_name)
+    };
+
+    if path.starts_with('/') {
+        format!("{}{}", site_url, path)
+    } else {
+        path
+    }
+}
+
+fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.trim().strip_prefix('#')?;
+    let hex = match hex.len() {
+        3 => hex.chars().flat_map(|c| [c, c]).collect(),
+        6 => hex.to_string(),
+        _ => return None,
+    };
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn hsl_to_rgb(hue: u32, saturation: f32, lightness: f32) -> [u8; 3] {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue as f32 / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match hue / 60 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    [r, g, b].map(|channel| ((channel + m) * 255.0).round() as u8)
+}
+
+fn text_width<F: Font>(font: &impl ScaleFont<F>, text: &str) -> f32 {
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = font.glyph_id(c);
+        if let Some(previous) = previous {
+            width += font.kern(previous, id);
+        }
+        width += font.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// Greedy word wrap, breaking words that don't fit on a line by themselves
+fn wrap<F: Font>(font: &impl ScaleFont<F>, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+        if text_width(font, &candidate) <= max_width {
+            line = candidate;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            line.push(c);
+            if text_width(font, &line) > max_width {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, c.to_string()));
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn draw_text(image: &mut RgbaImage, font: &FontRef<'static>, scale: PxScale, x: f32, baseline: f32, text: &str, color: [u8; 3]) {
+    let scaled = font.as_scaled(scale);
+    let mut caret = x;
+    let mut previous = None;
+
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += scaled.kern(previous, id);
+        }
+        previous = Some(id);
+
+        let glyph = id.with_scale_and_position(scale, point(caret, baseline));
+        caret += scaled.h_advance(id);
+        let Some(outlined) = font.outline_glyph(glyph) else { continue };
+
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            if px < 0 || py < 0 || px >= image.width() as i64 || py >= image.height() as i64 {
+                return;
+            }
+            let pixel = image.get_pixel_mut(px as u32, py as u32);
+            for i in 0..3 {
+                let blended = pixel[i] as f32 * (1.0 - coverage) + color[i] as f32 * coverage;
+                pixel[i] = blended.round() as u8;
+            }
+        });
+    }
+}
+
+/// Draws a social card with the site name and post title over a gradient from the theme color
+pub fn render(title: &str, site_name: &str, theme_color: &str) -> Vec<u8> {
+    let font = FontRef::try_from_slice(FONT).expect("bundled font is valid");
+    let base = parse_hex_color(theme_color).unwrap_or([0x12, 0x12, 0x12]);
+    let accent = hsl_to_rgb(crate::placeholder::hue(title), 0.6, 0.35);
+
+    let mut image = RgbaImage::from_fn(OG_WIDTH, OG_HEIGHT, |x, y| {
+        let t = (x as f32 / OG_WIDTH as f32 + y as f32 / OG_HEIGHT as f32) / 2.0;
+        let mix = |i: usize| (base[i] as f32 * (1.0 - t) + accent[i] as f32 * t).round() as u8;
+        Rgba([mix(0), mix(1), mix(2), 255])
+    });
+    for y in OG_HEIGHT - 12..OG_HEIGHT {
+        for x in 0..OG_WIDTH {
+            image.put_pixel(x, y, Rgba([accent[0], accent[1], accent[2], 255]));
+        }
+    }
+
+    draw_text(&mut image, &font, PxScale::from(40.0), MARGIN, MARGIN + 40.0, site_name, [0xb0, 0xb0, 0xb0]);
+
+    // Shrink long titles before giving up and truncating them
+    let max_width = OG_WIDTH as f32 - 2.0 * MARGIN;
+    let mut size = 80.0;
+    let mut lines = wrap(&font.as_scaled(PxScale::from(size)), title, max_width);
+    while lines.len() > MAX_TITLE_LINES && size > 48.0 {
+        size -= 8.0;
+        lines = wrap(&font.as_scaled(PxScale::from(size)), title, max_width);
+    }
+    if lines.len() > MAX_TITLE_LINES {
+        lines.truncate(MAX_TITLE_LINES);
+        let last = &mut lines[MAX_TITLE_LINES - 1];
+        *last = format!("{}…", last.rsplit_once(' ').map_or(last.as_str(), |(kept, _)| kept));
+    }
+
+    let line_height = size * 1.2;
+    let top = (OG_HEIGHT as f32 - line_height * lines.len() as f32) / 2.0 + MARGIN / 2.0;
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(&mut image, &font, PxScale::from(size), MARGIN, top + line_height * (i as f32 + 0.8), line, [0xff, 0xff, 0xff]);
+    }
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .expect("encoding a png into memory can't fail");
+    png
+}
+
+/// Serves `/og/:url_name.png`, rendering the card on first request and caching it with the assets
+pub async fn serve_og_image(admin: Option<Admin>, State(AppState { config, posts, locales, cache, .. }): State<AppState>, Path(file): Path<String>, Query(query): Query<LangQuery>) -> Result<Response<Body>, StatusCode> {
+    let url_name = file.strip_suffix(".png").ok_or(StatusCode::NOT_FOUND)?;
+    let lang = query.lang.unwrap_or_else(|| crate::i18n::DEFAULT_LANG.to_string());
+    let post = find_post(&posts, url_name, &lang).filter(|post| post.visible_to(admin.is_some())).ok_or(StatusCode::NOT_FOUND)?;
+    let key = format!("/og/{}?lang={}", file, post_lang(&post));
+
+    let cached = cache.lock().expect("cdn failed to lock the cache").get(&key).cloned();
+    let png = match cached {
+        Some(png) => png,
+        None => {
+            let site_name = locales.get(post_lang(&post), "site_title").to_string();
+            let title = post.title.clone();
+            let png = tokio::task::spawn_blocking(move || render(&title, &site_name, &config.theme_color))
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            cache.lock().expect("cdn failed to lock the cache").insert(key, png.clone());
+            png
+        }
+    };
+
+    Ok(Response::builder()
+        .header("Content-Type", "image/png")
+        .header("Cache-Control", "public, max-age=86400")
+        .body(Body::from(png))
+        .unwrap())
+}
+
+#[test]
+fn renders_a_card_of_the_right_size() {
+    let png = render(&"A rather long title that needs wrapping ".repeat(6), "Fancy Blog", "#336699");
+    let image = image::load_from_memory(&png).unwrap();
+    assert_eq!((image.width(), image.height()), (OG_WIDTH, OG_HEIGHT));
+
+    assert_eq!(parse_hex_color("#abc"), Some([0xaa, 0xbb, 0xcc]));
+    assert_eq!(parse_hex_color("red"), None);
+    assert_eq!(hsl_to_rgb(0, 1.0, 0.5), [255, 0, 0]);
+}
@@ -0,0 +1,114 @@
+use axum::extract::{Query, State};
+use axum::http::header::{CACHE_CONTROL, LOCATION, REFERRER_POLICY};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use maud::html;
+use serde::Deserialize;
+
+use crate::hotlink::host_of;
+use crate::state::AppState;
+use crate::share;
+use crate::signed::Key;
+
+/// Where offsite links go through when clicks are counted
+pub const OUT_PATH: &str = "/out";
+
+/// Clicks through [`OUT_PATH`], labelled with the host they went to
+pub const CLICK_METRIC: &str = "caden_blog_outbound_clicks_total";
+
+/// Marks offsite links as leaving the blog
+pub const CSS: &str = r#"
+    .external-link-icon {
+        margin-left: 0.15em;
+        font-size: 0.75em;
+        text-decoration: none;
+    }
+"#;
+
+/// How links to other sites are written
+#[derive(Debug, Clone, Copy)]
+pub struct LinkStyle {
+    /// Open in a new tab, on unless `CADEN_BLOG_EXTERNAL_LINKS_NEW_TAB` is `off`
+    pub new_tab: bool,
+    /// Go through [`OUT_PATH`] to count clicks, off unless `CADEN_BLOG_OUTBOUND_REDIRECT` is `on`
+    pub redirect: bool,
+}
+
+/// New tabs and no redirect, as when neither variable is set
+impl Default for LinkStyle {
+    fn default() -> LinkStyle {
+        LinkStyle { new_tab: true, redirect: false }
+    }
+}
+
+impl LinkStyle {
+    pub fn from_env() -> LinkStyle {
+        let setting = |name: &str| std::env::var(name).unwrap_or_default().trim().to_lowercase();
+        LinkStyle {
+            new_tab: !matches!(setting("CADEN_BLOG_EXTERNAL_LINKS_NEW_TAB").as_str(), "off" | "false" | "0"),
+            redirect: matches!(setting("CADEN_BLOG_OUTBOUND_REDIRECT").as_str(), "on" | "true" | "1"),
+        }
+    }
+
+    /// The opening tag of a link to `url`, which [`is_external`] said leaves the site, with redirects signed by `key`
+    pub fn open_tag(&self, url: &str, title: &str, key: &Key) -> String {
+        let href = if self.redirect { format!("{}?url={}&sig={}", OUT_PATH, share::encode(url), key.signature_of(url)) } else { url.to_string() };
+        // Maud only writes whole elements, so the tag is cut from the front of one
+        let element = html! {
+            a href=(href) title=[(!title.is_empty()).then_some(title)] class="external-link" rel="noopener noreferrer" target=[self.new_tab.then_some("_blank")] {}
+        };
+        element.into_string().trim_end_matches("</a>").to_string()
+    }
+}
+
+/// What goes after the text of an offsite link
+pub const CLOSE_TAG: &str = r#"<span class="external-link-icon" aria-hidden="true">↗</span></a>"#;
+
+/// Whether a link leaves the site: an absolute web URL to a host other than `CADEN_BLOG_SITE_URL`'s
+pub fn is_external(url: &str, site_url: &str) -> bool {
+    let web = url.get(..8).is_some_and(|scheme| scheme.eq_ignore_ascii_case("https://")) || url.get(..7).is_some_and(|scheme| scheme.eq_ignore_ascii_case("http://"));
+    web && host_of(url).is_some_and(|host| host_of(site_url).as_ref() != Some(&host))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OutQuery {
+    url: String,
+    sig: String,
+}
+
+/// `GET /out?url=&sig=`: counts a click on an offsite link and sends the reader on. Only URLs this site signed are
+/// followed, so it can't be used to dress other links up as the blog's.
+pub async fn out(State(AppState { config, metrics, .. }): State<AppState>, Query(query): Query<OutQuery>) -> Response {
+    if !is_external(&query.url, "") || !config.cookie_key.verify_signature(&query.url, &query.sig) {
+        return StatusCode::BAD_REQUEST.into_response();
+    }
+    let host = host_of(&query.url).unwrap_or_default();
+    metrics.incr(CLICK_METRIC, &[("host", &host)]);
+    (StatusCode::FOUND, [(LOCATION, query.url.as_str()), (CACHE_CONTROL, "no-store"), (REFERRER_POLICY, "no-referrer")]).into_response()
+}
+
+#[test]
+fn offsite_links_are_told_apart_and_tagged() {
+    for (url, external) in [
+        ("https://example.com/x", true),
+        ("HTTP://Example.com", true),
+        ("https://blog.example/post/a", false),
+        ("https://BLOG.example:443/", false),
+        ("/post/a", false),
+        ("mailto:me@example.com", false),
+        ("https://", false),
+        ("javascript:alert(1)", false),
+    ] {
+        assert_eq!(is_external(url, "https://blog.example"), external, "{}", url);
+    }
+
+    let (url, key) = ("https://example.com/?a=1&b=\"2\"", Key::random());
+    assert_eq!(
+        LinkStyle { new_tab: true, redirect: false }.open_tag(url, "A \"site\"", &key),
+        r#"<a href="https://example.com/?a=1&amp;b=&quot;2&quot;" title="A &quot;site&quot;" class="external-link" rel="noopener noreferrer" target="_blank">"#
+    );
+    let tag = LinkStyle { new_tab: false, redirect: true }.open_tag(url, "", &key);
+    let signature = key.signature_of(url);
+    assert_eq!(tag, format!(r#"<a href="/out?url=https%3A%2F%2Fexample.com%2F%3Fa%3D1%26b%3D%222%22&amp;sig={}" class="external-link" rel="noopener noreferrer">"#, signature));
+    assert!(key.verify_signature(url, &signature) && !key.verify_signature("https://evil.example", &signature));
+}
@@ -0,0 +1,44 @@
+use std::path::{Component, Path, PathBuf};
+
+/// `name` joined onto `root`, or `None` when the name could point outside of it. Names come from URLs and
+/// file listings, so only plain relative paths are let through: no `..`, no absolute paths and no drive prefixes.
+pub fn contained(root: &Path, name: &str) -> Option<PathBuf> {
+    let relative = Path::new(name);
+    let mut normal = 0;
+    for component in relative.components() {
+        match component {
+            Component::Normal(_) => normal += 1,
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    // An empty name or `.` would be the directory itself
+    (normal > 0).then(|| root.join(relative))
+}
+
+#[test]
+fn contained_rejects_escapes() {
+    let root = Path::new("./caden-blog/assets");
+    assert_eq!(contained(root, "notes.txt"), Some(root.join("notes.txt")));
+    assert_eq!(contained(root, "img/./cat.png"), Some(root.join("img/./cat.png")));
+    for name in ["", ".", "..", "../Cargo.toml", "img/../../secret", "/etc/passwd", "./.."] {
+        assert_eq!(contained(root, name), None, "{}", name);
+    }
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn contained_paths_stay_under_the_root(name in "\\PC*") {
+        let root = Path::new("./caden-blog/assets");
+        if let Some(path) = contained(root, &name) {
+            let relative = path.strip_prefix(root).unwrap();
+            proptest::prop_assert!(relative.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir)));
+        }
+    }
+
+    #[test]
+    fn plain_names_are_always_contained(segments in proptest::collection::vec("[A-Za-z0-9_-][A-Za-z0-9_.-]{0,11}", 1..4)) {
+        proptest::prop_assert!(contained(Path::new("root"), &segments.join("/")).is_some());
+    }
+}
@@ -0,0 +1,169 @@
+use maud::{html, Markup};
+
+use crate::prefs::ClientHints;
+
+/// Styles for the generated card placeholder, included once per page that lists posts
+pub const PLACEHOLDER_CSS: &str = r#"
+    .card-placeholder {
+        aspect-ratio: 16 / 9;
+        display: flex;
+        align-items: center;
+        justify-content: center;
+        color: rgba(255, 255, 255, 0.9);
+        font-size: 3rem;
+        font-weight: bold;
+        letter-spacing: 0.1em;
+        text-shadow: 0 2px 6px rgba(0, 0, 0, 0.4);
+    }
+    .card-placeholder[hidden] {
+        display: none;
+    }
+"#;
+
+/// Up to two initials from a title, e.g. "Hello World Again" -> "HW"
+pub fn initials(title: &str) -> String {
+    let initials: String = title
+        .split_whitespace()
+        .filter_map(|word| word.chars().find(|c| c.is_alphanumeric()))
+        .take(2)
+        .flat_map(char::to_uppercase)
+        .collect();
+
+    if initials.is_empty() {
+        "?".to_string()
+    } else {
+        initials
+    }
+}
+
+/// A stable hue for a title so each post keeps the same colors between renders
+pub fn hue(title: &str) -> u32 {
+    // FNV-1a, which is plenty for spreading titles around the color wheel
+    let hash = title.bytes().fold(0x811c9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193));
+    hash % 360
+}
+
+/// A gradient block with the post's initials, shown in place of a card image
+pub fn placeholder(title: &str, hidden: bool) -> Markup {
+    let hue = hue(title);
+    let style = format!(
+        "background: linear-gradient(135deg, hsl({}, 60%, 35%), hsl({}, 60%, 20%));",
+        hue,
+        (hue + 40) % 360
+    );
+
+    html! {
+        div class="card-img-top card-placeholder" role="img" aria-label=(title) style=(style) hidden[hidden] {
+            (initials(title))
+        }
+    }
+}
+
+/// A 5×5 mirrored pattern of squares in one color picked from `hash`, as an SVG. Each hash always makes the same
+/// picture, so a commenter's avatar follows them around without asking a third party for it.
+pub fn identicon(hash: &[u8]) -> String {
+    let hue = hash.first().map_or(0, |byte| *byte as u32 * 360 / 256);
+    let mut squares = String::new();
+    for row in 0..5 {
+        for column in 0..3 {
+            let bit = row * 3 + column;
+            if hash.get(1 + bit / 8).is_some_and(|byte| byte >> (bit % 8) & 1 == 1) {
+                let mirrored: &[usize] = if column == 2 { &[2] } else { &[column, 4 - column] };
+                for x in mirrored {
+                    squares.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"1\" height=\"1\"/>", x, row));
+                }
+            }
+        }
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-0.5 -0.5 6 6\" shape-rendering=\"crispEdges\"><rect x=\"-0.5\" y=\"-0.5\" width=\"6\" height=\"6\" fill=\"hsl({}, 30%, 92%)\"/><g fill=\"hsl({}, 55%, 45%)\">{}</g></svg>",
+        hue, hue, squares
+    )
+}
+
+/// Whether a cover is a video by its extension, which plays muted and looping like an animated image
+pub fn is_video(url: &str) -> bool {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    crate::assets::content_type(path).is_some_and(|mime| mime.starts_with("video/"))
+}
+
+/// How a cover image is shown
+#[derive(Debug, PartialEq, Eq)]
+pub enum Cover<'a> {
+    /// An `<img>`, which also plays animated GIFs and WebPs
+    Image(&'a str),
+    /// A `<video>` looping silently, showing the poster until it starts. Without `autoplay` it waits for the
+    /// reader to start it.
+    Video { src: &'a str, poster: Option<&'a str>, autoplay: bool },
+}
+
+/// The cover to show for `src`, or `None` when there's nothing to show. Readers saving data or asking for less
+/// motion get the still `poster` instead when there is one. Without one, a video is left out for readers saving
+/// data and doesn't play by itself for readers asking for less motion.
+pub fn cover<'a>(src: &'a str, poster: Option<&'a str>, hints: ClientHints) -> Option<Cover<'a>> {
+    let poster = poster.map(str::trim).filter(|poster| !poster.is_empty());
+    match (src.trim(), poster) {
+        ("", _) => None,
+        (_, Some(poster)) if hints.save_data || hints.reduced_motion => Some(Cover::Image(poster)),
+        (src, poster) if is_video(src) => (!hints.save_data).then_some(Cover::Video { src, poster, autoplay: !hints.reduced_motion }),
+        (src, _) => Some(Cover::Image(src)),
+    }
+}
+
+/// The card image for a post, falling back to the placeholder when there is no image or it fails to load.
+/// The image is described by `alt` when the post has one, otherwise by the title.
+pub fn card_image(title: &str, image_url: &str, alt: Option<&str>, poster: Option<&str>, hints: ClientHints) -> Markup {
+    let fallback = "this.hidden=true;this.nextElementSibling.hidden=false";
+    let alt = alt.unwrap_or(title);
+    html! {
+        @match cover(image_url, poster, hints) {
+            None => (placeholder(title, false)),
+            Some(Cover::Image(src)) => {
+                img src=(src) class="card-img-top" alt=(alt) onerror=(fallback);
+                (placeholder(title, true))
+            }
+            Some(Cover::Video { src, poster, autoplay }) => {
+                video src=(src) class="card-img-top" poster=[poster] aria-label=(alt) autoplay[autoplay] controls[!autoplay] muted loop playsinline onerror=(fallback) {}
+                (placeholder(title, true))
+            }
+        }
+    }
+}
+
+#[test]
+fn placeholder_initials_and_fallbacks() {
+    assert_eq!(initials("Hello world again"), "HW");
+    assert_eq!(initials("  \"quoted\" title"), "QT");
+    assert_eq!(initials("émile"), "É");
+    assert_eq!(initials("!!!"), "?");
+    assert_eq!(hue("Same title"), hue("Same title"));
+
+    let missing = card_image("No Image", "", None, None, ClientHints::default()).into_string();
+    assert!(!missing.contains("<img"));
+    assert!(missing.contains(">NI</div>"));
+
+    let present = card_image("Has Image", "/asset/cover.png", None, None, ClientHints::default()).into_string();
+    assert!(present.contains(r#"<img src="/asset/cover.png" class="card-img-top" alt="Has Image""#));
+    assert!(present.contains("hidden"));
+
+    let described = card_image("Has Image", "/asset/cover.png", Some("A red bicycle"), None, ClientHints::default()).into_string();
+    assert!(described.contains(r#"alt="A red bicycle""#));
+
+    let looping = card_image("Loop", "/asset/loop.webm?v=2", Some("A spinning cat"), Some("/asset/loop.jpg"), ClientHints::default()).into_string();
+    assert!(looping.contains(r#"<video src="/asset/loop.webm?v=2" class="card-img-top" poster="/asset/loop.jpg" aria-label="A spinning cat" autoplay muted loop playsinline"#), "{}", looping);
+    let saving = ClientHints { save_data: true, ..ClientHints::default() };
+    let still = card_image("Loop", "/asset/loop.webm", None, Some("/asset/loop.jpg"), saving).into_string();
+    assert!(still.contains(r#"<img src="/asset/loop.jpg""#) && !still.contains("<video"));
+    assert_eq!(cover("/asset/loop.webm", Some(" "), saving), None);
+    assert_eq!(cover("/asset/dance.gif", None, saving), Some(Cover::Image("/asset/dance.gif")));
+    assert_eq!(cover("/asset/dance.gif", Some("/asset/dance.png"), saving), Some(Cover::Image("/asset/dance.png")));
+    let calm = ClientHints { reduced_motion: true, ..ClientHints::default() };
+    assert_eq!(cover("/asset/dance.gif", Some("/asset/dance.png"), calm), Some(Cover::Image("/asset/dance.png")));
+    assert_eq!(cover("/asset/loop.webm", None, calm), Some(Cover::Video { src: "/asset/loop.webm", poster: None, autoplay: false }));
+    assert!(card_image("Loop", "/asset/loop.webm", None, None, calm).into_string().contains(r#"aria-label="Loop" controls muted loop"#));
+
+    let full = identicon(&[0, 0xff, 0xff]);
+    assert_eq!(full.matches("<rect").count(), 1 + 25);
+    assert_eq!(identicon(&[7, 1]).matches("<rect").count(), 1 + 2);
+    assert_eq!(identicon(&[0]).matches("<rect").count(), 1);
+}
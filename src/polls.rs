@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Form, Path, State};
+use axum::http::{header, HeaderMap, Response, StatusCode};
+use maud::{html, Markup};
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::i18n::Text;
+use crate::shortcode::{split, Segment};
+use crate::signed::Key;
+use crate::state::AppState;
+use crate::tally::Tally;
+
+/// Polls any post can embed, keyed by id
+const GLOBAL_FILE: &str = "polls.toml";
+
+/// Directory of `<url_name>.toml` files with polls for a single post, which take precedence over the global ones
+const POST_DIR: &str = "polls";
+
+const STATE_FILE: &str = "state/polls.json";
+
+/// Signed list of `poll=option` votes a visitor cast, so each visitor votes once
+const COOKIE: &str = "polls";
+
+const MAX_REMEMBERED: usize = 100;
+
+/// Votes keyed by poll and then by option index
+pub type PollStore = Arc<Tally>;
+
+pub fn load(content: &std::path::Path) -> Tally {
+    Tally::load(content.join(STATE_FILE))
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Poll {
+    pub question: String,
+    pub options: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct VoteForm {
+    option: usize,
+}
+
+async fn read_polls(path: impl AsRef<std::path::Path>) -> HashMap<String, Poll> {
+    let path = path.as_ref();
+    let Ok(source) = tokio::fs::read_to_string(path).await else { return HashMap::new() };
+    toml::from_str(&source).unwrap_or_else(|e| {
+        println!("Couldn't parse polls in {}: {}", path.display(), e);
+        HashMap::new()
+    })
+}
+
+/// The poll a post embeds under `id` and the key its votes are counted under. Global polls share their votes
+/// across every post embedding them.
+pub async fn find(content: &std::path::Path, url_name: &str, id: &str) -> Option<(String, Poll)> {
+    // The post name comes straight from the URL, so it mustn't reach outside the polls directory
+    if let Some(path) = crate::paths::contained(&content.join(POST_DIR), &format!("{}.toml", url_name)) {
+        if let Some(poll) = read_polls(path).await.remove(id) {
+            return Some((format!("{}/{}", url_name, id), poll));
+        }
+    }
+    read_polls(content.join(GLOBAL_FILE)).await.remove(id).map(|poll| (id.to_string(), poll))
+}
+
+/// The polls a post embeds and the reader's votes, read ahead of rendering so the templates never wait on the disk
+pub struct PagePolls {
+    url_name: String,
+    found: HashMap<String, (String, Poll)>,
+    voted: Vec<String>,
+}
+
+impl PagePolls {
+    pub async fn load(config: &Config, url_name: &str, body: &str, headers: &HeaderMap) -> PagePolls {
+        let mut found = HashMap::new();
+        for segment in split(body) {
+            if let Segment::Poll(id) = segment {
+                if !found.contains_key(id) {
+                    if let Some(poll) = find(&config.content_dir, url_name, id).await {
+                        found.insert(id.to_string(), poll);
+                    }
+                }
+            }
+        }
+        PagePolls { url_name: url_name.to_string(), found, voted: voted(&config.cookie_key, headers) }
+    }
+
+    /// The widget for one of the post's polls. Unknown polls render nothing.
+    pub fn widget(&self, id: &str, votes: &Tally, text: Text) -> Markup {
+        match self.found.get(id) {
+            Some((key, poll)) => widget(&self.url_name, id, key, poll, votes, &self.voted, text),
+            None => html! {},
+        }
+    }
+}
+
+/// The `poll=option` votes a visitor already cast
+pub fn voted(key: &Key, headers: &HeaderMap) -> Vec<String> {
+    key.read_list(headers, COOKIE)
+}
+
+fn choice(voted: &[String], key: &str) -> Option<usize> {
+    voted.iter().find_map(|entry| {
+        let (poll, option) = entry.rsplit_once('=')?;
+        (poll == key).then(|| option.parse().ok()).flatten()
+    })
+}
+
+fn results(poll: &Poll, counts: &HashMap<String, u64>, chosen: Option<usize>, text: Text) -> Markup {
+    let total: u64 = (0..poll.options.len()).filter_map(|i| counts.get(&i.to_string())).sum();
+
+    html! {
+        ul class="list-unstyled mb-1" {
+            @for (i, option) in poll.options.iter().enumerate() {
+                @let count = counts.get(&i.to_string()).copied().unwrap_or(0);
+                @let percent = (count * 100).checked_div(total).unwrap_or(0);
+                li class="mb-2" {
+                    div class="d-flex justify-content-between" {
+                        span {
+                            (option)
+                            @if chosen == Some(i) {
+                                " " span class="badge bg-primary" { (text.t("poll_your_vote")) }
+                            }
+                        }
+                        span class="text-muted" { (percent) "%" }
+                    }
+                    div class="progress" role="progressbar" aria-label=(option) aria-valuenow=(percent) aria-valuemin="0" aria-valuemax="100" {
+                        div class="progress-bar" style=(format!("width: {}%", percent)) {}
+                    }
+                }
+            }
+        }
+        p class="small text-muted" { (text.t("poll_votes").replace("{n}", &total.to_string())) }
+    }
+}
+
+/// The voting form for a poll, or its results once the visitor voted
+fn widget(url_name: &str, id: &str, key: &str, poll: &Poll, votes: &Tally, voted: &[String], text: Text) -> Markup {
+    let action = format!("/post/{}/poll/{}", url_name, id);
+    let target = format!("#poll-{}", id);
+
+    html! {
+        section id=(format!("poll-{}", id)) class="poll card card-body my-3" {
+            h5 { (poll.question) }
+            @match choice(voted, key) {
+                Some(chosen) => (results(poll, &votes.counts(key), Some(chosen), text)),
+                None => {
+                    form method="post" action=(action) up-submit up-target=(target) {
+                        fieldset {
+                            legend class="visually-hidden" { (poll.question) }
+                            @for (i, option) in poll.options.iter().enumerate() {
+                                div class="form-check" {
+                                    input class="form-check-input" type="radio" name="option" value=(i) id=(format!("poll-{}-{}", id, i)) required;
+                                    label class="form-check-label" for=(format!("poll-{}-{}", id, i)) { (option) }
+                                }
+                            }
+                        }
+                        button type="submit" class="btn btn-sm btn-primary mt-2 me-2" { (text.t("poll_vote")) }
+                        a href=(format!("{}/results", action)) class="btn btn-sm btn-link mt-2" up-target=(target) { (text.t("poll_results")) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn fragment(body: Markup) -> Response<Body> {
+    Response::builder()
+        .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(Body::from(body.into_string()))
+        .unwrap()
+}
+
+/// `POST /post/:url_name/poll/:id`: counts the visitor's first vote and answers with the results,
+/// or a redirect back to the post when the form was submitted without javascript
+pub async fn vote(State(AppState { config, posts, polls: votes, locales, .. }): State<AppState>, Path((url_name, id)): Path<(String, String)>, headers: HeaderMap, Form(form): Form<VoteForm>) -> Result<Response<Body>, StatusCode> {
+    let exists = posts.read().expect("failed to lock the post index").iter().any(|post| post.url_name == url_name);
+    let (key, poll) = find(&config.content_dir, &url_name, &id).await.filter(|_| exists).ok_or(StatusCode::NOT_FOUND)?;
+    if form.option >= poll.options.len() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut voted = voted(&config.cookie_key, &headers);
+    if choice(&voted, &key).is_none() {
+        votes.add(&key, &form.option.to_string()).await;
+        voted.push(format!("{}={}", key, form.option));
+    }
+    let cookie = config.cookie_key.list_cookie(COOKIE, &voted, MAX_REMEMBERED);
+
+    if crate::extract::fragment::Partial::detect(&headers).0 {
+        let lang = locales.negotiate(&headers);
+        let mut response = fragment(widget(&url_name, &id, &key, &poll, &votes, &voted, locales.text(&lang)));
+        response.headers_mut().insert(header::SET_COOKIE, cookie.parse().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?);
+        Ok(response)
+    } else {
+        Ok(Response::builder()
+            .status(StatusCode::SEE_OTHER)
+            .header(header::SET_COOKIE, cookie)
+            .header(header::LOCATION, format!("/post/{}", url_name))
+            .body(Body::empty())
+            .unwrap())
+    }
+}
+
+/// `GET /post/:url_name/poll/:id/results`: the current results, for visitors who want to look before voting
+pub async fn results_fragment(State(AppState { config, polls: votes, locales, .. }): State<AppState>, Path((url_name, id)): Path<(String, String)>, headers: HeaderMap) -> Result<Response<Body>, StatusCode> {
+    let (key, poll) = find(&config.content_dir, &url_name, &id).await.ok_or(StatusCode::NOT_FOUND)?;
+    let lang = locales.negotiate(&headers);
+    let text = locales.text(&lang);
+
+    Ok(fragment(html! {
+        section id=(format!("poll-{}", id)) class="poll card card-body my-3" {
+            h5 { (poll.question) }
+            (results(&poll, &votes.counts(&key), choice(&voted(&config.cookie_key, &headers), &key), text))
+        }
+    }))
+}
+
+#[test]
+fn results_show_percentages_and_the_visitors_choice() {
+    let poll = Poll { question: "Tabs or spaces?".to_string(), options: vec!["Tabs".to_string(), "Spaces".to_string()] };
+    let counts: HashMap<String, u64> = [("0".to_string(), 1), ("1".to_string(), 3)].into_iter().collect();
+    let locales = crate::i18n::Locales::load(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR));
+
+    let html = results(&poll, &counts, Some(1), locales.text("en")).into_string();
+    assert!(html.contains("25%") && html.contains("75%"));
+    assert!(html.contains("4 votes"));
+    assert_eq!(html.matches("badge").count(), 1);
+
+    assert_eq!(choice(&["tabs=1".to_string(), "post/tabs=0".to_string()], "post/tabs"), Some(0));
+    assert_eq!(choice(&["tabs=1".to_string()], "other"), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn slow_poll_files_dont_stall_other_requests() {
+    use std::time::Duration;
+
+    // Nothing ever writes to the FIFO, so reading it hangs like a disk that stopped answering
+    let fifo = std::env::temp_dir().join(format!("caden-blog-polls-{}.toml", std::process::id()));
+    assert!(std::process::Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+
+    let (done, finished) = std::sync::mpsc::channel();
+    let path = fifo.to_string_lossy().into_owned();
+    std::thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        runtime.block_on(async {
+            tokio::spawn(async move { read_polls(&path).await });
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            done.send(()).unwrap();
+        });
+    });
+    let served = finished.recv_timeout(Duration::from_secs(5));
+
+    // Opening the write end and closing it again lets the stuck read finish
+    drop(std::fs::OpenOptions::new().write(true).open(&fifo));
+    std::fs::remove_file(&fifo).unwrap();
+    assert!(served.is_ok(), "a slow poll file blocked the runtime");
+}
@@ -0,0 +1,368 @@
+use axum::extract::Request;
+use axum::http::header::{CONTENT_TYPE, VARY};
+use axum::http::{HeaderMap, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+use crate::extract::tz::UserTz;
+use crate::i18n::Text;
+
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Cookie a reader can set to `relative` or `absolute` to override the configured display
+pub const TIME_DISPLAY_COOKIE: &str = "time_display";
+
+/// Posts older than this always show their absolute date, even in relative mode
+const RELATIVE_CUTOFF_DAYS: i64 = 30;
+
+/// How timestamps are shown, configured with `CADEN_BLOG_TIME_DISPLAY` as `absolute` (the default) or `relative`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeDisplay {
+    Absolute,
+    /// "2 hours ago", "last week"; computed server side in the reader's timezone
+    Relative,
+}
+
+impl TimeDisplay {
+    fn parse(value: &str) -> Option<TimeDisplay> {
+        match value {
+            "absolute" => Some(TimeDisplay::Absolute),
+            "relative" => Some(TimeDisplay::Relative),
+            _ => None,
+        }
+    }
+
+    pub fn from_env() -> TimeDisplay {
+        std::env::var("CADEN_BLOG_TIME_DISPLAY")
+            .ok()
+            .and_then(|value| TimeDisplay::parse(value.trim()))
+            .unwrap_or(TimeDisplay::Absolute)
+    }
+
+    /// The reader's `time_display` cookie, falling back to the `configured` mode
+    pub fn resolve(headers: &HeaderMap, configured: TimeDisplay) -> TimeDisplay {
+        cookie(headers, TIME_DISPLAY_COOKIE)
+            .and_then(TimeDisplay::parse)
+            .unwrap_or(configured)
+    }
+}
+
+/// Cookie a reader can set to `list`, `grid` or `compact` to override the configured layout
+pub const LAYOUT_COOKIE: &str = "layout";
+
+/// How the home page lists posts, configured with `CADEN_BLOG_LAYOUT` as `list` (the default), `grid` or `compact`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutMode {
+    /// Full width cards, one under the other
+    List,
+    /// Cards side by side, [`posts_per_row_from_env`] of them on wide screens
+    Grid,
+    /// Just titles and dates
+    Compact,
+}
+
+impl LayoutMode {
+    pub const ALL: [LayoutMode; 3] = [LayoutMode::List, LayoutMode::Grid, LayoutMode::Compact];
+
+    pub fn parse(value: &str) -> Option<LayoutMode> {
+        LayoutMode::ALL.into_iter().find(|layout| layout.name() == value)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LayoutMode::List => "list",
+            LayoutMode::Grid => "grid",
+            LayoutMode::Compact => "compact",
+        }
+    }
+
+    pub fn from_env() -> LayoutMode {
+        std::env::var("CADEN_BLOG_LAYOUT")
+            .ok()
+            .and_then(|value| LayoutMode::parse(value.trim()))
+            .unwrap_or(LayoutMode::List)
+    }
+
+    /// The reader's `layout` cookie, falling back to the `configured` layout
+    pub fn resolve(headers: &HeaderMap, configured: LayoutMode) -> LayoutMode {
+        cookie(headers, LAYOUT_COOKIE)
+            .and_then(LayoutMode::parse)
+            .unwrap_or(configured)
+    }
+}
+
+/// Cookie a reader can set to `off`, `slow`, `normal` or `fast` to override the configured background speed
+pub const BACKGROUND_SPEED_COOKIE: &str = "background_speed";
+
+/// How fast the [background pattern](crate::backdrop) scrolls, configured with `CADEN_BLOG_BACKGROUND_SPEED` as
+/// `off`, `slow`, `normal` (the default) or `fast`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackgroundSpeed {
+    /// The pattern stays put
+    Off,
+    Slow,
+    Normal,
+    Fast,
+}
+
+impl BackgroundSpeed {
+    pub const ALL: [BackgroundSpeed; 4] = [BackgroundSpeed::Off, BackgroundSpeed::Slow, BackgroundSpeed::Normal, BackgroundSpeed::Fast];
+
+    pub fn parse(value: &str) -> Option<BackgroundSpeed> {
+        BackgroundSpeed::ALL.into_iter().find(|speed| speed.name() == value)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BackgroundSpeed::Off => "off",
+            BackgroundSpeed::Slow => "slow",
+            BackgroundSpeed::Normal => "normal",
+            BackgroundSpeed::Fast => "fast",
+        }
+    }
+
+    /// Seconds for the pattern to scroll one tile, which the page gets as `--speed`
+    pub fn seconds(self) -> Option<u32> {
+        match self {
+            BackgroundSpeed::Off => None,
+            BackgroundSpeed::Slow => Some(120),
+            BackgroundSpeed::Normal => Some(60),
+            BackgroundSpeed::Fast => Some(20),
+        }
+    }
+
+    pub fn from_env() -> BackgroundSpeed {
+        std::env::var("CADEN_BLOG_BACKGROUND_SPEED")
+            .ok()
+            .and_then(|value| BackgroundSpeed::parse(value.trim()))
+            .unwrap_or(BackgroundSpeed::Normal)
+    }
+
+    /// The reader's `background_speed` cookie, falling back to the `configured` speed
+    pub fn resolve(headers: &HeaderMap, configured: BackgroundSpeed) -> BackgroundSpeed {
+        cookie(headers, BACKGROUND_SPEED_COOKIE)
+            .and_then(BackgroundSpeed::parse)
+            .unwrap_or(configured)
+    }
+}
+
+/// Cards per row in the grid layout on wide screens when unconfigured
+pub const DEFAULT_POSTS_PER_ROW: usize = 2;
+
+/// Cards per row in the grid layout on wide screens, from `CADEN_BLOG_POSTS_PER_ROW` between 1 and 4
+pub fn posts_per_row_from_env() -> usize {
+    std::env::var("CADEN_BLOG_POSTS_PER_ROW")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .map(|count: usize| count.clamp(1, 4))
+        .unwrap_or(DEFAULT_POSTS_PER_ROW)
+}
+
+/// What the reader's browser asked us to hold back on, read from client hints so pages are lighter before they
+/// reach the reader rather than after
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ClientHints {
+    /// `Save-Data: on`, sent by browsers in data saver mode
+    pub save_data: bool,
+    /// `Sec-CH-Prefers-Reduced-Motion: reduce`, the `prefers-reduced-motion` setting, which browsers only send to
+    /// sites asking for it with [`ask_for_hints`]
+    pub reduced_motion: bool,
+}
+
+/// The hints pages ask browsers for, beyond the `Save-Data` they send anyway
+const REQUESTED_HINTS: &str = "Sec-CH-Prefers-Reduced-Motion";
+
+impl ClientHints {
+    pub fn resolve(headers: &HeaderMap) -> ClientHints {
+        let hint = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).map(|value| value.trim().trim_matches('"').to_ascii_lowercase());
+        ClientHints { save_data: hint("save-data").as_deref() == Some("on"), reduced_motion: hint("sec-ch-prefers-reduced-motion").as_deref() == Some("reduce") }
+    }
+
+    /// Tells apart the cached copies of a page rendered for different hints
+    pub fn key(self) -> &'static str {
+        match (self.save_data, self.reduced_motion) {
+            (false, false) => "full",
+            (true, false) => "lite",
+            (false, true) => "still",
+            (true, true) => "lite-still",
+        }
+    }
+}
+
+/// Middleware asking browsers for the reduced motion hint on every page, as critical so the first visit is
+/// retried with it instead of animating once, and marking pages as shaped by the hints for shared caches
+pub async fn ask_for_hints(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    let html = response.headers().get(CONTENT_TYPE).is_some_and(|value| value.as_bytes().starts_with(b"text/html"));
+    if html {
+        let headers = response.headers_mut();
+        headers.insert("accept-ch", HeaderValue::from_static(REQUESTED_HINTS));
+        headers.insert("critical-ch", HeaderValue::from_static(REQUESTED_HINTS));
+        headers.append(VARY, HeaderValue::from_static("Save-Data, Sec-CH-Prefers-Reduced-Motion"));
+    }
+    response
+}
+
+pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(axum::http::header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .find_map(|cookie| {
+            let (key, value) = cookie.trim().split_once('=')?;
+            (key == name).then_some(value)
+        })
+}
+
+/// Everything needed to turn a UTC timestamp into the text a particular reader sees
+pub struct TimeFormatter<'a> {
+    pub user_tz: UserTz,
+    pub display: TimeDisplay,
+    pub text: Text<'a>,
+    pub now: DateTime<Utc>,
+}
+
+impl<'a> TimeFormatter<'a> {
+    pub fn new(user_tz: UserTz, headers: &HeaderMap, text: Text<'a>, configured: TimeDisplay) -> TimeFormatter<'a> {
+        TimeFormatter { user_tz, display: TimeDisplay::resolve(headers, configured), text, now: Utc::now() }
+    }
+
+    /// Whether output can be served exactly as it was rendered
+    fn is_identity(&self) -> bool {
+        self.user_tz.tz == Tz::UTC && self.display == TimeDisplay::Absolute
+    }
+
+    /// A relative description of the timestamp, or `None` when it is too old or in the future
+    pub fn relative(&self, timestamp: &DateTime<Utc>) -> Option<String> {
+        let elapsed = self.now.signed_duration_since(*timestamp);
+        if elapsed < chrono::Duration::zero() || elapsed.num_days() >= RELATIVE_CUTOFF_DAYS {
+            return None;
+        }
+
+        let count = |key: &str, n: i64| self.text.t(key).replace("{n}", &n.to_string());
+        // Day boundaries depend on where the reader is
+        let days = (self.now.with_timezone(&self.user_tz.tz).date_naive() - timestamp.with_timezone(&self.user_tz.tz).date_naive()).num_days();
+
+        Some(match elapsed.num_minutes() {
+            0 => self.text.t("time_just_now").to_string(),
+            1 => self.text.t("time_minute_ago").to_string(),
+            minutes if minutes < 60 => count("time_minutes_ago", minutes),
+            minutes if minutes < 120 => self.text.t("time_hour_ago").to_string(),
+            _ if days == 0 => count("time_hours_ago", elapsed.num_hours()),
+            _ if days == 1 => self.text.t("time_yesterday").to_string(),
+            _ if days < 7 => count("time_days_ago", days),
+            _ if days < 14 => self.text.t("time_last_week").to_string(),
+            _ => count("time_weeks_ago", days / 7),
+        })
+    }
+
+    /// The element for one timestamp, with the absolute time kept as a tooltip when showing a relative one
+    fn element(&self, datetime: &str, timestamp: &DateTime<Utc>) -> String {
+        let absolute = self.user_tz.format(timestamp);
+        match self.display {
+            TimeDisplay::Relative => match self.relative(timestamp) {
+                Some(relative) => format!("<time datetime=\"{}\" title=\"{}\">{}</time>", datetime, absolute, maud::html! { (relative) }.into_string()),
+                None => format!("<time datetime=\"{}\">{}</time>", datetime, absolute),
+            },
+            TimeDisplay::Absolute => format!("<time datetime=\"{}\">{}</time>", datetime, absolute),
+        }
+    }
+}
+
+/// Rewrites every `<time datetime="...">` element into the reader's timezone and display mode.
+///
+/// Pages are cached with their timestamps rendered in UTC, and this pass localizes them on the way out, so the
+/// page cache holds one copy per page instead of one per timezone.
+pub fn localize_times(html: &str, formatter: &TimeFormatter) -> String {
+    const OPEN: &str = "<time datetime=\"";
+    const CLOSE: &str = "</time>";
+    if formatter.is_identity() {
+        return html.to_string();
+    }
+
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(OPEN) {
+        let after_attr = &rest[start + OPEN.len()..];
+        let element = after_attr.split_once('"').and_then(|(datetime, _)| {
+            let timestamp = DateTime::parse_from_rfc3339(datetime).ok()?.with_timezone(&Utc);
+            let close = after_attr.find(CLOSE)?;
+            Some((formatter.element(datetime, &timestamp), close + CLOSE.len()))
+        });
+
+        match element {
+            Some((element, end)) => {
+                output.push_str(&rest[..start]);
+                output.push_str(&element);
+                rest = &after_attr[end..];
+            }
+            None => {
+                output.push_str(&rest[..start + OPEN.len()]);
+                rest = after_attr;
+            }
+        }
+    }
+    output.push_str(rest);
+    output
+}
+
+#[test]
+fn localizes_cached_time_elements() {
+    use crate::extract::tz::TzSource;
+
+    let html = r#"<p><time datetime="2024-11-10T23:31:07+00:00">2024-11-10 23:31:07</time> and <time>now</time></p>"#;
+    let locales = crate::i18n::Locales::load(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR));
+    let mut formatter = TimeFormatter {
+        user_tz: UserTz { tz: Tz::Asia__Tokyo, source: TzSource::Query },
+        display: TimeDisplay::Absolute,
+        text: locales.text("en"),
+        now: DateTime::parse_from_rfc3339("2024-11-12T03:00:00Z").unwrap().with_timezone(&Utc),
+    };
+
+    assert_eq!(
+        localize_times(html, &formatter),
+        r#"<p><time datetime="2024-11-10T23:31:07+00:00">2024-11-11 08:31:07</time> and <time>now</time></p>"#,
+    );
+
+    // 27 hours later, but two calendar days later in Tokyo
+    formatter.display = TimeDisplay::Relative;
+    assert_eq!(
+        localize_times(html, &formatter),
+        r#"<p><time datetime="2024-11-10T23:31:07+00:00" title="2024-11-11 08:31:07">yesterday</time> and <time>now</time></p>"#,
+    );
+
+    formatter.now = DateTime::parse_from_rfc3339("2025-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+    assert!(localize_times(html, &formatter).contains(">2024-11-11 08:31:07</time>"));
+}
+
+#[test]
+fn layout_cookie_overrides_the_configured_layout() {
+    let mut headers = HeaderMap::new();
+    headers.insert("cookie", "lang=en; layout=compact".parse().unwrap());
+    assert_eq!(LayoutMode::resolve(&headers, LayoutMode::List), LayoutMode::Compact);
+
+    headers.insert("cookie", "layout=masonry".parse().unwrap());
+    assert_eq!(LayoutMode::resolve(&headers, LayoutMode::Grid), LayoutMode::Grid);
+    assert_eq!(LayoutMode::parse("grid").map(LayoutMode::name), Some("grid"));
+
+    headers.insert("cookie", "background_speed=off".parse().unwrap());
+    assert_eq!(BackgroundSpeed::resolve(&headers, BackgroundSpeed::Normal), BackgroundSpeed::Off);
+    headers.insert("cookie", "background_speed=warp".parse().unwrap());
+    assert_eq!(BackgroundSpeed::resolve(&headers, BackgroundSpeed::Slow), BackgroundSpeed::Slow);
+}
+
+#[test]
+fn save_data_and_reduced_motion_are_read_from_client_hints() {
+    let mut headers = HeaderMap::new();
+    assert_eq!(ClientHints::resolve(&headers), ClientHints::default());
+    headers.insert("save-data", "On".parse().unwrap());
+    assert!(ClientHints::resolve(&headers).save_data);
+    headers.insert("save-data", "off".parse().unwrap());
+    assert!(!ClientHints::resolve(&headers).save_data);
+    headers.insert("sec-ch-prefers-reduced-motion", "\"reduce\"".parse().unwrap());
+    assert_eq!(ClientHints::resolve(&headers), ClientHints { save_data: false, reduced_motion: true });
+    assert_eq!(ClientHints::resolve(&headers).key(), "still");
+}
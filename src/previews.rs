@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use maud::{html, Markup};
+use pulldown_cmark::{Event, Parser, Tag};
+use serde::{Deserialize, Serialize};
+
+use crate::model::post::RenderedBody;
+use crate::store::{PageCache, PostIndex};
+
+/// Where the previews are saved, relative to the content directory
+pub const STATE_FILE: &str = "state/link-previews.json";
+
+/// Most of a page read looking for its metadata, which lives in the head
+const MAX_PAGE: usize = 512 * 1024;
+
+/// What a page says about itself in its OpenGraph tags, shown as a card where a post has its bare URL
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Preview {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// `og:site_name`, or the host when the page doesn't name its site
+    pub site: String,
+}
+
+/// The previews fetched so far by URL, saved in the state directory so they're fetched once rather than on every
+/// start. Pages are only fetched when posts are loaded, never while serving.
+pub struct Previews {
+    path: PathBuf,
+    cards: RwLock<HashMap<String, Preview>>,
+}
+
+impl Previews {
+    pub fn load(path: impl Into<PathBuf>) -> Previews {
+        let path = path.into();
+        let cards = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                println!("Couldn't parse {}, fetching link previews again: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Previews { path, cards: RwLock::new(cards) }
+    }
+
+    pub fn get(&self, url: &str) -> Option<Preview> {
+        self.cards.read().expect("failed to lock the link previews").get(url).cloned()
+    }
+
+    /// Fetches the previews of the URLs that don't have one yet, returning the URLs that got one. Pages that
+    /// can't be fetched are tried again the next time.
+    pub async fn fetch_missing(&self, urls: &[String], client: &reqwest::Client) -> Vec<String> {
+        let mut fetched = Vec::new();
+        for url in urls {
+            if self.get(url).is_some() {
+                continue;
+            }
+            match fetch(client, url).await {
+                Ok(preview) => {
+                    self.cards.write().expect("failed to lock the link previews").insert(url.clone(), preview);
+                    fetched.push(url.clone());
+                }
+                Err(e) => println!("Couldn't fetch a link preview of {}: {}", url, e),
+            }
+        }
+        if !fetched.is_empty() {
+            self.save().await;
+        }
+        fetched
+    }
+
+    async fn save(&self) {
+        let json = serde_json::to_string_pretty(&*self.cards.read().expect("failed to lock the link previews")).expect("previews serialize");
+        let saved = match self.path.parent() {
+            Some(dir) => tokio::fs::create_dir_all(dir).await,
+            None => Ok(()),
+        };
+        if let Err(e) = saved.and(tokio::fs::write(&self.path, json).await) {
+            println!("Couldn't save {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// The URL a paragraph at `range` of the markdown holds when it holds nothing else and isn't quoted or starting
+/// a list item, which would leave a card with part of the syntax around it
+pub fn bare_url(markdown: &str, range: Range<usize>) -> Option<&str> {
+    let line_start = markdown[..range.start].rfind('\n').map_or(0, |at| at + 1);
+    if !markdown[line_start..range.start].chars().all(char::is_whitespace) {
+        return None;
+    }
+    let url = markdown[range].trim();
+    let web = url.starts_with("https://") || url.starts_with("http://");
+    let plain = !url.chars().any(|c| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '[' | ']' | '(' | ')' | '*' | '`' | '\\'));
+    (web && plain && url.len() > "https://".len()).then_some(url)
+}
+
+/// The URLs standing alone in a post body's paragraphs, in order and each once
+pub fn bare_urls(body: &str) -> Vec<String> {
+    let mut urls: Vec<String> = Vec::new();
+    for (event, range) in Parser::new(body).into_offset_iter() {
+        if let Event::Start(Tag::Paragraph) = event {
+            if let Some(url) = bare_url(body, range) {
+                if !urls.iter().any(|known| known == url) {
+                    urls.push(url.to_string());
+                }
+            }
+        }
+    }
+    urls
+}
+
+/// Fetches the previews of the posts' bare URLs, then has the posts showing a new one rendered again
+pub async fn refresh(posts: PostIndex, pages: PageCache, previews: Arc<Previews>) {
+    let urls: Vec<String> = {
+        let posts = posts.read().expect("failed to lock the post index");
+        let mut urls: Vec<String> = posts.iter().flat_map(|post| bare_urls(&post.body)).collect();
+        urls.sort();
+        urls.dedup();
+        urls
+    };
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(10)).user_agent(concat!("caden-blog/", env!("CARGO_PKG_VERSION"), " (link preview)")).build() {
+        Ok(client) => client,
+        Err(e) => return println!("Couldn't fetch link previews: {}", e),
+    };
+    let fetched = previews.fetch_missing(&urls, &client).await;
+    if fetched.is_empty() {
+        return;
+    }
+
+    println!("Fetched {} link previews", fetched.len());
+    for post in posts.write().expect("failed to lock the post index").iter_mut() {
+        if bare_urls(&post.body).iter().any(|url| fetched.contains(url)) {
+            post.rendered = RenderedBody::default();
+        }
+    }
+    pages.write().expect("failed to lock the page cache").clear();
+}
+
+async fn fetch(client: &reqwest::Client, url: &str) -> Result<Preview, String> {
+    let mut response = client.get(url).send().await.and_then(reqwest::Response::error_for_status).map_err(|e| e.to_string())?;
+    let html_page = response.headers().get(reqwest::header::CONTENT_TYPE).and_then(|value| value.to_str().ok()).is_some_and(|kind| kind.contains("html"));
+    if !html_page {
+        return Err("not an html page".to_string());
+    }
+    let mut page = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        page.extend_from_slice(&chunk);
+        if page.len() >= MAX_PAGE {
+            break;
+        }
+    }
+    parse(&String::from_utf8_lossy(&page), url).ok_or_else(|| "no title".to_string())
+}
+
+/// The attributes of a tag, from just after its name to its `>`
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = tag;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        let name_end = rest.find(|c: char| c.is_whitespace() || c == '=' || c == '>').unwrap_or(rest.len());
+        if name_end == 0 {
+            return attributes;
+        }
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let Some(value) = rest.strip_prefix('=') else {
+            attributes.push((name, String::new()));
+            continue;
+        };
+        let value = value.trim_start();
+        let (value, after) = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => match value[1..].find(quote) {
+                Some(end) => (&value[1..end + 1], &value[end + 2..]),
+                None => (&value[1..], ""),
+            },
+            _ => {
+                let end = value.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(value.len());
+                (&value[..end], &value[end..])
+            }
+        };
+        attributes.push((name, decode(value)));
+        rest = after;
+    }
+}
+
+/// Text with the common character references replaced and its whitespace collapsed
+fn decode(text: &str) -> String {
+    let decoded = text.replace("&quot;", "\"").replace("&#39;", "'").replace("&#x27;", "'").replace("&apos;", "'").replace("&lt;", "<").replace("&gt;", ">").replace("&nbsp;", " ").replace("&amp;", "&");
+    decoded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The preview a page describes in its head, or `None` when it has no title at all
+fn parse(page: &str, url: &str) -> Option<Preview> {
+    // Lowercasing ASCII keeps every byte where it was, so positions found in one work in the other
+    let lower = page.to_ascii_lowercase();
+    let head = &lower[..lower.find("</head").unwrap_or(lower.len())];
+    let mut meta: HashMap<String, String> = HashMap::new();
+    let mut search = 0;
+    while let Some(start) = head[search..].find("<meta").map(|at| search + at + "<meta".len()) {
+        let end = head[start..].find('>').map_or(head.len(), |at| start + at);
+        let attributes = attributes(&page[start..end]);
+        let value = |name: &str| attributes.iter().find(|(attribute, _)| attribute == name).map(|(_, value)| value.clone());
+        if let (Some(key), Some(content)) = (value("property").or_else(|| value("name")), value("content")) {
+            meta.entry(key.to_ascii_lowercase()).or_insert(content);
+        }
+        search = end;
+    }
+
+    let tag = |key: &str| meta.get(key).filter(|value| !value.is_empty()).cloned();
+    let title_tag = || {
+        let start = head.find("<title")?;
+        let start = start + head[start..].find('>')? + 1;
+        let end = start + head[start..].find("</title")?;
+        Some(decode(&page[start..end])).filter(|title| !title.is_empty())
+    };
+    let site_root = || url.find("://").map(|at| at + 3).map(|at| &url[..url[at..].find('/').map_or(url.len(), |slash| at + slash)]);
+    let image = tag("og:image").or_else(|| tag("twitter:image")).and_then(|image| match image {
+        image if image.starts_with("https://") || image.starts_with("http://") => Some(image),
+        image if image.starts_with("//") => Some(format!("{}:{}", url.split(':').next().unwrap_or("https"), image)),
+        image if image.starts_with('/') => site_root().map(|root| format!("{}{}", root, image)),
+        _ => None,
+    });
+    Some(Preview {
+        title: tag("og:title").or_else(|| tag("twitter:title")).or_else(title_tag)?,
+        description: tag("og:description").or_else(|| tag("description")).unwrap_or_default(),
+        image,
+        site: tag("og:site_name").or_else(|| crate::hotlink::host_of(url)).unwrap_or_default(),
+    })
+}
+
+/// Lays the cards out like a chat app's unfurled link: the image beside the title, description and site
+pub const CSS: &str = r#"
+    .link-preview {
+        margin: 1em 0;
+    }
+    .link-preview a {
+        display: flex;
+        gap: 1em;
+        padding: 0.75em;
+        border: 1px solid #343a40;
+        border-radius: 8px;
+        background-color: #181818;
+        color: inherit;
+        text-decoration: none;
+    }
+    .link-preview a:hover {
+        border-color: #66b2ff;
+    }
+    .link-preview img {
+        width: 120px;
+        height: 90px;
+        object-fit: cover;
+        border-radius: 4px;
+        flex-shrink: 0;
+    }
+    .link-preview-text {
+        min-width: 0;
+        overflow-wrap: break-word;
+    }
+    .link-preview-text p {
+        margin: 0.25em 0;
+        color: #bbb;
+    }
+"#;
+
+/// The card shown in place of a bare URL, opening in a new tab when offsite links do. The image is loaded from the
+/// other site, without a referrer.
+pub fn card(url: &str, preview: &Preview, new_tab: bool) -> Markup {
+    html! {
+        div class="link-preview" {
+            a href=(url) rel="noopener noreferrer" target=[new_tab.then_some("_blank")] {
+                @if let Some(image) = &preview.image {
+                    img src=(image) alt="" loading="lazy" referrerpolicy="no-referrer";
+                }
+                div class="link-preview-text" {
+                    strong { (preview.title) }
+                    @if !preview.description.is_empty() {
+                        p { (preview.description) }
+                    }
+                    small class="text-muted" { (preview.site) }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn pages_describe_themselves_in_their_head() {
+    let page = r#"<!DOCTYPE html><HTML><head>
+        <TITLE>Fallback &amp; title</TITLE>
+        <meta property="og:title" content="Axum &quot;0.7&quot;">
+        <meta name=description content='A web
+            framework'>
+        <meta property="og:image" content="/social.png" />
+        <meta content="Ignored" property="og:title">
+    </head><body><meta property="og:site_name" content="Body"></body></html>"#;
+    assert_eq!(
+        parse(page, "https://docs.example/axum/latest"),
+        Some(Preview { title: "Axum \"0.7\"".to_string(), description: "A web framework".to_string(), image: Some("https://docs.example/social.png".to_string()), site: "docs.example".to_string() })
+    );
+    let title_only = parse("<title>Just a title</title><meta property='og:image' content='//cdn.example/a.png'>", "http://x.example").unwrap();
+    assert_eq!((title_only.title.as_str(), title_only.image.as_deref()), ("Just a title", Some("http://cdn.example/a.png")));
+    assert_eq!(parse("<p>No head at all</p>", "https://x.example"), None);
+
+    assert_eq!(bare_urls("See\n\nhttps://a.example/x_y\n\n> http://quoted.example\n\nhttps://a.example/x_y\n\nnot https://inline.example\n\n[link](https://b.example)\n\n`https://code.example`"), vec!["https://a.example/x_y"]);
+}
+
+#[tokio::test]
+async fn previews_persist_between_loads() {
+    let path = std::env::temp_dir().join(format!("caden-blog-previews-{}", std::process::id())).join("previews.json");
+    let previews = Previews::load(&path);
+    let preview = Preview { title: "Known".to_string(), site: "known.example".to_string(), ..Preview::default() };
+    previews.cards.write().unwrap().insert("https://known.example".to_string(), preview.clone());
+    // Nothing to fetch for a URL that already has a preview
+    assert!(previews.fetch_missing(&["https://known.example".to_string()], &reqwest::Client::new()).await.is_empty());
+    previews.save().await;
+    assert_eq!(Previews::load(&path).get("https://known.example"), Some(preview));
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn cards_escape_what_the_page_said() {
+    let preview = Preview { title: "<script>".to_string(), description: String::new(), image: None, site: "x.example".to_string() };
+    let html = card("https://x.example/?a=1&b=2", &preview, true).into_string();
+    assert!(html.contains("&lt;script&gt;") && html.contains("?a=1&amp;b=2") && !html.contains("<img") && !html.contains("<p>"));
+}
@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use axum::body::Body;
+use axum::extract::State;
+use axum::http::Response;
+use maud::{html, Markup};
+use sha2::{Digest, Sha256};
+
+use crate::defaults;
+use crate::fingerprint::AssetManifest;
+use crate::icons::IconSet;
+use crate::model::post::Post;
+use crate::state::AppState;
+use crate::store::PostIndex;
+
+/// How many of the newest posts are precached for offline reading
+const PRECACHED_POSTS: usize = 10;
+
+/// Where the worker script is read from, under the content directory or the compiled in defaults
+const TEMPLATE: &str = "templates/sw.js";
+
+/// Registers the service worker on pages that include it, except in dev mode where its cache would hide changes
+pub fn register_script(dev: bool) -> Markup {
+    html! {
+        @if !dev {
+            script { "if ('serviceWorker' in navigator) { navigator.serviceWorker.register('/sw.js'); }" }
+        }
+    }
+}
+
+/// The shell pages and assets plus the newest posts, which the service worker caches on install, by the URLs pages
+/// link them with
+pub fn precache_list(posts: &[Post], icons: &IconSet, manifest: &AssetManifest) -> Vec<String> {
+    let mut urls = vec!["/".to_string(), "/site.webmanifest".to_string(), "/favicon.ico".to_string(), manifest.url(crate::theme::STYLESHEET_PATH), manifest.url(crate::theme::SCRIPT_PATH)];
+    urls.extend(icons.names().map(|name| format!("/{}", name)));
+    urls.extend(crate::vendor::local_urls());
+
+    let mut recent: Vec<&Post> = posts.iter().filter(|post| post.listed()).collect();
+    recent.sort_by_key(|post| std::cmp::Reverse(post.timestamp));
+    for post in recent.into_iter().take(PRECACHED_POSTS) {
+        urls.push(format!("/post/{}", post.url_name));
+        if post.image_url.starts_with("/asset/") && !crate::placeholder::is_video(&post.image_url) {
+            urls.push(manifest.url(&post.image_url));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    urls.retain(|url| seen.insert(url.clone()));
+    urls
+}
+
+/// Hashes the precache list along with the posts and the current version of every asset, so any change ships a new
+/// worker
+fn version(urls: &[String], posts: &[Post], manifest: &AssetManifest) -> String {
+    let mut hasher = Sha256::new();
+    for url in urls {
+        hasher.update(url.as_bytes());
+    }
+    for post in posts {
+        hasher.update(post.timestamp.to_rfc3339().as_bytes());
+        if let Some(updated) = post.updated {
+            hasher.update(updated.to_rfc3339().as_bytes());
+        }
+        hasher.update(post.body.as_bytes());
+    }
+
+    for (path, version) in manifest.entries() {
+        hasher.update(path.as_bytes());
+        hasher.update(version.as_bytes());
+    }
+
+    hex::encode(&hasher.finalize()[..8])
+}
+
+/// The worker script for the current content and the precache list in it
+pub struct Worker {
+    pub script: String,
+    pub precache: String,
+}
+
+impl Worker {
+    fn generate(template: &str, posts: &[Post], icons: &IconSet, manifest: &AssetManifest) -> Worker {
+        let urls = precache_list(posts, icons, manifest);
+        let precache = serde_json::to_string(&urls).expect("failed to serialize the precache list");
+        Worker { script: template.replace("__VERSION__", &version(&urls, posts, manifest)).replace("__PRECACHE__", &precache), precache }
+    }
+}
+
+/// The service worker, generated once per content change rather than per request like the feeds
+pub struct ServiceWorker {
+    /// `templates/sw.js` from the content directory, or the copy compiled into the binary
+    template: String,
+    current: RwLock<Option<Arc<Worker>>>,
+}
+
+impl Default for ServiceWorker {
+    fn default() -> ServiceWorker {
+        ServiceWorker::from_template(defaults::embedded(TEMPLATE).expect("the service worker template is compiled in"))
+    }
+}
+
+impl ServiceWorker {
+    pub fn load(content: &Path) -> ServiceWorker {
+        ServiceWorker::from_template(defaults::read(content, TEMPLATE).expect("the service worker template is compiled in"))
+    }
+
+    fn from_template(template: Vec<u8>) -> ServiceWorker {
+        ServiceWorker { template: String::from_utf8_lossy(&template).into_owned(), current: RwLock::new(None) }
+    }
+
+    /// The worker for the current posts, generating it when they changed since it was last asked for
+    pub fn get(&self, posts: &PostIndex, icons: &IconSet, manifest: &AssetManifest) -> Arc<Worker> {
+        if let Some(worker) = &*self.current.read().expect("failed to lock the service worker") {
+            return worker.clone();
+        }
+
+        let worker = Arc::new(Worker::generate(&self.template, &posts.read().expect("failed to lock the post index"), icons, manifest));
+        *self.current.write().expect("failed to lock the service worker") = Some(worker.clone());
+        worker
+    }
+
+    /// Generates the worker again after the posts change
+    pub fn regenerate(&self, posts: &PostIndex, icons: &IconSet, manifest: &AssetManifest) {
+        *self.current.write().expect("failed to lock the service worker") = None;
+        self.get(posts, icons, manifest);
+    }
+}
+
+pub async fn serve_service_worker(State(AppState { posts, icons, worker, manifest, .. }): State<AppState>) -> Response<Body> {
+    // Browsers check for a new worker on navigation, so it must never be served stale
+    Response::builder()
+        .header("Content-Type", "application/javascript")
+        .header("Cache-Control", "no-cache")
+        .body(Body::from(worker.get(&posts, &icons, &manifest).script.clone()))
+        .unwrap()
+}
+
+pub async fn serve_precache_manifest(State(AppState { posts, icons, worker, manifest, .. }): State<AppState>) -> Response<Body> {
+    Response::builder()
+        .header("Content-Type", "application/json")
+        .header("Cache-Control", "no-cache")
+        .body(Body::from(worker.get(&posts, &icons, &manifest).precache.clone()))
+        .unwrap()
+}
+
+#[test]
+fn posts_sharing_a_cover_precache_it_once() {
+    let post = |url_name: &str, timestamp: &str| -> Post {
+        let mut post: Post = serde_json::from_value(serde_json::json!({ "title": "", "body": "", "image_url": "/asset/cover.png", "summary": "", "timestamp": timestamp })).unwrap();
+        post.url_name = url_name.to_string();
+        post
+    };
+    let urls = precache_list(&[post("first", "2024-01-01T00:00:00Z"), post("second", "2024-01-02T00:00:00Z")], &IconSet::from_env(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR), crate::icons::DEFAULT_THEME_COLOR), &AssetManifest::default());
+    assert_eq!(urls.iter().filter(|url| *url == "/asset/cover.png").count(), 1);
+    assert!(urls.ends_with(&["/post/second".to_string(), "/asset/cover.png".to_string(), "/post/first".to_string()]), "{:?}", urls);
+}
+
+#[test]
+fn any_changed_asset_ships_a_new_worker() {
+    let manifest = AssetManifest::default();
+    manifest.record("/asset/about/portrait.png", b"before");
+    let before = version(&[], &[], &manifest);
+    assert_eq!(version(&[], &[], &manifest), before);
+
+    // Not precached, but a page the worker kept a copy of may still link the old one
+    manifest.record("/asset/about/portrait.png", b"after");
+    assert_ne!(version(&[], &[], &manifest), before);
+}
@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::extract::{Form, Path, State};
+use axum::http::{header, HeaderMap, Response, StatusCode};
+use maud::{html, Markup};
+use serde::Deserialize;
+
+use crate::admin::Admin;
+use crate::i18n::Text;
+use crate::routes::home::purge_card;
+use crate::signed::Key;
+use crate::state::AppState;
+use crate::tally::Tally;
+
+/// The reactions offered on every post: the name stored, its emoji, and the locale key labelling its button
+pub const REACTIONS: [(&str, &str, &str); 2] = [("like", "👍", "react_like"), ("clap", "👏", "react_clap")];
+
+/// Signed list of the reactions a visitor already gave, so each only counts once per visitor
+const COOKIE: &str = "reactions";
+
+/// Only the most recent reactions are remembered so the cookie stays well under browser size limits
+const MAX_REMEMBERED: usize = 100;
+
+const STATE_FILE: &str = "state/reactions.json";
+
+/// Reaction counts keyed by post and then by reaction
+pub type ReactionStore = Arc<Tally>;
+
+pub fn load(content: &std::path::Path) -> Tally {
+    Tally::load(content.join(STATE_FILE))
+}
+
+#[derive(Deserialize)]
+pub struct ReactForm {
+    kind: String,
+}
+
+/// The `url_name:kind` pairs a visitor already reacted with
+pub fn reacted(key: &Key, headers: &HeaderMap) -> Vec<String> {
+    key.read_list(headers, COOKIE)
+}
+
+/// The reaction buttons under a post, each showing its live count
+pub fn widget(url_name: &str, reactions: &Tally, reacted: &[String], text: Text) -> Markup {
+    let counts = reactions.counts(url_name);
+
+    html! {
+        div id="reactions" class="reactions mt-4" role="group" aria-label=(text.t("reactions")) {
+            @for (kind, emoji, label) in REACTIONS {
+                @let given = reacted.iter().any(|entry| *entry == format!("{}:{}", url_name, kind));
+                form method="post" action=(format!("/post/{}/react", url_name)) class="d-inline me-2" up-submit up-target="#reactions" {
+                    input type="hidden" name="kind" value=(kind);
+                    button type="submit" class=(if given { "btn btn-sm btn-primary" } else { "btn btn-sm btn-outline-primary" }) title=(text.t(label)) aria-pressed=(given) disabled[given] {
+                        (emoji) " " (counts.get(kind).copied().unwrap_or(0))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reaction totals for a post's card, left out entirely while it has none
+pub fn card_totals(url_name: &str, reactions: &Tally, text: Text) -> Markup {
+    let counts = reactions.counts(url_name);
+    html! {
+        @if counts.values().any(|count| *count > 0) {
+            p class="small text-muted" aria-label=(text.t("reactions")) {
+                @for (kind, emoji, label) in REACTIONS {
+                    @if let Some(count) = counts.get(kind).filter(|count| **count > 0) {
+                        span class="me-2" title=(text.t(label)) { (emoji) " " (count) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `POST /post/:url_name/react`: counts the reaction once per visitor and answers with the updated buttons,
+/// or a redirect back to the post when the form was submitted without javascript
+pub async fn react(admin: Option<Admin>, State(AppState { config, posts, pages, reactions, locales, .. }): State<AppState>, Path(url_name): Path<String>, headers: HeaderMap, Form(form): Form<ReactForm>) -> Result<Response<Body>, StatusCode> {
+    if !REACTIONS.iter().any(|(kind, _, _)| *kind == form.kind) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let exists = posts.read().expect("failed to lock the post index").iter().any(|post| post.url_name == url_name && post.visible_to(admin.is_some()));
+    if !exists {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let mut reacted = reacted(&config.cookie_key, &headers);
+    let entry = format!("{}:{}", url_name, form.kind);
+    if !reacted.contains(&entry) {
+        reactions.add(&url_name, &form.kind).await;
+        // Cards on the cached home pages show the totals
+        purge_card(&pages, &url_name);
+        reacted.push(entry);
+    }
+
+    let response = Response::builder().header(header::SET_COOKIE, config.cookie_key.list_cookie(COOKIE, &reacted, MAX_REMEMBERED));
+    if crate::extract::fragment::Partial::detect(&headers).0 {
+        let lang = locales.negotiate(&headers);
+        let body = widget(&url_name, &reactions, &reacted, locales.text(&lang)).into_string();
+        Ok(response.header(header::CONTENT_TYPE, "text/html; charset=utf-8").body(Body::from(body)).unwrap())
+    } else {
+        Ok(response
+            .status(StatusCode::SEE_OTHER)
+            .header(header::LOCATION, format!("/post/{}", url_name))
+            .body(Body::empty())
+            .unwrap())
+    }
+}
+
+#[tokio::test]
+async fn reactions_count_and_render() {
+    let dir = std::env::temp_dir().join(format!("caden-blog-reactions-{}", std::process::id()));
+    let reactions = Tally::load(dir.join("reactions.json"));
+    let locales = crate::i18n::Locales::load(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR));
+    let text = locales.text("en");
+
+    assert_eq!(card_totals("post", &reactions, text).into_string(), "");
+    for _ in 0..3 {
+        reactions.add("post", "clap").await;
+    }
+    assert!(card_totals("post", &reactions, text).into_string().contains("👏 3"));
+
+    let widget = widget("post", &reactions, &["post:clap".to_string()], text).into_string();
+    assert!(widget.contains(r#"aria-pressed="true" disabled>👏 3"#));
+    assert!(widget.contains(r#"aria-pressed="false">👍 0"#));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,148 @@
+use maud::{html, PreEscaped, DOCTYPE};
+
+use crate::model::post::Post;
+use crate::prefs::BackgroundSpeed;
+use crate::render::listing::{layout_switcher, render_posts_fragment, Cards};
+use crate::render::sidebar::render_sidebar;
+use crate::render::{hinted_css, search_box, skip_link, CARD_CSS, FOCUS_CSS, PRINT_CSS};
+use crate::{backdrop, dev, events, icons, placeholder, pwa, vendor};
+
+/// Renders the home page listing every post
+pub fn render_home(posts: &[Post], cards: Cards, speed: BackgroundSpeed) -> String {
+    let Cards { text, reactions, layout, hints, config, .. } = cards;
+    // for post in &posts {
+    //     println!("{}", serialize_post(&post));
+    // }
+    html! {
+        (DOCTYPE)
+        html lang=(text.lang) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                (icons::icon_links(&config.theme_color))
+                title { (text.t("site_title")) }
+                (vendor::stylesheet("bootstrap.min.css"))
+                (vendor::stylesheet("unpoly.min.css"))
+                (vendor::stylesheet("unpoly-bootstrap5.min.css"))
+                style { r#"
+                    body {
+                        font-family: Arial, sans-serif;
+                        background-color: #121212;
+                        color: #e0e0e0;
+                    }
+                    .header {
+                        background-position: center;
+                        color: #f0f0f0;
+                        padding: 20px;
+                        text-align: center;
+                        background-size: cover;
+                    }
+                    .sidebar {
+                        background-color: #242424;
+                        color: #e0e0e0;
+                        padding: 20px;
+                        border-radius: 8px;
+                    }
+                    .footer {
+                        background-color: #1c1c1c;
+                        color: #f0f0f0;
+                        text-align: center;
+                        padding: 15px;
+                        margin-top: 20px;
+                    }
+                    .navbar-nav .nav-link {
+                        color: #e0e0e0 !important;
+                    }
+                    .btn-primary {
+                        background-color: #007bff;
+                        border-color: #007bff;
+                    }
+                    .btn-outline-primary {
+                        color: #007bff;
+                        border-color: #007bff;
+                    }
+                    .btn-outline-primary:hover {
+                        background-color: #007bff;
+                        color: #fff;
+                    }
+                "# }
+                style media="print" { (PreEscaped(PRINT_CSS)) }
+                style { (PreEscaped(FOCUS_CSS)) }
+                (hinted_css(hints, true))
+                (backdrop::css(config.background_pattern, speed, hints))
+                style { (PreEscaped(CARD_CSS)) }
+                style { (PreEscaped(placeholder::PLACEHOLDER_CSS)) }
+            }
+            body {
+                (skip_link(text))
+
+                // Header
+                header class="header" {
+                    h1 { "The Caden Times" }
+                    p { (text.t("tagline")) }
+                    (search_box(text))
+                }
+
+                // Navigation Bar
+                nav class="navbar navbar-expand-lg navbar-dark bg-dark" aria-label=(text.t("main_navigation")) {
+                    div class="container" {
+                        a class="navbar-brand" href="#" { (text.t("site_title")) }
+                        button class="navbar-toggler" type="button" data-bs-toggle="collapse" data-bs-target="#navbarNav" aria-controls="navbarNav" aria-expanded="false" aria-label=(text.t("toggle_navigation")) {
+                            span class="navbar-toggler-icon" {}
+                        }
+                        div class="collapse navbar-collapse" id="navbarNav" {
+                            ul class="navbar-nav ms-auto" {
+                                li class="nav-item" {
+                                    a class="nav-link active" href="#" aria-current="page" { (text.t("nav_home")) }
+                                }
+                                li class="nav-item" {
+                                    a class="nav-link" href="#" { (text.t("nav_about")) }
+                                }
+                                li class="nav-item" {
+                                    a class="nav-link" href="/notes" { (text.t("nav_notes")) }
+                                }
+                                li class="nav-item" {
+                                    a class="nav-link" href="/projects" { (text.t("nav_projects")) }
+                                }
+                                li class="nav-item" {
+                                    a class="nav-link" href="/contact" up-layer="new" { (text.t("nav_contact")) }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Main Content
+                main id="main" class="container my-4" {
+                    div class="row" {
+                        // Blog Posts
+                        div class="col-lg-8" {
+                            (layout_switcher(text, layout))
+                            (render_posts_fragment(posts, cards))
+                        }
+
+                        // Sidebar
+                        aside class="col-lg-4" aria-label=(text.t("about_heading")) {
+                            (render_sidebar(&config.sidebar, posts, text, reactions))
+                        }
+                    }
+                }
+
+                // Footer
+                footer class="footer" {
+                    p { (text.t("footer")) }
+                    (backdrop::speed_switcher(text, config.background_pattern, speed))
+                }
+
+                (vendor::script("jquery.min.js"))
+                (vendor::script("bootstrap.bundle.min.js"))
+                (vendor::script("unpoly.min.js"))
+                (vendor::script("unpoly-bootstrap5.min.js"))
+                (vendor::script("htmx.min.js"))
+                (pwa::register_script(config.dev))
+                (dev::reload_script(config.dev))
+                (events::subscribe_script())
+            }
+        }
+    }.into_string()
+}
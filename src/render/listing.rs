@@ -0,0 +1,98 @@
+use maud::{html, Markup};
+
+use crate::config::Config;
+use crate::fingerprint::AssetManifest;
+use crate::i18n::Text;
+use crate::model::post::Post;
+use crate::prefs::{ClientHints, LayoutMode};
+use crate::render::{timestamp, with_date};
+use crate::tally::Tally;
+use crate::{excerpt, placeholder, reactions};
+
+/// Sets `--accent` for [`CARD_CSS`](crate::render::CARD_CSS) to tint the post with
+pub fn accent_style(post: &Post) -> Option<String> {
+    post.accent().map(|color| format!("--accent: {}", color))
+}
+
+/// What every card in a listing is rendered with besides its post
+#[derive(Clone, Copy)]
+pub struct Cards<'a> {
+    pub text: Text<'a>,
+    pub reactions: &'a Tally,
+    /// For linking covers in the assets by their fingerprinted names
+    pub manifest: &'a AssetManifest,
+    pub layout: LayoutMode,
+    pub hints: ClientHints,
+    pub config: &'a Config,
+}
+
+/// A post's card in the home page listing
+pub fn render_card(post: &Post, Cards { text, reactions, manifest, hints, config, .. }: Cards) -> Markup {
+    let image_url = manifest.url(&post.image_url);
+    let poster = post.image_poster.as_deref().map(|poster| manifest.url(poster));
+    html! {
+        article.card.post-card.accented[post.accent().is_some()] data-post=(post.url_name) style=[accent_style(post)] {
+            (placeholder::card_image(&post.title, &image_url, post.image_alt.as_deref(), poster.as_deref(), hints))
+            div class="card-body" {
+                h2 class="card-title h5" { (post.title) }
+                p class="text-muted" { (with_date(text.t("posted_on"), timestamp(&post.timestamp))) }
+                @let summary = excerpt::truncate_words(&post.summary, config.summary_chars);
+                p class="card-text" title=[(summary != post.summary).then_some(&post.summary)] { (summary) }
+                (reactions::card_totals(&post.url_name, reactions, text))
+                a href=(format!("/post/{}",post.url_name)) class="btn btn-primary" up-target=".modal-content" up-layer="new" aria-label=(format!("{}: {}", text.t("read_more"), post.title)) { (text.t("read_more")) }
+            }
+        }
+    }
+}
+
+/// One post in the home page listing, shaped for the reader's layout
+pub fn render_listing_item(post: &Post, cards: Cards) -> Markup {
+    match cards.layout {
+        LayoutMode::List => render_card(post, cards),
+        LayoutMode::Grid => html! {
+            div class="col" { (render_card(post, cards)) }
+        },
+        LayoutMode::Compact => html! {
+            article.list-group-item.post-compact.d-flex.justify-content-between.align-items-baseline.accented[post.accent().is_some()] data-post=(post.url_name) style=[accent_style(post)] {
+                h2 class="h6 mb-0" {
+                    a href=(format!("/post/{}", post.url_name)) up-target=".modal-content" up-layer="new" { (post.title) }
+                }
+                small class="text-muted ms-3" { (timestamp(&post.timestamp)) }
+            }
+        },
+    }
+}
+
+/// The home page's post listing, which new posts are prepended to while the page is open
+pub fn render_posts_fragment(posts: &[Post], cards: Cards) -> Markup {
+    let layout = cards.layout;
+    let class = match layout {
+        LayoutMode::List => String::new(),
+        LayoutMode::Grid => format!("row row-cols-1 row-cols-md-{} g-3", cards.config.posts_per_row),
+        LayoutMode::Compact => "list-group".to_string(),
+    };
+    html! {
+        div id="posts" class=(class) data-layout=(layout.name()) {
+            @for post in posts {
+                (render_listing_item(post, cards))
+            }
+        }
+    }
+}
+
+/// Links for switching the home page layout, with the current one marked
+pub fn layout_switcher(text: Text, current: LayoutMode) -> Markup {
+    let layouts = [(LayoutMode::List, "layout_list"), (LayoutMode::Grid, "layout_grid"), (LayoutMode::Compact, "layout_compact")];
+    html! {
+        nav class="layout-switcher mb-3" aria-label=(text.t("layout")) {
+            (text.t("layout")) ": "
+            @for (layout, label) in layouts {
+                @if layout == current {
+                    strong class="me-2" aria-current="true" { (text.t(label)) }
+                } @else {
+                    a class="me-2" href=(format!("/layout/{}", layout.name())) { (text.t(label)) }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,517 @@
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::Path;
+
+use maud::{html, Markup, PreEscaped};
+use pulldown_cmark::{html, BlockQuoteKind, CodeBlockKind, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd};
+
+use crate::outbound;
+use crate::config::Config;
+use crate::previews::{self, Preview, Previews};
+use crate::{emoji, gallery};
+use crate::i18n::Text;
+use crate::model::post::{Post, Renderings};
+use crate::polls::PagePolls;
+use crate::prefs::ClientHints;
+use crate::render::{attachments, audio_player, last_updated, timestamp, video_player};
+use crate::shortcode::{self, Details, Segment};
+use crate::tally::Tally;
+
+/// What rendering markdown looks things up in besides the markdown: the assets in the content directory for dark
+/// image copies, and the link previews fetched so far
+#[derive(Clone, Copy)]
+pub struct Context<'a> {
+    pub config: &'a Config,
+    pub previews: &'a Previews,
+}
+
+/// Converts Markdown text to HTML for use in a Maud template
+pub fn markdown_to_html(markdown_text: &str, cx: Context) -> Markup {
+    let options = Options::empty();
+    let markdown_text = preprocess(markdown_text, cx);
+    let parser = Parser::new_ext(&markdown_text, options);
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, parser);
+
+    PreEscaped(html_output)
+}
+
+/// Rewrites what plain markdown can't say into HTML within the markdown, so the post page, which renders its
+/// markdown in the browser, shows the same as the pages rendered here
+pub fn preprocess<'a>(markdown: &'a str, cx: Context) -> Cow<'a, str> {
+    preprocess_for(markdown, ClientHints::default(), cx)
+}
+
+/// [`preprocess`] for one reader, leaving the pictures out of link previews when they're saving data
+pub fn preprocess_for<'a>(markdown: &'a str, hints: ClientHints, cx: Context) -> Cow<'a, str> {
+    let preview = |url: &str| cx.previews.get(url).map(|preview| if hints.save_data { Preview { image: None, ..preview } } else { preview });
+    preprocess_with(markdown, cx.config, |name| asset_exists(&cx.config.content_dir, name), preview)
+}
+
+fn asset_exists(content: &Path, name: &str) -> bool {
+    crate::assets::local_size(content, name).is_some()
+}
+
+/// The `/asset/` URLs of the dark mode copies of a body's images that are in the assets, which the body's
+/// renderings depend on besides its text
+pub fn dark_variants(markdown: &str, content: &Path) -> Vec<String> {
+    Parser::new_ext(markdown, Options::ENABLE_GFM)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Image { dest_url, .. }) => dark_variant(&dest_url, |name| asset_exists(content, name)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The `/asset/` URL of an image's dark mode copy, `diagram.dark.png` for `diagram.png`, when `exists` says it's
+/// in the assets
+fn dark_variant(url: &str, exists: impl Fn(&str) -> bool) -> Option<String> {
+    let name = url.strip_prefix("/asset/")?;
+    let (stem, extension) = name.rsplit_once('.')?;
+    let dark = format!("{}.dark.{}", stem, extension);
+    (!stem.ends_with(".dark") && exists(&dark)).then(|| format!("/asset/{}", dark))
+}
+
+/// The language and label of a code fence's info string, from `rust title=main.rs` or `title="build script"`
+fn fence_title(info: &str) -> Option<(&str, String)> {
+    let lang = info.split_whitespace().next().filter(|word| !word.contains('=')).unwrap_or_default();
+    let (_, rest) = info.split_once("title=")?;
+    let title = match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default(),
+        None => rest.split_whitespace().next().unwrap_or_default(),
+    };
+    (!title.is_empty()).then(|| (lang, title.to_string()))
+}
+
+/// The opening line of a fenced code block with a label, as the label above the fence keeping only its language.
+/// The label is an HTML block, which needs a blank line before the fence so the fence isn't taken into it.
+fn titled_fence(markdown: &str, start: usize, lang: &str, title: &str) -> Option<(Range<usize>, String)> {
+    let line_start = markdown[..start].rfind('\n').map_or(0, |at| at + 1);
+    let prefix = &markdown[line_start..start];
+    // Containers other than quotes and indents, like a list item starting on the fence, would take the label in
+    if !prefix.chars().all(|c| c == '>' || c.is_whitespace()) {
+        return None;
+    }
+    let line_end = markdown[start..].find('\n').map_or(markdown.len(), |at| start + at);
+    let line = &markdown[start..line_end];
+    let fence = &line[..line.len() - line.trim_start_matches(['`', '~']).len()];
+    let label = html! { div class="code-title" { (title) } };
+    Some((start..line_end, format!("{}\n{}\n{}{}{}", label.into_string(), prefix.trim_end(), prefix, fence, lang)))
+}
+
+/// The class, icon and title of a callout
+fn callout_style(kind: BlockQuoteKind) -> (&'static str, &'static str, &'static str) {
+    match kind {
+        BlockQuoteKind::Note => ("note", "ℹ️", "Note"),
+        BlockQuoteKind::Tip => ("tip", "💡", "Tip"),
+        BlockQuoteKind::Important => ("important", "❗", "Important"),
+        BlockQuoteKind::Warning => ("warning", "⚠️", "Warning"),
+        BlockQuoteKind::Caution => ("caution", "🛑", "Caution"),
+    }
+}
+
+/// A `> [!NOTE]` quote as a callout box: its marker line becomes the box's opening tag and title, and the rest
+/// stays a quote inside the box, closed after it
+fn callout(markdown: &str, range: &Range<usize>, kind: BlockQuoteKind) -> Option<[(Range<usize>, String); 2]> {
+    let line_start = markdown[..range.start].rfind('\n').map_or(0, |at| at + 1);
+    let indent = &markdown[line_start..range.start];
+    let line_end = markdown[range.start..].find('\n').map_or(markdown.len(), |at| range.start + at);
+    // Quotes in quotes or starting a list item would need their markers kept on every line
+    if !indent.chars().all(char::is_whitespace) || !markdown[range.start..line_end].trim_start_matches('>').trim_start().starts_with("[!") {
+        return None;
+    }
+    let (class, icon, title) = callout_style(kind);
+    let open = format!("<div class=\"callout callout-{}\" role=\"note\"><p class=\"callout-title\"><span class=\"callout-icon\" aria-hidden=\"true\">{}</span> {}</p>\n{}", class, icon, title, indent);
+    let close = if markdown[..range.end].ends_with('\n') { String::new() } else { "\n".to_string() } + &format!("{}\n{}</div>\n{}\n", indent, indent, indent);
+    Some([(range.start..line_end, open), (range.end..range.end, close)])
+}
+
+/// `||spoiler||` marks as the tags of a blurred span, pairing the marks of one paragraph, heading or list item in
+/// order and leaving an odd one out as it is
+fn spoiler_edits(marks: &[usize], edits: &mut Vec<(Range<usize>, String)>) {
+    for pair in marks.chunks_exact(2) {
+        if pair[1] > pair[0] + 2 {
+            edits.push((pair[0]..pair[0] + 2, "<span class=\"spoiler\" tabindex=\"0\">".to_string()));
+            edits.push((pair[1]..pair[1] + 2, "</span>".to_string()));
+        }
+    }
+}
+
+/// An offsite link as an HTML link opening in a new tab and marked as leaving, over the `[` or `<` that starts it
+/// and the `](url)` or `>` that ends it. `text_end` is where the last thing inside the brackets ends.
+fn external_link(markdown: &str, range: &Range<usize>, link_type: LinkType, url: &str, title: &str, text_end: usize, config: &Config) -> Option<[(Range<usize>, String); 2]> {
+    let open = config.link_style.open_tag(url, title, &config.cookie_key);
+    let close = outbound::CLOSE_TAG.to_string();
+    if link_type == LinkType::Autolink {
+        return Some([(range.start..range.start + 1, open), (range.end - 1..range.end, close)]);
+    }
+    let bracket = markdown[text_end..range.end].find(']')? + text_end;
+    markdown[range.start..].starts_with('[').then(|| [(range.start..range.start + 1, open), (bracket..range.end, close)])
+}
+
+/// Turns `{{details}}` lines outside code blocks into the tags of a `<details>` element, with blank lines around
+/// them so the markdown inside still renders. Sections left open are closed at the end.
+fn details_edits(markdown: &str, code: &[Range<usize>], edits: &mut Vec<(Range<usize>, String)>) {
+    let overlaps = |line: &Range<usize>, other: &Range<usize>| line.start < other.end && other.start < line.end;
+    let mut open = 0;
+    let mut start = 0;
+    for line in markdown.split_inclusive('\n') {
+        let range = start..start + line.trim_end_matches(['\n', '\r']).len();
+        start += line.len();
+        if code.iter().chain(edits.iter().map(|(range, _)| range)).any(|other| overlaps(&range, other)) {
+            continue;
+        }
+        let tag = match shortcode::details(line) {
+            Some(Details::Open(summary)) => {
+                open += 1;
+                format!("<details class=\"post-details\">{}", html! { summary { (summary) } }.into_string())
+            }
+            Some(Details::Close) if open > 0 => {
+                open -= 1;
+                "</details>".to_string()
+            }
+            _ => continue,
+        };
+        let indent = &line[..line.len() - line.trim_start().len()];
+        edits.push((range, format!("{}\n{}{}\n{}", indent, indent, tag, indent)));
+    }
+    for _ in 0..open {
+        edits.push((markdown.len()..markdown.len(), "\n\n</details>\n".to_string()));
+    }
+}
+
+/// [`preprocess`] with the asset and link preview lookups passed in, and the site's URL and link style from `config`. Images that have a dark mode copy become a `<picture>` picking
+/// it for readers in dark mode, code fences with a `title=` get it as a label above them, `> [!NOTE]` quotes
+/// become callouts, `||spoilers||` are blurred, `:emoji:` shortcodes are expanded, links to other sites are
+/// marked as such, bare URLs with a preview become cards and `{{details}}` sections become `<details>`.
+fn preprocess_with<'a>(markdown: &'a str, config: &Config, exists: impl Fn(&str) -> bool, preview: impl Fn(&str) -> Option<Preview>) -> Cow<'a, str> {
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    // The image being replaced: where it is, its URL, title and dark copy, and the alt text gathered so far
+    let mut open: Option<(Range<usize>, CowStr, CowStr, String, String)> = None;
+    let mut code = Vec::new();
+    // Where the `||` marks of the current block start
+    let mut spoilers = Vec::new();
+    let mut in_code = false;
+    // The offsite link being read: where it is, its type, URL and title, and where its text ends so far
+    let mut link: Option<(Range<usize>, LinkType, CowStr, CowStr, usize)> = None;
+    for (event, range) in Parser::new_ext(markdown, Options::ENABLE_GFM).into_offset_iter() {
+        if let Some((at, link_type, url, title, text_end)) = &mut link {
+            if event == Event::End(TagEnd::Link) {
+                edits.extend(external_link(markdown, at, *link_type, url, title, *text_end, config).into_iter().flatten());
+                link = None;
+                continue;
+            }
+            *text_end = range.end.max(*text_end);
+            if *link_type == LinkType::Autolink {
+                continue;
+            }
+        }
+        if let Some((at, src, title, dark, alt)) = &mut open {
+            match event {
+                Event::End(TagEnd::Image) => {
+                    let picture = html! {
+                        picture {
+                            source srcset=(dark) media="(prefers-color-scheme: dark)";
+                            img src=(src.as_ref()) alt=(alt) title=[(!title.is_empty()).then_some(title.as_ref())];
+                        }
+                    };
+                    edits.push((at.clone(), picture.into_string()));
+                    open = None;
+                }
+                Event::Text(text) | Event::Code(text) => alt.push_str(&text),
+                _ => {}
+            }
+            continue;
+        }
+        match event {
+            Event::Start(Tag::Image { dest_url, title, .. }) => {
+                if let Some(dark) = dark_variant(&dest_url, &exists) {
+                    open = Some((range, dest_url, title, dark, String::new()));
+                }
+            }
+            Event::Start(Tag::Link { link_type, dest_url, title, .. }) if outbound::is_external(&dest_url, &config.site_url) => {
+                link = Some((range.clone(), link_type, dest_url, title, range.start + 1));
+            }
+            Event::Start(Tag::CodeBlock(kind)) => {
+                if let CodeBlockKind::Fenced(info) = kind {
+                    if let Some(edit) = fence_title(&info).and_then(|(lang, title)| titled_fence(markdown, range.start, lang, &title)) {
+                        edits.push(edit);
+                    }
+                }
+                code.push(range);
+                in_code = true;
+            }
+            Event::End(TagEnd::CodeBlock) => in_code = false,
+            // The card is an HTML block, so it needs a blank line after it to end before whatever comes next
+            Event::Start(Tag::Paragraph) => {
+                if let Some((url, preview)) = previews::bare_url(markdown, range.clone()).and_then(|url| preview(url).map(|preview| (url, preview))) {
+                    let start = range.start + markdown[range.start..].find(url).unwrap_or_default();
+                    edits.push((start..start + url.len(), format!("{}\n", previews::card(url, &preview, config.link_style.new_tab).into_string())));
+                }
+            }
+            Event::Start(Tag::BlockQuote(Some(kind))) => edits.extend(callout(markdown, &range, kind).into_iter().flatten()),
+            Event::Html(_) => code.push(range),
+            // Text with escapes or entities doesn't match its source, and any marks in it are left alone
+            Event::Text(text) if !in_code && markdown.get(range.clone()) == Some(&*text) => {
+                spoilers.extend(text.match_indices("||").map(|(at, _)| range.start + at));
+                edits.extend(emoji::shortcodes(&text).into_iter().map(|(at, emoji)| (range.start + at.start..range.start + at.end, emoji.to_string())));
+            }
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item) => {
+                spoiler_edits(&spoilers, &mut edits);
+                spoilers.clear();
+            }
+            _ => {}
+        }
+    }
+    details_edits(markdown, &code, &mut edits);
+    edits.sort_by_key(|(range, _)| range.start);
+
+    if edits.is_empty() {
+        return Cow::Borrowed(markdown);
+    }
+    let mut edited = String::with_capacity(markdown.len());
+    let mut copied = 0;
+    for (range, replacement) in edits {
+        // Edits never overlap, but one that did would otherwise cut the markdown apart
+        if range.start < copied {
+            continue;
+        }
+        edited.push_str(&markdown[copied..range.start]);
+        edited.push_str(&replacement);
+        copied = range.end;
+    }
+    edited.push_str(&markdown[copied..]);
+    Cow::Owned(edited)
+}
+
+/// The post's renderings, noting the assets they depend on the first time
+fn renderings<'a>(post: &'a Post, content: &Path) -> &'a Renderings {
+    post.rendered.assets.get_or_init(|| dark_variants(&post.body, content));
+    &post.rendered
+}
+
+/// The markdown segments of a post body [preprocessed](preprocess_for) for this reader, in order. They're only
+/// preprocessed the first time for readers saving data and for readers who aren't.
+pub fn preprocessed_body<'a>(post: &'a Post, hints: ClientHints, cx: Context) -> &'a [String] {
+    renderings(post, &cx.config.content_dir).preprocessed[usize::from(hints.save_data)].get_or_init(|| {
+        shortcode::split(&post.body)
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Markdown(markdown) => Some(preprocess_for(markdown, hints, cx).into_owned()),
+                Segment::Poll(_) | Segment::Gallery(_) => None,
+            })
+            .collect()
+    })
+}
+
+/// A post body as HTML with its shortcodes expanded for this reader. The markdown around them is only rendered
+/// the first time.
+pub fn render_body(post: &Post, text: Text, votes: &Tally, polls: &PagePolls, cx: Context) -> Markup {
+    let segments = shortcode::split(&post.body);
+    let rendered = renderings(post, &cx.config.content_dir).html.get_or_init(|| {
+        segments
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Markdown(markdown) => Some(markdown_to_html(markdown, cx).into_string()),
+                Segment::Poll(_) | Segment::Gallery(_) => None,
+            })
+            .collect()
+    });
+    let mut rendered = rendered.iter();
+
+    html! {
+        @for segment in segments {
+            @match segment {
+                Segment::Markdown(_) => (PreEscaped(rendered.next().map(String::as_str).unwrap_or_default())),
+                Segment::Poll(id) => (polls.widget(id, votes, text)),
+                Segment::Gallery(images) => (gallery::widget(&images, text)),
+            }
+        }
+    }
+}
+
+/// Renders the post in a Maud template, converting the body from Markdown to HTML
+pub fn render_post(post: &Post, text: Text, votes: &Tally, polls: &PagePolls, hints: ClientHints, cx: Context) -> Markup {
+    html! {
+        article class="post" {
+            h1 { (post.title) }
+            p class="text-muted" {
+                (timestamp(&post.timestamp))
+                (last_updated(post, text))
+            }
+            (audio_player(post, text, hints))
+            (video_player(post, text, hints))
+            div class="post-content" {
+                (render_body(post, text, votes, polls, cx))
+            }
+            (attachments(post, text, &cx.config.content_dir))
+        }
+    }
+}
+
+/// [`markdown_to_html`] with the default config and no link previews
+#[cfg(test)]
+fn to_html(markdown: &str) -> String {
+    let (config, previews) = (Config::default(), Previews::load("/dev/null"));
+    markdown_to_html(markdown, Context { config: &config, previews: &previews }).into_string()
+}
+
+#[test]
+fn images_with_a_dark_copy_become_pictures() {
+    let preprocess = |markdown: &'static str| preprocess_with(markdown, &Config::default(), |name| name == "posts/diagram.dark.png", |_| None);
+    assert_eq!(
+        preprocess(r#"See ![the *wiring* diagram](/asset/posts/diagram.png "Wiring") and [![it](/asset/posts/diagram.png)](/x)."#),
+        r#"See <picture><source srcset="/asset/posts/diagram.dark.png" media="(prefers-color-scheme: dark)"><img src="/asset/posts/diagram.png" alt="the wiring diagram" title="Wiring"></picture> and [<picture><source srcset="/asset/posts/diagram.dark.png" media="(prefers-color-scheme: dark)"><img src="/asset/posts/diagram.png" alt="it"></picture>](/x)."#
+    );
+    assert!(matches!(preprocess("![cat](/asset/cat.png)"), Cow::Borrowed(_)));
+    assert_eq!(dark_variant("/asset/posts/diagram.dark.png", |_| true), None);
+    assert_eq!(dark_variant("https://example.com/diagram.png", |_| true), None);
+}
+
+#[test]
+fn code_fences_get_their_title_as_a_label() {
+    let preprocess = |markdown: &'static str| preprocess_with(markdown, &Config::default(), |_| false, |_| None);
+    assert_eq!(
+        preprocess("Run:\n\n```rust title=main.rs\nfn main() {}\n```\n"),
+        "Run:\n\n<div class=\"code-title\">main.rs</div>\n\n```rust\nfn main() {}\n```\n"
+    );
+    assert_eq!(
+        preprocess("> ~~~~ title=\"<build> script\" sh\n> make\n> ~~~~"),
+        "> <div class=\"code-title\">&lt;build&gt; script</div>\n>\n> ~~~~\n> make\n> ~~~~"
+    );
+    let html = to_html("```rust title=main.rs\nfn main() {}\n```");
+    assert!(html.starts_with("<div class=\"code-title\">main.rs</div>\n<pre><code class=\"language-rust\">fn main() {}"), "{}", html);
+    assert!(matches!(preprocess("```rust\nlet title=1;\n```\n- ```sh title=run.sh\n  make\n  ```"), Cow::Borrowed(_)));
+}
+
+#[test]
+fn details_shortcodes_become_collapsible_sections() {
+    let preprocess = |markdown: &'static str| preprocess_with(markdown, &Config::default(), |_| false, |_| None);
+    assert_eq!(
+        preprocess("Intro\n{{details The <full> log}}\n```\n{{/details}}\n```\n{{/details}}\nOutro"),
+        "Intro\n\n<details class=\"post-details\"><summary>The &lt;full&gt; log</summary>\n\n```\n{{/details}}\n```\n\n</details>\n\nOutro"
+    );
+    let html = to_html("{{details Why}}\n*Because*\n{{/details}}\n\n{{/details}}");
+    assert_eq!(html, "<details class=\"post-details\"><summary>Why</summary>\n<p><em>Because</em></p>\n</details>\n<p>{{/details}}</p>\n");
+    // Indented sections stay in their list item
+    let html = to_html("- Item\n\n  {{details Open}}\n  text\n  {{/details}}\n- Next");
+    assert!(html.contains("<details class=\"post-details\"><summary>Open</summary>\n<p>text</p>\n</details>\n</li>"), "{}", html);
+}
+
+#[test]
+fn marked_quotes_become_callouts() {
+    let html = to_html("> [!WARNING]\n> Mind the *mains*.\nStill warning\n# Next");
+    assert_eq!(
+        html,
+        "<div class=\"callout callout-warning\" role=\"note\"><p class=\"callout-title\"><span class=\"callout-icon\" aria-hidden=\"true\">⚠️</span> Warning</p>\n<blockquote>\n<p>Mind the <em>mains</em>.\nStill warning</p>\n</blockquote>\n</div>\n<h1>Next</h1>\n"
+    );
+    let html = to_html("1. Step\n\n   > [!tip]\n   > Use flux\n2. Solder");
+    assert!(html.contains("<li>\n<p>Step</p>\n<div class=\"callout callout-tip\""), "{}", html);
+    assert!(html.contains("</div>\n</li>\n<li>\n<p>Solder"), "{}", html);
+    for plain in ["> Just a quote", "> [!NOTE] with text", "> > [!NOTE]\n> > Nested", "- > [!NOTE]\n  > Item"] {
+        assert!(matches!(preprocess_with(plain, &Config::default(), |_| false, |_| None), Cow::Borrowed(_)), "{}", plain);
+    }
+}
+
+#[test]
+fn spoilers_are_wrapped_in_blurred_spans() {
+    let html = to_html("The ||*butler*|| did it, ||or|| not||\n\n`||code||` and ||||");
+    assert_eq!(html, "<p>The <span class=\"spoiler\" tabindex=\"0\"><em>butler</em></span> did it, <span class=\"spoiler\" tabindex=\"0\">or</span> not||</p>\n<p><code>||code||</code> and ||||</p>\n");
+    // Marks don't pair across paragraphs or list items
+    assert!(matches!(preprocess_with("||one\n\ntwo||\n- ||three\n- four||", &Config::default(), |_| false, |_| None), Cow::Borrowed(_)));
+    assert_eq!(to_html("# Ending: ||sad||"), "<h1>Ending: <span class=\"spoiler\" tabindex=\"0\">sad</span></h1>\n");
+}
+
+#[test]
+fn emoji_shortcodes_are_expanded_outside_code() {
+    assert_eq!(
+        to_html("Shipped :rocket: *at 10:30:tada:*\n\n`:fire:`\n\n```\n:fire: ||not a spoiler||\n```"),
+        "<p>Shipped 🚀 <em>at 10:30🎉</em></p>\n<p><code>:fire:</code></p>\n<pre><code>:fire: ||not a spoiler||\n</code></pre>\n"
+    );
+    assert_eq!(to_html("- ||a\n  ```\n  b||\n  ```").matches("spoiler").count(), 0);
+}
+
+#[test]
+fn offsite_links_open_in_a_new_tab_with_an_icon() {
+    let open = |url: &str| format!(r#"<a href="{}" class="external-link" rel="noopener noreferrer" target="_blank">"#, url);
+    let html = to_html("[The *docs* :fire:](https://docs.rs/axum \"Docs\"), <https://example.com/a_b> and [home](/post/a) or [ref]\n\n[ref]: http://example.org");
+    assert_eq!(
+        html,
+        format!(
+            "<p><a href=\"https://docs.rs/axum\" title=\"Docs\" class=\"external-link\" rel=\"noopener noreferrer\" target=\"_blank\">The <em>docs</em> 🔥{}, {}https://example.com/a_b{} and <a href=\"/post/a\">home</a> or {}ref{}</p>\n",
+            outbound::CLOSE_TAG,
+            open("https://example.com/a_b"),
+            outbound::CLOSE_TAG,
+            open("http://example.org"),
+            outbound::CLOSE_TAG
+        )
+    );
+    assert!(to_html("[![](/asset/a.png)](https://example.com)").contains(r#"target="_blank"><img src="/asset/a.png" alt="" /><span"#));
+}
+
+#[test]
+fn bare_urls_with_a_preview_become_cards() {
+    let preview = |url: &str| (url == "https://example.com/post").then(|| Preview { title: "A post".to_string(), site: "Example".to_string(), ..Preview::default() });
+    let preprocess = |markdown: &'static str| preprocess_with(markdown, &Config::default(), |_| false, preview);
+    assert_eq!(
+        preprocess("Read this:\n\nhttps://example.com/post\n# Next"),
+        format!("Read this:\n\n{}\n\n# Next", previews::card("https://example.com/post", &preview("https://example.com/post").unwrap(), true).into_string())
+    );
+    for unchanged in ["https://example.com/other", "See https://example.com/post", "> https://example.com/post"] {
+        assert!(matches!(preprocess(unchanged), Cow::Borrowed(_)), "{}", unchanged);
+    }
+}
+
+#[tokio::test]
+async fn reloads_keep_renderings_of_unchanged_bodies() {
+    use std::sync::Arc;
+
+    use axum::http::HeaderMap;
+
+    use crate::i18n::Locales;
+    use crate::model::post::{deserialize_post, keep_renderings};
+
+    let post = |body: &str| deserialize_post(&format!(r#"{{"title":"","body":"{}","image_url":"","summary":"","timestamp":"2024-01-01T00:00:00Z"}}"#, body), "post");
+    let (config, previews) = (Config::default(), Previews::load("/dev/null"));
+    let cx = Context { config: &config, previews: &previews };
+    let locales = Locales::load(&config.content_dir);
+    let votes = Tally::load("/dev/null");
+    let polls = PagePolls::load(&config, "post", "", &HeaderMap::new()).await;
+
+    let current = vec![post("*same*"), post("old"), post("![Chart](/asset/photo.png)")];
+    // The photo had a dark copy when it was rendered, which has been removed since
+    current[2].rendered.assets.set(vec!["/asset/photo.dark.png".to_string()]).unwrap();
+    for post in &current {
+        render_body(post, locales.text("en"), &votes, &polls, cx);
+    }
+
+    let mut loaded = vec![post("*same*"), post("new"), post("![Chart](/asset/photo.png)")];
+    keep_renderings(&current, &mut loaded, &config.content_dir);
+    assert!(Arc::ptr_eq(&loaded[0].rendered, &current[0].rendered));
+    assert!(loaded[1].rendered.html.get().is_none());
+    assert!(loaded[2].rendered.html.get().is_none());
+    assert_eq!(render_body(&loaded[0], locales.text("en"), &votes, &polls, cx).into_string(), "<p><em>same</em></p>\n");
+
+    assert_eq!(preprocessed_body(&loaded[1], ClientHints::default(), cx), ["new"]);
+    assert!(loaded[1].rendered.preprocessed[0].get().is_some() && loaded[1].rendered.preprocessed[1].get().is_none());
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    /// Post bodies go through the shortcodes, pulldown-cmark and the excerpt, none of which may panic on any input
+    #[test]
+    fn any_body_renders(body in "\\PC*", max_chars in 1..300usize) {
+        use axum::http::HeaderMap;
+
+        use crate::i18n::Locales;
+        use crate::model::post::deserialize_post;
+
+        let post = deserialize_post(&serde_json::json!({"title": "", "body": body, "image_url": "", "summary": "", "timestamp": "2024-01-01T00:00:00Z"}).to_string(), "post");
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let (config, previews) = (Config::default(), Previews::load("/dev/null"));
+        let polls = runtime.block_on(PagePolls::load(&config, "post", &post.body, &HeaderMap::new()));
+        render_body(&post, Locales::load(&config.content_dir).text("en"), &Tally::load("/dev/null"), &polls, Context { config: &config, previews: &previews });
+        proptest::prop_assert!(crate::excerpt::excerpt(&post.body, max_chars).chars().count() <= max_chars + 1);
+    }
+}
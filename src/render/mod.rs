@@ -0,0 +1,323 @@
+pub mod home;
+pub mod listing;
+pub mod markdown;
+pub mod sidebar;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use maud::{html, Markup, PreEscaped};
+
+use crate::i18n::Text;
+use crate::model::post::Post;
+use crate::prefs::ClientHints;
+use crate::{prefs, search, share};
+
+/// The photo behind the home and contact page headers, left out for readers saving data
+pub const HEADER_IMAGE_CSS: &str = r#"
+    .header {
+        background-image: url('https://external-content.duckduckgo.com/iu/?u=https%3A%2F%2Fpreview.redd.it%2Fi0h9ke187tk31.png%3Fwidth%3D960%26crop%3Dsmart%26auto%3Dwebp%26s%3Ddc294c8327d576f78d3cd0e08982cd6e3f619a21&f=1&nofb=1&ipt=47a8aff3e3499390c872b22b77ba3ad02b9f28fc0c0f5b5d3d82c84dd16ed6a6&ipo=images');
+    }
+"#;
+
+/// Sent only to readers asking for less motion, so nothing on the page moves by itself
+pub const REDUCED_MOTION_CSS: &str = r#"
+    *, *::before, *::after {
+        animation: none !important;
+        transition: none !important;
+        scroll-behavior: auto !important;
+    }
+"#;
+
+/// The styles that depend on the reader's [`ClientHints`], the header photo when `header_image` is set
+pub fn hinted_css(hints: ClientHints, header_image: bool) -> Markup {
+    html! {
+        @if header_image && !hints.save_data {
+            style { (PreEscaped(HEADER_IMAGE_CSS)) }
+        }
+        @if hints.reduced_motion {
+            style { (PreEscaped(REDUCED_MOTION_CSS)) }
+        }
+    }
+}
+
+/// Print rules shared by every page: no backgrounds or chrome, code blocks fully expanded and link targets spelled out
+pub const PRINT_CSS: &str = r#"
+    * {
+        animation: none !important;
+        background-image: none !important;
+    }
+    body, .post-body, .post-card, github-md {
+        background: #fff !important;
+        color: #000 !important;
+        box-shadow: none !important;
+    }
+    .header, .footer, .navbar, .sidebar, .btn, script {
+        display: none !important;
+    }
+    pre, code {
+        white-space: pre-wrap !important;
+        word-break: break-word;
+        overflow: visible !important;
+        max-height: none !important;
+    }
+    a[href^="http"]::after {
+        content: " (" attr(href) ")";
+        font-size: 0.8em;
+    }
+    img {
+        max-width: 100% !important;
+        page-break-inside: avoid;
+    }
+"#;
+
+/// Keyboard rules shared by every page: a focus ring that stands out on the dark theme and a skip link that
+/// only appears once it's focused
+pub const FOCUS_CSS: &str = r#"
+    :focus-visible {
+        outline: 3px solid #66b2ff !important;
+        outline-offset: 2px;
+    }
+    .skip-link:focus {
+        position: fixed;
+        top: 0.5rem;
+        left: 0.5rem;
+        z-index: 2000;
+        padding: 0.5rem 1rem;
+        background-color: #fff;
+        color: #000;
+    }
+"#;
+
+/// `{{details}}` sections in post bodies and notes, as a card that opens under its summary
+pub const DETAILS_CSS: &str = r#"
+    .post-details {
+        margin: 1em 0;
+        border: 1px solid #343a40;
+        border-radius: 8px;
+        background-color: #181818;
+    }
+    .post-details > summary {
+        padding: 0.5em 1em;
+        cursor: pointer;
+        color: #f0f0f0;
+        font-weight: bold;
+    }
+    .post-details[open] > summary {
+        border-bottom: 1px solid #343a40;
+    }
+    .post-details > :not(summary) {
+        margin-left: 1em;
+        margin-right: 1em;
+    }
+"#;
+
+/// `> [!NOTE]` callouts, a colored bar and title per kind over the quote they hold
+pub const CALLOUT_CSS: &str = r#"
+    .callout {
+        margin: 1em 0;
+        padding: 0.5em 1em;
+        border-left: 4px solid var(--callout-color);
+        border-radius: 4px;
+        background-color: #181818;
+    }
+    .callout-note { --callout-color: #66b2ff; }
+    .callout-tip { --callout-color: #3fb950; }
+    .callout-important { --callout-color: #a371f7; }
+    .callout-warning { --callout-color: #d29922; }
+    .callout-caution { --callout-color: #f85149; }
+    .callout-title {
+        margin-bottom: 0.5em;
+        color: var(--callout-color);
+        font-weight: bold;
+    }
+    .callout > blockquote {
+        margin: 0;
+        padding: 0;
+        border: none;
+    }
+    .callout > blockquote > :last-child {
+        margin-bottom: 0;
+    }
+"#;
+
+/// `||spoilers||`, blurred until they're clicked or tabbed to
+pub const SPOILER_CSS: &str = r#"
+    .spoiler {
+        filter: blur(5px);
+        cursor: pointer;
+        transition: filter 0.2s;
+    }
+    .spoiler:focus,
+    .spoiler:active {
+        filter: none;
+    }
+    @media print {
+        .spoiler {
+            filter: none;
+        }
+    }
+"#;
+
+/// Post cards in every layout. Cards grow with their content instead of a fixed height, and long words in
+/// titles or summaries wrap rather than overflow.
+pub const CARD_CSS: &str = r#"
+    .post-card {
+        background-color: #1e1e1e;
+        color: #e0e0e0;
+        border: none;
+        margin-bottom: 20px;
+        box-shadow: 0 4px 8px rgba(0, 0, 0, 0.3);
+        transition: 0.3s;
+        height: auto;
+        overflow-wrap: anywhere;
+    }
+    .post-card:hover {
+        box-shadow: 0 8px 16px rgba(0, 0, 0, 0.5);
+    }
+    [data-layout="grid"] .post-card {
+        height: 100%;
+        margin-bottom: 0;
+    }
+    .post-compact {
+        background-color: #1e1e1e;
+        color: #e0e0e0;
+        border-color: #2c2c2c;
+        overflow-wrap: anywhere;
+    }
+    .post-card.accented {
+        border-top: 4px solid var(--accent);
+    }
+    .post-card.accented .card-title,
+    .post-compact.accented a {
+        color: var(--accent);
+    }
+    .post-compact.accented {
+        border-left: 4px solid var(--accent);
+    }
+"#;
+
+/// First thing in every page's body, so keyboard users can jump past the header and navigation
+pub fn skip_link(text: Text) -> Markup {
+    html! {
+        a class="visually-hidden-focusable skip-link" href="#main" { (text.t("skip_to_content")) }
+    }
+}
+
+/// The search form for page headers, offering titles and tags from `/search/suggest` as the reader types. The
+/// suggestions need htmx on the page, the search itself works without it.
+pub fn search_box(text: Text) -> Markup {
+    html! {
+        form class="search-box position-relative mx-auto mt-3" action=(search::SEARCH_PATH) method="get" role="search" style="max-width: 400px" {
+            input type="search" name="q" class="form-control" autocomplete="off" aria-label=(text.t("search")) placeholder=(text.t("search_placeholder"))
+                hx-get=(search::SUGGEST_PATH) hx-trigger="input changed delay:250ms, search" hx-target="next .search-suggestions";
+            div class="search-suggestions list-group position-absolute w-100 text-start" style="z-index: 1000" {}
+        }
+    }
+}
+
+/// A `<time>` element rendered in UTC, which `prefs::localize_times` rewrites for the reader's timezone and display mode
+pub fn timestamp(timestamp: &DateTime<Utc>) -> Markup {
+    html! {
+        time datetime=(timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)) { (timestamp.format(prefs::TIMESTAMP_FORMAT).to_string()) }
+    }
+}
+
+/// Fills the `{date}` placeholder of a translated string with markup
+pub fn with_date(template: &str, date: Markup) -> Markup {
+    let (before, after) = template.split_once("{date}").unwrap_or((template, ""));
+    html! { (before) (date) (after) }
+}
+
+/// What a player fetches before it's played: a little to show the length, or nothing for readers saving data
+fn preload(hints: ClientHints) -> &'static str {
+    if hints.save_data {
+        "none"
+    } else {
+        "metadata"
+    }
+}
+
+/// A player for a post's episode, with a link to download it for listening elsewhere
+pub fn audio_player(post: &Post, text: Text, hints: ClientHints) -> Markup {
+    html! {
+        @if let Some(audio) = &post.audio {
+            figure class="episode my-3" {
+                audio class="w-100" controls preload=(preload(hints)) {
+                    source src=(audio.url("")) type=(audio.mime());
+                }
+                figcaption class="small" {
+                    a href=(audio.url("")) download { (text.t("audio_download")) }
+                }
+            }
+        }
+    }
+}
+
+/// A player for a post's video, as wide as the post, showing its poster or the post's card image until it plays
+pub fn video_player(post: &Post, text: Text, hints: ClientHints) -> Markup {
+    html! {
+        @if let Some(video) = &post.video {
+            figure class="post-video my-3" {
+                video class="w-100 h-auto rounded" controls preload=(preload(hints)) playsinline poster=(video.poster_url("").unwrap_or_else(|| crate::og::image_for(post, ""))) {
+                    source src=(video.url("")) type=(video.mime());
+                    a href=(video.url("")) { (text.t("video_download")) }
+                }
+            }
+        }
+    }
+}
+
+/// A size in bytes as people read it, like `2.4 MB`
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// A box of the post's attachments to download, each with its type and, when they're in the assets under `content`,
+/// its size
+pub fn attachments(post: &Post, text: Text, content: &std::path::Path) -> Markup {
+    html! {
+        @if !post.attachments.is_empty() {
+            aside class="attachments border border-secondary rounded p-3 my-3" aria-label=(text.t("attachments")) {
+                h3 class="h6" { (text.t("attachments")) }
+                ul class="list-unstyled mb-0" {
+                    @for file in &post.attachments {
+                        li {
+                            a href=(share::attachment_url(post, file)) download { (file) }
+                            small class="text-muted" {
+                                " · " (crate::assets::content_type(file).and_then(|mime| mime.split(';').next()).unwrap_or("application/octet-stream"))
+                                @if let Some(size) = crate::assets::local_size(content, &post.attachment_asset(file)) {
+                                    " · " (human_size(size))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The "Last updated" note for posts edited after publishing
+pub fn last_updated(post: &Post, text: Text) -> Markup {
+    html! {
+        @if let Some(updated) = post.updated.filter(|updated| *updated > post.timestamp) {
+            " · " (with_date(text.t("last_updated"), timestamp(&updated)))
+        }
+    }
+}
+
+#[test]
+fn sizes_are_shown_in_the_largest_fitting_unit() {
+    assert_eq!(human_size(512), "512 B");
+    assert_eq!(human_size(1536), "1.5 KB");
+    assert_eq!(human_size(5 * 1024 * 1024), "5.0 MB");
+}
@@ -0,0 +1,377 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate};
+use chrono_tz::Tz;
+use maud::{html, Markup};
+
+use crate::i18n::Text;
+use crate::model::post::Post;
+use crate::render::{search_box, timestamp};
+use crate::tally::Tally;
+use crate::{search, share};
+
+/// How many posts the recent and popular widgets list
+const LISTED_POSTS: usize = 5;
+
+/// Where every tag is shown, beyond those the sidebar has room for
+pub const TAGS_PATH: &str = "/tags";
+
+/// Where [`recent_box`] fetches the newest posts from
+pub const RECENT_PATH: &str = "/fragments/recent";
+
+/// Where the on this day widget fetches its posts from, since they depend on the reader's date
+pub const ON_THIS_DAY_PATH: &str = "/fragments/on-this-day";
+
+/// Where the surprise me link sends the reader off to a random post
+pub const RANDOM_PATH: &str = "/random";
+
+/// What the home page sidebar shows and how
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sidebar {
+    /// The widgets in order, from the comma separated `CADEN_BLOG_SIDEBAR`. Unknown names are skipped with a warning.
+    pub widgets: Vec<Widget>,
+    /// Links under the follow me heading, from `CADEN_BLOG_SOCIAL_LINKS` as comma separated `Name=url` pairs
+    pub social_links: Vec<(String, String)>,
+    /// Most tags the cloud shows, the most used ones, from `CADEN_BLOG_TAG_CLOUD_SIZE`
+    pub cloud_size: usize,
+}
+
+impl Sidebar {
+    pub fn from_env() -> Sidebar {
+        let default = Sidebar::default();
+        let widgets = match std::env::var("CADEN_BLOG_SIDEBAR") {
+            Ok(list) => list
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .filter_map(|name| {
+                    let widget = Widget::parse(name);
+                    if widget.is_none() {
+                        println!("Unknown sidebar widget {}, leaving it out", name);
+                    }
+                    widget
+                })
+                .collect(),
+            Err(_) => default.widgets,
+        };
+        let social_links = match std::env::var("CADEN_BLOG_SOCIAL_LINKS") {
+            Ok(list) => list
+                .split(',')
+                .filter_map(|pair| pair.split_once('='))
+                .map(|(name, url)| (name.trim().to_string(), url.trim().to_string()))
+                .filter(|(name, url)| !name.is_empty() && !url.is_empty())
+                .collect(),
+            Err(_) => default.social_links,
+        };
+        let cloud_size = std::env::var("CADEN_BLOG_TAG_CLOUD_SIZE")
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+            .filter(|size| *size > 0)
+            .unwrap_or(default.cloud_size);
+        Sidebar { widgets, social_links, cloud_size }
+    }
+}
+
+/// About, tags and social, with placeholder links and a cloud of 20 tags
+impl Default for Sidebar {
+    fn default() -> Sidebar {
+        Sidebar {
+            widgets: vec![Widget::About, Widget::Tags, Widget::Social],
+            social_links: ["Twitter", "Facebook", "Instagram"].into_iter().map(|name| (name.to_string(), "#".to_string())).collect(),
+            cloud_size: 20,
+        }
+    }
+}
+
+/// One block of the home page sidebar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Widget {
+    About,
+    /// The tags in use, each searching for its posts
+    Tags,
+    Recent,
+    /// The posts with the most reactions
+    Popular,
+    /// The posts grouped by the month they were published
+    Archive,
+    Search,
+    Social,
+    /// The posts published on today's date in earlier years, in the reader's timezone
+    OnThisDay,
+}
+
+impl Widget {
+    pub fn parse(value: &str) -> Option<Widget> {
+        match value {
+            "about" => Some(Widget::About),
+            "tags" => Some(Widget::Tags),
+            "recent" => Some(Widget::Recent),
+            "popular" => Some(Widget::Popular),
+            "archive" => Some(Widget::Archive),
+            "search" => Some(Widget::Search),
+            "social" => Some(Widget::Social),
+            "on-this-day" => Some(Widget::OnThisDay),
+            _ => None,
+        }
+    }
+}
+
+/// The sidebar's widgets for a listing one after another, with a rule between them
+pub fn render_sidebar(sidebar: &Sidebar, posts: &[Post], text: Text, reactions: &Tally) -> Markup {
+    html! {
+        div class="sidebar" {
+            @for (index, widget) in sidebar.widgets.iter().enumerate() {
+                @if index > 0 {
+                    hr;
+                }
+                (render_widget(*widget, sidebar, posts, text, reactions))
+            }
+            @if !posts.is_empty() {
+                hr;
+                a class="surprise-me" href=(RANDOM_PATH) rel="nofollow" { (text.t("surprise_me")) }
+            }
+        }
+    }
+}
+
+fn render_widget(widget: Widget, sidebar: &Sidebar, posts: &[Post], text: Text, reactions: &Tally) -> Markup {
+    match widget {
+        Widget::About => html! {
+            h2 class="h4" { (text.t("about_heading")) }
+            p { (text.t("about_text")) }
+        },
+        Widget::Tags => tags(posts, sidebar.cloud_size, text),
+        Widget::Recent => recent_box(Some(posts), text),
+        Widget::Popular => post_list(text.t("popular_posts"), &popular(posts, reactions), text),
+        Widget::Archive => archive(posts, text),
+        Widget::Search => html! {
+            h3 class="h5" { (text.t("search")) }
+            (search_box(text))
+        },
+        // Filled in after the page loads, since the cached page can't know the reader's date
+        Widget::OnThisDay => html! {
+            div class="on-this-day" hx-get=(ON_THIS_DAY_PATH) hx-trigger="load" {}
+        },
+        Widget::Social => html! {
+            h3 class="h5" { (text.t("follow_me")) }
+            @for (name, url) in &sidebar.social_links {
+                a href=(url) class="btn btn-outline-primary btn-sm" rel="me noopener" { (name) }
+            }
+        },
+    }
+}
+
+/// Every tag with how many posts have it, most used first, compared without case
+pub fn tag_counts(posts: &[Post]) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for tag in posts.iter().flat_map(|post| &post.tags) {
+        match counts.iter_mut().find(|(seen, _)| seen.eq_ignore_ascii_case(tag)) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((tag.clone(), 1)),
+        }
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.to_lowercase().cmp(&b.0.to_lowercase())));
+    counts
+}
+
+/// Where a tag's posts are listed
+pub fn tag_url(tag: &str) -> String {
+    format!("{}?q={}", search::SEARCH_PATH, share::encode(tag))
+}
+
+/// The font size and opacity of a tag used `count` times, between the least and most used tags
+fn weight(count: usize, least: usize, most: usize) -> String {
+    let weight = if most > least { (count - least) as f64 / (most - least) as f64 } else { 0.5 };
+    format!("font-size: {:.2}em; opacity: {:.2}", 0.85 + 0.75 * weight, 0.7 + 0.3 * weight)
+}
+
+/// The most used `limit` of the tags in alphabetical order, each sized by how many posts have it
+pub fn tag_cloud(counts: &[(String, usize)], limit: usize, text: Text) -> Markup {
+    let mut shown: Vec<&(String, usize)> = counts.iter().take(limit).collect();
+    let least = shown.iter().map(|(_, count)| *count).min().unwrap_or_default();
+    let most = shown.iter().map(|(_, count)| *count).max().unwrap_or_default();
+    shown.sort_by_key(|(tag, _)| tag.to_lowercase());
+    html! {
+        ul class="list-inline tag-cloud" {
+            @for (tag, count) in shown {
+                li class="list-inline-item" {
+                    a href=(tag_url(tag)) style=(weight(*count, least, most)) title=(text.t(if *count == 1 { "tag_post" } else { "tag_posts" }).replace("{count}", &count.to_string())) { (tag) }
+                }
+            }
+        }
+    }
+}
+
+fn tags(posts: &[Post], cloud_size: usize, text: Text) -> Markup {
+    let counts = tag_counts(posts);
+    html! {
+        h3 class="h5" { (text.t("categories")) }
+        @if counts.is_empty() {
+            p class="text-muted" { (text.t("no_posts_yet")) }
+        } @else {
+            (tag_cloud(&counts, cloud_size, text))
+            @if counts.len() > cloud_size {
+                a class="small" href=(TAGS_PATH) { (text.t("all_tags")) }
+            }
+        }
+    }
+}
+
+/// The newest posts of the listing
+fn recent(posts: &[Post]) -> Vec<&Post> {
+    let mut recent: Vec<&Post> = posts.iter().collect();
+    recent.sort_by_key(|post| std::cmp::Reverse(post.timestamp));
+    recent.truncate(LISTED_POSTS);
+    recent
+}
+
+/// The posts published on `today`'s month and day in earlier years, both in `tz`, newest first. Posts from the
+/// 29th of February turn up on the 28th in other years.
+pub fn on_this_day(posts: &[Post], today: NaiveDate, tz: Tz) -> Vec<&Post> {
+    let leap_day_missing = today.month() == 2 && today.day() == 28 && NaiveDate::from_ymd_opt(today.year(), 2, 29).is_none();
+    let mut found: Vec<&Post> = posts
+        .iter()
+        .filter(|post| {
+            let published = post.timestamp.with_timezone(&tz).date_naive();
+            let same_day = (published.month(), published.day()) == (today.month(), today.day()) || (leap_day_missing && (published.month(), published.day()) == (2, 29));
+            same_day && published.year() < today.year()
+        })
+        .collect();
+    found.sort_by_key(|post| std::cmp::Reverse(post.timestamp));
+    found
+}
+
+/// [`on_this_day`] as a widget, or nothing on days without any
+pub fn on_this_day_widget(posts: &[&Post], today: NaiveDate, text: Text) -> Markup {
+    html! {
+        @if !posts.is_empty() {
+            h3 class="h5" { (text.t("on_this_day")) }
+            ul class="list-unstyled" {
+                @for post in posts {
+                    li {
+                        a href=(share::post_url("", post)) { (post.title) }
+                        " "
+                        small class="text-muted" { (text.t("years_ago").replace("{years}", &(today.year() - post.timestamp.year()).to_string())) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The posts with the most reactions, leaving out those without any
+fn popular<'a>(posts: &'a [Post], reactions: &Tally) -> Vec<&'a Post> {
+    let mut popular: Vec<(&Post, u64)> = posts.iter().map(|post| (post, reactions.counts(&post.url_name).values().sum())).filter(|(_, total)| *total > 0).collect();
+    popular.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.timestamp.cmp(&a.0.timestamp)));
+    popular.into_iter().take(LISTED_POSTS).map(|(post, _)| post).collect()
+}
+
+/// The newest posts, as [`RECENT_PATH`] sends them
+pub fn recent_posts(posts: &[Post], text: Text) -> Markup {
+    post_list(text.t("recent_posts"), &recent(posts), text)
+}
+
+/// [`recent_posts`] in a box htmx refreshes every few minutes. Without `posts` it stays empty until htmx fills it in
+/// after the page loads, for pages that shouldn't wait on it.
+pub fn recent_box(posts: Option<&[Post]>, text: Text) -> Markup {
+    html! {
+        @match posts {
+            Some(posts) => div class="recent-posts" hx-get=(RECENT_PATH) hx-trigger="every 10m" { (recent_posts(posts, text)) },
+            None => div class="recent-posts" hx-get=(RECENT_PATH) hx-trigger="load, every 10m" {},
+        }
+    }
+}
+
+/// A headed list of posts with when each went out
+pub fn post_list(heading: &str, posts: &[&Post], text: Text) -> Markup {
+    html! {
+        h3 class="h5" { (heading) }
+        @if posts.is_empty() {
+            p class="text-muted" { (text.t("no_posts_yet")) }
+        } @else {
+            ul class="list-unstyled" {
+                @for post in posts {
+                    li {
+                        a href=(share::post_url("", post)) { (post.title) }
+                        br;
+                        small class="text-muted" { (timestamp(&post.timestamp)) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn archive(posts: &[Post], text: Text) -> Markup {
+    let mut months: BTreeMap<String, Vec<&Post>> = BTreeMap::new();
+    for post in posts {
+        months.entry(post.timestamp.format("%Y-%m").to_string()).or_default().push(post);
+    }
+    html! {
+        h3 class="h5" { (text.t("archive")) }
+        ul class="list-unstyled" {
+            @for (month, posts) in months.iter().rev() {
+                li {
+                    details {
+                        summary { (month) " " span class="text-muted" { "(" (posts.len()) ")" } }
+                        ul {
+                            @for post in posts {
+                                li { a href=(share::post_url("", post)) { (post.title) } }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn widgets_list_the_posts_their_own_way() {
+    use crate::i18n::Locales;
+
+    let post = |url_name: &str, day: &str, tags: &[&str]| {
+        let mut post: Post = serde_json::from_value(serde_json::json!({
+            "title": url_name, "body": "", "image_url": "", "summary": "", "timestamp": if day.contains('T') { day.to_string() } else { format!("{}T00:00:00Z", day) }, "tags": tags
+        }))
+        .unwrap();
+        post.url_name = url_name.to_string();
+        post
+    };
+    let posts = vec![post("old", "2023-11-02", &["Rust"]), post("new", "2024-02-01", &["rust", "Web"]), post("mid", "2024-01-15", &["web", "rust"])];
+    assert_eq!(tag_counts(&posts), vec![("Rust".to_string(), 3), ("Web".to_string(), 2)]);
+    assert_eq!(recent(&posts).iter().map(|post| post.url_name.as_str()).collect::<Vec<_>>(), ["new", "mid", "old"]);
+
+    let dir = std::env::temp_dir().join(format!("caden-blog-sidebar-{}", std::process::id()));
+    let reactions = Tally::load(dir.join("reactions.json"));
+    assert!(popular(&posts, &reactions).is_empty());
+
+    let locales = Locales::load(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR));
+    let sidebar = |widgets: &[Widget]| Sidebar { widgets: widgets.to_vec(), ..Sidebar::default() };
+    let html = render_sidebar(&sidebar(&[Widget::Archive, Widget::Tags]), &posts, locales.text("en"), &reactions).into_string();
+    assert!(html.find("2024-02").unwrap() < html.find("2024-01").unwrap() && html.contains("2023-11"), "{}", html);
+    assert!(html.contains(r#"<a href="/search?q=Rust" style="font-size: 1.60em; opacity: 1.00" title="3 posts">Rust</a>"#), "{}", html);
+    assert!(html.contains(r#"style="font-size: 0.85em; opacity: 0.70" title="2 posts">Web</a>"#), "{}", html);
+    assert!(html.contains(r#"<a class="surprise-me" href="/random" rel="nofollow">Surprise me</a>"#), "{}", html);
+    assert!(!render_sidebar(&sidebar(&[Widget::Tags]), &[], locales.text("en"), &reactions).into_string().contains("surprise-me"));
+
+    let counts: Vec<(String, usize)> = [("zig", 9), ("c", 5), ("go", 5), ("ada", 1)].into_iter().map(|(tag, count)| (tag.to_string(), count)).collect();
+    let cloud = tag_cloud(&counts, 3, locales.text("en")).into_string();
+    assert!(!cloud.contains("ada"));
+    assert!(cloud.find(">c<").unwrap() < cloud.find(">go<").unwrap() && cloud.find(">go<").unwrap() < cloud.find(">zig<").unwrap());
+    // Between the two widgets, then before the surprise me link
+    assert_eq!(html.matches("<hr>").count(), 2);
+    assert_eq!(Widget::parse("popular"), Some(Widget::Popular));
+
+    let day = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+    let posts = vec![post("late", "2022-03-01T03:30:00Z", &[]), post("leap", "2020-02-29T12:00:00Z", &[]), post("today", "2023-03-01T12:00:00Z", &[])];
+    let names = |found: Vec<&Post>| found.iter().map(|post| post.url_name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(on_this_day(&posts, day("2023-03-01"), Tz::UTC)), ["late"]);
+    // Still the last day of February in New York
+    assert_eq!(names(on_this_day(&posts, day("2023-02-28"), Tz::America__New_York)), ["late", "leap"]);
+    assert!(on_this_day(&posts, day("2024-02-28"), Tz::UTC).is_empty());
+    let html = on_this_day_widget(&on_this_day(&posts, day("2025-03-01"), Tz::UTC), day("2025-03-01"), locales.text("en")).into_string();
+    assert!(html.contains(r#"<a href="/post/today">today</a> <small class="text-muted">2 years ago</small>"#), "{}", html);
+    assert!(on_this_day_widget(&[], day("2025-03-01"), locales.text("en")).into_string().is_empty());
+    assert_eq!(Widget::parse("weather"), None);
+}
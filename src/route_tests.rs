@@ -0,0 +1,897 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::response::Response;
+use tower::util::ServiceExt;
+
+use crate::assets::FilesystemStore;
+use crate::backup::{BackupConfig, Backups};
+use crate::i18n::Locales;
+use crate::tally::Tally;
+use crate::prefs::{ClientHints, LayoutMode};
+use crate::model::note::{parse_note, Note};
+use crate::model::post::{parse_post, Post};
+use crate::render::listing::{render_posts_fragment, Cards};
+use crate::signed::Key;
+use crate::state::AppState;
+use crate::{build_app, events, icons, Config};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn fixture_posts() -> Vec<Post> {
+    let mut posts: Vec<Post> = std::fs::read_dir(format!("{}/posts", FIXTURES))
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            parse_post(path.file_name().unwrap().to_str().unwrap(), &std::fs::read_to_string(&path).unwrap())
+        })
+        .collect();
+    posts.sort_by(|a, b| a.source_file.cmp(&b.source_file));
+    posts
+}
+
+fn fixture_notes() -> Vec<Note> {
+    let mut notes: Vec<Note> = std::fs::read_dir(format!("{}/notes", FIXTURES))
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            parse_note(path.file_name().unwrap().to_str().unwrap(), &std::fs::read_to_string(&path).unwrap()).unwrap()
+        })
+        .collect();
+    crate::store::notes::sort_notes(&mut notes);
+    notes
+}
+
+/// Resizing the icons is slow in debug builds, so every test shares one set
+fn icons() -> Arc<icons::IconSet> {
+    static ICONS: OnceLock<Arc<icons::IconSet>> = OnceLock::new();
+    ICONS.get_or_init(|| Arc::new(icons::IconSet::from_env(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR), icons::DEFAULT_THEME_COLOR))).clone()
+}
+
+/// The key every test's state signs with, so the cookies and links a test makes verify
+fn key() -> Key {
+    Key::new("route tests")
+}
+
+fn state() -> AppState {
+    AppState {
+        config: Arc::new(Config { addr: String::new(), max_cached_size: 1024, cookie_key: key(), ..Default::default() }),
+        posts: Arc::new(RwLock::new(fixture_posts())),
+        notes: Arc::new(RwLock::new(fixture_notes())),
+        pages: Arc::new(RwLock::new(HashMap::new())),
+        cache: Arc::new(Mutex::new(HashMap::new())),
+        store: Arc::new(FilesystemStore::new(format!("{}/assets", FIXTURES))),
+        missing: Default::default(),
+        hotlinks: Default::default(),
+        icons: icons(),
+        locales: Arc::new(Locales::load(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR))),
+        publisher: events::publisher(),
+        // Only read by these GET requests, so nothing is ever written to them
+        reactions: Arc::new(Tally::load("tests/fixtures/state/reactions.json")),
+        polls: Arc::new(Tally::load("tests/fixtures/state/polls.json")),
+        comments: Arc::new(crate::comments::Comments::load("tests/fixtures/state/comments.json")),
+        access: Default::default(),
+        bots: Default::default(),
+        metrics: Default::default(),
+        maintenance: Default::default(),
+        backups: Arc::new(Backups::new(BackupConfig { content: Config::default().content_dir, dir: std::env::temp_dir().join("caden-blog-route-tests"), interval: None, keep: 1, s3: None })),
+        feeds: Default::default(),
+        worker: Default::default(),
+        manifest: Default::default(),
+        suggestions: Default::default(),
+        search: Arc::new(crate::search::Scan),
+        related: Default::default(),
+        links: Default::default(),
+        spam: Arc::new(crate::spam::Heuristic::default()),
+        notifier: Default::default(),
+        previews: Arc::new(crate::previews::Previews::load("/dev/null")),
+    }
+}
+
+async fn send(request: Request<Body>) -> Response {
+    build_app(&state()).oneshot(request).await.unwrap()
+}
+
+async fn get(uri: &str) -> Response {
+    send(Request::builder().uri(uri).body(Body::empty()).unwrap()).await
+}
+
+async fn body(response: Response) -> String {
+    String::from_utf8(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec()).unwrap()
+}
+
+fn header_value<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response.headers().get(name).and_then(|value| value.to_str().ok())
+}
+
+#[tokio::test]
+async fn home_lists_posts_in_the_readers_language() {
+    let response = get("/").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let html = body(response).await;
+    assert!(html.contains(r#"<html lang="en">"#));
+    assert!(html.contains("Hello World") && html.contains("Second Post"));
+    assert!(!html.contains("Hola Mundo"));
+    // The fixture summary is kept, the missing one is generated from the body
+    assert!(html.contains("The second fixture") && html.contains("The first post."));
+    assert!(html.contains(r#"alt="Some notes""#));
+
+    let spanish = send(Request::builder().uri("/").header(header::ACCEPT_LANGUAGE, "es-ES,es;q=0.9").body(Body::empty()).unwrap()).await;
+    let html = body(spanish).await;
+    assert!(html.contains(r#"<html lang="es">"#));
+    assert!(html.contains("Hola Mundo") && !html.contains("Hello World"));
+}
+
+#[tokio::test]
+async fn post_pages_pick_the_requested_translation() {
+    let response = get("/post/hello-world").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let html = body(response).await;
+    assert!(html.contains("<h2>Hello World</h2>"));
+    assert!(html.contains(r#"hreflang="es""#));
+
+    let html = body(get("/post/hello-world?lang=es").await).await;
+    assert!(html.contains("<h2>Hola Mundo</h2>"));
+
+    let plain = body(get("/post/hello-world/plain").await).await;
+    assert!(plain.contains("<p>The <strong>first</strong> post.</p>"));
+    assert!(!plain.contains("<script"));
+}
+
+#[tokio::test]
+async fn unlisted_posts_are_only_served_at_their_url_and_private_ones_not_at_all() {
+    let html = body(get("/post/unlisted-notes").await).await;
+    assert!(html.contains("<h2>Unlisted Notes</h2>") && html.contains(r#"<meta name="robots" content="noindex">"#));
+    for uri in ["/", "/search?q=notes", "/search/suggest?q=notes", "/sitemap.xml", "/feed.xml"] {
+        let page = body(get(uri).await).await;
+        assert!(!page.contains("Unlisted Notes") && !page.contains("-notes</loc>"), "{}", uri);
+        assert!(!page.contains("Private Notes"), "{}", uri);
+    }
+    for uri in ["/post/private-notes", "/post/private-notes/plain", "/fragment/card/private-notes", "/og/private-notes.png"] {
+        assert_eq!(get(uri).await.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn preview_links_show_one_private_post_until_they_expire() {
+    let token = crate::extract::preview::token(&key(), "private-notes", chrono::Utc::now() + chrono::Duration::hours(1));
+    let response = get(&format!("/post/private-notes?token={}", token)).await;
+    // Kept out of the service worker's cache, which would otherwise still show it once the link expires
+    assert_eq!(header_value(&response, "cache-control"), Some("private, no-store"));
+    let html = body(response).await;
+    assert!(html.contains("<h2>Private Notes</h2>") && html.contains(r#"<meta name="referrer" content="no-referrer">"#));
+    let plain = get(&format!("/post/private-notes/plain?token={}", token)).await;
+    assert_eq!(plain.status(), StatusCode::OK);
+    assert_eq!(header_value(&plain, "cache-control"), Some("private, no-store"));
+    assert_eq!(header_value(&get("/post/hello-world").await, "cache-control"), None);
+
+    let expired = crate::extract::preview::token(&key(), "private-notes", chrono::Utc::now() - chrono::Duration::hours(1));
+    assert_eq!(get(&format!("/post/private-notes?token={}", expired)).await.status(), StatusCode::NOT_FOUND);
+    let other = crate::extract::preview::token(&key(), "second-post", chrono::Utc::now() + chrono::Duration::hours(1));
+    assert_eq!(get(&format!("/post/private-notes?token={}", other)).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn comments_are_threaded_with_inline_reply_forms() {
+    let html = body(get("/post/hello-world").await).await;
+    assert!(html.contains("2 comments"), "{}", html);
+    let reply = html.find(r#"id="comment-2""#).unwrap();
+    assert!(html.find(r#"id="comment-1""#).unwrap() < reply && html[..reply].contains("<details"));
+    assert!(html.contains("Thanks &lt;3") && !html.contains("Another comment."));
+
+    let form = send(Request::builder().uri("/post/hello-world/comments/1/reply").header("HX-Request", "true").body(Body::empty()).unwrap()).await;
+    assert!(body(form).await.contains(r#"<input type="hidden" name="parent" value="1">"#));
+    let redirect = get("/post/hello-world/comments/1/reply").await;
+    assert_eq!(redirect.status(), StatusCode::SEE_OTHER);
+    assert_eq!(header_value(&redirect, "location"), Some("/post/hello-world#comment-1"));
+    assert_eq!(get("/post/hello-world/comments/3/reply").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn spam_comments_are_turned_away() {
+    let spam = Request::builder()
+        .method("POST")
+        .uri("/post/hello-world/comments")
+        .header("content-type", "application/x-www-form-urlencoded")
+        .body(Body::from("author=Bot&body=Cheap+seo+services+at+http%3A%2F%2Fa.example+http%3A%2F%2Fb.example+http%3A%2F%2Fc.example"))
+        .unwrap();
+    assert_eq!(send(spam).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(!body(get("/post/hello-world").await).await.contains("Cheap seo"));
+}
+
+#[tokio::test]
+async fn closed_posts_show_their_comments_without_forms() {
+    let closed = body(get("/post/unlisted-notes").await).await;
+    assert!(closed.contains("Comments are closed.") && !closed.contains("hx-post"), "{}", closed);
+    let comment = |uri: &str| Request::builder().method("POST").uri(uri).header("content-type", "application/x-www-form-urlencoded").body(Body::from("author=Ann&body=Hi")).unwrap();
+    assert_eq!(send(comment("/post/unlisted-notes/comments")).await.status(), StatusCode::FORBIDDEN);
+
+    // Locking comments closes every post, leaving what's there to read
+    let state = state();
+    state.comments.set_locked(true);
+    let response = build_app(&state).oneshot(Request::builder().uri("/post/hello-world").body(Body::empty()).unwrap()).await.unwrap();
+    let locked = body(response).await;
+    assert!(locked.contains("Great first post!") && locked.contains("Comments are closed.") && !locked.contains("/comments/1/reply"), "{}", locked);
+    let response = build_app(&state).oneshot(comment("/post/hello-world/comments")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn new_comments_stream_to_the_posts_open_pages() {
+    use tokio_stream::StreamExt;
+
+    let dir = std::env::temp_dir().join(format!("caden-blog-live-comments-{}", std::process::id()));
+    let mut state = state();
+    state.comments = Arc::new(crate::comments::Comments::load(dir.join("comments.json")));
+    let app = build_app(&state);
+    assert!(body(app.clone().oneshot(Request::builder().uri("/post/hello-world").body(Body::empty()).unwrap()).await.unwrap()).await.contains(r#"data-events="/post/hello-world/comments/events""#));
+    assert!(!body(get("/post/unlisted-notes").await).await.contains("data-events"));
+    assert_eq!(get("/post/private-notes/comments/events").await.status(), StatusCode::NOT_FOUND);
+
+    let events = |url_name: &str| app.clone().oneshot(Request::builder().uri(format!("/post/{}/comments/events", url_name)).body(Body::empty()).unwrap());
+    let (mut hello, mut second) = (events("hello-world").await.unwrap().into_body().into_data_stream(), events("second-post").await.unwrap().into_body().into_data_stream());
+    let comment = Request::builder().method("POST").uri("/post/hello-world/comments").header("content-type", "application/x-www-form-urlencoded").body(Body::from("author=Ann&body=Live")).unwrap();
+    assert_eq!(app.clone().oneshot(comment).await.unwrap().status(), StatusCode::SEE_OTHER);
+
+    let event = tokio::time::timeout(std::time::Duration::from_secs(5), hello.next()).await.unwrap().unwrap().unwrap();
+    let event = String::from_utf8(event.to_vec()).unwrap();
+    assert!(event.starts_with("event: comment_added\n") && event.contains("id: 1\n") && event.contains("<strong class=\"text-reset ms-2\">Ann</strong>"), "{}", event);
+    assert!(!event.contains("hx-delete"));
+    assert!(tokio::time::timeout(std::time::Duration::from_millis(100), second.next()).await.is_err());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn reactions_only_reach_visible_posts_and_their_cached_cards() {
+    let dir = std::env::temp_dir().join(format!("caden-blog-route-reactions-{}", std::process::id()));
+    let mut state = state();
+    state.reactions = Arc::new(Tally::load(dir.join("reactions.json")));
+    let app = build_app(&state);
+    let react = |url_name: &str| {
+        let request = Request::builder().method("POST").uri(format!("/post/{}/react", url_name)).header("content-type", "application/x-www-form-urlencoded");
+        app.clone().oneshot(request.body(Body::from("kind=clap")).unwrap())
+    };
+    assert_eq!(react("private-notes").await.unwrap().status(), StatusCode::NOT_FOUND);
+    assert_eq!(state.reactions.counts("private-notes").get("clap"), None);
+
+    for (key, url_name) in [("/?lang=en", "hello-world"), ("/?lang=es", "second-post")] {
+        state.pages.write().unwrap().insert(key.to_string(), format!(r#"<article data-post="{}"></article>"#, url_name));
+    }
+    assert_eq!(react("hello-world").await.unwrap().status(), StatusCode::SEE_OTHER);
+    assert_eq!(state.reactions.counts("hello-world").get("clap"), Some(&1));
+    assert_eq!(state.pages.read().unwrap().keys().collect::<Vec<_>>(), ["/?lang=es"]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn moderation_links_lead_to_a_delete_button() {
+    let link = crate::comments::moderation_url(&key(), "", 1, chrono::Utc::now());
+    let html = body(get(&link).await).await;
+    assert!(html.contains("Great first post!") && html.contains(r#"<form method="post">"#), "{}", html);
+    // A link only works for its own comment and until it expires
+    assert_eq!(get(&link.replace("/comments/1?", "/comments/2?")).await.status(), StatusCode::NOT_FOUND);
+    let expired = crate::comments::moderation_url(&key(), "", 1, chrono::Utc::now() - chrono::Duration::days(31));
+    assert_eq!(get(&expired).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(get("/admin/comments/1").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn only_authors_change_comments_and_only_for_a_while() {
+    let change = |method: &str, id: u64, cookie: Option<String>| {
+        let mut request = Request::builder().method(method).uri(format!("/comments/{}", id)).header("content-type", "application/x-www-form-urlencoded");
+        if let Some(cookie) = cookie {
+            request = request.header("cookie", cookie);
+        }
+        send(request.body(Body::from("body=Changed")).unwrap())
+    };
+    assert_eq!(change("PATCH", 1, None).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(change("DELETE", 1, None).await.status(), StatusCode::FORBIDDEN);
+    // The author's cookie doesn't help once the edit window has passed
+    let cookie = key().list_cookie("comments", &["1".to_string()], 20);
+    let cookie = cookie.split(';').next().map(String::from);
+    assert_eq!(change("PATCH", 1, cookie.clone()).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(change("DELETE", 99, cookie).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn commenters_get_avatars_made_here() {
+    let html = body(get("/post/hello-world").await).await;
+    let avatar = format!("/avatar/{}.svg", crate::comments::email_hash("ann@example.com"));
+    assert!(html.contains(&format!(r#"src="{}""#, avatar)), "{}", html);
+    // Caden left no email, so gets initials instead
+    assert!(html.contains(r#"aria-hidden="true">C</span>"#));
+
+    let response = get(&avatar).await;
+    assert_eq!(header_value(&response, "content-type"), Some("image/svg+xml"));
+    assert!(body(response).await.starts_with("<svg"));
+    assert_eq!(get("/avatar/not-a-hash.svg").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_link_graph_is_served_as_json_and_a_page() {
+    let response = get("/api/graph").await;
+    assert_eq!(header_value(&response, "content-type"), Some("application/json"));
+    let graph: serde_json::Value = serde_json::from_str(&body(response).await).unwrap();
+    let ids: Vec<&str> = graph["nodes"].as_array().unwrap().iter().map(|node| node["id"].as_str().unwrap()).collect();
+    assert!(ids.contains(&"hello-world") && !ids.contains(&"unlisted-notes") && !ids.contains(&"private-notes"), "{:?}", ids);
+    assert_eq!(graph["edges"], serde_json::json!([{"source": "second-post", "target": "hello-world", "kind": "link"}]));
+
+    let page = body(get("/graph").await).await;
+    assert!(page.contains(r#"data-src="/api/graph?lang=en""#) && page.contains(r#"<a href="/post/second-post">Second Post</a>"#), "{}", page);
+}
+
+#[tokio::test]
+async fn missing_posts_are_not_found() {
+    for uri in ["/post/nope", "/post/nope/plain", "/fragment/card/nope", "/asset/nope.txt", "/layout/masonry"] {
+        assert_eq!(get(uri).await.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+    let html = body(get("/post/nope").await).await;
+    assert!(html.contains(r#"href="/""#));
+}
+
+#[tokio::test]
+async fn maintenance_mode_keeps_health_and_admin_up() {
+    let state = state();
+    state.maintenance.set(true);
+    let app = build_app(&state);
+
+    let home = app.clone().oneshot(Request::builder().uri("/").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(home.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(header_value(&home, "retry-after"), Some("600"));
+    assert!(body(home).await.contains("Back soon"));
+
+    let health = app.clone().oneshot(Request::builder().uri("/health").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(health.status(), StatusCode::OK);
+    // No admin token is configured in the tests, so the admin routes answer as if they weren't there
+    let admin = app.oneshot(Request::builder().uri("/admin/maintenance").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(admin.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn fragments_are_bare_for_htmx_and_unpoly_only() {
+    let direct = get("/posts").await;
+    assert_eq!(direct.status(), StatusCode::OK);
+    assert_eq!(header_value(&direct, "vary"), Some("HX-Request, X-Up-Target"));
+    let html = body(direct).await;
+    assert!(html.starts_with("<!DOCTYPE html>") && html.contains(r#"id="posts""#));
+
+    for (name, value) in [("hx-request", "true"), ("x-up-target", "#posts")] {
+        let html = body(send(Request::builder().uri("/posts").header(name, value).body(Body::empty()).unwrap()).await).await;
+        assert!(html.starts_with(r#"<div id="posts""#) && html.contains("Hello World"), "{}", html);
+    }
+
+    let card = get("/fragment/card/hello-world").await;
+    assert_eq!(card.status(), StatusCode::SEE_OTHER);
+    assert_eq!(header_value(&card, "location"), Some("/post/hello-world"));
+    let card = send(Request::builder().uri("/fragment/card/hello-world").header("hx-request", "true").body(Body::empty()).unwrap()).await;
+    assert!(body(card).await.contains(r#"data-post="hello-world""#));
+}
+
+#[tokio::test]
+async fn search_highlights_matches() {
+    let html = body(get("/search?q=second").await).await;
+    assert!(html.starts_with("<!DOCTYPE html>") && html.contains(r#"value="second""#));
+    assert!(html.contains("<mark>Second</mark> Post"), "{}", html);
+    assert!(!html.contains(r#"data-post="hello-world""#));
+
+    let fragment = body(send(Request::builder().uri("/search?q=nothing+like+this").header("hx-request", "true").body(Body::empty()).unwrap()).await).await;
+    assert!(fragment.starts_with(r#"<div id="search-results">"#) && fragment.contains("No posts match your search."), "{}", fragment);
+}
+
+#[tokio::test]
+async fn suggestions_come_as_json_or_a_fragment() {
+    let json = body(get("/search/suggest?q=sec").await).await;
+    let suggestions: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(suggestions[0]["label"], "Second Post");
+    assert_eq!(suggestions[0]["kind"], "post");
+
+    let html = body(send(Request::builder().uri("/search/suggest?q=hello").header("hx-request", "true").body(Body::empty()).unwrap()).await).await;
+    assert!(html.contains(r#"href="/post/hello-world""#) && html.contains("Hello World"), "{}", html);
+    assert_eq!(body(get("/search/suggest?q=").await).await, "[]");
+}
+
+#[tokio::test]
+async fn feeds_are_generated_once_and_revalidated() {
+    let state = state();
+    let app = build_app(&state);
+    let feed = |headers: &[(header::HeaderName, &str)]| {
+        let mut request = Request::builder().uri("/feed.xml").header(header::HOST, "blog.example");
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    let response = feed(&[]).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header_value(&response, "content-type"), Some("application/atom+xml; charset=utf-8"));
+    let etag = header_value(&response, "etag").unwrap().to_string();
+    let last_modified = header_value(&response, "last-modified").unwrap().to_string();
+    let xml = body(response).await;
+    assert!(xml.contains("<link href=\"http://blog.example/post/hello-world\"/>"), "{}", xml);
+    assert!(!xml.contains("Hola Mundo"));
+
+    assert_eq!(feed(&[(header::IF_NONE_MATCH, &etag)]).await.unwrap().status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(feed(&[(header::IF_MODIFIED_SINCE, &last_modified)]).await.unwrap().status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(feed(&[(header::IF_NONE_MATCH, "\"stale\"")]).await.unwrap().status(), StatusCode::OK);
+
+    let sitemap = body(get("/sitemap.xml").await).await;
+    assert!(sitemap.contains("/post/hello-world?lang=es</loc>"), "{}", sitemap);
+    assert!(body(get("/rss.xml").await).await.contains("<rss version=\"2.0\">"));
+}
+
+#[tokio::test]
+async fn notes_have_their_own_timeline_and_show_up_in_the_feeds() {
+    let page = body(get("/notes").await).await;
+    let (newer, older) = (page.find(r#"id="dark-mode""#).unwrap(), page.find(r#"id="v2""#).unwrap());
+    assert!(newer < older, "{}", page);
+    assert!(page.contains("<h3 class=\"h5\">Version 2</h3>") && page.contains("<strong>on</strong>"), "{}", page);
+
+    let request = Request::builder().uri("/feed.xml").header(header::HOST, "blog.example").body(Body::empty()).unwrap();
+    let xml = body(send(request).await).await;
+    assert!(xml.contains("<title>Dark mode is on for everyone now.</title>\n<id>http://blog.example/notes#dark-mode</id>"), "{}", xml);
+    assert!(xml.contains("<category term=\"note\"/>\n<category term=\"meta\"/>"), "{}", xml);
+    assert!(body(get("/rss.xml").await).await.contains("<category>note</category>"));
+    assert!(body(get("/sitemap.xml").await).await.contains("/notes</loc>"));
+}
+
+#[tokio::test]
+async fn the_projects_page_is_empty_without_a_projects_file() {
+    let response = get("/projects").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(body(response).await.contains("<p>No projects yet.</p>"));
+}
+
+#[tokio::test]
+async fn thumbnails_come_in_a_few_widths_and_fall_back_to_the_asset() {
+    let response = get("/thumb/240/photo.png").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header_value(&response, "content-type"), Some("image/jpeg"));
+    let thumbnail = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(image::load_from_memory(&thumbnail).unwrap().width(), 64);
+
+    assert_eq!(get("/thumb/100/photo.png").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(get("/thumb/240/missing.png").await.status(), StatusCode::NOT_FOUND);
+    let response = get("/thumb/240/notes.txt").await;
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(header_value(&response, "location"), Some("/asset/notes.txt"));
+}
+
+#[tokio::test]
+async fn episodes_get_a_player_and_an_enclosure() {
+    let page = body(get("/post/second-post").await).await;
+    assert!(page.contains(r#"<audio class="w-100" controls preload="metadata"><source src="/asset/episode.mp3" type="audio/mpeg"></audio>"#), "{}", page);
+
+    let request = Request::builder().uri("/rss.xml").header(header::HOST, "blog.example").body(Body::empty()).unwrap();
+    let rss = body(send(request).await).await;
+    assert!(rss.contains(r#"<enclosure url="http://blog.example/asset/episode.mp3" length="4096" type="audio/mpeg"/>"#), "{}", rss);
+    assert!(body(get("/feed.xml").await).await.contains(r#"<link rel="enclosure" href="http://localhost/asset/episode.mp3" length="4096" type="audio/mpeg"/>"#));
+
+    let response = send(Request::builder().uri("/asset/episode.mp3").header(header::RANGE, "bytes=0-2").body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(header_value(&response, "content-type"), Some("audio/mpeg"));
+    assert_eq!(body(response).await, "ID3");
+}
+
+#[tokio::test]
+async fn videos_stream_in_a_native_player_with_a_poster() {
+    let page = body(get("/post/unlisted-notes").await).await;
+    assert!(page.contains(r#"<video class="w-100 h-auto rounded" controls preload="metadata" playsinline poster="/asset/photo.png"><source src="/asset/clip.webm" type="video/webm">"#), "{}", page);
+    assert!(body(get("/post/unlisted-notes/plain").await).await.contains(r#"<source src="/asset/clip.webm""#));
+
+    let response = send(Request::builder().uri("/asset/clip.webm").header(header::RANGE, "bytes=-4").body(Body::empty()).unwrap()).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(header_value(&response, "content-type"), Some("video/webm"));
+    assert_eq!(header_value(&response, "content-range"), Some("bytes 2048-2051/2052"));
+}
+
+#[tokio::test]
+async fn attachments_are_listed_and_sent_as_downloads() {
+    let page = body(get("/post/second-post").await).await;
+    assert!(page.contains(r#"<a href="/post/second-post/attachments/slides.txt" download>slides.txt</a><small class="text-muted"> · text/plain"#), "{}", page);
+
+    let response = get("/post/second-post/attachments/slides.txt").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header_value(&response, "content-disposition"), Some(r#"attachment; filename="slides.txt"; filename*=UTF-8''slides.txt"#));
+    assert_eq!(header_value(&response, "content-type"), Some("text/plain; charset=utf-8"));
+    assert_eq!(body(response).await, "Slide one\nSlide two\n");
+
+    // Listed but not uploaded, uploaded but not listed, and another post's files
+    assert_eq!(get("/post/second-post/attachments/missing.zip").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(get("/post/second-post/attachments/notes.txt").await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(get("/post/hello-world/attachments/slides.txt").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn missing_assets_are_remembered_and_counted() {
+    let state = state();
+    let app = build_app(&state);
+    for uri in ["/asset/nope.png", "/asset/nope.png", "/asset/notes.txt", "/asset/notes.txt"] {
+        app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+    }
+    assert!(state.missing.is_missing("nope.png"));
+    let metrics = state.metrics.render();
+    for result in ["hit", "negative"] {
+        assert!(metrics.contains(&format!("caden_blog_asset_cache_total{{result=\"{}\"}} 1", result)), "{}", metrics);
+    }
+    assert!(metrics.contains("caden_blog_asset_cache_total{result=\"miss\"} 2"), "{}", metrics);
+}
+
+#[tokio::test]
+async fn assets_can_live_in_directories() {
+    let response = get("/asset/second-post/slides.txt").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header_value(&response, "cache-control"), Some("public, max-age=3600"));
+    assert_eq!(body(response).await, "Slide one\nSlide two\n");
+
+    for uri in ["/asset/second-post/..%2F..%2Fposts%2Fsecond-post.json", "/asset/second-post/", "/asset/%2Fetc%2Fpasswd"] {
+        assert_eq!(get(uri).await.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+}
+
+#[tokio::test]
+async fn images_embedded_by_other_sites_get_a_placeholder() {
+    let mut state = state();
+    state.hotlinks = Arc::new(crate::hotlink::Hotlinks::new(vec!["friend.example".to_string()], crate::hotlink::Refusal::Placeholder));
+    let app = build_app(&state);
+    let request = |uri: &str, referrer: &str| Request::builder().uri(uri).header(header::HOST, "blog.example").header(header::REFERER, referrer).body(Body::empty()).unwrap();
+
+    let embedded = app.clone().oneshot(request("/asset/photo.png", "https://evil.example/page")).await.unwrap();
+    assert_eq!(embedded.status(), StatusCode::OK);
+    assert_eq!(header_value(&embedded, "content-type"), Some("image/svg+xml"));
+    assert!(body(embedded).await.contains("Image hosted at"));
+    let thumbnail = app.clone().oneshot(request("/thumb/240/photo.png", "https://evil.example/page")).await.unwrap();
+    assert_eq!(header_value(&thumbnail, "content-type"), Some("image/svg+xml"));
+
+    for referrer in ["http://blog.example/post/hello-world", "https://friend.example/"] {
+        let allowed = app.clone().oneshot(request("/asset/photo.png", referrer)).await.unwrap();
+        assert_eq!(header_value(&allowed, "content-type"), Some("image/png"), "{}", referrer);
+        assert_eq!(header_value(&allowed, "vary"), Some("Accept, Referer"));
+    }
+    // Only images are protected
+    let text = app.oneshot(request("/asset/notes.txt", "https://evil.example/page")).await.unwrap();
+    assert_eq!(header_value(&text, "content-type"), Some("text/plain; charset=utf-8"));
+}
+
+#[tokio::test]
+async fn photos_come_as_webp_to_browsers_that_take_it() {
+    let request = |uri: &str, accept: &str| send(Request::builder().uri(uri).header(header::ACCEPT, accept).body(Body::empty()).unwrap());
+
+    let webp = request("/asset/photo.png", "image/avif,image/webp,*/*;q=0.8").await;
+    assert_eq!(header_value(&webp, "content-type"), Some("image/webp"));
+    assert_eq!(header_value(&webp, "vary"), Some("Accept"));
+    assert!(axum::body::to_bytes(webp.into_body(), usize::MAX).await.unwrap().starts_with(b"RIFF"));
+
+    let png = request("/asset/photo.png", "image/*").await;
+    assert_eq!(header_value(&png, "content-type"), Some("image/png"));
+    assert_eq!(header_value(&png, "vary"), Some("Accept"));
+    assert_eq!(header_value(&request("/asset/notes.txt", "image/webp").await, "vary"), None);
+}
+
+#[tokio::test]
+async fn code_blocks_get_a_copy_button_script() {
+    let response = get("/theme/code.js").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header_value(&response, "content-type"), Some("application/javascript"));
+    assert!(body(response).await.contains("navigator.clipboard.writeText"));
+
+    for (uri, copy) in [("/post/hello-world", "Copy"), ("/post/hello-world?lang=es", "Copiar")] {
+        let html = body(get(uri).await).await;
+        assert!(html.contains("pre.code-block"), "{}", uri);
+        assert!(html.contains(&format!(r#"<script src="/theme/code.js" defer data-copy="{}""#, copy)), "{}", uri);
+    }
+    // Reader mode labels code blocks but stays without scripts
+    let plain = body(get("/post/hello-world/plain").await).await;
+    assert!(plain.contains(".code-title") && !plain.contains("/theme/code.js"));
+}
+
+#[tokio::test]
+async fn outbound_clicks_are_counted_for_signed_links_only() {
+    let state = state();
+    let app = build_app(&state);
+    let url = "https://example.com/a?b=c";
+    let out = |url: &str, sig: &str| Request::builder().uri(format!("/out?url={}&sig={}", crate::share::encode(url), sig)).body(Body::empty()).unwrap();
+
+    let response = app.clone().oneshot(out(url, &key().signature_of(url))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::FOUND);
+    assert_eq!(header_value(&response, "location"), Some(url));
+    assert_eq!(header_value(&response, "referrer-policy"), Some("no-referrer"));
+
+    let forged = app.clone().oneshot(out("https://evil.example/", &key().signature_of(url))).await.unwrap();
+    assert_eq!(forged.status(), StatusCode::BAD_REQUEST);
+    let local = "/post/hello-world";
+    assert_eq!(app.clone().oneshot(out(local, &key().signature_of(local))).await.unwrap().status(), StatusCode::BAD_REQUEST);
+    assert!(state.metrics.render().contains("caden_blog_outbound_clicks_total{host=\"example.com\"} 1"));
+}
+
+#[tokio::test]
+async fn accent_colors_tint_cards_and_heroes_top_post_pages() {
+    let home = body(get("/").await).await;
+    assert!(home.contains(r#"class="card post-card accented" data-post="second-post" style="--accent: #e06c75""#), "{}", home);
+    assert!(home.contains(r#"class="card post-card" data-post="hello-world">"#));
+
+    let html = body(get("/post/second-post").await).await;
+    assert!(html.contains(r#"<div class="post-hero" style="--accent: #e06c75"><img src="/asset/photo.png" alt=""></div>"#), "{}", html);
+    assert!(html.contains(r#"<article style="--accent: #e06c75"><h2>Second Post</h2>"#));
+    assert!(!body(get("/post/hello-world").await).await.contains(r#"class="post-hero"#));
+}
+
+#[tokio::test]
+async fn video_covers_loop_unless_the_reader_saves_data() {
+    let card = |save_data: bool| {
+        let mut request = Request::builder().uri("/fragment/card/unlisted-notes").header("hx-request", "true");
+        if save_data {
+            request = request.header("save-data", "on");
+        }
+        request.body(Body::empty()).unwrap()
+    };
+    let looping = body(send(card(false)).await).await;
+    assert!(looping.contains(r#"<video src="/asset/clip.webm" class="card-img-top" poster="/asset/photo.png" aria-label="Unlisted Notes" autoplay muted loop playsinline"#), "{}", looping);
+    let still = body(send(card(true)).await).await;
+    assert!(still.contains(r#"<img src="/asset/photo.png" class="card-img-top""#) && !still.contains("<video"), "{}", still);
+
+    let page = body(get("/post/unlisted-notes").await).await;
+    assert!(page.contains(r#"<div class="post-hero"><video src="/asset/clip.webm" aria-hidden="true" autoplay muted loop playsinline></video></div>"#), "{}", page);
+    assert!(page.contains(r#"<meta property="og:image" content="/asset/photo.png">"#));
+    let saving = body(send(Request::builder().uri("/post/unlisted-notes").header("save-data", "on").body(Body::empty()).unwrap()).await).await;
+    assert!(!saving.contains(r#"class="post-hero""#));
+}
+
+#[tokio::test]
+async fn pages_are_lighter_and_stiller_when_the_browser_asks() {
+    let app = build_app(&state());
+    let home = |hint: Option<(&str, &str)>| {
+        let mut request = Request::builder().uri("/");
+        if let Some((name, value)) = hint {
+            request = request.header(name, value);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+    let full = home(None).await.unwrap();
+    assert_eq!(header_value(&full, "accept-ch"), Some("Sec-CH-Prefers-Reduced-Motion"));
+    assert!(header_value(&full, "vary").is_some_and(|vary| vary.contains("Save-Data")));
+    let full = body(full).await;
+    assert!(full.contains("external-content.duckduckgo.com") && !full.contains("transition: none"));
+
+    // Cached separately from the full page rendered just before
+    let lite = body(home(Some(("save-data", "on"))).await.unwrap()).await;
+    assert!(!lite.contains("external-content.duckduckgo.com"));
+    let still = body(home(Some(("sec-ch-prefers-reduced-motion", "\"reduce\""))).await.unwrap()).await;
+    assert!(still.contains("external-content.duckduckgo.com") && still.contains("transition: none !important"));
+
+    let post = body(send(Request::builder().uri("/post/second-post").header("save-data", "on").body(Body::empty()).unwrap()).await).await;
+    assert!(post.contains(r#"<audio class="w-100" controls preload="none">"#), "{}", post);
+    assert!(header_value(&get("/asset/photo.png").await, "accept-ch").is_none());
+}
+
+#[tokio::test]
+async fn background_speed_is_remembered_per_reader() {
+    let response = get("/background/off").await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert!(header_value(&response, "set-cookie").is_some_and(|cookie| cookie.starts_with("background_speed=off;")));
+    assert_eq!(header_value(&response, "location"), Some("/"));
+    assert_eq!(get("/background/warp").await.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn tags_are_sized_by_how_many_posts_have_them() {
+    let html = body(get("/tags").await).await;
+    assert!(html.contains(r#"<a href="/search?q=Rust" style="font-size: 1.23em; opacity: 0.85" title="1 post">Rust</a>"#), "{}", html);
+    assert!(html.find(">Rust<").unwrap() < html.find(">Testing<").unwrap());
+    assert!(body(get("/").await).await.contains(r#"<ul class="list-inline tag-cloud">"#));
+}
+
+#[tokio::test]
+async fn recent_posts_are_a_fragment_shared_by_post_and_not_found_pages() {
+    let recent = body(get("/fragments/recent").await).await;
+    assert!(recent.starts_with(r#"<h3 class="h5">Recent posts</h3>"#), "{}", recent);
+    assert!(recent.find("Second Post").unwrap() < recent.find("Hello World").unwrap());
+    assert!(!recent.contains("Unlisted Notes"));
+
+    let post = body(get("/post/hello-world").await).await;
+    assert!(post.contains(r#"<div class="recent-posts" hx-get="/fragments/recent" hx-trigger="load, every 10m"></div>"#), "{}", post);
+    let response = get("/post/nope").await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(body(response).await.contains(r#"<a href="/post/second-post">Second Post</a>"#));
+}
+
+#[tokio::test]
+async fn posts_from_this_day_in_earlier_years_load_into_the_sidebar() {
+    let response = get("/fragments/on-this-day").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header_value(&response, "vary"), Some("Accept-Language"));
+    // Which fixtures turn up depends on the date the tests run
+    let html = body(response).await;
+    assert!(html.is_empty() || html.starts_with(r#"<h3 class="h5">On this day</h3>"#), "{}", html);
+}
+
+#[tokio::test]
+async fn random_sends_readers_to_a_public_post() {
+    for _ in 0..20 {
+        let response = get("/random").await;
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(header_value(&response, "cache-control"), Some("no-store"));
+        let location = header_value(&response, "location").unwrap();
+        assert!(["/post/hello-world", "/post/second-post"].contains(&location), "{}", location);
+    }
+    assert!(body(get("/").await).await.contains(r#"href="/random""#));
+}
+
+#[tokio::test]
+async fn the_archive_is_a_heatmap_linking_to_each_days_posts() {
+    let archive = body(get("/archive").await).await;
+    assert!(archive.contains("Posts in 2024"), "{}", archive);
+    assert!(archive.contains(r#"<a href="/archive?day=2024-11-10">"#), "{}", archive);
+    assert!(archive.contains(r#"<a href="/archive?day=2024-12-01">"#));
+    assert!(!archive.contains("Second Post"));
+
+    let day = body(get("/archive?day=2024-12-01").await).await;
+    assert!(day.contains(r#"<a href="/post/second-post">Second Post</a>"#), "{}", day);
+    assert!(!day.contains("Hello World"));
+    // Still the 10th in UTC, but already the 11th in Tokyo
+    let tokyo = body(get("/archive?day=2024-11-11&tz=Asia/Tokyo").await).await;
+    assert!(tokyo.contains(r#"<a href="/post/hello-world">Hello World</a>"#), "{}", tokyo);
+    assert!(body(get("/archive?year=2023").await).await.contains("Posts in 2023"));
+}
+
+#[tokio::test]
+async fn stats_sum_up_the_public_posts() {
+    let stats = body(get("/stats").await).await;
+    assert!(stats.contains(r#"<dt class="col-sm-4">Posts</dt><dd class="col-sm-8">2</dd>"#), "{}", stats);
+    assert!(stats.contains(r#"<a class="text-muted small" href="/archive?day=2024-11-10">2024-11-10</a>"#), "{}", stats);
+    assert!(stats.contains("<title>1 post in 2024-12</title>"));
+    assert!(stats.contains(r#"<a href="/search?q=Testing">Testing</a>"#));
+    assert!(body(send(Request::builder().uri("/stats").header(header::ACCEPT_LANGUAGE, "es").body(Body::empty()).unwrap()).await).await.contains("Racha más larga"));
+}
+
+#[tokio::test]
+async fn assets_are_served_whole_or_by_range_and_cached_by_browsers() {
+    let response = get("/asset/notes.txt").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header_value(&response, "cache-control"), Some("public, max-age=3600"));
+    assert_eq!(header_value(&response, "accept-ranges"), Some("bytes"));
+    assert_eq!(body(response).await, "0123456789");
+
+    let ranged = send(Request::builder().uri("/asset/notes.txt").header(header::RANGE, "bytes=2-4").body(Body::empty()).unwrap()).await;
+    assert_eq!(ranged.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(header_value(&ranged, "content-range"), Some("bytes 2-4/10"));
+    assert_eq!(body(ranged).await, "234");
+
+    let unsatisfiable = send(Request::builder().uri("/asset/notes.txt").header(header::RANGE, "bytes=20-30").body(Body::empty()).unwrap()).await;
+    assert_eq!(unsatisfiable.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+    let favicon = get("/favicon.ico").await;
+    assert_eq!(favicon.status(), StatusCode::OK);
+    assert_eq!(header_value(&favicon, "cache-control"), Some("public, max-age=3600"));
+}
+
+#[tokio::test]
+async fn only_fingerprinted_names_are_cached_for_good() {
+    let mut state = state();
+    state.config = Arc::new(Config { content_dir: FIXTURES.into(), ..(*state.config).clone() });
+    state.refresh_manifest().await;
+    let app = build_app(&state);
+    let fetch = |uri: String| app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+
+    let page = body(fetch("/post/hello-world".to_string()).await.unwrap()).await;
+    for path in [crate::theme::STYLESHEET_PATH, crate::theme::SCRIPT_PATH, "/asset/notes.txt"] {
+        let current = state.manifest.url(path);
+        assert_ne!(current, path);
+        if path.starts_with("/theme/") {
+            assert!(page.contains(&format!(r#"="{}""#, current)), "{} isn't linked by {}", path, current);
+        }
+
+        let fingerprinted = fetch(current.clone()).await.unwrap();
+        assert_eq!(fingerprinted.status(), StatusCode::OK, "{}", current);
+        assert_eq!(header_value(&fingerprinted, "cache-control"), Some("public, max-age=31536000, immutable"), "{}", current);
+        let plain = fetch(path.to_string()).await.unwrap();
+        assert_eq!(header_value(&plain, "cache-control"), Some("public, max-age=3600"), "{}", path);
+        assert_eq!(body(fingerprinted).await, body(plain).await);
+
+        let stale = fetch(crate::fingerprint::name(path, "000000000000")).await.unwrap();
+        assert_eq!(stale.status(), StatusCode::TEMPORARY_REDIRECT, "{}", path);
+        assert_eq!(header_value(&stale, "location"), Some(current.as_str()));
+    }
+}
+
+#[tokio::test]
+async fn a_fresh_deploy_serves_the_compiled_in_defaults_until_the_content_directory_overrides_them() {
+    let dir = std::env::temp_dir().join(format!("caden-blog-route-defaults-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let with_content = |dir: &std::path::Path| {
+        let mut state = state();
+        state.config = Arc::new(Config { content_dir: dir.to_path_buf(), ..(*state.config).clone() });
+        state.store = crate::assets::from_env(dir);
+        state.worker = Arc::new(crate::pwa::ServiceWorker::load(dir));
+        build_app(&state)
+    };
+    let fetch = |app: axum::Router, uri: &str| app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap());
+
+    let app = with_content(&dir);
+    for (uri, compiled_in) in [("/favicon.ico", "favicon.ico"), ("/robots.txt", "robots.txt"), ("/theme/code.js", "templates/code.js"), ("/theme/code.css", "assets/themes/github-dark.css")] {
+        let response = fetch(app.clone(), uri).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{}", uri);
+        assert_eq!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap(), crate::defaults::embedded(compiled_in).unwrap(), "{}", uri);
+    }
+    let worker = body(fetch(app.clone(), "/sw.js").await.unwrap()).await;
+    assert!(worker.contains("addEventListener") && !worker.contains("__VERSION__"), "{}", worker);
+    // htmx and the base stylesheet are compiled in by build.rs when it could fetch and check them
+    for name in ["htmx.min.js", "bootstrap.min.css"] {
+        let (url, _) = crate::vendor::source(name);
+        if url.starts_with("/assets/vendor/") {
+            assert_eq!(fetch(app.clone(), &url).await.unwrap().status(), StatusCode::OK, "{}", url);
+        }
+    }
+
+    std::fs::create_dir_all(dir.join("templates")).unwrap();
+    std::fs::write(dir.join("templates/code.js"), "// the site's own").unwrap();
+    std::fs::write(dir.join("templates/sw.js"), "// worker __VERSION__").unwrap();
+    let app = with_content(&dir);
+    assert_eq!(body(fetch(app.clone(), "/theme/code.js").await.unwrap()).await, "// the site's own");
+    assert!(body(fetch(app, "/sw.js").await.unwrap()).await.starts_with("// worker "));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn time_zone_comes_from_header_then_cookie_then_query() {
+    let response = get("/post/hello-world").await;
+    assert_eq!(header_value(&response, "x-time-zone"), Some("UTC"));
+    assert!(body(response).await.contains(">2024-11-10 23:31:07</time>"));
+
+    let response = send(Request::builder().uri("/post/hello-world?tz=Asia/Tokyo").header(header::COOKIE, "tz=America%2FNew_York").body(Body::empty()).unwrap()).await;
+    assert_eq!(header_value(&response, "x-time-zone"), Some("America/New_York"));
+    assert!(body(response).await.contains(">2024-11-10 18:31:07</time>"));
+
+    let response = get("/post/hello-world?tz=Asia/Tokyo").await;
+    assert_eq!(header_value(&response, "x-time-zone"), Some("Asia/Tokyo"));
+    assert!(body(response).await.contains(">2024-11-11 08:31:07</time>"));
+
+    let response = send(Request::builder().uri("/").header("x-time-zone", "Europe/Berlin").body(Body::empty()).unwrap()).await;
+    assert_eq!(header_value(&response, "x-time-zone"), Some("Europe/Berlin"));
+}
+
+#[tokio::test]
+async fn layout_choice_is_remembered_in_a_cookie() {
+    let response = get("/layout/compact").await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    assert!(header_value(&response, "set-cookie").is_some_and(|cookie| cookie.starts_with("layout=compact;")));
+
+    let html = body(send(Request::builder().uri("/").header(header::COOKIE, "layout=compact").body(Body::empty()).unwrap()).await).await;
+    assert!(html.contains(r#"data-layout="compact""#));
+}
+
+/// The listing in every layout, checked against `src/snapshots`. Review changes with `cargo insta review` after
+/// touching the templates.
+#[test]
+fn listing_snapshots() {
+    let locales = Locales::load(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR));
+    let votes = Tally::load("tests/fixtures/state/reactions.json");
+    let posts: Vec<Post> = fixture_posts().into_iter().filter(|post| post.lang.is_none() && post.listed()).collect();
+
+    for layout in LayoutMode::ALL {
+        let cards = Cards { text: locales.text("en"), reactions: &votes, manifest: &Default::default(), layout, hints: ClientHints::default(), config: &Config::default() };
+        let html = render_posts_fragment(&posts, cards).into_string();
+        insta::assert_snapshot!(format!("listing_{}", layout.name()), html);
+    }
+}
+
+#[tokio::test]
+async fn page_snapshots() {
+    for (name, uri) in [("home", "/"), ("post", "/post/hello-world"), ("post_es", "/post/hello-world?lang=es"), ("plain", "/post/hello-world/plain"), ("not_found", "/post/nope")] {
+        // Libraries link to the embedded copy or upstream depending on whether the build could fetch them
+        let page = crate::vendor::LIBRARIES.iter().fold(body(get(uri).await).await, |page, library| page.replace(&crate::vendor::source(library.name).0, &format!("vendor:{}", library.name)));
+        insta::assert_snapshot!(format!("page_{}", name), page);
+    }
+}
+
+#[tokio::test]
+async fn asset_names_cannot_escape_the_store() {
+    for uri in ["/asset/..%2Fposts%2Fhello-world.json", "/asset/%2Fetc%2Fpasswd", "/asset/.", "/asset/..", "/post/..%2Fpolls/poll/x/results"] {
+        assert_eq!(get(uri).await.status(), StatusCode::NOT_FOUND, "{}", uri);
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{Html, IntoResponse, Response};
+use chrono::{Datelike, NaiveDate, Utc};
+use chrono_tz::Tz;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+use serde::Deserialize;
+
+use crate::extract::tz::UserTz;
+use crate::i18n::Text;
+use crate::model::post::Post;
+use crate::prefs::{self, TimeFormatter};
+use crate::render::sidebar::post_list;
+use crate::render::{skip_link, FOCUS_CSS};
+use crate::state::AppState;
+use crate::store::posts::localized_listing;
+use crate::{icons, vendor};
+
+pub const ARCHIVE_PATH: &str = "/archive";
+
+/// Side of a day's square in the heatmap, and the gap after it
+const CELL: i64 = 11;
+const GAP: i64 = 2;
+/// Room above the squares for the month names
+const TOP: i64 = 16;
+
+/// Fill for a day without posts, then for busier and busier days
+const LEVELS: [&str; 5] = ["#2d333b", "#0e4429", "#006d32", "#26a641", "#39d353"];
+
+const MONTHS: [&str; 12] = ["month_jan", "month_feb", "month_mar", "month_apr", "month_may", "month_jun", "month_jul", "month_aug", "month_sep", "month_oct", "month_nov", "month_dec"];
+
+#[derive(Debug, Deserialize)]
+pub struct ArchiveQuery {
+    pub year: Option<i32>,
+    /// A `YYYY-MM-DD` date to list the posts of
+    pub day: Option<String>,
+}
+
+/// How many posts went out on each day, by the date in `tz`
+pub fn posts_per_day(posts: &[Post], tz: Tz) -> BTreeMap<NaiveDate, usize> {
+    let mut days = BTreeMap::new();
+    for post in posts {
+        *days.entry(post.timestamp.with_timezone(&tz).date_naive()).or_default() += 1;
+    }
+    days
+}
+
+/// Which fill of [`LEVELS`] a day gets, in quarters of the busiest day
+fn level(count: usize, most: usize) -> usize {
+    if count == 0 {
+        0
+    } else {
+        (count * 4).div_ceil(most.max(1)).clamp(1, 4)
+    }
+}
+
+/// A square for every day of `year`, a column to a week starting on Sunday, with the days that have posts linking
+/// to them
+pub fn heatmap(days: &BTreeMap<NaiveDate, usize>, year: i32, text: Text) -> Markup {
+    let first = NaiveDate::from_ymd_opt(year, 1, 1).expect("every year has a first of January");
+    let last = NaiveDate::from_ymd_opt(year, 12, 31).expect("every year has a last of December");
+    let most = days.range(first..=last).map(|(_, count)| *count).max().unwrap_or(0);
+    let offset = first.weekday().num_days_from_sunday() as i64;
+    let weeks = (offset + last.ordinal() as i64 + 6) / 7;
+    let position = |date: NaiveDate| {
+        let index = offset + date.ordinal0() as i64;
+        ((index / 7) * (CELL + GAP), TOP + (index % 7) * (CELL + GAP))
+    };
+    let label = |count: usize, date: NaiveDate| text.t(if count == 1 { "day_post" } else { "day_posts" }).replace("{count}", &count.to_string()).replace("{date}", &date.to_string());
+
+    html! {
+        svg class="heatmap" xmlns="http://www.w3.org/2000/svg" width=(weeks * (CELL + GAP)) height=(TOP + 7 * (CELL + GAP)) role="img" aria-label=(text.t("archive_heading").replace("{year}", &year.to_string())) {
+            @for (month, key) in MONTHS.iter().enumerate() {
+                @let (x, _) = position(NaiveDate::from_ymd_opt(year, month as u32 + 1, 1).expect("every month has a first"));
+                text x=(x) y=(TOP - 5) font-size="10" fill="#8b949e" { (text.t(key)) }
+            }
+            @for date in first.iter_days().take_while(|date| *date <= last) {
+                @let count = days.get(&date).copied().unwrap_or(0);
+                @let (x, y) = position(date);
+                @if count == 0 {
+                    rect x=(x) y=(y) width=(CELL) height=(CELL) rx="2" fill=(LEVELS[0]) { title { (label(count, date)) } }
+                } @else {
+                    a href={ (ARCHIVE_PATH) "?day=" (date) } {
+                        rect x=(x) y=(y) width=(CELL) height=(CELL) rx="2" fill=(LEVELS[level(count, most)]) { title { (label(count, date)) } }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn page(days: &BTreeMap<NaiveDate, usize>, year: i32, day: Option<(NaiveDate, Vec<&Post>)>, theme_color: &str, text: Text) -> Markup {
+    let mut years: Vec<i32> = days.keys().map(|date| date.year()).collect();
+    years.dedup();
+    html! {
+        (DOCTYPE)
+        html lang=(text.lang) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                (icons::icon_links(theme_color))
+                title { (text.t("archive")) " - " (text.t("site_title")) }
+                (vendor::stylesheet("bootstrap.min.css"))
+                style { r#"
+                    body {
+                        font-family: Arial, sans-serif;
+                        background-color: #121212;
+                        color: #e0e0e0;
+                    }
+                    .header {
+                        text-align: center;
+                        background-color: #343a40;
+                        color: #f0f0f0;
+                        padding: 20px;
+                    }
+                    .heatmap-scroll {
+                        overflow-x: auto;
+                    }
+                    main a {
+                        color: #66b2ff;
+                    }
+                "# }
+                style { (PreEscaped(FOCUS_CSS)) }
+            }
+            body {
+                (skip_link(text))
+                header class="header" {
+                    h1 { a href="/" class="text-reset text-decoration-none" { "The Caden Times" } }
+                }
+                main id="main" class="container my-4" {
+                    h2 class="h4" { (text.t("archive_heading").replace("{year}", &year.to_string())) }
+                    @if years.len() > 1 {
+                        nav class="mb-3" aria-label=(text.t("archive")) {
+                            @for shown in years.iter().rev() {
+                                @if *shown == year {
+                                    strong class="me-2" aria-current="true" { (shown) }
+                                } @else {
+                                    a class="me-2" href={ (ARCHIVE_PATH) "?year=" (shown) } { (shown) }
+                                }
+                            }
+                        }
+                    }
+                    div class="heatmap-scroll" { (heatmap(days, year, text)) }
+                    @if let Some((date, posts)) = day {
+                        section class="mt-4" {
+                            (post_list(&date.to_string(), &posts, text))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `GET /archive`: a year of posting as a heatmap, by default the latest year with posts, and with `?day=` the
+/// posts of that day. Days are the reader's.
+pub async fn archive_page(State(AppState { config, posts, locales, .. }): State<AppState>, Query(query): Query<ArchiveQuery>, user_tz: UserTz, headers: HeaderMap) -> Response {
+    let lang = locales.negotiate(&headers);
+    let text = locales.text(&lang);
+    let listing = localized_listing(&posts.read().expect("failed to lock the post index"), &lang);
+    let days = posts_per_day(&listing, user_tz.tz);
+
+    let day = query.day.as_deref().and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok());
+    let year = day
+        .map(|day| day.year())
+        .or(query.year)
+        .filter(|year| NaiveDate::from_ymd_opt(*year, 1, 1).is_some())
+        .or_else(|| days.keys().next_back().map(|date| date.year()))
+        .unwrap_or_else(|| Utc::now().with_timezone(&user_tz.tz).year());
+    let day = day.map(|day| {
+        let mut posts: Vec<&Post> = listing.iter().filter(|post| post.timestamp.with_timezone(&user_tz.tz).date_naive() == day).collect();
+        posts.sort_by_key(|post| post.timestamp);
+        (day, posts)
+    });
+
+    let html = page(&days, year, day, &config.theme_color, text).into_string();
+    ([(header::VARY, "Accept-Language")], Html(prefs::localize_times(&html, &TimeFormatter::new(user_tz, &headers, text, config.time_display)))).into_response()
+}
+
+#[test]
+fn busier_days_are_brighter_squares() {
+    let locales = crate::i18n::Locales::load(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR));
+    let date = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap();
+    assert_eq!((level(0, 8), level(1, 8), level(2, 8), level(3, 8), level(8, 8)), (0, 1, 1, 2, 4));
+
+    let days: BTreeMap<NaiveDate, usize> = [(date("2024-01-01"), 1), (date("2024-12-31"), 4), (date("2023-06-01"), 9)].into_iter().collect();
+    let svg = heatmap(&days, 2024, locales.text("en")).into_string();
+    // 2024 starts on a Monday and is a leap year
+    assert_eq!(svg.matches("<rect").count(), 366);
+    assert_eq!(svg.matches("<a ").count(), 2);
+    assert!(svg.contains(r##"<a href="/archive?day=2024-01-01"><rect x="0" y="29" width="11" height="11" rx="2" fill="#0e4429"><title>1 post on 2024-01-01</title></rect></a>"##), "{}", svg);
+    assert!(svg.contains(r##"<rect x="676" y="42" width="11" height="11" rx="2" fill="#39d353"><title>4 posts on 2024-12-31</title>"##), "{}", svg);
+    assert!(svg.contains(r##"<text x="0" y="11" font-size="10" fill="#8b949e">Jan</text>"##));
+    assert!(svg.contains(r#"width="689""#));
+}
@@ -0,0 +1,254 @@
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, HeaderValue, Response, StatusCode};
+
+use crate::admin::Admin;
+use crate::assets::{AssetError, AssetObject, ByteRange, ContentRange, ASSET_CACHE_METRIC};
+use crate::defaults;
+use crate::extract::preview::Preview;
+use crate::fingerprint::{self, Resolved};
+use crate::state::AppState;
+use crate::store::FileCache;
+
+/// Asset names are relative paths, so a leading slash keeps the favicon from colliding with them
+pub const FAVICON_CACHE_KEY: &str = "/favicon.ico";
+
+pub fn cache_control_response(content: Vec<u8>) -> Response<Body> {
+    use hyper::header::{ACCEPT_RANGES, CACHE_CONTROL, HeaderValue};
+
+    Response::builder()
+        .header(CACHE_CONTROL, HeaderValue::from_static(fingerprint::UNVERSIONED))
+        .header(ACCEPT_RANGES, HeaderValue::from_static("bytes"))
+        .body(Body::from(content))
+        .unwrap()
+}
+
+/// Builds the response for a streamed asset, as `206 Partial Content` when only a range of it was read
+pub fn asset_response(asset: AssetObject) -> Response<Body> {
+    use hyper::header::{ACCEPT_RANGES, CACHE_CONTROL, CONTENT_LENGTH, CONTENT_RANGE};
+
+    let mut response = Response::builder()
+        .header(CACHE_CONTROL, fingerprint::UNVERSIONED)
+        .header(ACCEPT_RANGES, "bytes");
+    if let Some(len) = asset.len {
+        response = response.header(CONTENT_LENGTH, len);
+    }
+    if let Some(range) = asset.range {
+        response = response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(CONTENT_RANGE, range.header_value());
+    }
+    response.body(asset.body).unwrap()
+}
+
+pub fn range_not_satisfiable(size: u64) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::RANGE_NOT_SATISFIABLE)
+        .header(hyper::header::CONTENT_RANGE, format!("bytes */{}", size))
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Serves an asset by its path in the assets, like `/asset/posts/my-post/diagram.png`, typed by its extension so
+/// browsers and podcast apps know what they're getting and cached as its directory's policy says. The fingerprinted
+/// name pages link, like `/asset/posts/my-post/diagram.<version>.png`, is cached for good instead.
+pub async fn handle_asset_request(State(state): State<AppState>, Path(filename): Path<String>, headers: HeaderMap) -> Result<Response<Body>, StatusCode> {
+    let (filename, fingerprinted) = match state.manifest.resolve(&format!("/asset/{}", filename)) {
+        Resolved::Plain => (filename, false),
+        Resolved::Current(plain) => (plain["/asset/".len()..].to_string(), true),
+        Resolved::Stale(current) => return Ok(fingerprint::redirect(&current)),
+    };
+    if !crate::assets::valid_name(&filename) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    // Private posts' attachments are only handed out by the attachment route, which checks who's asking
+    let private_attachment = state.posts.read().expect("failed to lock the post index").iter().any(|post| {
+        !post.visible_to(false) && post.attachments.iter().any(|file| post.attachment_asset(file) == filename)
+    });
+    if private_attachment {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let content_type = crate::assets::content_type(&filename);
+    let image = content_type.is_some_and(|content_type| content_type.starts_with("image/"));
+    if let Some(refusal) = state.hotlinks.check(&headers).filter(|_| image) {
+        return Ok(state.hotlinks.refuse(refusal, &headers));
+    }
+    let protected = image && state.hotlinks.enabled();
+
+    let cache_control = if fingerprinted { fingerprint::IMMUTABLE } else { crate::assets::cache_control(&state.config.asset_cache_policies, &filename) };
+    let cache_control = HeaderValue::from_str(cache_control).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let negotiable = crate::assets::negotiable(&filename) && state.store.signed_url(&filename).is_none();
+    let accept = headers.get(hyper::header::ACCEPT).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    let variants = if negotiable { crate::assets::variants(&filename, accept) } else { Vec::new() };
+
+    // The first variant there is wins, the ones that aren't are remembered as missing so asking again is cheap
+    let mut served = None;
+    for (variant, variant_type) in variants {
+        match serve_asset(state.clone(), variant, headers.clone()).await {
+            Err(StatusCode::NOT_FOUND) => continue,
+            result => {
+                served = Some((result?, Some(variant_type)));
+                break;
+            }
+        }
+    }
+    let (mut response, content_type) = match served {
+        Some(served) => served,
+        None => (serve_asset(state, filename, headers).await?, content_type),
+    };
+    if response.status().is_success() {
+        let response_headers = response.headers_mut();
+        if let Some(content_type) = content_type {
+            response_headers.insert(hyper::header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+        }
+        response_headers.insert(hyper::header::CACHE_CONTROL, cache_control);
+    }
+    let vary = match (negotiable, protected) {
+        (true, true) => Some("Accept, Referer"),
+        (true, false) => Some("Accept"),
+        (false, true) => Some("Referer"),
+        (false, false) => None,
+    };
+    if let Some(vary) = vary {
+        response.headers_mut().insert(hyper::header::VARY, HeaderValue::from_static(vary));
+    }
+    Ok(response)
+}
+
+async fn serve_asset(AppState { config, cache, store, missing, metrics, .. }: AppState, filename: String, headers: HeaderMap) -> Result<Response<Body>, StatusCode> {
+    let range = headers.get(hyper::header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(ByteRange::parse);
+
+    // Check if file is already cached
+    if let Some(content) = cache.lock().expect("cdn failed to lock the cache").get(&filename).cloned() {
+        metrics.incr(ASSET_CACHE_METRIC, &[("result", "hit")]);
+        return Ok(buffered_response(content, range));
+    }
+
+    if missing.is_missing(&filename) {
+        metrics.incr(ASSET_CACHE_METRIC, &[("result", "negative")]);
+        return Err(StatusCode::NOT_FOUND);
+    }
+    metrics.incr(ASSET_CACHE_METRIC, &[("result", "miss")]);
+
+    // Let the client fetch straight from the store when it hands out signed URLs, unless the photo needs cleaning
+    let strip = config.strip_metadata && crate::exif::applies(&filename);
+    if let Some(url) = store.signed_url(&filename).filter(|_| !strip) {
+        return Ok(Response::builder()
+            .status(StatusCode::TEMPORARY_REDIRECT)
+            .header(hyper::header::LOCATION, url)
+            .body(Body::empty())
+            .unwrap());
+    }
+
+    // Photos are cleaned whole, ranges are then cut from the cleaned copy
+    let asset = match store.stream(&filename, range.filter(|_| !strip)).await {
+        Ok(asset) => asset,
+        Err(AssetError::RangeNotSatisfiable(size)) => return Ok(range_not_satisfiable(size)),
+        Err(AssetError::NotFound) => {
+            missing.remember(&filename);
+            return Err(StatusCode::NOT_FOUND);
+        }
+    };
+    if strip && asset.len.is_none_or(|len| len <= crate::exif::MAX_SIZE as u64) {
+        let contents = axum::body::to_bytes(asset.body, crate::exif::MAX_SIZE).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let contents = crate::exif::clean(&filename, contents.to_vec());
+        if store.cache_in_memory() && contents.len() as u64 <= config.max_cached_size {
+            cache.lock().expect("cdn failed to lock the cache").insert(filename, contents.clone());
+        }
+        return Ok(buffered_response(contents, range));
+    }
+
+    // Small files are read in full and cached; ranged reads and anything over the limit stream straight through
+    match asset.len {
+        Some(len) if range.is_none() && store.cache_in_memory() && len <= config.max_cached_size => {
+            let contents = cache_asset(filename, asset, &cache, config.strip_metadata).await.ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+            Ok(cache_control_response(contents))
+        }
+        _ => Ok(asset_response(asset)),
+    }
+}
+
+/// `GET /post/:url_name/attachments/:file`: one of the files a post lists as attachments, from the asset store
+/// under the post's directory. It's sent as a download, and only to readers who can see the post.
+pub async fn serve_attachment(admin: Option<Admin>, preview: Preview, State(AppState { posts, store, .. }): State<AppState>, Path((url_name, file)): Path<(String, String)>, headers: HeaderMap) -> Result<Response<Body>, StatusCode> {
+    let post = posts
+        .read()
+        .expect("failed to lock the post index")
+        .iter()
+        .find(|post| post.url_name == url_name && post.attachments.contains(&file) && post.visible_to(admin.is_some() || preview.grants(&post.url_name)))
+        .cloned()
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let range = headers.get(hyper::header::RANGE).and_then(|value| value.to_str().ok()).and_then(ByteRange::parse);
+    let asset = match store.stream(&post.attachment_asset(&file), range).await {
+        Ok(asset) => asset,
+        Err(AssetError::RangeNotSatisfiable(size)) => return Ok(range_not_satisfiable(size)),
+        Err(AssetError::NotFound) => return Err(StatusCode::NOT_FOUND),
+    };
+
+    let mut response = asset_response(asset);
+    let response_headers = response.headers_mut();
+    let content_type = crate::assets::content_type(&file).unwrap_or("application/octet-stream");
+    response_headers.insert(hyper::header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+    // Quotes and line breaks can't end the name early, anything else non-ascii goes in the encoded form
+    let plain: String = file.chars().map(|c| if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' { c } else { '_' }).collect();
+    let disposition = format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", plain, crate::share::encode(&file));
+    response_headers.insert(hyper::header::CONTENT_DISPOSITION, HeaderValue::from_str(&disposition).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?);
+    if !post.listed() {
+        response_headers.insert(hyper::header::CACHE_CONTROL, HeaderValue::from_static("private, no-store"));
+    }
+    Ok(response)
+}
+
+/// An asset read into memory, or the requested range of it
+fn buffered_response(content: Vec<u8>, range: Option<ByteRange>) -> Response<Body> {
+    let Some(range) = range else {
+        return cache_control_response(content);
+    };
+    let size = content.len() as u64;
+    match range.resolve(size) {
+        Some((start, end)) => asset_response(AssetObject {
+            len: Some(end - start + 1),
+            range: Some(ContentRange { start, end, size }),
+            body: Body::from(content[start as usize..=end as usize].to_vec()),
+        }),
+        None => range_not_satisfiable(size),
+    }
+}
+
+/// Reads a whole asset body into the cache, photos cleaned of their metadata first when `strip_metadata` is on
+pub async fn cache_asset(filename: String, asset: AssetObject, cache: &FileCache, strip_metadata: bool) -> Option<Vec<u8>> {
+    let limit = asset.len.map_or(usize::MAX, |len| len as usize);
+    let contents = axum::body::to_bytes(asset.body, limit).await.ok()?.to_vec();
+    let contents = if strip_metadata { crate::exif::clean(&filename, contents) } else { contents };
+
+    // Cache the file contents
+    cache.lock().expect("cdn falied to lock the cache").insert(filename, contents.clone());
+    Some(contents)
+}
+
+/// Reads the favicon from the `content` directory into the file cache
+pub async fn load_favicon(content: &std::path::Path, cache: &FileCache) -> Result<Vec<u8>, StatusCode> {
+    // The site's own favicon, or the default one compiled into the binary
+    let contents = defaults::read_async(content, "favicon.ico").await.ok_or(StatusCode::NOT_FOUND)?;
+
+    cache.lock().expect("cdn failed to lock the cache").insert(FAVICON_CACHE_KEY.to_string(), contents.clone());
+    Ok(contents)
+}
+
+pub async fn serve_favicon(State(AppState { config, cache, .. }): State<AppState>) -> Result<Response<Body>, StatusCode> {
+    let cached = cache.lock().expect("cdn failed to lock the cache").get(FAVICON_CACHE_KEY).cloned();
+    let contents = match cached {
+        Some(contents) => contents,
+        None => load_favicon(&config.content_dir, &cache).await?,
+    };
+
+    // Create and return the response with caching headers
+    Ok(Response::builder()
+        .header("Content-Type", "image/x-icon")
+        .header("Cache-Control", fingerprint::UNVERSIONED)
+        .body(Body::from(contents))
+        .unwrap())
+}
@@ -0,0 +1,158 @@
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Html;
+use maud::{html, PreEscaped, DOCTYPE};
+
+use crate::state::AppState;
+use crate::prefs::{BackgroundSpeed, ClientHints};
+use crate::render::{hinted_css, skip_link, CARD_CSS, FOCUS_CSS, PRINT_CSS};
+use crate::{backdrop, dev, icons, pwa, vendor};
+
+pub async fn contact(State(AppState { config, locales, .. }): State<AppState>, headers: HeaderMap) -> Html<String> {
+    let lang = locales.negotiate(&headers);
+    let text = locales.text(&lang);
+    let hints = ClientHints::resolve(&headers);
+    let dev = config.dev;
+
+    Html(html! {
+        (DOCTYPE)
+        html lang=(text.lang) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                (icons::icon_links(&config.theme_color))
+                title { (text.t("site_title")) }
+                (vendor::stylesheet("bootstrap.min.css"))
+                (vendor::stylesheet("unpoly.min.css"))
+                (vendor::stylesheet("unpoly-bootstrap5.min.css"))
+                style { r#"
+                    body {
+                        font-family: Arial, sans-serif;
+                        background-color: #121212;
+                        color: #e0e0e0;
+                    }
+                    .header {
+                        background-position: center;
+                        color: #f0f0f0;
+                        padding: 20px;
+                        text-align: center;
+                        background-size: cover;
+                    }
+                    .sidebar {
+                        background-color: #242424;
+                        color: #e0e0e0;
+                        padding: 20px;
+                        border-radius: 8px;
+                    }
+                    .footer {
+                        background-color: #1c1c1c;
+                        color: #f0f0f0;
+                        text-align: center;
+                        padding: 15px;
+                        margin-top: 20px;
+                    }
+                    .navbar-nav .nav-link {
+                        color: #e0e0e0 !important;
+                    }
+                    .btn-primary {
+                        background-color: #007bff;
+                        border-color: #007bff;
+                    }
+                    .btn-outline-primary {
+                        color: #007bff;
+                        border-color: #007bff;
+                    }
+                    .btn-outline-primary:hover {
+                        background-color: #007bff;
+                        color: #fff;
+                    }
+                "# }
+                style media="print" { (PreEscaped(PRINT_CSS)) }
+                style { (PreEscaped(FOCUS_CSS)) }
+                (hinted_css(hints, true))
+                (backdrop::css(config.background_pattern, BackgroundSpeed::resolve(&headers, config.background_speed), hints))
+                style { (PreEscaped(CARD_CSS)) }
+            }
+            body {
+                (skip_link(text))
+
+                // Header
+                header class="header" {
+                    h1 { "The Caden Times" }
+                    p { (text.t("tagline")) }
+                }
+
+                // Navigation Bar
+                nav class="navbar navbar-expand-lg navbar-dark bg-dark" aria-label=(text.t("main_navigation")) {
+                    div class="container" {
+                        a class="navbar-brand" href="#" { (text.t("site_title")) }
+                        button class="navbar-toggler" type="button" data-bs-toggle="collapse" data-bs-target="#navbarNav" aria-controls="navbarNav" aria-expanded="false" aria-label=(text.t("toggle_navigation")) {
+                            span class="navbar-toggler-icon" {}
+                        }
+                        div class="collapse navbar-collapse" id="navbarNav" {
+                            ul class="navbar-nav ms-auto" {
+                                li class="nav-item" {
+                                    a class="nav-link active" href="#" aria-current="page" { (text.t("nav_home")) }
+                                }
+                                li class="nav-item" {
+                                    a class="nav-link" href="#" { (text.t("nav_about")) }
+                                }
+                                li class="nav-item" {
+                                    a class="nav-link" href="/notes" { (text.t("nav_notes")) }
+                                }
+                                li class="nav-item" {
+                                    a class="nav-link" href="/projects" { (text.t("nav_projects")) }
+                                }
+                                li class="nav-item" {
+                                    a class="nav-link" href="/contact" up-layer="new" { (text.t("nav_contact")) }
+                                }
+                            }
+                        }
+                    }
+                }
+
+                // Main Content
+                main id="main" class="container my-4" {
+                    div class="row" {
+                        div class="col-lg-8" up-main {
+                            h2 { (text.t("contact_heading")) }
+                        }
+
+                        // Sidebar
+                        aside class="col-lg-4" aria-label=(text.t("about_heading")) {
+                            div class="sidebar" {
+                                h2 class="h4" { (text.t("about_heading")) }
+                                p { (text.t("about_text")) }
+                                hr;
+                                h3 class="h5" { (text.t("categories")) }
+                                ul class="list-unstyled" {
+                                    li { a href="#" { "Tech" } }
+                                    li { a href="#" { "Programming" } }
+                                    li { a href="#" { "Computer Science" } }
+                                    li { a href="#" { "Software Engineering" } }
+                                }
+                                hr;
+                                h3 class="h5" { (text.t("follow_me")) }
+                                a href="#" class="btn btn-outline-primary btn-sm" { "Twitter" }
+                                a href="#" class="btn btn-outline-primary btn-sm" { "Facebook" }
+                                a href="#" class="btn btn-outline-primary btn-sm" { "Instagram" }
+                            }
+                        }
+                    }
+                }
+
+                // Footer
+                footer class="footer" {
+                    p { (text.t("footer")) }
+                }
+
+                (vendor::script("jquery.min.js"))
+                (vendor::script("bootstrap.bundle.min.js"))
+                (vendor::script("unpoly.min.js"))
+                (vendor::script("unpoly-bootstrap5.min.js"))
+                (pwa::register_script(dev))
+                (dev::reload_script(dev))
+            }
+        }
+    }.into_string())
+}
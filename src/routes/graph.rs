@@ -0,0 +1,261 @@
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{Html, IntoResponse, Json, Response};
+use maud::{html, PreEscaped, DOCTYPE};
+use serde::Serialize;
+
+use crate::extract::LangQuery;
+use crate::i18n::Text;
+use crate::model::post::Post;
+use crate::render::{skip_link, FOCUS_CSS};
+use crate::state::AppState;
+use crate::store::links::LinkGraph;
+use crate::store::posts::localized_listing;
+use crate::{icons, share, vendor};
+
+pub const GRAPH_PATH: &str = "/graph";
+pub const GRAPH_API_PATH: &str = "/api/graph";
+
+#[derive(Debug, Serialize)]
+pub struct Node {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Edge {
+    pub source: String,
+    pub target: String,
+    /// `link` when the source links to the target, `tag` when they share tags
+    pub kind: &'static str,
+    /// The tags they share, for `tag` edges
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+/// The posts of a listing and how they connect
+#[derive(Debug, Serialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Graph {
+    /// Links between posts of the listing, then one edge for each pair sharing a tag, compared without case
+    pub fn build(listing: &[Post], links: &LinkGraph) -> Graph {
+        let listed = |url_name: &str| listing.iter().any(|post| post.url_name == url_name);
+        let mut edges: Vec<Edge> = links
+            .links()
+            .iter()
+            .filter(|(source, target)| listed(source) && listed(target))
+            .map(|(source, target)| Edge { source: source.clone(), target: target.clone(), kind: "link", tags: Vec::new() })
+            .collect();
+
+        for (index, post) in listing.iter().enumerate() {
+            for other in &listing[index + 1..] {
+                let shared: Vec<String> = post.tags.iter().filter(|tag| other.tags.iter().any(|other_tag| other_tag.eq_ignore_ascii_case(tag))).cloned().collect();
+                if !shared.is_empty() {
+                    edges.push(Edge { source: post.url_name.clone(), target: other.url_name.clone(), kind: "tag", tags: shared });
+                }
+            }
+        }
+
+        let nodes = listing
+            .iter()
+            .map(|post| Node { id: post.url_name.clone(), title: post.title.clone(), url: share::post_url("", post), tags: post.tags.clone() })
+            .collect();
+        Graph { nodes, edges }
+    }
+}
+
+/// `GET /api/graph`: the public posts as nodes, in the reader's language or `?lang=`, with their links and shared
+/// tags as edges
+pub async fn api_graph(State(AppState { posts, locales, links, .. }): State<AppState>, Query(query): Query<LangQuery>, headers: HeaderMap) -> Response {
+    let lang = query.lang.unwrap_or_else(|| locales.negotiate(&headers));
+    let listing = localized_listing(&posts.read().expect("failed to lock the post index"), &lang);
+    ([(header::VARY, "Accept-Language")], Json(Graph::build(&listing, &links.get(&posts)))).into_response()
+}
+
+/// Lays the graph out with a small force simulation and draws it as SVG. Without javascript the page lists the
+/// posts instead.
+const GRAPH_SCRIPT: &str = r#"
+(async () => {
+    const svg = document.getElementById('graph');
+    const { nodes, edges } = await (await fetch(svg.dataset.src)).json();
+    if (!nodes.length) return;
+    document.getElementById('graph-fallback').hidden = true;
+    svg.hidden = false;
+    const width = 800, height = 600, ns = 'http://www.w3.org/2000/svg';
+    const byId = new Map(nodes.map((node, i) => {
+        const angle = i * 2.4;
+        return [node.id, Object.assign(node, { x: width / 2 + Math.cos(angle) * 10 * Math.sqrt(i + 1), y: height / 2 + Math.sin(angle) * 10 * Math.sqrt(i + 1), dx: 0, dy: 0 })];
+    }));
+    const springs = edges.map(edge => ({ ...edge, a: byId.get(edge.source), b: byId.get(edge.target) })).filter(edge => edge.a && edge.b);
+
+    const step = () => {
+        for (const a of nodes) {
+            for (const b of nodes) {
+                if (a === b) continue;
+                const x = a.x - b.x, y = a.y - b.y, d2 = Math.max(x * x + y * y, 25);
+                a.dx += x / d2 * 400; a.dy += y / d2 * 400;
+            }
+            a.dx += (width / 2 - a.x) * 0.01; a.dy += (height / 2 - a.y) * 0.01;
+        }
+        for (const { a, b, kind } of springs) {
+            const x = b.x - a.x, y = b.y - a.y, d = Math.sqrt(x * x + y * y) || 1;
+            const pull = (d - (kind === 'link' ? 80 : 120)) * (kind === 'link' ? 0.05 : 0.02);
+            a.dx += x / d * pull; a.dy += y / d * pull; b.dx -= x / d * pull; b.dy -= y / d * pull;
+        }
+        for (const node of nodes) {
+            node.x = Math.min(width - 20, Math.max(20, node.x + node.dx * 0.5));
+            node.y = Math.min(height - 20, Math.max(20, node.y + node.dy * 0.5));
+            node.dx *= 0.5; node.dy *= 0.5;
+        }
+    };
+
+    const lines = springs.map(edge => {
+        const line = document.createElementNS(ns, 'line');
+        line.setAttribute('class', 'graph-edge graph-edge-' + edge.kind);
+        if (edge.tags) {
+            const title = document.createElementNS(ns, 'title');
+            title.textContent = '#' + edge.tags.join(' #');
+            line.append(title);
+        }
+        svg.append(line);
+        return line;
+    });
+    const dots = nodes.map(node => {
+        const link = document.createElementNS(ns, 'a');
+        link.setAttribute('href', node.url);
+        const circle = document.createElementNS(ns, 'circle');
+        circle.setAttribute('r', 6 + Math.min(springs.filter(edge => edge.a === node || edge.b === node).length, 8));
+        const label = document.createElementNS(ns, 'text');
+        label.textContent = node.title;
+        label.setAttribute('dy', -12);
+        link.append(circle, label);
+        svg.append(link);
+        return link;
+    });
+    const draw = () => {
+        springs.forEach((edge, i) => {
+            lines[i].setAttribute('x1', edge.a.x); lines[i].setAttribute('y1', edge.a.y);
+            lines[i].setAttribute('x2', edge.b.x); lines[i].setAttribute('y2', edge.b.y);
+        });
+        nodes.forEach((node, i) => dots[i].setAttribute('transform', `translate(${node.x} ${node.y})`));
+    };
+
+    if (matchMedia('(prefers-reduced-motion: reduce)').matches) {
+        for (let i = 0; i < 300; i++) step();
+        draw();
+    } else {
+        let frames = 300;
+        const tick = () => { step(); draw(); if (--frames > 0) requestAnimationFrame(tick); };
+        tick();
+    }
+})();
+"#;
+
+fn page(listing: &[Post], lang: &str, theme_color: &str, text: Text) -> maud::Markup {
+    html! {
+        (DOCTYPE)
+        html lang=(text.lang) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                (icons::icon_links(theme_color))
+                title { (text.t("graph")) " - " (text.t("site_title")) }
+                (vendor::stylesheet("bootstrap.min.css"))
+                style { r#"
+                    body {
+                        font-family: Arial, sans-serif;
+                        background-color: #121212;
+                        color: #e0e0e0;
+                    }
+                    .header {
+                        text-align: center;
+                        background-color: #343a40;
+                        color: #f0f0f0;
+                        padding: 20px;
+                    }
+                    #graph {
+                        width: 100%;
+                        height: auto;
+                        background-color: #1c1c1c;
+                        border-radius: 8px;
+                    }
+                    .graph-edge-link {
+                        stroke: #66b2ff;
+                        stroke-width: 1.5;
+                    }
+                    .graph-edge-tag {
+                        stroke: #555;
+                        stroke-dasharray: 4 4;
+                    }
+                    #graph circle {
+                        fill: #007bff;
+                    }
+                    #graph text {
+                        fill: #e0e0e0;
+                        font-size: 12px;
+                        text-anchor: middle;
+                        pointer-events: none;
+                    }
+                    #graph a:hover circle,
+                    #graph a:focus circle {
+                        fill: #66b2ff;
+                    }
+                "# }
+                style { (PreEscaped(FOCUS_CSS)) }
+            }
+            body {
+                (skip_link(text))
+                header class="header" {
+                    h1 { a href="/" class="text-reset text-decoration-none" { "The Caden Times" } }
+                }
+                main id="main" class="container my-4" {
+                    h2 class="h4" { (text.t("graph")) }
+                    p class="text-muted" { (text.t("graph_intro")) }
+                    svg id="graph" viewBox="0 0 800 600" role="img" aria-label=(text.t("graph")) data-src=(format!("{}?lang={}", GRAPH_API_PATH, share::encode(lang))) hidden {}
+                    ul id="graph-fallback" {
+                        @for post in listing {
+                            li { a href=(share::post_url("", post)) { (post.title) } }
+                        }
+                    }
+                }
+                script { (PreEscaped(GRAPH_SCRIPT)) }
+            }
+        }
+    }
+}
+
+/// `GET /graph`: the posts as an interactive map of how they link to each other and share tags
+pub async fn graph_page(State(AppState { config, posts, locales, .. }): State<AppState>, headers: HeaderMap) -> Html<String> {
+    let lang = locales.negotiate(&headers);
+    let listing = localized_listing(&posts.read().expect("failed to lock the post index"), &lang);
+    Html(page(&listing, &lang, &config.theme_color, locales.text(&lang)).into_string())
+}
+
+#[test]
+fn edges_connect_linked_posts_and_shared_tags() {
+    let post = |url_name: &str, body: &str, tags: &[&str]| {
+        let mut post: Post = serde_json::from_value(serde_json::json!({
+            "title": url_name, "body": body, "image_url": "", "summary": "", "timestamp": "2024-01-01T00:00:00Z", "tags": tags
+        }))
+        .unwrap();
+        post.url_name = url_name.to_string();
+        post
+    };
+    let posts = vec![post("a", "[b](/post/b) and [gone](/post/gone)", &["Rust"]), post("b", "", &["rust", "Bots"]), post("c", "", &["bots"])];
+    let graph = Graph::build(&posts, &LinkGraph::build(&posts, ""));
+    assert_eq!(graph.nodes.len(), 3);
+    assert_eq!(
+        graph.edges,
+        vec![
+            Edge { source: "a".to_string(), target: "b".to_string(), kind: "link", tags: Vec::new() },
+            Edge { source: "a".to_string(), target: "b".to_string(), kind: "tag", tags: vec!["Rust".to_string()] },
+            Edge { source: "b".to_string(), target: "c".to_string(), kind: "tag", tags: vec!["Bots".to_string()] },
+        ]
+    );
+}
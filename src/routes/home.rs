@@ -0,0 +1,63 @@
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, Response, StatusCode};
+use axum::response::Html;
+use maud::html;
+
+use crate::extract::tz::UserTz;
+use crate::prefs::{self, BackgroundSpeed, ClientHints, LayoutMode, TimeFormatter};
+use crate::render::home::render_home;
+use crate::render::listing::Cards;
+use crate::state::AppState;
+use crate::store::posts::localized_listing;
+use crate::store::PageCache;
+
+pub async fn handler(State(AppState { config, posts, pages, locales, reactions, manifest, .. }): State<AppState>, user_tz: UserTz, headers: HeaderMap) -> Html<String> {
+    let lang = locales.negotiate(&headers);
+    let layout = LayoutMode::resolve(&headers, config.layout);
+    let hints = ClientHints::resolve(&headers);
+    let speed = BackgroundSpeed::resolve(&headers, config.background_speed);
+    let key = home_cache_key(&lang, layout, hints, speed);
+    if let Some(page) = pages.read().expect("failed to lock the page cache").get(&key) {
+        return Html(prefs::localize_times(page, &TimeFormatter::new(user_tz, &headers, locales.text(&lang), config.time_display)));
+    }
+
+    let cards = Cards { text: locales.text(&lang), reactions: &reactions, manifest: &manifest, layout, hints, config: &config };
+    let page = render_home(&localized_listing(&posts.read().expect("failed to lock the post index"), &lang), cards, speed);
+    pages.write().expect("failed to lock the page cache").insert(key, page.clone());
+    Html(prefs::localize_times(&page, &TimeFormatter::new(user_tz, &headers, locales.text(&lang), config.time_display)))
+}
+
+/// Drops the cached pages that show the card of the post at `url_name`, leaving every other page cached
+pub fn purge_card(pages: &PageCache, url_name: &str) {
+    let card = format!(r#"data-post="{}""#, html! { (url_name) }.into_string());
+    pages.write().expect("failed to lock the page cache").retain(|_, page| !page.contains(&card));
+}
+
+/// The home page is cached once per language, layout, set of client hints and background speed
+pub fn home_cache_key(lang: &str, layout: LayoutMode, hints: ClientHints, speed: BackgroundSpeed) -> String {
+    format!("/?lang={}&layout={}&hints={}&speed={}", lang, layout.name(), hints.key(), speed.name())
+}
+
+/// `GET /layout/:mode`: remembers the reader's layout and sends them back to the home page
+pub async fn set_layout(Path(mode): Path<String>) -> Result<Response<Body>, StatusCode> {
+    let layout = LayoutMode::parse(&mode).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Response::builder()
+        .status(StatusCode::SEE_OTHER)
+        .header(hyper::header::SET_COOKIE, format!("{}={}; Path=/; Max-Age=31536000; SameSite=Lax", prefs::LAYOUT_COOKIE, layout.name()))
+        .header(hyper::header::LOCATION, "/")
+        .body(Body::empty())
+        .unwrap())
+}
+
+/// `GET /background/:speed`: remembers how fast the reader wants the background to scroll and sends them back to
+/// the home page
+pub async fn set_background_speed(Path(speed): Path<String>) -> Result<Response<Body>, StatusCode> {
+    let speed = BackgroundSpeed::parse(&speed).ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Response::builder()
+        .status(StatusCode::SEE_OTHER)
+        .header(hyper::header::SET_COOKIE, format!("{}={}; Path=/; Max-Age=31536000; SameSite=Lax", prefs::BACKGROUND_SPEED_COOKIE, speed.name()))
+        .header(hyper::header::LOCATION, "/")
+        .body(Body::empty())
+        .unwrap())
+}
@@ -0,0 +1,99 @@
+pub mod archive;
+pub mod assets;
+pub mod contact;
+pub mod graph;
+pub mod home;
+pub mod notes;
+pub mod post;
+pub mod posts;
+pub mod projects;
+pub mod search;
+pub mod stats;
+pub mod tags;
+
+use axum::extract::State;
+use axum::routing::{get, patch, post};
+use axum::Router;
+
+use crate::state::AppState;
+use crate::{access, admin, backup, bots, comments, dev, events, extract, feeds, icons, maintenance, metrics, og, outbound, polls, prefs, pwa, reactions, theme, thumbnail, vendor};
+
+/// Every route of the site. Dev mode's live reload is layered on by [`build_app`] since it needs a background watcher.
+fn router(state: &AppState) -> Router<AppState> {
+    let app = Router::new()
+        .route("/", get(home::handler))
+        .route("/layout/:mode", get(home::set_layout))
+        .route("/background/:speed", get(home::set_background_speed))
+        .route("/contact", get(contact::contact))
+        .route("/post/:url_name", get(post::post_handler))
+        .route("/post/:url_name/react", post(reactions::react))
+        .route("/post/:url_name/comments", post(comments::submit))
+        .route("/post/:url_name/comments/:id/reply", get(comments::reply_form))
+        .route("/comments/:id", patch(comments::edit).delete(comments::delete))
+        .route(comments::AVATAR_PATH, get(comments::serve_avatar))
+        .route(comments::LIVE_PATH, get(comments::live))
+        .route("/post/:url_name/poll/:id", post(polls::vote))
+        .route("/post/:url_name/poll/:id/results", get(polls::results_fragment))
+        .route("/post/:url_name/plain", get(post::plain_post_handler))
+        .route("/post/:url_name/attachments/:file", get(assets::serve_attachment))
+        .route(events::EVENTS_PATH, get(events::events))
+        .route("/posts", get(posts::listing))
+        .route(crate::search::SEARCH_PATH, get(search::search))
+        .route(crate::search::SUGGEST_PATH, get(search::suggest))
+        .route(notes::NOTES_PATH, get(notes::notes_page))
+        .route(projects::PROJECTS_PATH, get(projects::projects_page))
+        .route(graph::GRAPH_PATH, get(graph::graph_page))
+        .route(graph::GRAPH_API_PATH, get(graph::api_graph))
+        .route(crate::render::sidebar::TAGS_PATH, get(tags::tags_page))
+        .route(archive::ARCHIVE_PATH, get(archive::archive_page))
+        .route(stats::STATS_PATH, get(stats::stats_page))
+        .route("/fragment/card/:url_name", get(posts::card_fragment))
+        .route(crate::render::sidebar::RECENT_PATH, get(posts::recent_fragment))
+        .route(crate::render::sidebar::ON_THIS_DAY_PATH, get(posts::on_this_day_fragment))
+        .route(crate::render::sidebar::RANDOM_PATH, get(posts::random))
+        .route("/asset/*filename", get(assets::handle_asset_request))
+        .route("/favicon.ico", get(assets::serve_favicon))
+        .route("/assets/vendor/:name", get(vendor::serve_vendor))
+        .route("/theme/:file", get(theme::serve_theme_file))
+        .route("/og/:file", get(og::serve_og_image))
+        .route(outbound::OUT_PATH, get(outbound::out))
+        .route(thumbnail::THUMB_PATH, get(thumbnail::serve_thumbnail))
+        .route("/sw.js", get(pwa::serve_service_worker))
+        .route("/precache.json", get(pwa::serve_precache_manifest))
+        .route("/site.webmanifest", get(icons::serve_manifest))
+        .route(feeds::ATOM_PATH, get(feeds::serve_atom))
+        .route(feeds::RSS_PATH, get(feeds::serve_rss))
+        .route(feeds::SITEMAP_PATH, get(feeds::serve_sitemap))
+        .route("/robots.txt", get(bots::serve_robots))
+        .route(metrics::METRICS_PATH, get(metrics::serve_metrics))
+        .route(admin::HEALTH_PATH, get(admin::health))
+        .route("/admin/maintenance", get(admin::maintenance_status).post(admin::set_maintenance))
+        .route("/admin/backup", post(backup::backup_now))
+        .route("/admin/preview", post(admin::create_preview))
+        .route("/admin/cache/purge", post(admin::purge_cache))
+        .route("/admin/comments", get(admin::comments_status).post(admin::set_comments))
+        .route("/admin/comments/:id", get(comments::moderation_page).post(comments::moderate));
+    let app = icons::ICON_SIZES.iter().fold(app, |app, (name, _)| {
+        app.route(&format!("/{}", name), get(move |State(state): State<AppState>| icons::serve_icon(name, state.icons)))
+    });
+    app.layer(axum::middleware::from_fn_with_state(state.clone(), extract::tz::echo_time_zone))
+        .layer(axum::middleware::from_fn(prefs::ask_for_hints))
+}
+
+/// The whole site as a router behind the access rules, the bot guard and maintenance mode, with dev mode's live reload and its background watcher when the
+/// config asks for it
+pub fn build_app(state: &AppState) -> Router {
+    let app = router(state);
+    let app = if state.config.dev {
+        let (changes, _) = tokio::sync::broadcast::channel(16);
+        tokio::spawn(dev::watch(changes.clone(), state.config.content_dir.clone()));
+        app.route(dev::RELOAD_PATH, get(move || dev::events(changes.clone())))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), dev::reload))
+    } else {
+        app
+    };
+    app.layer(axum::middleware::from_fn_with_state(state.clone(), maintenance::gate))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), bots::guard))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), access::enforce))
+        .with_state(state.clone())
+}
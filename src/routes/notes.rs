@@ -0,0 +1,109 @@
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Html;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use crate::extract::tz::UserTz;
+use crate::i18n::Text;
+use crate::model::note::Note;
+use crate::prefs::{self, TimeFormatter};
+use crate::render::markdown::{markdown_to_html, Context};
+use crate::render::{skip_link, timestamp, CALLOUT_CSS, DETAILS_CSS, FOCUS_CSS, SPOILER_CSS};
+use crate::state::AppState;
+use crate::{dev, feeds, icons, outbound, previews, pwa, vendor};
+
+pub const NOTES_PATH: &str = "/notes";
+
+fn render_note(note: &Note, cx: Context) -> Markup {
+    html! {
+        article class="note" id=(note.id) {
+            @if let Some(title) = note.title.as_deref().filter(|title| !title.trim().is_empty()) {
+                h3 class="h5" { (title) }
+            }
+            div class="note-body" { (markdown_to_html(&note.body, cx)) }
+            p class="text-muted small mb-0" {
+                a href=(format!("#{}", note.id)) class="text-reset" { (timestamp(&note.timestamp)) }
+                @for tag in &note.tags {
+                    " · #" (tag)
+                }
+            }
+        }
+    }
+}
+
+fn page(notes: &[Note], text: Text, cx: Context) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang=(text.lang) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                (icons::icon_links(&cx.config.theme_color))
+                title { (text.t("notes")) " - " (text.t("site_title")) }
+                link rel="alternate" type="application/atom+xml" href=(feeds::ATOM_PATH);
+                (vendor::stylesheet("bootstrap.min.css"))
+                style { r#"
+                    body {
+                        font-family: Arial, sans-serif;
+                        background-color: #121212;
+                        color: #e0e0e0;
+                    }
+                    .header {
+                        text-align: center;
+                        background-color: #343a40;
+                        color: #f0f0f0;
+                        padding: 20px;
+                    }
+                    .notes {
+                        max-width: 40rem;
+                    }
+                    .note {
+                        background-color: #1c1c1c;
+                        border-left: 3px solid #007bff;
+                        border-radius: 4px;
+                        padding: 12px 16px;
+                        margin-bottom: 16px;
+                    }
+                    .note-body p:last-child {
+                        margin-bottom: 8px;
+                    }
+                    .note-body a {
+                        color: #66b2ff;
+                    }
+                "# }
+                style { (PreEscaped(FOCUS_CSS)) }
+                style { (PreEscaped(DETAILS_CSS)) }
+                style { (PreEscaped(CALLOUT_CSS)) }
+                style { (PreEscaped(SPOILER_CSS)) }
+                style { (PreEscaped(outbound::CSS)) }
+                style { (PreEscaped(previews::CSS)) }
+            }
+            body {
+                (skip_link(text))
+                header class="header" {
+                    h1 { a href="/" class="text-reset text-decoration-none" { "The Caden Times" } }
+                }
+                main id="main" class="container my-4 notes" {
+                    h2 class="h4" { (text.t("notes")) }
+                    p class="text-muted" { (text.t("notes_intro")) }
+                    @for note in notes {
+                        (render_note(note, cx))
+                    }
+                    @if notes.is_empty() {
+                        p { (text.t("notes_empty")) }
+                    }
+                }
+                (pwa::register_script(cx.config.dev))
+                (dev::reload_script(cx.config.dev))
+            }
+        }
+    }
+}
+
+/// `GET /notes`: every note, newest first
+pub async fn notes_page(State(AppState { config, notes, locales, previews, .. }): State<AppState>, user_tz: UserTz, headers: HeaderMap) -> Html<String> {
+    let lang = locales.negotiate(&headers);
+    let text = locales.text(&lang);
+    let html = page(&notes.read().expect("failed to lock the notes"), text, Context { config: &config, previews: &previews }).into_string();
+    Html(prefs::localize_times(&html, &TimeFormatter::new(user_tz, &headers, text, config.time_display)))
+}
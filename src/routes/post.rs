@@ -0,0 +1,354 @@
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{Html, IntoResponse, Response};
+use chrono::Utc;
+use maud::{html, PreEscaped, DOCTYPE};
+
+use crate::admin::Admin;
+use crate::extract::client::Client;
+use crate::extract::preview::Preview;
+use crate::extract::tz::UserTz;
+use crate::extract::LangQuery;
+use crate::model::post::{post_lang, Post};
+use crate::polls::PagePolls;
+use crate::placeholder::{self, Cover};
+use crate::prefs::{self, BackgroundSpeed, ClientHints, TimeFormatter};
+use crate::render::listing::accent_style;
+use crate::render::sidebar::recent_box;
+use crate::render::markdown::{preprocessed_body, render_post, Context};
+use crate::render::{attachments, audio_player, hinted_css, last_updated, skip_link, timestamp, video_player, CALLOUT_CSS, DETAILS_CSS, FOCUS_CSS, PRINT_CSS, SPOILER_CSS};
+use crate::shortcode::{self, Segment};
+use crate::state::AppState;
+use crate::store::posts::{find_post, localized_listing, translations_of};
+use crate::{backdrop, comments, dev, gallery, icons, og, outbound, previews, pwa, reactions, share, theme, vendor};
+
+pub async fn post_handler(preview: Preview, State(AppState { config, posts, locales, reactions, polls, comments, related, links, previews, manifest, .. }): State<AppState>, Path(url_name): Path<String>, Query(query): Query<LangQuery>, user_tz: UserTz, client: Client, headers: HeaderMap) -> Response {
+    let negotiated = locales.negotiate(&headers);
+    let requested = query.lang.unwrap_or_else(|| negotiated.clone());
+    let text = locales.text(locales.find(&requested).unwrap_or(&negotiated));
+    let translations = translations_of(&posts, &url_name);
+    let hints = ClientHints::resolve(&headers);
+    let dev = config.dev;
+    let cx = Context { config: &config, previews: &previews };
+
+    if let Some(post) = find_post(&posts, &url_name, &requested).filter(|post| post.visible_to(Admin::authorized(&config, &headers) || preview.grants(&post.url_name))) {
+        let canonical = share::canonical_url(&post, &config.site_url, &headers, &client);
+        let page_polls = PagePolls::load(&config, &post.url_name, &post.body, &headers).await;
+        let related = related.get(post_lang(&post), &posts);
+        let related = related.related(&post.url_name);
+        let has_gallery = shortcode::split(&post.body).iter().any(|segment| matches!(segment, Segment::Gallery(_)));
+        let hero = post.hero_url().map(|hero| manifest.url(&hero));
+        let mut preprocessed = preprocessed_body(&post, hints, cx).iter();
+        let referenced_by: Vec<Post> = links.get(&posts).referenced_by(&post.url_name).iter().filter_map(|other| find_post(&posts, other, &requested)).collect();
+        let rendered_html = html! {
+            (maud::DOCTYPE)
+            html data-bs-theme="dark" lang=(post.lang.as_deref().unwrap_or(text.lang)) {
+                head {
+                    (vendor::script("markdown-tag.js"))
+                    meta charset="UTF-8";
+                    meta name="viewport" content="width=device-width, initial-scale=1.0";
+                    (icons::icon_links(&config.theme_color))
+                    title { (post.title) }
+                    link rel="canonical" href=(canonical);
+                    meta property="og:type" content="article";
+                    meta property="og:url" content=(canonical);
+                    meta property="og:title" content=(post.title);
+                    meta property="og:description" content=(post.summary);
+                    meta property="og:image" content=(og::image_for(&post, &config.site_url));
+                    @if let Some(alt) = &post.image_alt {
+                        meta property="og:image:alt" content=(alt);
+                    }
+                    meta name="twitter:card" content="summary_large_image";
+                    @if !post.listed() {
+                        meta name="robots" content="noindex";
+                        // Keeps a preview token from leaking to the sites the post links to
+                        meta name="referrer" content="no-referrer";
+                    }
+                    link rel="alternate" type="text/html" title=(text.t("reader_mode")) href=(format!("/post/{}/plain", post.url_name));
+                    @if translations.len() > 1 {
+                        @for (lang, _) in &translations {
+                            link rel="alternate" hreflang=(lang) href=(format!("/post/{}?lang={}", post.url_name, lang));
+                        }
+                    }
+                    (vendor::stylesheet("bootstrap.min.css"))
+                    link rel="stylesheet" href=(manifest.url(theme::STYLESHEET_PATH));
+                    style { r#"
+                        body {
+                            font-family: Arial, sans-serif;
+                            background-color: #121212;
+                            color: #e0e0e0;
+                            padding: 20px;
+                        }
+                        .container {
+                            max-width: 800px;
+                            margin: 0 auto;
+                        }
+                        .header, .footer {
+                            text-align: center;
+                            background-color: #343a40;
+                            color: #f0f0f0;
+                            padding: 20px;
+                        }
+                        .post-body {
+                            background-color: #1e1e1e;
+                            padding: 20px;
+                            border-radius: 8px;
+                            box-shadow: 0 4px 8px rgba(0, 0, 0, 0.3);
+                        }
+                        .footer {
+                            margin-top: 20px;
+                        }
+                        .post-hero {
+                            max-width: 800px;
+                            margin: 20px auto 0;
+                        }
+                        .post-hero img, .post-hero video {
+                            display: block;
+                            width: 100%;
+                            max-height: 320px;
+                            object-fit: cover;
+                            border-radius: 8px;
+                            border-bottom: 4px solid var(--accent, transparent);
+                        }
+                        article > h2 {
+                            color: var(--accent, inherit);
+                        }
+                        .btn-primary {
+                            background-color: #007bff;
+                            border-color: #007bff;
+                        }
+                    "# }
+                    style media="print" { (PreEscaped(PRINT_CSS)) }
+                    style { (PreEscaped(FOCUS_CSS)) }
+                    (hinted_css(hints, false))
+                    (backdrop::css(config.background_pattern, BackgroundSpeed::resolve(&headers, config.background_speed), hints))
+                    style { (PreEscaped(theme::CODE_CSS)) }
+                    style { (PreEscaped(DETAILS_CSS)) }
+                    style { (PreEscaped(CALLOUT_CSS)) }
+                    style { (PreEscaped(SPOILER_CSS)) }
+                    style { (PreEscaped(outbound::CSS)) }
+                    style { (PreEscaped(previews::CSS)) }
+                }
+                body {
+                    (skip_link(text))
+
+                    // Header
+                    header class="header" {
+                        h1 { "The Caden Times" }
+                    }
+                    @if let Some(cover) = hero.as_deref().and_then(|hero| placeholder::cover(hero, None, hints)) {
+                        div class="post-hero" style=[accent_style(&post)] {
+                            @match cover {
+                                Cover::Image(src) => { img src=(src) alt=""; }
+                                Cover::Video { src, autoplay, .. } => { video src=(src) aria-hidden=[autoplay.then_some("true")] autoplay[autoplay] controls[!autoplay] muted loop playsinline {} }
+                            }
+                        }
+                    }
+
+                    // Main Content Container
+                    main id="main" class="container" {
+                        article style=[accent_style(&post)] {
+                            h2 { (post.title) }
+                            p class="text-muted" {
+                                (timestamp(&post.timestamp))
+                                (last_updated(&post, text))
+                            }
+                            (audio_player(&post, text, hints))
+                            (video_player(&post, text, hints))
+                            @if translations.len() > 1 {
+                                nav class="language-switcher mb-3" aria-label=(text.t("translations")) {
+                                    (text.t("translations")) ": "
+                                    @for (lang, title) in &translations {
+                                        @if lang == post_lang(&post) {
+                                            strong class="me-2" { (locales.language_name(lang)) }
+                                        } @else {
+                                            a class="me-2" href=(format!("/post/{}?lang={}", post.url_name, lang)) hreflang=(lang) lang=(lang) title=(title) {
+                                                (locales.language_name(lang))
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            div class="post-body" {
+                                @for segment in shortcode::split(&post.body) {
+                                    @match segment {
+                                        Segment::Markdown(_) => { github-md { (preprocessed.next().map(String::as_str).unwrap_or_default()) } }
+                                        Segment::Poll(id) => (page_polls.widget(id, &polls, text)),
+                                        Segment::Gallery(images) => (gallery::widget(&images, text)),
+                                    }
+                                }
+                            }
+                            (attachments(&post, text, &config.content_dir))
+                            (reactions::widget(&post.url_name, &reactions, &reactions::reacted(&config.cookie_key, &headers), text))
+                            (share::widget(&canonical, &post.title, &config.mastodon_share, text))
+                            (comments::widget(&post.url_name, &comments.on(&post.url_name), &comments.changeable(&comments::written(&config.cookie_key, &headers), Utc::now()), comments.open_on(&post), text))
+                            @if comments.open_on(&post) {
+                                (comments::live_script(&post.url_name))
+                            }
+                            @if !related.is_empty() {
+                                nav class="related-posts mt-4" aria-label=(text.t("related_posts")) {
+                                    h3 class="h5" { (text.t("related_posts")) }
+                                    ul class="list-unstyled" {
+                                        @for other in &related {
+                                            li { a href=(share::post_url("", other)) { (other.title) } }
+                                        }
+                                    }
+                                }
+                            }
+                            @if !referenced_by.is_empty() {
+                                nav class="referenced-by mt-4" aria-label=(text.t("referenced_by")) {
+                                    h3 class="h5" { (text.t("referenced_by")) }
+                                    ul class="list-unstyled" {
+                                        @for other in &referenced_by {
+                                            li { a href=(share::post_url("", other)) { (other.title) } }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        aside class="mt-4" aria-label=(text.t("recent_posts")) {
+                            (recent_box(None, text))
+                        }
+                        a href="/" class="btn btn-primary mt-4" { (text.t("back_home")) }
+                    }
+
+                    // Footer
+                    footer class="footer" {
+                        p { (text.t("post_footer")) }
+                    }
+
+                    @if has_gallery {
+                        (gallery::lightbox(text))
+                    }
+                    (theme::code_script(&manifest, text.t("code_copy"), text.t("code_copied")))
+                    (vendor::script("htmx.min.js"))
+                    (pwa::register_script(dev))
+                    (dev::reload_script(dev))
+                }
+            }
+        };
+        kept_private(&post, Html(prefs::localize_times(&rendered_html.into_string(), &TimeFormatter::new(user_tz, &headers, text, config.time_display))))
+    }   else {
+        // Render a 404 page with consistent styling if the post is not found
+        let rendered_html = html! {
+            (maud::DOCTYPE)
+            html lang=(text.lang) {
+                head {
+                    meta charset="UTF-8";
+                    meta name="viewport" content="width=device-width, initial-scale=1.0";
+                    (icons::icon_links(&config.theme_color))
+                    title { (text.t("not_found_title")) }
+                    (vendor::stylesheet("bootstrap.min.css"))
+                    style { r#"
+                        body {
+                            font-family: Arial, sans-serif;
+                            background-color: #121212;
+                            color: #e0e0e0;
+                            padding: 20px;
+                        }
+                        .container {
+                            max-width: 800px;
+                            margin: 0 auto;
+                            text-align: center;
+                        }
+                        .header, .footer {
+                            text-align: center;
+                            background-color: #343a40;
+                            color: #f0f0f0;
+                            padding: 20px;
+                        }
+                        .error-message {
+                            background-color: #1e1e1e;
+                            padding: 20px;
+                            border-radius: 8px;
+                            box-shadow: 0 4px 8px rgba(0, 0, 0, 0.3);
+                        }
+                        .footer {
+                            margin-top: 20px;
+                        }
+                        .btn-primary {
+                            background-color: #007bff;
+                            border-color: #007bff;
+                        }
+                    "# }
+                    style { (PreEscaped(FOCUS_CSS)) }
+                }
+                body {
+                    (skip_link(text))
+
+                    // Header
+                    header class="header" {
+                        h1 { "The Caden Times" }
+                    }
+
+                    // Main Content Container
+                    main id="main" class="container" {
+                        div class="error-message" {
+                            h2 { (text.t("not_found_title")) }
+                            p { (text.t("not_found_text")) }
+                            a href="/" class="btn btn-primary mt-4" { (text.t("back_home")) }
+                        }
+                        aside class="error-message mt-4 text-start" aria-label=(text.t("recent_posts")) {
+                            (recent_box(Some(&localized_listing(&posts.read().expect("failed to lock the post index"), text.lang)), text))
+                        }
+                    }
+
+                    // Footer
+                    footer class="footer" {
+                        p { (text.t("post_footer")) }
+                    }
+                }
+            }
+        };
+        (StatusCode::NOT_FOUND, Html(prefs::localize_times(&rendered_html.into_string(), &TimeFormatter::new(user_tz, &headers, text, config.time_display)))).into_response()
+    }
+
+}
+
+/// The page for a post, marked `private, no-store` when the post isn't listed so neither shared caches nor the
+/// service worker keep it after a preview link expires
+fn kept_private(post: &Post, page: Html<String>) -> Response {
+    let mut response = page.into_response();
+    if !post.listed() {
+        response.headers_mut().insert(header::CACHE_CONTROL, HeaderValue::from_static("private, no-store"));
+    }
+    response
+}
+
+/// Reader mode: the post rendered server side with a little inline CSS and no scripts, for text browsers and slow connections
+pub async fn plain_post_handler(preview: Preview, State(AppState { config, posts, locales, polls, previews, .. }): State<AppState>, Path(url_name): Path<String>, Query(query): Query<LangQuery>, user_tz: UserTz, headers: HeaderMap) -> Result<Response, StatusCode> {
+    let negotiated = locales.negotiate(&headers);
+    let requested = query.lang.unwrap_or_else(|| negotiated.clone());
+    let text = locales.text(locales.find(&requested).unwrap_or(&negotiated));
+    let post = find_post(&posts, &url_name, &requested).filter(|post| post.visible_to(Admin::authorized(&config, &headers) || preview.grants(&post.url_name))).ok_or(StatusCode::NOT_FOUND)?;
+    let page_polls = PagePolls::load(&config, &post.url_name, &post.body, &headers).await;
+
+    Ok(kept_private(&post, Html(prefs::localize_times(&html! {
+        (DOCTYPE)
+        html lang=(post.lang.as_deref().unwrap_or(text.lang)) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                title { (post.title) }
+                link rel="canonical" href=(format!("/post/{}", post.url_name));
+                style { r#"
+                    body { max-width: 40em; margin: 0 auto; padding: 1em; font-family: Georgia, serif; line-height: 1.6; color: #222; background: #fff; }
+                    img { max-width: 100%; height: auto; }
+                    pre { overflow-x: auto; background: #f4f4f4; padding: 0.5em; }
+                    .text-muted { color: #666; }
+                "# }
+                style media="print" { (PreEscaped(PRINT_CSS)) }
+                style { (PreEscaped(FOCUS_CSS)) }
+                style { (PreEscaped(theme::CODE_CSS)) }
+                style { (PreEscaped(SPOILER_CSS)) }
+                style { (PreEscaped(outbound::CSS)) }
+                style { (PreEscaped(previews::CSS)) }
+            }
+            body {
+                main { (render_post(&post, text, &polls, &page_polls, ClientHints::resolve(&headers), Context { config: &config, previews: &previews })) }
+                hr;
+                p { a href=(format!("/post/{}", post.url_name)) { (text.t("full_version")) } " | " a href="/" { "The Caden Times" } }
+            }
+        }
+    }.into_string(), &TimeFormatter::new(user_tz, &headers, text, config.time_display)))))
+}
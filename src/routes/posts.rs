@@ -0,0 +1,73 @@
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{Html, IntoResponse, Redirect, Response};
+use chrono::Utc;
+
+use crate::admin::Admin;
+use crate::extract::fragment::{self, Partial};
+use crate::extract::tz::UserTz;
+use crate::prefs::{self, ClientHints, LayoutMode, TimeFormatter};
+use crate::render::listing::{render_listing_item, render_posts_fragment, Cards};
+use crate::render::sidebar::{on_this_day, on_this_day_widget, recent_posts};
+use crate::state::AppState;
+use crate::store::posts::{find_post, localized_listing, random_post};
+
+/// `GET /posts`: the post listing on its own for htmx and unpoly, or the whole home page around it for a browser
+/// that visits it directly
+pub async fn listing(Partial(partial): Partial, State(state): State<AppState>, user_tz: UserTz, headers: HeaderMap) -> Response {
+    if !partial {
+        let page = super::home::handler(State(state), user_tz, headers).await;
+        return ([(header::VARY, fragment::VARY)], page).into_response();
+    }
+
+    let AppState { config, posts, locales, reactions, manifest, .. } = state;
+    let lang = locales.negotiate(&headers);
+    let text = locales.text(&lang);
+    let listing = localized_listing(&posts.read().expect("failed to lock the post index"), &lang);
+    let cards = Cards { text, reactions: &reactions, manifest: &manifest, layout: LayoutMode::resolve(&headers, config.layout), hints: ClientHints::resolve(&headers), config: &config };
+    let html = render_posts_fragment(&listing, cards).into_string();
+    ([(header::VARY, fragment::VARY)], Html(prefs::localize_times(&html, &TimeFormatter::new(user_tz, &headers, text, config.time_display)))).into_response()
+}
+
+/// Just the card for one post, fetched by the home page when the post is published while it's open. Visited
+/// directly it sends the browser to the post itself.
+pub async fn card_fragment(admin: Option<Admin>, Partial(partial): Partial, State(AppState { config, posts, locales, reactions, manifest, .. }): State<AppState>, Path(url_name): Path<String>, user_tz: UserTz, headers: HeaderMap) -> Result<Response, StatusCode> {
+    let lang = locales.negotiate(&headers);
+    let text = locales.text(&lang);
+    let post = find_post(&posts, &url_name, &lang).filter(|post| post.visible_to(admin.is_some())).ok_or(StatusCode::NOT_FOUND)?;
+    if !partial {
+        return Ok(([(header::VARY, fragment::VARY)], Redirect::to(&format!("/post/{}", post.url_name))).into_response());
+    }
+
+    let cards = Cards { text, reactions: &reactions, manifest: &manifest, layout: LayoutMode::resolve(&headers, config.layout), hints: ClientHints::resolve(&headers), config: &config };
+    let item = render_listing_item(&post, cards);
+    Ok(([(header::VARY, fragment::VARY)], Html(prefs::localize_times(&item.into_string(), &TimeFormatter::new(user_tz, &headers, text, config.time_display)))).into_response())
+}
+
+/// `GET /fragments/recent`: the newest posts in the reader's language, for the boxes htmx keeps fresh on the home,
+/// post and not found pages
+pub async fn recent_fragment(State(AppState { config, posts, locales, .. }): State<AppState>, user_tz: UserTz, headers: HeaderMap) -> Response {
+    let lang = locales.negotiate(&headers);
+    let text = locales.text(&lang);
+    let listing = localized_listing(&posts.read().expect("failed to lock the post index"), &lang);
+    let html = recent_posts(&listing, text).into_string();
+    ([(header::VARY, "Accept-Language")], Html(prefs::localize_times(&html, &TimeFormatter::new(user_tz, &headers, text, config.time_display)))).into_response()
+}
+
+/// `GET /fragments/on-this-day`: the posts published on the reader's date in earlier years, worked out in their
+/// timezone, or nothing
+pub async fn on_this_day_fragment(State(AppState { posts, locales, .. }): State<AppState>, user_tz: UserTz, headers: HeaderMap) -> Response {
+    let lang = locales.negotiate(&headers);
+    let text = locales.text(&lang);
+    let listing = localized_listing(&posts.read().expect("failed to lock the post index"), &lang);
+    let today = Utc::now().with_timezone(&user_tz.tz).date_naive();
+    let html = on_this_day_widget(&on_this_day(&listing, today, user_tz.tz), today, text).into_string();
+    ([(header::VARY, "Accept-Language"), (header::CACHE_CONTROL, "private, no-cache")], Html(html)).into_response()
+}
+
+/// `GET /random`: a `302` to a public post picked at random, or home when there are none
+pub async fn random(State(AppState { posts, .. }): State<AppState>) -> Response {
+    let posts = posts.read().expect("failed to lock the post index");
+    let location = random_post(&posts, uuid::Uuid::new_v4().as_u128()).map_or_else(|| "/".to_string(), |url_name| format!("/post/{}", url_name));
+    (StatusCode::FOUND, [(header::LOCATION, location), (header::CACHE_CONTROL, "no-store".to_string())]).into_response()
+}
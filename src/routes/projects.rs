@@ -0,0 +1,144 @@
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Html;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use crate::i18n::Text;
+use crate::model::project::{load_projects, Project};
+use crate::prefs::ClientHints;
+use crate::render::markdown::{markdown_to_html, Context};
+use crate::render::{skip_link, CARD_CSS, FOCUS_CSS};
+use crate::state::AppState;
+use crate::{dev, icons, placeholder, pwa, vendor};
+
+pub const PROJECTS_PATH: &str = "/projects";
+
+/// A project's card, with its first screenshot on top and the rest as thumbnails linking to the full images
+fn render_project(project: &Project, text: Text, cx: Context) -> Markup {
+    let screenshots = project.screenshot_urls();
+    html! {
+        div class="col" {
+            article class="card post-card" {
+                (placeholder::card_image(&project.name, screenshots.first().map(String::as_str).unwrap_or_default(), None, None, ClientHints::default()))
+                div class="card-body" {
+                    h3 class="card-title h5" { (project.name) }
+                    div class="card-text" { (markdown_to_html(&project.description, cx)) }
+                    @if screenshots.len() > 1 {
+                        div class="project-screenshots mb-3" {
+                            @for (index, screenshot) in screenshots.iter().enumerate().skip(1) {
+                                a href=(screenshot) {
+                                    img src=(screenshot) alt=(format!("{} {}", project.name, index + 1)) loading="lazy";
+                                }
+                            }
+                        }
+                    }
+                    @if !project.tags.is_empty() {
+                        p class="text-muted small" {
+                            @for tag in &project.tags {
+                                span class="me-2" { "#" (tag) }
+                            }
+                        }
+                    }
+                    @if let Some(repo) = &project.repo {
+                        a href=(repo) class="btn btn-primary btn-sm me-2" aria-label=(format!("{}: {}", text.t("project_repo"), project.name)) { (text.t("project_repo")) }
+                    }
+                    @if let Some(url) = &project.url {
+                        a href=(url) class="btn btn-outline-primary btn-sm" aria-label=(format!("{}: {}", text.t("project_visit"), project.name)) { (text.t("project_visit")) }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn page(projects: &[Project], text: Text, cx: Context) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang=(text.lang) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                (icons::icon_links(&cx.config.theme_color))
+                title { (text.t("projects")) " - " (text.t("site_title")) }
+                (vendor::stylesheet("bootstrap.min.css"))
+                style { r#"
+                    body {
+                        font-family: Arial, sans-serif;
+                        background-color: #121212;
+                        color: #e0e0e0;
+                    }
+                    .header {
+                        text-align: center;
+                        background-color: #343a40;
+                        color: #f0f0f0;
+                        padding: 20px;
+                    }
+                    .project-screenshots img {
+                        width: 64px;
+                        height: 48px;
+                        object-fit: cover;
+                        border-radius: 4px;
+                        margin-right: 6px;
+                    }
+                    .btn-outline-primary {
+                        color: #66b2ff;
+                        border-color: #66b2ff;
+                    }
+                "# }
+                style { (PreEscaped(FOCUS_CSS)) }
+                style { (PreEscaped(CARD_CSS)) }
+                style { (PreEscaped(placeholder::PLACEHOLDER_CSS)) }
+            }
+            body {
+                (skip_link(text))
+                header class="header" {
+                    h1 { a href="/" class="text-reset text-decoration-none" { "The Caden Times" } }
+                }
+                main id="main" class="container my-4" {
+                    h2 class="h4" { (text.t("projects")) }
+                    p class="text-muted" { (text.t("projects_intro")) }
+                    @if projects.is_empty() {
+                        p { (text.t("projects_empty")) }
+                    } @else {
+                        div class="row row-cols-1 row-cols-md-2 row-cols-lg-3 g-3" data-layout="grid" {
+                            @for project in projects {
+                                (render_project(project, text, cx))
+                            }
+                        }
+                    }
+                }
+                (pwa::register_script(cx.config.dev))
+                (dev::reload_script(cx.config.dev))
+            }
+        }
+    }
+}
+
+/// `GET /projects`: a card for every project in `projects.toml`
+pub async fn projects_page(State(AppState { config, locales, previews, .. }): State<AppState>, headers: HeaderMap) -> Html<String> {
+    let lang = locales.negotiate(&headers);
+    Html(page(&load_projects(&config.content_dir).await, locales.text(&lang), Context { config: &config, previews: &previews }).into_string())
+}
+
+#[test]
+fn project_cards_link_to_the_code_and_the_project() {
+    let (config, previews) = (crate::config::Config::default(), crate::previews::Previews::load("/dev/null"));
+    let locales = crate::i18n::Locales::load(&config.content_dir);
+    let projects = crate::model::project::parse_projects(
+        r#"
+        [[project]]
+        name = "Line follower"
+        description = "Follows *tape*"
+        repo = "https://github.com/example/line-follower"
+        url = "https://line.example"
+        screenshots = ["robot.jpg", "robot-2.jpg"]
+        tags = ["robotics"]
+        "#,
+    )
+    .unwrap();
+    let html = page(&projects, locales.text("en"), Context { config: &config, previews: &previews }).into_string();
+    assert!(html.contains(r#"<img src="/asset/robot.jpg" class="card-img-top" alt="Line follower""#), "{}", html);
+    assert!(html.contains(r#"<a href="/asset/robot-2.jpg"><img src="/asset/robot-2.jpg" alt="Line follower 2" loading="lazy"></a>"#), "{}", html);
+    assert!(html.contains("<em>tape</em>") && html.contains("#robotics"), "{}", html);
+    assert!(html.contains(r#"href="https://github.com/example/line-follower""#) && html.contains(r#"href="https://line.example""#), "{}", html);
+}
@@ -0,0 +1,133 @@
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap};
+use axum::response::{Html, IntoResponse, Json, Response};
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+use serde::Deserialize;
+
+use crate::extract::fragment::{self, Partial};
+use crate::extract::tz::UserTz;
+use crate::i18n::Text;
+use crate::prefs::{self, TimeFormatter};
+use crate::render::{skip_link, timestamp, with_date, FOCUS_CSS};
+use crate::search::{Hit, SEARCH_PATH};
+use crate::state::AppState;
+use crate::store::posts::localized_listing;
+use crate::{icons, vendor};
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub q: String,
+}
+
+/// `GET /search?q=`: posts matching the query with the matches highlighted, as a whole page or just the results
+/// for htmx and unpoly
+pub async fn search(Partial(partial): Partial, State(AppState { config, posts, locales, search, .. }): State<AppState>, Query(query): Query<SearchQuery>, user_tz: UserTz, headers: HeaderMap) -> Response {
+    let lang = locales.negotiate(&headers);
+    let text = locales.text(&lang);
+    let hits = search.search(&localized_listing(&posts.read().expect("failed to lock the post index"), &lang), &query.q);
+
+    let html = if partial { results(&query.q, &hits, text) } else { page(&query.q, &hits, &config.theme_color, text) };
+    let html = prefs::localize_times(&html.into_string(), &TimeFormatter::new(user_tz, &headers, text, config.time_display));
+    ([(header::VARY, fragment::VARY)], Html(html)).into_response()
+}
+
+/// Titles and tags offered for one prefix, few enough to fit under the search box
+const MAX_SUGGESTIONS: usize = 8;
+
+/// `GET /search/suggest?q=`: post titles and tags starting like the query, as a list for htmx to swap under the
+/// search box or as JSON for anything else
+pub async fn suggest(Partial(partial): Partial, State(AppState { posts, locales, suggestions, .. }): State<AppState>, Query(query): Query<SearchQuery>, headers: HeaderMap) -> Response {
+    let lang = locales.negotiate(&headers);
+    let index = suggestions.get(&lang, &posts);
+    let found = index.suggest(&query.q, MAX_SUGGESTIONS);
+    if !partial {
+        return ([(header::VARY, fragment::VARY)], Json(found)).into_response();
+    }
+
+    let list = html! {
+        @for suggestion in &found {
+            a class="list-group-item list-group-item-action" href=(suggestion.url) data-kind=(suggestion.kind) {
+                @if suggestion.kind == "tag" { "#" }
+                (suggestion.label)
+            }
+        }
+    };
+    ([(header::VARY, fragment::VARY)], Html(list.into_string())).into_response()
+}
+
+fn results(query: &str, hits: &[Hit], text: Text) -> Markup {
+    html! {
+        div id="search-results" {
+            @if !query.trim().is_empty() {
+                p class="text-muted" role="status" {
+                    @if hits.is_empty() { (text.t("search_no_results")) } @else { (text.t("search_results").replace("{n}", &hits.len().to_string())) }
+                }
+            }
+            @for hit in hits {
+                article class="search-result mb-4" data-post=(hit.post.url_name) {
+                    h2 class="h5 mb-1" {
+                        a href=(format!("/post/{}", hit.post.url_name)) { (hit.title) }
+                    }
+                    p class="text-muted small mb-1" { (with_date(text.t("posted_on"), timestamp(&hit.post.timestamp))) }
+                    p class="mb-0" { (hit.snippet) }
+                }
+            }
+        }
+    }
+}
+
+fn page(query: &str, hits: &[Hit], theme_color: &str, text: Text) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang=(text.lang) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                (icons::icon_links(theme_color))
+                title { (text.t("search")) " - " (text.t("site_title")) }
+                (vendor::stylesheet("bootstrap.min.css"))
+                (vendor::stylesheet("unpoly.min.css"))
+                style { r#"
+                    body {
+                        font-family: Arial, sans-serif;
+                        background-color: #121212;
+                        color: #e0e0e0;
+                    }
+                    .header {
+                        text-align: center;
+                        background-color: #343a40;
+                        color: #f0f0f0;
+                        padding: 20px;
+                    }
+                    .search-result a {
+                        color: #66b2ff;
+                    }
+                    mark {
+                        background-color: #5c4d00;
+                        color: inherit;
+                        padding: 0;
+                    }
+                    .text-muted {
+                        color: #a0a0a0 !important;
+                    }
+                "# }
+                style { (PreEscaped(FOCUS_CSS)) }
+            }
+            body {
+                (skip_link(text))
+                header class="header" {
+                    h1 { a href="/" class="text-reset text-decoration-none" { "The Caden Times" } }
+                }
+                main id="main" class="container my-4" style="max-width: 800px" {
+                    form action=(SEARCH_PATH) method="get" role="search" class="d-flex mb-4" up-target="#search-results" up-autosubmit {
+                        input type="search" name="q" value=(query) class="form-control me-2" aria-label=(text.t("search")) placeholder=(text.t("search_placeholder"));
+                        button type="submit" class="btn btn-primary" { (text.t("search")) }
+                    }
+                    (results(query, hits, text))
+                }
+                (vendor::script("unpoly.min.js"))
+            }
+        }
+    }
+}
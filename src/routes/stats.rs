@@ -0,0 +1,213 @@
+use axum::extract::State;
+use axum::http::{header, HeaderMap};
+use axum::response::{Html, IntoResponse, Response};
+use chrono::{Datelike, NaiveDate};
+use chrono_tz::Tz;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use crate::excerpt::plain_text;
+use crate::extract::tz::UserTz;
+use crate::i18n::Text;
+use crate::model::post::Post;
+use crate::render::sidebar::{tag_counts, tag_url};
+use crate::render::{skip_link, FOCUS_CSS};
+use crate::routes::archive::{posts_per_day, ARCHIVE_PATH};
+use crate::state::AppState;
+use crate::store::posts::localized_listing;
+use crate::{icons, vendor};
+
+pub const STATS_PATH: &str = "/stats";
+
+/// Width of a month's bar in the cadence chart, and the gap after it
+const BAR: i64 = 14;
+const GAP: i64 = 4;
+/// Height of the busiest month's bar
+const CHART_HEIGHT: i64 = 120;
+
+/// Figures about the public posts, with days counted in the reader's timezone
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub posts: usize,
+    pub words: usize,
+    /// Tags with how many posts have them, most used first
+    pub tags: Vec<(String, usize)>,
+    /// `(year, month, posts)` for every month from the first post to the last, quiet months included
+    pub months: Vec<(i32, u32, usize)>,
+    /// The most days in a row with a post, and the first of those days
+    pub longest_streak: Option<(usize, NaiveDate)>,
+}
+
+impl Stats {
+    pub fn build(posts: &[Post], tz: Tz) -> Stats {
+        let days = posts_per_day(posts, tz);
+
+        let mut months: Vec<(i32, u32, usize)> = Vec::new();
+        if let (Some(first), Some(last)) = (days.keys().next(), days.keys().next_back()) {
+            let (mut year, mut month) = (first.year(), first.month());
+            while (year, month) <= (last.year(), last.month()) {
+                let count = days.iter().filter(|(date, _)| (date.year(), date.month()) == (year, month)).map(|(_, count)| count).sum();
+                months.push((year, month, count));
+                (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+            }
+        }
+
+        let mut longest_streak: Option<(usize, NaiveDate)> = None;
+        let mut current: Option<(usize, NaiveDate)> = None;
+        let mut previous: Option<NaiveDate> = None;
+        for date in days.keys() {
+            current = match (current, previous) {
+                (Some((length, start)), Some(previous)) if previous.succ_opt() == Some(*date) => Some((length + 1, start)),
+                _ => Some((1, *date)),
+            };
+            if current.map(|(length, _)| length) > longest_streak.map(|(length, _)| length) {
+                longest_streak = current;
+            }
+            previous = Some(*date);
+        }
+
+        Stats {
+            posts: posts.len(),
+            words: posts.iter().map(|post| plain_text(&post.body).split_whitespace().count()).sum(),
+            tags: tag_counts(posts),
+            months,
+            longest_streak,
+        }
+    }
+}
+
+/// A bar for each month of [`Stats::months`], as tall as its share of the busiest month
+fn cadence_chart(months: &[(i32, u32, usize)], text: Text) -> Markup {
+    let most = months.iter().map(|(_, _, count)| *count).max().unwrap_or(0).max(1);
+    html! {
+        svg class="cadence" xmlns="http://www.w3.org/2000/svg" width=(months.len() as i64 * (BAR + GAP)) height=(CHART_HEIGHT) role="img" aria-label=(text.t("stats_cadence")) {
+            @for (index, (year, month, count)) in months.iter().enumerate() {
+                @let height = (*count as i64 * CHART_HEIGHT / most as i64).max(if *count > 0 { 2 } else { 1 });
+                rect x=(index as i64 * (BAR + GAP)) y=(CHART_HEIGHT - height) width=(BAR) height=(height) fill=(if *count > 0 { "#26a641" } else { "#2d333b" }) {
+                    title { (text.t(if *count == 1 { "month_post" } else { "month_posts" }).replace("{count}", &count.to_string()).replace("{month}", &format!("{}-{:02}", year, month))) }
+                }
+            }
+        }
+    }
+}
+
+fn page(stats: &Stats, theme_color: &str, text: Text) -> Markup {
+    let most_tagged = stats.tags.first().map_or(1, |(_, count)| *count);
+    html! {
+        (DOCTYPE)
+        html lang=(text.lang) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                (icons::icon_links(theme_color))
+                title { (text.t("stats")) " - " (text.t("site_title")) }
+                (vendor::stylesheet("bootstrap.min.css"))
+                style { r#"
+                    body {
+                        font-family: Arial, sans-serif;
+                        background-color: #121212;
+                        color: #e0e0e0;
+                    }
+                    .header {
+                        text-align: center;
+                        background-color: #343a40;
+                        color: #f0f0f0;
+                        padding: 20px;
+                    }
+                    .cadence-scroll {
+                        overflow-x: auto;
+                    }
+                    .tag-bar {
+                        display: inline-block;
+                        height: 0.8em;
+                        background-color: #26a641;
+                    }
+                    main a {
+                        color: #66b2ff;
+                    }
+                "# }
+                style { (PreEscaped(FOCUS_CSS)) }
+            }
+            body {
+                (skip_link(text))
+                header class="header" {
+                    h1 { a href="/" class="text-reset text-decoration-none" { "The Caden Times" } }
+                }
+                main id="main" class="container my-4" {
+                    h2 class="h4" { (text.t("stats")) }
+                    @if stats.posts == 0 {
+                        p { (text.t("no_posts_yet")) }
+                    } @else {
+                        dl class="row" {
+                            dt class="col-sm-4" { (text.t("stats_posts")) }
+                            dd class="col-sm-8" { (stats.posts) }
+                            dt class="col-sm-4" { (text.t("stats_words")) }
+                            dd class="col-sm-8" { (stats.words) }
+                            @if let Some((days, start)) = stats.longest_streak {
+                                dt class="col-sm-4" { (text.t("stats_streak")) }
+                                dd class="col-sm-8" {
+                                    (text.t(if days == 1 { "streak_day" } else { "streak_days" }).replace("{days}", &days.to_string()))
+                                    " "
+                                    a class="text-muted small" href={ (ARCHIVE_PATH) "?day=" (start) } { (start) }
+                                }
+                            }
+                        }
+                        h3 class="h5" { (text.t("stats_cadence")) }
+                        div class="cadence-scroll mb-4" { (cadence_chart(&stats.months, text)) }
+                        @if !stats.tags.is_empty() {
+                            h3 class="h5" { (text.t("stats_per_tag")) }
+                            table class="table table-dark table-sm" {
+                                tbody {
+                                    @for (tag, count) in &stats.tags {
+                                        tr {
+                                            td { a href=(tag_url(tag)) { (tag) } }
+                                            td class="text-end" { (count) }
+                                            td class="w-50" { span class="tag-bar" style={ "width: " (count * 100 / most_tagged) "%" } {} }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `GET /stats`: how much has been written and how often, from the public posts in the reader's language
+pub async fn stats_page(State(AppState { config, posts, locales, .. }): State<AppState>, user_tz: UserTz, headers: HeaderMap) -> Response {
+    let lang = locales.negotiate(&headers);
+    let listing = localized_listing(&posts.read().expect("failed to lock the post index"), &lang);
+    let html = page(&Stats::build(&listing, user_tz.tz), &config.theme_color, locales.text(&lang)).into_string();
+    ([(header::VARY, "Accept-Language")], Html(html)).into_response()
+}
+
+#[test]
+fn stats_count_words_months_and_the_longest_streak() {
+    let post = |timestamp: &str, body: &str, tags: &[&str]| -> Post {
+        serde_json::from_value(serde_json::json!({ "title": "", "body": body, "image_url": "", "summary": "", "timestamp": timestamp, "tags": tags })).unwrap()
+    };
+    let posts = vec![
+        post("2024-01-30T12:00:00Z", "One *two* three", &["Rust"]),
+        post("2024-01-31T12:00:00Z", "Four", &["rust", "Web"]),
+        post("2024-02-01T12:00:00Z", "[Five](/post/x)\n\n# Six", &[]),
+        post("2024-04-10T23:30:00Z", "", &[]),
+        post("2024-04-11T12:00:00Z", "", &[]),
+    ];
+    let stats = Stats::build(&posts, Tz::UTC);
+    assert_eq!((stats.posts, stats.words), (5, 6));
+    assert_eq!(stats.tags, vec![("Rust".to_string(), 2), ("Web".to_string(), 1)]);
+    assert_eq!(stats.months, vec![(2024, 1, 2), (2024, 2, 1), (2024, 3, 0), (2024, 4, 2)]);
+    assert_eq!(stats.longest_streak, Some((3, NaiveDate::from_ymd_opt(2024, 1, 30).unwrap())));
+    // Both April posts fall on the 11th in Tokyo
+    let tokyo = Stats::build(&posts, Tz::Asia__Tokyo);
+    assert_eq!(tokyo.months.last(), Some(&(2024, 4, 2)));
+    assert_eq!(tokyo.longest_streak, Some((3, NaiveDate::from_ymd_opt(2024, 1, 30).unwrap())));
+    assert_eq!(Stats::build(&posts[3..], Tz::Asia__Tokyo).longest_streak, Some((1, NaiveDate::from_ymd_opt(2024, 4, 11).unwrap())));
+    assert_eq!(Stats::build(&[], Tz::UTC).longest_streak, None);
+
+    let locales = crate::i18n::Locales::load(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR));
+    let chart = cadence_chart(&stats.months, locales.text("en")).into_string();
+    assert!(chart.contains(r##"<rect x="0" y="0" width="14" height="120" fill="#26a641"><title>2 posts in 2024-01</title></rect>"##), "{}", chart);
+    assert!(chart.contains(r##"<rect x="36" y="119" width="14" height="1" fill="#2d333b"><title>0 posts in 2024-03</title>"##), "{}", chart);
+}
@@ -0,0 +1,65 @@
+use axum::extract::State;
+use axum::http::HeaderMap;
+use axum::response::Html;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use crate::i18n::Text;
+use crate::render::sidebar::{tag_cloud, tag_counts};
+use crate::render::{skip_link, FOCUS_CSS};
+use crate::state::AppState;
+use crate::store::posts::localized_listing;
+use crate::{icons, vendor};
+
+fn page(counts: &[(String, usize)], theme_color: &str, text: Text) -> Markup {
+    html! {
+        (DOCTYPE)
+        html lang=(text.lang) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                (icons::icon_links(theme_color))
+                title { (text.t("all_tags")) " - " (text.t("site_title")) }
+                (vendor::stylesheet("bootstrap.min.css"))
+                style { r#"
+                    body {
+                        font-family: Arial, sans-serif;
+                        background-color: #121212;
+                        color: #e0e0e0;
+                    }
+                    .header {
+                        text-align: center;
+                        background-color: #343a40;
+                        color: #f0f0f0;
+                        padding: 20px;
+                    }
+                    .tag-cloud a {
+                        color: #66b2ff;
+                    }
+                "# }
+                style { (PreEscaped(FOCUS_CSS)) }
+            }
+            body {
+                (skip_link(text))
+                header class="header" {
+                    h1 { a href="/" class="text-reset text-decoration-none" { "The Caden Times" } }
+                }
+                main id="main" class="container my-4" {
+                    h2 class="h4" { (text.t("all_tags")) }
+                    p class="text-muted" { (text.t("tags_intro")) }
+                    @if counts.is_empty() {
+                        p { (text.t("no_posts_yet")) }
+                    } @else {
+                        (tag_cloud(counts, counts.len(), text))
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// `GET /tags`: every tag of the public posts in the reader's language as one cloud
+pub async fn tags_page(State(AppState { config, posts, locales, .. }): State<AppState>, headers: HeaderMap) -> Html<String> {
+    let lang = locales.negotiate(&headers);
+    let listing = localized_listing(&posts.read().expect("failed to lock the post index"), &lang);
+    Html(page(&tag_counts(&listing), &config.theme_color, locales.text(&lang)).into_string())
+}
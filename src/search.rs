@@ -0,0 +1,256 @@
+#[cfg(feature = "tantivy")]
+mod index;
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use maud::{html, Markup};
+
+use crate::excerpt;
+use crate::model::post::Post;
+
+pub const SEARCH_PATH: &str = "/search";
+pub const SUGGEST_PATH: &str = "/search/suggest";
+
+/// Characters of body text shown around the best match
+const SNIPPET_CHARS: usize = 200;
+
+/// Results listed for one query
+pub const MAX_RESULTS: usize = 20;
+
+/// Words of a query that count, the rest are ignored
+const MAX_TERMS: usize = 8;
+
+/// How much a match counts for, by where it is
+const TITLE_WEIGHT: usize = 10;
+const TAG_WEIGHT: usize = 5;
+/// Matches in a body past this many per term stop adding to its score, so long posts don't win by length alone
+const MAX_BODY_MATCHES: usize = 10;
+
+/// A post matching a search, with its title and a snippet of the best-matching part of the body highlighted
+pub struct Hit {
+    pub post: Post,
+    pub score: usize,
+    pub title: Markup,
+    pub snippet: Markup,
+}
+
+/// The words of a query, lowercased and without repeats
+pub fn terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for word in query.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()) {
+        let word = word.to_lowercase();
+        if !terms.contains(&word) {
+            terms.push(word);
+        }
+    }
+    terms.truncate(MAX_TERMS);
+    // Longest first, so a term that starts with another one wins the highlight
+    terms.sort_by_key(|term| std::cmp::Reverse(term.chars().count()));
+    terms
+}
+
+/// Where `/search` looks posts up. Backends are handed the reader's localized listing on every search and the
+/// whole post index whenever it changes, so they can keep an index of their own up to date.
+pub trait SearchBackend: Send + Sync {
+    /// The posts out of `posts` matching the query, best first
+    fn search(&self, posts: &[Post], query: &str) -> Vec<Hit>;
+
+    /// Catches up with a new post index
+    fn update(&self, _posts: &[Post]) {}
+}
+
+/// The backend picked by `CADEN_BLOG_SEARCH`: `tantivy` for an index when built with the `tantivy` feature,
+/// otherwise [`Scan`]
+pub fn from_env() -> Arc<dyn SearchBackend> {
+    match std::env::var("CADEN_BLOG_SEARCH").unwrap_or_default().trim() {
+        #[cfg(feature = "tantivy")]
+        "tantivy" => match index::TantivyIndex::new() {
+            Ok(index) => {
+                println!("Searching posts with a tantivy index");
+                return Arc::new(index);
+            }
+            Err(e) => println!("Couldn't create the search index, scanning posts instead: {}", e),
+        },
+        #[cfg(not(feature = "tantivy"))]
+        "tantivy" => println!("CADEN_BLOG_SEARCH=tantivy needs a build with the tantivy feature, scanning posts instead"),
+        "" | "scan" => {}
+        other => println!("Unknown search backend {}, scanning posts instead", other),
+    }
+    Arc::new(Scan)
+}
+
+/// Matches every post against the query as it's asked, which is plenty for a blog-sized archive
+pub struct Scan;
+
+impl SearchBackend for Scan {
+    /// The posts where every term starts a word in the title, the tags or the body
+    fn search(&self, posts: &[Post], query: &str) -> Vec<Hit> {
+        let terms = terms(query);
+        if terms.is_empty() {
+            return Vec::new();
+        }
+
+        let mut hits: Vec<Hit> = posts
+            .iter()
+            .filter_map(|post| {
+                let body = excerpt::plain_text(&post.body);
+                let (title_matches, body_matches) = (matches(&post.title, &terms), matches(&body, &terms));
+                let mut score = 0;
+                for term in &terms {
+                    let count = |ranges: &[Range<usize>], text: &str| ranges.iter().filter(|&range| text[range.clone()].to_lowercase() == *term).count();
+                    let in_title = count(&title_matches, &post.title);
+                    let in_tags = post.tags.iter().filter(|tag| !matches(tag, std::slice::from_ref(term)).is_empty()).count();
+                    let in_body = count(&body_matches, &body).min(MAX_BODY_MATCHES);
+                    if in_title + in_tags + in_body == 0 {
+                        return None;
+                    }
+                    score += in_title * TITLE_WEIGHT + in_tags * TAG_WEIGHT + in_body;
+                }
+                Some(hit(post, &terms, &body, score))
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.cmp(&a.score).then(b.post.timestamp.cmp(&a.post.timestamp)));
+        hits.truncate(MAX_RESULTS);
+        hits
+    }
+}
+
+/// A post found by a backend, with the terms highlighted in its title and a snippet of `body`, its plain text
+pub fn hit(post: &Post, terms: &[String], body: &str, score: usize) -> Hit {
+    let body_matches = matches(body, terms);
+    let snippet = match best_window(body, &body_matches) {
+        Some(window) => highlight_window(body, window, &body_matches),
+        None => highlight(&post.summary, &matches(&post.summary, terms)),
+    };
+    Hit { post: post.clone(), score, title: highlight(&post.title, &matches(&post.title, terms)), snippet }
+}
+
+/// The byte length of `term` at the start of `text`, compared without case
+fn match_at(text: &str, term: &str) -> Option<usize> {
+    let mut wanted = term.chars().peekable();
+    for (at, c) in text.char_indices() {
+        for lower in c.to_lowercase() {
+            if wanted.next() != Some(lower) {
+                return None;
+            }
+        }
+        if wanted.peek().is_none() {
+            return Some(at + c.len_utf8());
+        }
+    }
+    None
+}
+
+/// Where the terms start a word in `text`, in order and without overlaps
+pub fn matches(text: &str, terms: &[String]) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut previous: Option<char> = None;
+    let mut skip_to = 0;
+    for (at, c) in text.char_indices() {
+        let word_start = !previous.is_some_and(char::is_alphanumeric);
+        previous = Some(c);
+        if at < skip_to || !word_start {
+            continue;
+        }
+        if let Some(len) = terms.iter().find_map(|term| match_at(&text[at..], term)) {
+            ranges.push(at..at + len);
+            skip_to = at + len;
+        }
+    }
+    ranges
+}
+
+/// `text` escaped, with the `ranges` wrapped in `<mark>`
+pub fn highlight(text: &str, ranges: &[Range<usize>]) -> Markup {
+    let mut last = 0;
+    let mut parts = Vec::new();
+    for range in ranges {
+        parts.push((&text[last..range.start], &text[range.clone()]));
+        last = range.end;
+    }
+    html! {
+        @for (before, matched) in parts {
+            (before) mark { (matched) }
+        }
+        (text[last..])
+    }
+}
+
+/// The byte range of the [`SNIPPET_CHARS`] long stretch of `text` with the most different terms in it, then the
+/// most matches, starting a little before its first match and cut at word boundaries
+fn best_window(text: &str, ranges: &[Range<usize>]) -> Option<Range<usize>> {
+    let chars: Vec<usize> = text.char_indices().map(|(at, _)| at).chain([text.len()]).collect();
+    let char_of = |byte: usize| chars.partition_point(|&at| at < byte);
+
+    let window = |first: &Range<usize>| {
+        let start = char_of(first.start).saturating_sub(SNIPPET_CHARS / 4);
+        let end = (start + SNIPPET_CHARS).min(chars.len() - 1);
+        chars[start]..chars[end]
+    };
+    let best = ranges.iter().map(window).max_by_key(|window| {
+        let inside: Vec<&str> = ranges.iter().filter(|range| window.start <= range.start && range.end <= window.end).map(|range| &text[range.clone()]).collect();
+        let mut distinct: Vec<String> = inside.iter().map(|matched| matched.to_lowercase()).collect();
+        distinct.sort();
+        distinct.dedup();
+        // Earlier windows win ties, `max_by_key` keeps the last maximum
+        (distinct.len(), inside.len(), std::cmp::Reverse(window.start))
+    })?;
+
+    // Don't start or end halfway through a word, as long as that doesn't cut off a match
+    let first_match = ranges.iter().find(|range| range.start >= best.start).map_or(best.end, |range| range.start);
+    let start = match text[best.start..first_match].find(' ') {
+        Some(space) if best.start > 0 => best.start + space + 1,
+        _ => best.start,
+    };
+    let last_match = ranges.iter().rev().find(|range| range.end <= best.end).map_or(start, |range| range.end);
+    let end = match text[last_match..best.end].rfind(' ') {
+        Some(space) if best.end < text.len() => last_match + space,
+        _ => best.end,
+    };
+    Some(start..end)
+}
+
+fn highlight_window(text: &str, window: Range<usize>, ranges: &[Range<usize>]) -> Markup {
+    let inside: Vec<Range<usize>> = ranges
+        .iter()
+        .filter(|range| window.start <= range.start && range.end <= window.end)
+        .map(|range| range.start - window.start..range.end - window.start)
+        .collect();
+    html! {
+        @if window.start > 0 { "…" }
+        (highlight(&text[window.clone()], &inside))
+        @if window.end < text.len() { "…" }
+    }
+}
+
+#[test]
+fn matches_start_words_and_ignore_case() {
+    let terms = terms("rust RUST, Ünï");
+    assert_eq!(terms, vec!["rust", "ünï"]);
+    let text = "Trust Rustaceans with ÜNÏCODE";
+    assert_eq!(matches(text, &terms), vec![6..10, 22..27]);
+    assert_eq!(highlight(text, &matches(text, &terms)).into_string(), "Trust <mark>Rust</mark>aceans with <mark>ÜNÏ</mark>CODE");
+    assert_eq!(highlight("<b>rust</b>", &matches("<b>rust</b>", &terms)).into_string(), "&lt;b&gt;<mark>rust</mark>&lt;/b&gt;");
+}
+
+#[test]
+fn snippets_come_from_the_best_matching_part_of_the_body() {
+    let filler = "filler words go here ".repeat(20);
+    let body = format!("{}the borrow checker likes rust {}and rust again", filler, filler);
+    let post: Post = serde_json::from_value(serde_json::json!({
+        "title": "Ownership", "body": body, "image_url": "", "summary": "", "timestamp": "2024-01-01T00:00:00Z", "tags": ["Rust"]
+    }))
+    .unwrap();
+
+    let hits = Scan.search(std::slice::from_ref(&post), "borrow rust");
+    assert_eq!(hits.len(), 1);
+    let snippet = hits[0].snippet.clone().into_string();
+    assert!(snippet.starts_with('…') && snippet.ends_with('…'), "{}", snippet);
+    assert!(snippet.contains("the <mark>borrow</mark> checker likes <mark>rust</mark>"), "{}", snippet);
+    assert!(snippet.chars().count() <= SNIPPET_CHARS + 2 + "<mark></mark>".len() * 2);
+
+    assert_eq!(Scan.search(std::slice::from_ref(&post), "ownership").len(), 1);
+    assert!(Scan.search(std::slice::from_ref(&post), "rust missing").is_empty());
+    assert!(Scan.search(std::slice::from_ref(&post), "  ").is_empty());
+}
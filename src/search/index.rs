@@ -0,0 +1,182 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use tantivy::collector::TopDocs;
+use tantivy::query::QueryParser;
+use tantivy::schema::{Field, Schema, Value, STORED, STRING, TEXT};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+use super::{hit, terms, Hit, SearchBackend, MAX_RESULTS};
+use crate::excerpt;
+use crate::model::post::{content_hash, Post};
+
+/// Memory the writer buffers documents in before flushing a segment, tantivy's minimum
+const WRITER_MEMORY: usize = 15_000_000;
+
+/// Matches from the index, across every language, looked at to fill a page of results for one of them
+const CANDIDATES: usize = MAX_RESULTS * 4;
+
+/// Title matches rank above tag matches, which rank above body matches, as they do for [`super::Scan`]
+const TITLE_BOOST: f32 = 3.0;
+const TAG_BOOST: f32 = 2.0;
+
+struct Fields {
+    file: Field,
+    title: Field,
+    tags: Field,
+    body: Field,
+}
+
+/// An in-memory tantivy index of every post, keyed by the file it was loaded from. It's built from the posts at
+/// startup and on each change only the files that were added, edited or removed are reindexed. Unlike [`super::Scan`]
+/// it matches whole words, so `borrow` finds `borrow` but not `borrowing`.
+pub struct TantivyIndex {
+    index: Index,
+    fields: Fields,
+    writer: Mutex<IndexWriter>,
+    reader: IndexReader,
+    /// What each indexed file looked like, to tell which ones changed
+    indexed: Mutex<HashMap<String, u64>>,
+}
+
+impl TantivyIndex {
+    pub fn new() -> tantivy::Result<TantivyIndex> {
+        let mut schema = Schema::builder();
+        let fields = Fields {
+            file: schema.add_text_field("file", STRING | STORED),
+            title: schema.add_text_field("title", TEXT),
+            tags: schema.add_text_field("tags", TEXT),
+            body: schema.add_text_field("body", TEXT),
+        };
+        let index = Index::create_in_ram(schema.build());
+        let writer = index.writer(WRITER_MEMORY)?;
+        let reader = index.reader_builder().reload_policy(ReloadPolicy::Manual).try_into()?;
+        Ok(TantivyIndex { index, fields, writer: Mutex::new(writer), reader, indexed: Mutex::new(HashMap::new()) })
+    }
+
+    fn fingerprint(post: &Post) -> u64 {
+        content_hash(&format!("{}\n{}\n{}", post.title, post.tags.join("\n"), post.body))
+    }
+
+    fn reindex(&self, posts: &[Post]) -> tantivy::Result<usize> {
+        let mut indexed = self.indexed.lock().expect("failed to lock the search index");
+        let mut writer = self.writer.lock().expect("failed to lock the search index writer");
+        let current: HashSet<&str> = posts.iter().map(|post| post.source_file.as_str()).collect();
+        let mut changes = 0;
+
+        for file in indexed.keys().filter(|&file| !current.contains(file.as_str())) {
+            writer.delete_term(Term::from_field_text(self.fields.file, file));
+            changes += 1;
+        }
+        indexed.retain(|file, _| current.contains(file.as_str()));
+
+        for post in posts {
+            let fingerprint = TantivyIndex::fingerprint(post);
+            if indexed.get(&post.source_file) == Some(&fingerprint) {
+                continue;
+            }
+            writer.delete_term(Term::from_field_text(self.fields.file, &post.source_file));
+            writer.add_document(doc!(
+                self.fields.file => post.source_file.as_str(),
+                self.fields.title => post.title.as_str(),
+                self.fields.tags => post.tags.join(" "),
+                self.fields.body => excerpt::plain_text(&post.body),
+            ))?;
+            indexed.insert(post.source_file.clone(), fingerprint);
+            changes += 1;
+        }
+
+        if changes > 0 {
+            writer.commit()?;
+            self.reader.reload()?;
+        }
+        Ok(changes)
+    }
+
+    /// Files matching every term, best first, with their scores
+    fn lookup(&self, terms: &[String]) -> tantivy::Result<Vec<(String, f32)>> {
+        let mut parser = QueryParser::for_index(&self.index, vec![self.fields.title, self.fields.tags, self.fields.body]);
+        parser.set_conjunction_by_default();
+        parser.set_field_boost(self.fields.title, TITLE_BOOST);
+        parser.set_field_boost(self.fields.tags, TAG_BOOST);
+        // The terms are plain words, so there's no query syntax in them to trip over
+        let (query, _) = parser.parse_query_lenient(&terms.join(" "));
+
+        let searcher = self.reader.searcher();
+        let mut files = Vec::new();
+        for (score, address) in searcher.search(&query, &TopDocs::with_limit(CANDIDATES))? {
+            let document: TantivyDocument = searcher.doc(address)?;
+            if let Some(file) = document.get_first(self.fields.file).and_then(|value| value.as_str()) {
+                files.push((file.to_string(), score));
+            }
+        }
+        Ok(files)
+    }
+}
+
+impl SearchBackend for TantivyIndex {
+    fn search(&self, posts: &[Post], query: &str) -> Vec<Hit> {
+        let terms = terms(query);
+        if terms.is_empty() {
+            return Vec::new();
+        }
+        let found = match self.lookup(&terms) {
+            Ok(found) => found,
+            Err(e) => {
+                println!("Search for {:?} failed: {}", query, e);
+                return Vec::new();
+            }
+        };
+
+        let by_file: HashMap<&str, &Post> = posts.iter().map(|post| (post.source_file.as_str(), post)).collect();
+        found
+            .iter()
+            .filter_map(|(file, score)| by_file.get(file.as_str()).map(|post| hit(post, &terms, &excerpt::plain_text(&post.body), (score * 1000.0) as usize)))
+            .take(MAX_RESULTS)
+            .collect()
+    }
+
+    fn update(&self, posts: &[Post]) {
+        match self.reindex(posts) {
+            Ok(0) => {}
+            Ok(changes) => println!("Reindexed {} posts for search", changes),
+            Err(e) => println!("Couldn't update the search index: {}", e),
+        }
+    }
+}
+
+#[test]
+fn the_index_follows_the_posts() {
+    let post = |file: &str, title: &str, body: &str| {
+        let mut post: Post = serde_json::from_value(serde_json::json!({
+            "title": title, "body": body, "image_url": "", "summary": "", "timestamp": "2024-01-01T00:00:00Z"
+        }))
+        .unwrap();
+        post.source_file = file.to_string();
+        post.url_name = file.trim_end_matches(".json").to_string();
+        post
+    };
+    let index = TantivyIndex::new().unwrap();
+    let mut posts = vec![post("a.json", "Borrowing", "The borrow checker"), post("b.json", "Lifetimes", "Borrow checker and lifetimes")];
+    assert_eq!(index.reindex(&posts).unwrap(), 2);
+    assert_eq!(index.reindex(&posts).unwrap(), 0);
+
+    let titles = |query: &str, posts: &[Post]| {
+        let mut titles: Vec<String> = index.search(posts, query).into_iter().map(|hit| hit.post.title).collect();
+        titles.sort();
+        titles
+    };
+    assert_eq!(titles("borrow", &posts), vec!["Borrowing", "Lifetimes"]);
+    assert_eq!(titles("checker lifetimes", &posts), vec!["Lifetimes"]);
+
+    posts[1] = post("b.json", "Traits", "Nothing in common");
+    posts.push(post("c.json", "Checker", "A game"));
+    assert_eq!(index.reindex(&posts).unwrap(), 2);
+    assert_eq!(titles("lifetimes", &posts), Vec::<String>::new());
+    assert_eq!(titles("checker", &posts), vec!["Borrowing", "Checker"]);
+
+    posts.remove(2);
+    assert_eq!(index.reindex(&posts).unwrap(), 1);
+    // Only posts from the listing it's handed come back, the way other languages are left out
+    assert_eq!(titles("checker", &posts[1..]), Vec::<String>::new());
+}
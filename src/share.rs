@@ -0,0 +1,110 @@
+use axum::http::{header, HeaderMap};
+use maud::{html, Markup};
+
+use crate::extract::client::Client;
+use crate::i18n::Text;
+use crate::model::note::Note;
+use crate::model::post::Post;
+
+/// Share page used when `CADEN_BLOG_MASTODON_SHARE` isn't set. It asks visitors for their own instance, since
+/// Mastodon has no single site to share to.
+pub const DEFAULT_MASTODON_SHARE: &str = "https://toot.kytta.dev/";
+
+/// Where the Mastodon button sends visitors, from `CADEN_BLOG_MASTODON_SHARE`: either a share page like the default
+/// or an instance's own `https://instance/share`
+pub fn mastodon_share_from_env() -> String {
+    match std::env::var("CADEN_BLOG_MASTODON_SHARE") {
+        Ok(url) if !url.trim().is_empty() => url.trim().to_string(),
+        _ => DEFAULT_MASTODON_SHARE.to_string(),
+    }
+}
+
+/// Percent-encodes a query value, leaving only the unreserved characters as they are
+pub fn encode(value: &str) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// The configured `site_url` when set, otherwise the host and scheme the request was made with
+pub fn origin(site_url: &str, headers: &HeaderMap, client: &Client) -> String {
+    match site_url {
+        "" => {
+            let host = headers.get(header::HOST).and_then(|host| host.to_str().ok()).unwrap_or("localhost");
+            format!("{}://{}", client.scheme(), host)
+        }
+        site => site.to_string(),
+    }
+}
+
+/// The absolute URL of a post, on the [`origin`] of the request
+pub fn canonical_url(post: &Post, site_url: &str, headers: &HeaderMap, client: &Client) -> String {
+    post_url(&origin(site_url, headers, client), post)
+}
+
+/// The URL of a post on `origin`, naming its language when it's a translation
+pub fn post_url(origin: &str, post: &Post) -> String {
+    match &post.lang {
+        Some(lang) => format!("{}/post/{}?lang={}", origin, post.url_name, lang),
+        None => format!("{}/post/{}", origin, post.url_name),
+    }
+}
+
+/// Where one of a post's attachments is downloaded from
+pub fn attachment_url(post: &Post, file: &str) -> String {
+    format!("/post/{}/attachments/{}", post.url_name, encode(file))
+}
+
+/// The URL of a note on `origin`, its anchor on the notes timeline
+pub fn note_url(origin: &str, note: &Note) -> String {
+    format!("{}{}#{}", origin, crate::routes::notes::NOTES_PATH, note.id)
+}
+
+fn links(url: &str, title: &str, mastodon_share: &str) -> [(&'static str, String); 3] {
+    let separator = if mastodon_share.contains('?') { '&' } else { '?' };
+    [
+        ("share_mastodon", format!("{}{}text={}", mastodon_share, separator, encode(&format!("{} {}", title, url)))),
+        ("share_reddit", format!("https://www.reddit.com/submit?url={}&title={}", encode(url), encode(title))),
+        ("share_email", format!("mailto:?subject={}&body={}", encode(title), encode(url))),
+    ]
+}
+
+/// Share buttons for a post. They are plain links, apart from copying which needs the clipboard API and
+/// so only shows up once the inline script finds it.
+pub fn widget(url: &str, title: &str, mastodon_share: &str, text: Text) -> Markup {
+    html! {
+        div class="share mt-4" role="group" aria-label=(text.t("share")) {
+            span class="me-2" { (text.t("share")) ":" }
+            button type="button" class="btn btn-sm btn-outline-secondary me-2 share-copy" data-url=(url) data-copied=(text.t("share_copied")) hidden {
+                (text.t("share_copy"))
+            }
+            @for (label, href) in links(url, title, mastodon_share) {
+                a class="btn btn-sm btn-outline-secondary me-2" href=(href) target=[(!href.starts_with("mailto:")).then_some("_blank")] rel="noopener noreferrer" {
+                    (text.t(label))
+                }
+            }
+            script {
+                (maud::PreEscaped(r#"
+                    document.querySelectorAll('.share-copy').forEach(button => {
+                        if (!navigator.clipboard) return;
+                        button.hidden = false;
+                        button.onclick = () => navigator.clipboard.writeText(button.dataset.url).then(() => button.textContent = button.dataset.copied);
+                    });
+                "#))
+            }
+        }
+    }
+}
+
+#[test]
+fn share_links_encode_the_url_and_title() {
+    let [mastodon, reddit, email] = links("https://example.com/post/a-b?lang=es", "Tabs & spaces", DEFAULT_MASTODON_SHARE);
+    assert_eq!(mastodon.1, "https://toot.kytta.dev/?text=Tabs%20%26%20spaces%20https%3A%2F%2Fexample.com%2Fpost%2Fa-b%3Flang%3Des");
+    assert_eq!(reddit.1, "https://www.reddit.com/submit?url=https%3A%2F%2Fexample.com%2Fpost%2Fa-b%3Flang%3Des&title=Tabs%20%26%20spaces");
+    assert_eq!(email.1, "mailto:?subject=Tabs%20%26%20spaces&body=https%3A%2F%2Fexample.com%2Fpost%2Fa-b%3Flang%3Des");
+}
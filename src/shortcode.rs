@@ -0,0 +1,102 @@
+/// A piece of a post body: markdown to render as usual, or a shortcode to expand in its place
+#[derive(Debug, PartialEq)]
+pub enum Segment<'a> {
+    Markdown(&'a str),
+    /// `{{poll id}}`
+    Poll(&'a str),
+    /// `{{gallery photo-1.jpg photo-2.jpg}}`, asset names separated by spaces
+    Gallery(Vec<&'a str>),
+}
+
+/// A line opening or closing a collapsible section. Unlike the other shortcodes these wrap markdown, so they're
+/// expanded by [`crate::render::markdown::preprocess`] rather than split out.
+#[derive(Debug, PartialEq)]
+pub enum Details<'a> {
+    /// `{{details Summary shown while it's closed}}`
+    Open(&'a str),
+    /// `{{/details}}`
+    Close,
+}
+
+/// The section boundary a line holds, when it holds nothing else
+pub fn details(line: &str) -> Option<Details<'_>> {
+    let inner = line.trim().strip_prefix("{{")?.strip_suffix("}}")?.trim();
+    if inner.contains("{{") || inner.contains("}}") {
+        return None;
+    }
+    match inner.split_once(' ') {
+        _ if inner == "/details" => Some(Details::Close),
+        Some(("details", summary)) if !summary.trim().is_empty() => Some(Details::Open(summary.trim())),
+        _ => None,
+    }
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// A path in the assets directory, without anything that could step out of it or need escaping in a URL
+fn valid_asset(name: &str) -> bool {
+    name.split('/').all(|segment| !segment.starts_with('.') && valid_id(&segment.replace('.', "")))
+}
+
+/// Splits a post body around its shortcodes, leaving anything else in double braces as markdown
+pub fn split(body: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut search = 0;
+
+    while let Some(open) = body[search..].find("{{").map(|i| search + i) {
+        let Some(close) = body[open..].find("}}").map(|i| open + i) else { break };
+
+        let shortcode = match body[open + 2..close].trim().split_once(' ') {
+            Some(("poll", id)) if valid_id(id.trim()) => Segment::Poll(id.trim()),
+            Some(("gallery", names)) if names.split_whitespace().all(valid_asset) => Segment::Gallery(names.split_whitespace().collect()),
+            _ => {
+                search = open + 2;
+                continue;
+            }
+        };
+        if !body[start..open].trim().is_empty() {
+            segments.push(Segment::Markdown(&body[start..open]));
+        }
+        segments.push(shortcode);
+        start = close + 2;
+        search = start;
+    }
+
+    if !body[start..].trim().is_empty() || segments.is_empty() {
+        segments.push(Segment::Markdown(&body[start..]));
+    }
+    segments
+}
+
+#[test]
+fn splits_body_around_shortcodes() {
+    assert_eq!(split("just text"), vec![Segment::Markdown("just text")]);
+    assert_eq!(split(""), vec![Segment::Markdown("")]);
+    assert_eq!(
+        split("Intro\n\n{{poll favorite-editor}}\n\nOutro {{ poll tabs_or_spaces }}"),
+        vec![
+            Segment::Markdown("Intro\n\n"),
+            Segment::Poll("favorite-editor"),
+            Segment::Markdown("\n\nOutro "),
+            Segment::Poll("tabs_or_spaces"),
+        ]
+    );
+    assert_eq!(split("{{not a shortcode}} {{poll bad/id}}"), vec![Segment::Markdown("{{not a shortcode}} {{poll bad/id}}")]);
+    assert_eq!(
+        split("Build log\n\n{{gallery chassis.jpg  robot/wiring_2.png}}"),
+        vec![Segment::Markdown("Build log\n\n"), Segment::Gallery(vec!["chassis.jpg", "robot/wiring_2.png"])]
+    );
+    assert_eq!(split("{{gallery ../secret.png}} {{gallery .env}}"), vec![Segment::Markdown("{{gallery ../secret.png}} {{gallery .env}}")]);
+}
+
+#[test]
+fn details_lines_open_and_close_sections() {
+    assert_eq!(details("  {{details Full build log}} "), Some(Details::Open("Full build log")));
+    assert_eq!(details("{{ /details }}"), Some(Details::Close));
+    for line in ["{{details}}", "{{details  }}", "see {{details x}}", "{{details a}} b {{/details}}", "{{detailsx}}"] {
+        assert_eq!(details(line), None, "{}", line);
+    }
+}
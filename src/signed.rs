@@ -0,0 +1,107 @@
+use axum::http::HeaderMap;
+use base64::Engine;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+
+/// Key for signing visitor cookies, preview links and redirects
+#[derive(Clone)]
+pub struct Key(Vec<u8>);
+
+impl Key {
+    /// From `CADEN_BLOG_COOKIE_SECRET`. Without one a random key is made at startup, so signed cookies stop
+    /// verifying after every restart.
+    pub fn from_env() -> Key {
+        match std::env::var("CADEN_BLOG_COOKIE_SECRET") {
+            Ok(secret) if !secret.is_empty() => Key::new(secret),
+            _ => {
+                println!("CADEN_BLOG_COOKIE_SECRET is not set, visitor cookies will reset on restart");
+                Key::random()
+            }
+        }
+    }
+
+    pub fn new(secret: impl Into<Vec<u8>>) -> Key {
+        Key(secret.into())
+    }
+
+    pub fn random() -> Key {
+        Key::new(uuid::Uuid::new_v4().as_bytes().to_vec())
+    }
+
+    /// Encodes a value into a cookie-safe string the visitor can't alter without it failing to verify
+    pub fn sign(&self, value: &str) -> String {
+        sign_with(&self.0, value)
+    }
+
+    /// The value inside a cookie made by [`Key::sign`], or `None` when it was tampered with or signed with another key
+    pub fn verify(&self, cookie: &str) -> Option<String> {
+        verify_with(&self.0, cookie)
+    }
+
+    /// A signature of the value alone, for values carried in the clear next to it, like a redirect's target
+    pub fn signature_of(&self, value: &str) -> String {
+        signature(&self.0, value)
+    }
+
+    /// Whether `signature` is what [`Key::signature_of`] gives for the value
+    pub fn verify_signature(&self, value: &str, signature: &str) -> bool {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.0).expect("hmac accepts keys of any length");
+        mac.update(value.as_bytes());
+        hex::decode(signature).is_ok_and(|signature| mac.verify_slice(&signature).is_ok())
+    }
+
+    /// The entries of a signed list cookie, empty when it's missing or was tampered with
+    pub fn read_list(&self, headers: &HeaderMap, name: &str) -> Vec<String> {
+        crate::prefs::cookie(headers, name)
+            .and_then(|cookie| self.verify(cookie))
+            .map(|list| list.lines().map(String::from).collect())
+            .unwrap_or_default()
+    }
+
+    /// A `Set-Cookie` value keeping the newest `max` entries as a signed list for a year
+    pub fn list_cookie(&self, name: &str, entries: &[String], max: usize) -> String {
+        let newest = &entries[entries.len().saturating_sub(max)..];
+        format!("{}={}; Path=/; Max-Age=31536000; SameSite=Lax; HttpOnly", name, self.sign(&newest.join("\n")))
+    }
+}
+
+/// Keeps the key out of logged configs
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Key(..)")
+    }
+}
+
+fn signature(key: &[u8], payload: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+fn sign_with(key: &[u8], value: &str) -> String {
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(value);
+    let signature = signature(key, &payload);
+    format!("{}.{}", payload, signature)
+}
+
+fn verify_with(key: &[u8], cookie: &str) -> Option<String> {
+    let (payload, signature) = cookie.rsplit_once('.')?;
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts keys of any length");
+    mac.update(payload.as_bytes());
+    mac.verify_slice(&hex::decode(signature).ok()?).ok()?;
+
+    let value = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(payload).ok()?;
+    String::from_utf8(value).ok()
+}
+
+#[test]
+fn signed_values_round_trip_and_reject_tampering() {
+    let cookie = sign_with(b"key", "post-one:like\npost-two:clap");
+    assert_eq!(verify_with(b"key", &cookie).as_deref(), Some("post-one:like\npost-two:clap"));
+    assert_eq!(verify_with(b"other key", &cookie), None);
+
+    let (_, signature) = cookie.rsplit_once('.').unwrap();
+    let forged = format!("{}.{}", base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("post-three:like"), signature);
+    assert_eq!(verify_with(b"key", &forged), None);
+    assert_eq!(verify_with(b"key", "garbage"), None);
+}
@@ -0,0 +1,41 @@
+/// Longest slug generated from a title, cut at a word boundary
+const MAX_SLUG_LEN: usize = 80;
+
+/// Turns a title into a URL-safe slug: transliterated to ASCII, lowercased, with every run of
+/// spaces or punctuation collapsed into a single hyphen
+pub fn slugify(title: &str) -> String {
+    let ascii = deunicode::deunicode(title).to_lowercase();
+
+    let mut slug = String::with_capacity(ascii.len());
+    for c in ascii.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if c != '\'' && !slug.is_empty() && !slug.ends_with('-') {
+            // Apostrophes join words ("don't" -> "dont") instead of splitting them
+            slug.push('-');
+        }
+    }
+    let mut slug = slug.trim_end_matches('-').to_string();
+
+    if slug.len() > MAX_SLUG_LEN {
+        let cut = slug[..MAX_SLUG_LEN].rfind('-').unwrap_or(MAX_SLUG_LEN);
+        slug.truncate(cut);
+    }
+
+    if slug.is_empty() {
+        "post".to_string()
+    } else {
+        slug
+    }
+}
+
+#[test]
+fn slugify_handles_punctuation_and_unicode() {
+    assert_eq!(slugify("Hello, World!"), "hello-world");
+    assert_eq!(slugify("  Don't   panic -- it's fine  "), "dont-panic-its-fine");
+    assert_eq!(slugify("Café à la crème"), "cafe-a-la-creme");
+    assert_eq!(slugify("Привет мир"), "privet-mir");
+    assert_eq!(slugify("東京"), "dong-jing");
+    assert_eq!(slugify("!!!"), "post");
+    assert!(slugify(&"word ".repeat(40)).len() <= MAX_SLUG_LEN);
+}
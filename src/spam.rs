@@ -0,0 +1,195 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+/// Metric counting the submissions turned away as spam, labelled with the checker that caught them
+pub const SPAM_METRIC: &str = "caden_blog_spam_total";
+
+/// Links a submission can have before the heuristic takes it for spam, from `CADEN_BLOG_SPAM_MAX_LINKS`
+const DEFAULT_MAX_LINKS: usize = 2;
+
+/// Words spam is made of and comments on a tech blog aren't, matched case-insensitively.
+/// `CADEN_BLOG_SPAM_WORDS` replaces the list.
+const DEFAULT_SPAM_WORDS: [&str; 8] = ["viagra", "cialis", "casino", "payday loan", "seo services", "buy backlinks", "crypto giveaway", "[url="];
+
+const AKISMET_URL: &str = "https://rest.akismet.com/1.1/comment-check";
+
+/// Something a visitor sent in, as a spam checker sees it
+#[derive(Debug, Clone, Default)]
+pub struct Submission<'a> {
+    pub author: &'a str,
+    pub email: &'a str,
+    pub body: &'a str,
+    pub ip: Option<IpAddr>,
+    pub user_agent: &'a str,
+    pub referrer: &'a str,
+    /// The page it was sent from
+    pub permalink: &'a str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Ham,
+    Spam,
+}
+
+/// Decides whether a submission is spam before it's kept. Checkers that can't reach a decision, like an API
+/// that's down, let the submission through rather than lose a real one.
+#[async_trait]
+pub trait SpamChecker: Send + Sync {
+    /// Names the checker in logs and metrics
+    fn name(&self) -> &'static str;
+
+    async fn check(&self, submission: &Submission<'_>) -> Verdict;
+}
+
+/// The checker picked by `CADEN_BLOG_SPAM_CHECK`: `heuristic` (the default), `akismet` with
+/// `CADEN_BLOG_AKISMET_KEY` and `CADEN_BLOG_AKISMET_SITE`, or `off`
+pub fn from_env() -> Arc<dyn SpamChecker> {
+    match std::env::var("CADEN_BLOG_SPAM_CHECK").unwrap_or_default().trim() {
+        "akismet" => match Akismet::from_env() {
+            Some(akismet) => {
+                println!("Checking submissions for spam with Akismet");
+                return Arc::new(akismet);
+            }
+            None => println!("CADEN_BLOG_SPAM_CHECK=akismet needs CADEN_BLOG_AKISMET_KEY and CADEN_BLOG_AKISMET_SITE, using the heuristic instead"),
+        },
+        "off" => return Arc::new(Off),
+        "" | "heuristic" => {}
+        other => println!("Unknown spam checker {}, using the heuristic instead", other),
+    }
+    Arc::new(Heuristic::from_env())
+}
+
+/// Lets everything through
+pub struct Off;
+
+#[async_trait]
+impl SpamChecker for Off {
+    fn name(&self) -> &'static str {
+        "off"
+    }
+
+    async fn check(&self, _submission: &Submission<'_>) -> Verdict {
+        Verdict::Ham
+    }
+}
+
+/// Catches the laziest spam locally: piles of links, links in the name field and the usual sales pitches
+pub struct Heuristic {
+    max_links: usize,
+    words: Vec<String>,
+}
+
+impl Default for Heuristic {
+    fn default() -> Heuristic {
+        Heuristic { max_links: DEFAULT_MAX_LINKS, words: DEFAULT_SPAM_WORDS.iter().map(|word| word.to_string()).collect() }
+    }
+}
+
+impl Heuristic {
+    pub fn from_env() -> Heuristic {
+        let mut heuristic = Heuristic::default();
+        if let Some(max_links) = std::env::var("CADEN_BLOG_SPAM_MAX_LINKS").ok().and_then(|max| max.trim().parse().ok()) {
+            heuristic.max_links = max_links;
+        }
+        if let Ok(words) = std::env::var("CADEN_BLOG_SPAM_WORDS") {
+            heuristic.words = words.split(',').map(|word| word.trim().to_lowercase()).filter(|word| !word.is_empty()).collect();
+        }
+        heuristic
+    }
+}
+
+fn links(text: &str) -> usize {
+    let text = text.to_lowercase();
+    ["http://", "https://", "www."].iter().map(|marker| text.matches(marker).count()).sum::<usize>()
+        // `https://www.` is one link, not two
+        - text.matches("://www.").count()
+}
+
+#[async_trait]
+impl SpamChecker for Heuristic {
+    fn name(&self) -> &'static str {
+        "heuristic"
+    }
+
+    async fn check(&self, submission: &Submission<'_>) -> Verdict {
+        let text = format!("{}\n{}", submission.author, submission.body).to_lowercase();
+        let spam = links(submission.author) > 0 || links(submission.body) > self.max_links || self.words.iter().any(|word| text.contains(word.as_str()));
+        if spam {
+            Verdict::Spam
+        } else {
+            Verdict::Ham
+        }
+    }
+}
+
+/// Asks Akismet's comment-check API
+pub struct Akismet {
+    key: String,
+    /// The blog's address, as registered with Akismet
+    site: String,
+    client: reqwest::Client,
+}
+
+impl Akismet {
+    pub fn from_env() -> Option<Akismet> {
+        let var = |name: &str| std::env::var(name).ok().map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+        Some(Akismet { key: var("CADEN_BLOG_AKISMET_KEY")?, site: var("CADEN_BLOG_AKISMET_SITE")?, client: reqwest::Client::new() })
+    }
+}
+
+#[async_trait]
+impl SpamChecker for Akismet {
+    fn name(&self) -> &'static str {
+        "akismet"
+    }
+
+    async fn check(&self, submission: &Submission<'_>) -> Verdict {
+        let ip = submission.ip.map(|ip| ip.to_string()).unwrap_or_default();
+        let form = [
+            ("api_key", self.key.as_str()),
+            ("blog", self.site.as_str()),
+            ("user_ip", ip.as_str()),
+            ("user_agent", submission.user_agent),
+            ("referrer", submission.referrer),
+            ("permalink", submission.permalink),
+            ("comment_type", "comment"),
+            ("comment_author", submission.author),
+            ("comment_author_email", submission.email),
+            ("comment_content", submission.body),
+        ];
+        let answer = match self.client.post(AKISMET_URL).form(&form).send().await {
+            Ok(response) => response.text().await,
+            Err(e) => Err(e),
+        };
+        // Anything but a plain `true` or `false` is an error, with the reason in a header we don't get to here
+        match answer.as_deref().map(str::trim) {
+            Ok("true") => Verdict::Spam,
+            Ok("false") => Verdict::Ham,
+            Ok(other) => {
+                println!("Akismet couldn't check a submission, letting it through: {:?}", other);
+                Verdict::Ham
+            }
+            Err(e) => {
+                println!("Couldn't reach Akismet, letting a submission through: {}", e);
+                Verdict::Ham
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn the_heuristic_catches_link_piles_and_sales_pitches() {
+    let heuristic = Heuristic::default();
+    let check = |author: &'static str, body: &'static str| {
+        let heuristic = &heuristic;
+        async move { heuristic.check(&Submission { author, body, ..Submission::default() }).await }
+    };
+    assert_eq!(check("Ann", "Nice post, see https://example.com for more").await, Verdict::Ham);
+    assert_eq!(check("Ann", "https://www.a.example http://b.example www.c.example").await, Verdict::Spam);
+    assert_eq!(check("https://cheap.example", "Great post").await, Verdict::Spam);
+    assert_eq!(check("Bob", "Best ONLINE CASINO bonuses").await, Verdict::Spam);
+    assert_eq!(links("https://www.a.example and www.b.example"), 2);
+}
@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::access::AccessRules;
+use crate::assets::{AssetStore, MissingAssets};
+use crate::backup::{BackupConfig, Backups};
+use crate::bots::BotGuard;
+use crate::hotlink::Hotlinks;
+use crate::comments::CommentStore;
+use crate::config::Config;
+use crate::feeds::Feeds;
+use crate::fingerprint::{self, AssetManifest};
+use crate::i18n::Locales;
+use crate::maintenance::Maintenance;
+use crate::metrics::Metrics;
+use crate::notify::Notifier;
+use crate::polls::PollStore;
+use crate::previews::Previews;
+use crate::pwa::ServiceWorker;
+use crate::reactions::ReactionStore;
+use crate::search::SearchBackend;
+use crate::spam::SpamChecker;
+use crate::store::links::Links;
+use crate::store::related::Related;
+use crate::store::suggest::Suggestions;
+use crate::store::{notes, posts, FileCache, NoteIndex, PageCache, PostIndex};
+use crate::{assets, backup, comments, events, icons, notify, polls, previews, reactions, search, spam, sync, theme, vendor, warm};
+
+/// Everything the routes share, set up once at startup and handed to every handler with axum's `State`.
+/// Handlers destructure the parts they need, so a new subsystem is a new field rather than another capture
+/// threaded through each route.
+#[derive(Clone)]
+pub struct AppState {
+    pub(crate) config: Arc<Config>,
+    pub(crate) posts: PostIndex,
+    pub(crate) notes: NoteIndex,
+    pub(crate) pages: PageCache,
+    pub(crate) cache: FileCache,
+    pub(crate) store: Arc<dyn AssetStore>,
+    pub(crate) missing: Arc<MissingAssets>,
+    pub(crate) hotlinks: Arc<Hotlinks>,
+    pub(crate) icons: Arc<icons::IconSet>,
+    pub(crate) locales: Arc<Locales>,
+    pub(crate) publisher: events::Publisher,
+    pub(crate) reactions: ReactionStore,
+    pub(crate) polls: PollStore,
+    pub(crate) comments: CommentStore,
+    pub(crate) access: Arc<AccessRules>,
+    pub(crate) bots: Arc<BotGuard>,
+    pub(crate) metrics: Arc<Metrics>,
+    pub(crate) maintenance: Arc<Maintenance>,
+    pub(crate) backups: Arc<Backups>,
+    pub(crate) feeds: Arc<Feeds>,
+    pub(crate) worker: Arc<ServiceWorker>,
+    pub(crate) manifest: Arc<AssetManifest>,
+    pub(crate) suggestions: Arc<Suggestions>,
+    pub(crate) search: Arc<dyn SearchBackend>,
+    pub(crate) related: Arc<Related>,
+    pub(crate) links: Arc<Links>,
+    pub(crate) spam: Arc<dyn SpamChecker>,
+    pub(crate) notifier: Arc<Notifier>,
+    pub(crate) previews: Arc<Previews>,
+}
+
+impl AppState {
+    /// Loads the posts, notes and state from the content directory and sets up the asset store configured in the environment
+    pub async fn load(config: Config) -> Result<AppState, String> {
+        let content = config.content_dir.as_path();
+        Ok(AppState {
+            posts: Arc::new(RwLock::new(posts::load_posts(content).await?)),
+            notes: Arc::new(RwLock::new(notes::load_notes(content).await?)),
+            pages: Arc::new(RwLock::new(HashMap::new())),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            store: assets::from_env(content),
+            missing: Arc::new(MissingAssets::from_env()),
+            hotlinks: Arc::new(Hotlinks::from_env(&config.site_url)),
+            icons: Arc::new(icons::IconSet::from_env(content, &config.theme_color)),
+            locales: Arc::new(Locales::load(content)),
+            publisher: events::publisher(),
+            reactions: Arc::new(reactions::load(content)),
+            polls: Arc::new(polls::load(content)),
+            comments: Arc::new(comments::load(content)),
+            access: Arc::new(AccessRules::from_env()?),
+            bots: Arc::new(BotGuard::from_env(content)),
+            metrics: Arc::new(Metrics::default()),
+            maintenance: Arc::new(Maintenance::from_env()),
+            backups: Arc::new(Backups::new(BackupConfig::from_env(content))),
+            feeds: Arc::new(Feeds::from_env(content, &config.site_url)),
+            worker: Arc::new(ServiceWorker::load(content)),
+            manifest: Arc::new(AssetManifest::default()),
+            suggestions: Arc::new(Suggestions::default()),
+            search: search::from_env(),
+            related: Arc::new(Related::from_env()),
+            links: Arc::new(Links::new(&config.site_url)),
+            spam: spam::from_env(),
+            notifier: Arc::new(Notifier::from_env(&config.site_url)),
+            previews: Arc::new(Previews::load(content.join(previews::STATE_FILE))),
+            config: Arc::new(config),
+        })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Fills the caches, generates the feeds and service worker and builds the search, related post and link indexes
+    /// before the first request, then starts fetching link previews, and syncing posts, backing up and emailing
+    /// notifications when those are configured
+    pub async fn start(&self) {
+        vendor::report();
+        self.refresh_manifest().await;
+        warm::warm_caches(self).await;
+        self.feeds.regenerate(&self.posts, &self.notes, &self.locales);
+        self.worker.regenerate(&self.posts, &self.icons, &self.manifest);
+        self.search.update(&self.posts.read().expect("failed to lock the post index"));
+        self.related.rebuild(&self.posts, &self.locales);
+        self.links.rebuild(&self.posts);
+
+        tokio::spawn(previews::refresh(self.posts.clone(), self.pages.clone(), self.previews.clone()));
+        if let Some(config) = sync::SyncConfig::from_env(&self.config.content_dir) {
+            tokio::spawn(sync::run(config, self.clone()));
+        }
+        tokio::spawn(backup::run(self.backups.clone()));
+        tokio::spawn(notify::run(self.notifier.clone()));
+    }
+
+    /// Loads the posts and notes again and swaps them in, then drops the cached pages and assets and rebuilds
+    /// everything derived from the content. A post or note that fails to load leaves the current content in place,
+    /// and the error is returned for the caller to report. Returns how many posts and notes were loaded.
+    pub async fn reload_index(&self) -> Result<(usize, usize), String> {
+        let (loaded, loaded_notes) = match (posts::reload_posts(&self.config.content_dir).await, notes::load_notes(&self.config.content_dir).await) {
+            (Ok(loaded), Ok(loaded_notes)) => (loaded, loaded_notes),
+            (Err(e), _) | (_, Err(e)) => return Err(e),
+        };
+        let counts = (loaded.len(), loaded_notes.len());
+
+        events::replace_posts(&self.posts, loaded, &self.publisher, &self.config.content_dir);
+        *self.notes.write().expect("failed to lock the notes") = loaded_notes;
+        self.pages.write().expect("failed to lock the page cache").clear();
+        self.cache.lock().expect("cdn failed to lock the cache").clear();
+        self.missing.purge(|_| true);
+        self.refresh_manifest().await;
+        warm::warm_pages(&self.posts, &self.pages, &self.locales, &self.reactions, &self.manifest, &self.config);
+        self.feeds.regenerate(&self.posts, &self.notes, &self.locales);
+        self.worker.regenerate(&self.posts, &self.icons, &self.manifest);
+        self.suggestions.clear();
+        self.search.update(&self.posts.read().expect("failed to lock the post index"));
+        self.related.rebuild(&self.posts, &self.locales);
+        self.links.rebuild(&self.posts);
+        tokio::spawn(previews::refresh(self.posts.clone(), self.pages.clone(), self.previews.clone()));
+        Ok(counts)
+    }
+
+    /// Hashes the assets and the code block theme into the manifest pages link them by. Only the assets directory
+    /// is cheap to read through, so files in an s3 bucket keep their plain names.
+    pub async fn refresh_manifest(&self) {
+        if self.store.cache_in_memory() {
+            let (dir, max_size) = (self.config.content_dir.join("assets"), self.config.max_cached_size);
+            let versions = tokio::task::spawn_blocking(move || fingerprint::scan_assets(&dir, max_size)).await.unwrap_or_default();
+            self.manifest.replace_assets(versions);
+        }
+        theme::load(&self.config.code_theme, self.store.as_ref(), &self.cache, &self.manifest).await;
+        theme::load_script(&self.config.content_dir, &self.cache, &self.manifest).await;
+    }
+}
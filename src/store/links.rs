@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use pulldown_cmark::{Event, Parser, Tag};
+
+use crate::model::post::Post;
+use crate::store::PostIndex;
+
+/// The url names of the posts a body links to, from markdown links to `/post/<name>` on this site
+pub fn internal_links(body: &str, site_url: &str) -> Vec<String> {
+    let mut links: Vec<String> = Vec::new();
+    for event in Parser::new(body) {
+        let Event::Start(Tag::Link { dest_url, .. }) = event else { continue };
+        let path = match dest_url.strip_prefix(site_url) {
+            Some(path) if !site_url.is_empty() => path,
+            _ => &dest_url,
+        };
+        let Some(rest) = path.strip_prefix("/post/") else { continue };
+        let name = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        if !name.is_empty() && !links.iter().any(|link| link == name) {
+            links.push(name.to_string());
+        }
+    }
+    links
+}
+
+/// Which posts link to which, worked out from the public posts whenever the posts change. Unlisted and private
+/// posts can be linked to but never count as linking, so a draft doesn't give itself away.
+#[derive(Debug, Default)]
+pub struct LinkGraph {
+    /// `(from, to)` url names, each pair once, newest linking post first
+    links: Vec<(String, String)>,
+    referenced_by: HashMap<String, Vec<String>>,
+}
+
+impl LinkGraph {
+    pub fn build(posts: &[Post], site_url: &str) -> LinkGraph {
+        let mut sources: Vec<&Post> = posts.iter().filter(|post| post.listed()).collect();
+        sources.sort_by_key(|post| std::cmp::Reverse(post.timestamp));
+
+        let mut graph = LinkGraph::default();
+        for post in sources {
+            for target in internal_links(&post.body, site_url) {
+                let link = (post.url_name.clone(), target);
+                // Translations of a post share its url name and usually its links
+                if link.0 == link.1 || graph.links.contains(&link) {
+                    continue;
+                }
+                graph.referenced_by.entry(link.1.clone()).or_default().push(link.0.clone());
+                graph.links.push(link);
+            }
+        }
+        graph
+    }
+
+    pub fn links(&self) -> &[(String, String)] {
+        &self.links
+    }
+
+    /// The url names of the posts linking to `url_name`, newest first
+    pub fn referenced_by(&self, url_name: &str) -> &[String] {
+        self.referenced_by.get(url_name).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+/// The [`LinkGraph`] of the current posts
+#[derive(Default)]
+pub struct Links {
+    graph: RwLock<Option<Arc<LinkGraph>>>,
+    /// Absolute links to this origin count as links between posts
+    site_url: String,
+}
+
+impl Links {
+    pub fn new(site_url: &str) -> Links {
+        Links { graph: RwLock::default(), site_url: site_url.to_string() }
+    }
+
+    pub fn clear(&self) {
+        *self.graph.write().expect("failed to lock the link graph") = None;
+    }
+
+    /// The graph, built now if the posts changed since it was last asked for
+    pub fn get(&self, posts: &PostIndex) -> Arc<LinkGraph> {
+        if let Some(graph) = &*self.graph.read().expect("failed to lock the link graph") {
+            return graph.clone();
+        }
+        let graph = Arc::new(LinkGraph::build(&posts.read().expect("failed to lock the post index"), &self.site_url));
+        *self.graph.write().expect("failed to lock the link graph") = Some(graph.clone());
+        graph
+    }
+
+    /// Builds the graph after the posts change, so no reader waits for it
+    pub fn rebuild(&self, posts: &PostIndex) {
+        self.clear();
+        self.get(posts);
+    }
+}
+
+#[test]
+fn links_between_public_posts_are_recorded_both_ways() {
+    let post = |url_name: &str, body: &str, visibility: &str, day: u32| {
+        let mut post: Post = serde_json::from_value(serde_json::json!({
+            "title": url_name, "body": body, "image_url": "", "summary": "", "visibility": visibility,
+            "timestamp": format!("2024-01-{:02}T00:00:00Z", day)
+        }))
+        .unwrap();
+        post.url_name = url_name.to_string();
+        post
+    };
+    assert_eq!(
+        internal_links("[a](/post/a) [again](/post/a#top) [b](https://blog.example/post/b/plain?lang=es) [c](/posts/c) [self](https://other.example/post/d)", "https://blog.example"),
+        vec!["a", "b"]
+    );
+
+    let posts = vec![
+        post("intro", "Nothing to see", "public", 1),
+        post("follow-up", "See [the intro](/post/intro) and [me](/post/follow-up)", "public", 2),
+        post("later", "Back to [the intro](/post/intro?x=1)", "public", 3),
+        post("draft", "Also [the intro](/post/intro)", "private", 4),
+    ];
+    let graph = LinkGraph::build(&posts, "");
+    assert_eq!(graph.referenced_by("intro"), ["later", "follow-up"]);
+    assert!(graph.referenced_by("follow-up").is_empty());
+    assert_eq!(graph.links.len(), 2);
+}
@@ -0,0 +1,18 @@
+pub mod links;
+pub mod notes;
+pub mod posts;
+pub mod related;
+pub mod suggest;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::model::note::Note;
+use crate::model::post::Post;
+
+pub type FileCache = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+pub type PostIndex = Arc<RwLock<Vec<Post>>>;
+/// The notes, newest first
+pub type NoteIndex = Arc<RwLock<Vec<Note>>>;
+/// Fully rendered pages keyed by route, cleared whenever the post index changes
+pub type PageCache = Arc<RwLock<HashMap<String, String>>>;
@@ -0,0 +1,32 @@
+use std::path::Path;
+
+use crate::model::note::{parse_note, Note};
+use crate::store::posts::list_files_in_directory;
+
+/// Reads every note in the notes directory, newest first. The directory is optional, without it there are no
+/// notes. Fails when any note can't be read or parsed.
+pub async fn load_notes(content: &Path) -> Result<Vec<Note>, String> {
+    let dir = content.join("notes");
+    tokio::task::spawn_blocking(move || {
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut notes = list_files_in_directory(&dir)
+            .into_iter()
+            .filter(|file| file.ends_with(".json"))
+            .map(|file| {
+                let contents = std::fs::read_to_string(dir.join(&file)).map_err(|e| format!("couldn't read {}: {}", file, e))?;
+                parse_note(&file, &contents)
+            })
+            .collect::<Result<Vec<Note>, String>>()?;
+        sort_notes(&mut notes);
+        Ok(notes)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Newest first, with notes from the same moment in file name order so the timeline doesn't shuffle
+pub fn sort_notes(notes: &mut [Note]) {
+    notes.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| a.id.cmp(&b.id)));
+}
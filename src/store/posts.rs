@@ -0,0 +1,174 @@
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::model::post::{find_slug_conflicts, parse_post, pick_translation, post_lang, Post};
+use crate::paths;
+use crate::store::PostIndex;
+
+pub fn list_files_in_directory(dir: impl AsRef<std::path::Path>) -> Vec<String> {
+    let path = dir.as_ref();
+
+    // Ensure the directory exists
+    if !path.is_dir() {
+        println!("Directory {} does not exist.", path.display());
+        return vec![];
+    }
+
+    // Collect file names into a Vec<String>
+    let mut file_list = Vec::new();
+    match fs::read_dir(path) {
+        Ok(entries) => {
+            for entry in entries.flatten() {
+                // Check if it's a file (not a directory)
+                if let Ok(file_type) = entry.file_type() {
+                    if file_type.is_file() {
+                        // Get file name as a String
+                        if let Some(file_name) = entry.file_name().to_str() {
+                            file_list.push(file_name.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        Err(e) => {
+            println!("Error reading directory {}: {}", path.display(), e);
+        }
+    }
+
+    file_list
+}
+
+/// How many post files are read at once while loading the index
+pub const LOAD_CONCURRENCY: usize = 32;
+
+pub fn get_from_file(content: &Path, file_name: &str) -> Option<Post> {
+    let path = paths::contained(&content.join("posts"), file_name)?;
+    let display = path.display();
+    if path.is_file() {
+        // Open the path in read-only mode, returns `io::Result<File>`
+        let mut file = match File::open(&path) {
+            Err(why) => panic!("couldn't open {}: {}", display, why),
+            Ok(file) => file,
+        };
+
+        let mut post_string = String::new();
+        if let Err(why) = file.read_to_string(&mut post_string) {
+            panic!("couldn't read {}: {}", display, why);
+        }
+        Some(parse_post(file_name, &post_string))
+    } else {
+        None
+    }
+}
+
+pub fn find_post(posts: &PostIndex, url_name: &str, lang: &str) -> Option<Post> {
+    let posts = posts.read().expect("failed to lock the post index");
+    pick_translation(posts.iter().filter(|post| post.url_name == url_name), lang).cloned()
+}
+
+/// Every translation of a post as (language, post) pairs
+pub fn translations_of(posts: &PostIndex, url_name: &str) -> Vec<(String, String)> {
+    posts.read().expect("failed to lock the post index")
+        .iter()
+        .filter(|post| post.url_name == url_name)
+        .map(|post| (post_lang(post).to_string(), post.title.clone()))
+        .collect()
+}
+
+/// One entry per slug, each in the translation that best matches the reader's language, leaving out unlisted and
+/// private posts
+pub fn localized_listing(posts: &[Post], lang: &str) -> Vec<Post> {
+    let mut listing: Vec<Post> = vec![];
+    for post in posts.iter().filter(|post| post.listed()) {
+        if listing.iter().any(|listed| listed.url_name == post.url_name) {
+            continue;
+        }
+        if let Some(best) = pick_translation(posts.iter().filter(|other| other.listed() && other.url_name == post.url_name), lang) {
+            listing.push(best.clone());
+        }
+    }
+    listing
+}
+
+/// Reads every post in the posts directory, [`LOAD_CONCURRENCY`] files at a time on the blocking pool.
+/// Fails when any post can't be read or parsed.
+pub async fn read_posts(content: &Path) -> Result<Vec<Post>, String> {
+    let dir = content.join("posts");
+    let files = tokio::task::spawn_blocking(|| list_files_in_directory(dir)).await.map_err(|e| e.to_string())?;
+    let limit = Arc::new(tokio::sync::Semaphore::new(LOAD_CONCURRENCY));
+    let mut tasks = tokio::task::JoinSet::new();
+
+    for (index, file) in files.into_iter().enumerate() {
+        let limit = limit.clone();
+        let content = content.to_path_buf();
+        tasks.spawn(async move {
+            let _permit = limit.acquire_owned().await.expect("the load semaphore is never closed");
+            let post = tokio::task::spawn_blocking(move || get_from_file(&content, &file)).await;
+            (index, post)
+        });
+    }
+
+    let mut posts = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((index, Ok(post))) => posts.extend(post.map(|post| (index, post))),
+            Ok((_, Err(e))) | Err(e) => return Err(e.to_string()),
+        }
+    }
+    // Keep directory order so loading in parallel doesn't shuffle the index
+    posts.sort_by_key(|(index, _)| *index);
+    Ok(posts.into_iter().map(|(_, post)| post).collect())
+}
+
+/// The url name of one public post, picked by `roll` so each post is as likely as any other whatever its translations
+pub fn random_post(posts: &[Post], roll: u128) -> Option<&str> {
+    let mut names: Vec<&str> = Vec::new();
+    for post in posts.iter().filter(|post| post.listed()) {
+        if !names.contains(&post.url_name.as_str()) {
+            names.push(&post.url_name);
+        }
+    }
+    names.get((roll % names.len().max(1) as u128) as usize).copied()
+}
+
+/// Reads every post in the posts directory into memory for the server to start with. Fails when two files claim
+/// the same URL, the same check `validate` makes, rather than serving whichever loaded last.
+pub async fn load_posts(content: &Path) -> Result<Vec<Post>, String> {
+    let started = std::time::Instant::now();
+    let posts = read_posts(content).await?;
+
+    let conflicts = find_slug_conflicts(&posts);
+    if !conflicts.is_empty() {
+        return Err(format!("Slug conflicts in {}: {}", content.join("posts").display(), conflicts.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ")));
+    }
+    println!("Loaded {} posts in {}ms", posts.len(), started.elapsed().as_millis());
+    Ok(posts)
+}
+
+/// Reads the posts again for a running server. A URL claimed by more than one file stays with its oldest post and
+/// only the newcomers are left out, so a typo in a new slug can't take down a post that's already live.
+pub async fn reload_posts(content: &Path) -> Result<Vec<Post>, String> {
+    let mut posts = read_posts(content).await?;
+    let conflicts = find_slug_conflicts(&posts);
+    for conflict in &conflicts {
+        println!("Not serving {}: {}", conflict.dropped.join(", "), conflict);
+    }
+    posts.retain(|post| !conflicts.iter().any(|conflict| conflict.dropped.contains(&post.source_file)));
+    Ok(posts)
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    /// File names from the posts directory, including ones that try to climb out of it
+    #[test]
+    fn any_post_file_name_loads_or_is_skipped(name in "(\\.\\./|/)?\\PC{0,20}") {
+        if name.starts_with("../") || name.starts_with('/') {
+            proptest::prop_assert!(get_from_file(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR), &name).is_none());
+        } else {
+            get_from_file(std::path::Path::new(crate::config::DEFAULT_CONTENT_DIR), &name);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::excerpt;
+use crate::i18n::Locales;
+use crate::model::post::Post;
+use crate::store::posts::localized_listing;
+use crate::store::PostIndex;
+
+/// Related posts listed under a post
+pub const RELATED_LEN: usize = 3;
+
+/// Words shorter than this say little about what a post is about
+const MIN_WORD_CHARS: usize = 3;
+
+/// How much a word counts for by where it appears, from `CADEN_BLOG_RELATED_WEIGHTS` as
+/// `title=2,tags=3,body=1`. Tags count as words of their own, so posts sharing one lean together without it
+/// being the only way to be related.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights {
+    pub title: f64,
+    pub tags: f64,
+    pub body: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights { title: 2.0, tags: 3.0, body: 1.0 }
+    }
+}
+
+impl Weights {
+    pub fn from_env() -> Weights {
+        std::env::var("CADEN_BLOG_RELATED_WEIGHTS").map(|value| Weights::parse(&value)).unwrap_or_default()
+    }
+
+    /// Any of the weights, leaving the others at their defaults and skipping what doesn't parse
+    fn parse(value: &str) -> Weights {
+        let mut weights = Weights::default();
+        for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let parsed = part.split_once('=').and_then(|(name, weight)| Some((name.trim(), weight.trim().parse::<f64>().ok().filter(|weight| *weight >= 0.0)?)));
+            match parsed {
+                Some(("title", weight)) => weights.title = weight,
+                Some(("tags", weight)) => weights.tags = weight,
+                Some(("body", weight)) => weights.body = weight,
+                _ => println!("Ignoring related post weight {:?}, expected title=, tags= or body= and a number", part),
+            }
+        }
+        weights
+    }
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|word| word.chars().count() >= MIN_WORD_CHARS).map(str::to_lowercase)
+}
+
+/// How often each word appears in a post, weighted by where. Tags are kept whole behind a `#` so they don't
+/// mix with the same word in the text.
+fn term_counts(post: &Post, weights: Weights) -> HashMap<String, f64> {
+    let mut counts: HashMap<String, f64> = HashMap::new();
+    for word in words(&post.title) {
+        *counts.entry(word).or_default() += weights.title;
+    }
+    for tag in &post.tags {
+        *counts.entry(format!("#{}", tag.trim().to_lowercase())).or_default() += weights.tags;
+    }
+    for word in words(&excerpt::plain_text(&post.body)) {
+        *counts.entry(word).or_default() += weights.body;
+    }
+    counts.retain(|_, count| *count > 0.0);
+    counts
+}
+
+/// Each post's [`RELATED_LEN`] nearest neighbours by the cosine similarity of their TF-IDF vectors, worked out
+/// once for a listing
+pub struct RelatedIndex {
+    posts: Vec<Post>,
+    related: HashMap<String, Vec<usize>>,
+}
+
+impl RelatedIndex {
+    pub fn build(posts: Vec<Post>, weights: Weights) -> RelatedIndex {
+        let counts: Vec<HashMap<String, f64>> = posts.iter().map(|post| term_counts(post, weights)).collect();
+        let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+        for post_counts in &counts {
+            for word in post_counts.keys() {
+                *document_frequency.entry(word).or_default() += 1;
+            }
+        }
+
+        // A word every post has says nothing about any of them, and counts are dampened so a word repeated
+        // throughout one long post doesn't drown out the rest
+        let total = posts.len() as f64;
+        let vectors: Vec<HashMap<&str, f64>> = counts
+            .iter()
+            .map(|post_counts| {
+                let mut vector: HashMap<&str, f64> = post_counts
+                    .iter()
+                    .map(|(word, count)| (word.as_str(), (1.0 + count.ln().max(0.0)) * (total / document_frequency[word.as_str()] as f64).ln()))
+                    .collect();
+                let norm = vector.values().map(|weight| weight * weight).sum::<f64>().sqrt();
+                if norm > 0.0 {
+                    vector.values_mut().for_each(|weight| *weight /= norm);
+                }
+                vector
+            })
+            .collect();
+
+        let mut related = HashMap::new();
+        for (index, vector) in vectors.iter().enumerate() {
+            let mut scores: Vec<(usize, f64)> = vectors
+                .iter()
+                .enumerate()
+                .filter(|(other, _)| *other != index)
+                .map(|(other, other_vector)| (other, vector.iter().filter_map(|(word, weight)| other_vector.get(word).map(|other_weight| weight * other_weight)).sum::<f64>()))
+                .filter(|(_, score)| *score > 0.0)
+                .collect();
+            // Newer posts win ties
+            scores.sort_by(|(a, a_score), (b, b_score)| b_score.total_cmp(a_score).then(posts[*b].timestamp.cmp(&posts[*a].timestamp)));
+            related.insert(posts[index].url_name.clone(), scores.into_iter().take(RELATED_LEN).map(|(other, _)| other).collect());
+        }
+        RelatedIndex { posts, related }
+    }
+
+    /// The posts most like the one at `url_name`, most similar first
+    pub fn related(&self, url_name: &str) -> Vec<&Post> {
+        self.related.get(url_name).map(|related| related.iter().map(|&index| &self.posts[index]).collect()).unwrap_or_default()
+    }
+}
+
+/// A [`RelatedIndex`] per language, rebuilt whenever the posts change
+#[derive(Default)]
+pub struct Related {
+    weights: Weights,
+    by_lang: RwLock<HashMap<String, Arc<RelatedIndex>>>,
+}
+
+impl Related {
+    pub fn from_env() -> Related {
+        Related { weights: Weights::from_env(), by_lang: RwLock::default() }
+    }
+
+    pub fn clear(&self) {
+        self.by_lang.write().expect("failed to lock the related posts").clear();
+    }
+
+    /// The index for `lang`, built now if the posts changed since it was last asked for
+    pub fn get(&self, lang: &str, posts: &PostIndex) -> Arc<RelatedIndex> {
+        if let Some(index) = self.by_lang.read().expect("failed to lock the related posts").get(lang) {
+            return index.clone();
+        }
+        let index = Arc::new(RelatedIndex::build(localized_listing(&posts.read().expect("failed to lock the post index"), lang), self.weights));
+        self.by_lang.write().expect("failed to lock the related posts").insert(lang.to_string(), index.clone());
+        index
+    }
+
+    /// Builds the index for every language after the posts change, so no reader waits for it
+    pub fn rebuild(&self, posts: &PostIndex, locales: &Locales) {
+        self.clear();
+        for lang in locales.languages() {
+            self.get(lang, posts);
+        }
+    }
+}
+
+#[test]
+fn related_posts_share_words_even_without_tags() {
+    let post = |url_name: &str, title: &str, body: &str, tags: &[&str]| {
+        let mut post: Post = serde_json::from_value(serde_json::json!({
+            "title": title, "body": body, "image_url": "", "summary": "", "timestamp": "2024-01-01T00:00:00Z", "tags": tags
+        }))
+        .unwrap();
+        post.url_name = url_name.to_string();
+        post
+    };
+    let posts = vec![
+        post("borrowing", "Borrowing in Rust", "The borrow checker tracks references and lifetimes.", &["rust"]),
+        post("lifetimes", "Lifetimes explained", "Lifetimes tell the borrow checker how long references live.", &[]),
+        post("sourdough", "Sourdough starter", "Feed the starter flour and water every day.", &["baking"]),
+        post("bread", "Weekend bread", "A loaf from the starter, flour, water and salt.", &[]),
+        post("crab", "Ferris the crab", "Why the mascot is a crab.", &["rust"]),
+    ];
+    let index = RelatedIndex::build(posts.clone(), Weights::default());
+    let related = |url_name: &str| index.related(url_name).into_iter().map(|post| post.url_name.as_str()).collect::<Vec<_>>();
+
+    assert_eq!(related("lifetimes")[0], "borrowing");
+    assert_eq!(related("bread")[0], "sourdough");
+    assert!(related("borrowing").starts_with(&["lifetimes", "crab"]), "{:?}", related("borrowing"));
+    assert_eq!(related("missing"), Vec::<&str>::new());
+
+    // With the tags weighted up the shared tag wins over the shared words
+    let index = RelatedIndex::build(posts, Weights::parse("tags=20, nonsense, body=x"));
+    assert_eq!(index.related("borrowing")[0].url_name, "crab");
+    assert_eq!(Weights::parse("tags=20, nonsense, body=x"), Weights { tags: 20.0, ..Weights::default() });
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use serde::Serialize;
+
+use crate::model::post::Post;
+use crate::store::posts::localized_listing;
+use crate::store::PostIndex;
+
+/// Something to offer while a reader types into the search box
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub label: String,
+    pub url: String,
+    /// `post` for a post title, `tag` for a tag, which links to a search for it
+    pub kind: &'static str,
+}
+
+/// Post titles and tags by every word in them, sorted so the words starting with a prefix are next to each other
+pub struct PrefixIndex {
+    entries: Vec<Suggestion>,
+    words: Vec<(String, usize)>,
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|word| !word.is_empty()).map(str::to_lowercase)
+}
+
+impl PrefixIndex {
+    pub fn build(posts: &[Post]) -> PrefixIndex {
+        let mut entries: Vec<Suggestion> = Vec::new();
+        for post in posts {
+            entries.push(Suggestion { label: post.title.clone(), url: crate::share::post_url("", post), kind: "post" });
+            for tag in &post.tags {
+                if !entries.iter().any(|entry| entry.kind == "tag" && entry.label.eq_ignore_ascii_case(tag)) {
+                    let url = format!("{}?q={}", crate::search::SEARCH_PATH, crate::share::encode(tag));
+                    entries.push(Suggestion { label: tag.clone(), url, kind: "tag" });
+                }
+            }
+        }
+
+        let mut words: Vec<(String, usize)> = entries.iter().enumerate().flat_map(|(index, entry)| words(&entry.label).map(move |word| (word, index))).collect();
+        words.sort();
+        words.dedup();
+        PrefixIndex { entries, words }
+    }
+
+    /// Up to `limit` titles and tags with a word starting with the last word of the query and, for each earlier
+    /// word, a word it's the start of. Ones that start like the query come first, then posts before tags.
+    pub fn suggest(&self, query: &str, limit: usize) -> Vec<&Suggestion> {
+        let query: Vec<String> = words(query).collect();
+        let Some(last) = query.last() else { return Vec::new() };
+
+        let start = self.words.partition_point(|(word, _)| word.as_str() < last.as_str());
+        let mut found: Vec<usize> = self.words[start..].iter().take_while(|(word, _)| word.starts_with(last.as_str())).map(|(_, index)| *index).collect();
+        found.sort();
+        found.dedup();
+
+        let query_text = query.join(" ");
+        let mut suggestions: Vec<&Suggestion> = found
+            .into_iter()
+            .map(|index| &self.entries[index])
+            .filter(|entry| query[..query.len() - 1].iter().all(|term| words(&entry.label).any(|word| word.starts_with(term.as_str()))))
+            .collect();
+        suggestions.sort_by_key(|entry| (!words(&entry.label).collect::<Vec<_>>().join(" ").starts_with(&query_text), entry.kind != "post", entry.label.to_lowercase()));
+        suggestions.truncate(limit);
+        suggestions
+    }
+}
+
+/// A [`PrefixIndex`] per language, built on first use and dropped whenever the posts change
+#[derive(Default)]
+pub struct Suggestions {
+    by_lang: RwLock<HashMap<String, Arc<PrefixIndex>>>,
+}
+
+impl Suggestions {
+    pub fn clear(&self) {
+        self.by_lang.write().expect("failed to lock the suggestions").clear();
+    }
+
+    pub fn get(&self, lang: &str, posts: &PostIndex) -> Arc<PrefixIndex> {
+        if let Some(index) = self.by_lang.read().expect("failed to lock the suggestions").get(lang) {
+            return index.clone();
+        }
+        let index = Arc::new(PrefixIndex::build(&localized_listing(&posts.read().expect("failed to lock the post index"), lang)));
+        self.by_lang.write().expect("failed to lock the suggestions").insert(lang.to_string(), index.clone());
+        index
+    }
+}
+
+#[test]
+fn suggests_titles_and_tags_by_word_prefix() {
+    let post = |url_name: &str, title: &str, tags: &[&str]| {
+        let mut post: Post = serde_json::from_value(serde_json::json!({
+            "title": title, "body": "", "image_url": "", "summary": "", "timestamp": "2024-01-01T00:00:00Z", "tags": tags
+        }))
+        .unwrap();
+        post.url_name = url_name.to_string();
+        post
+    };
+    let index = PrefixIndex::build(&[
+        post("rust-tips", "Rust tips", &["Rust", "C++ & Rust"]),
+        post("trusty", "Why I trust Rustaceans", &["rust"]),
+        post("go", "Go notes", &[]),
+    ]);
+
+    let labels = |query: &str| index.suggest(query, 8).into_iter().map(|entry| entry.label.as_str()).collect::<Vec<_>>();
+    assert_eq!(labels("ru"), vec!["Rust tips", "Rust", "Why I trust Rustaceans", "C++ & Rust"]);
+    assert_eq!(labels("why ru"), vec!["Why I trust Rustaceans"]);
+    assert_eq!(labels("  "), Vec::<&str>::new());
+    assert_eq!(index.suggest("c", 1)[0].url, "/search?q=C%2B%2B%20%26%20Rust");
+    assert_eq!(index.suggest("go", 1)[0].url, "/post/go");
+}
@@ -0,0 +1,123 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::state::AppState;
+
+/// Seconds between pulls when `CADEN_BLOG_SYNC_INTERVAL` isn't set
+const DEFAULT_INTERVAL_SECS: u64 = 300;
+
+/// Shortest interval between pulls, so a zero or tiny setting can't stall the timer or hammer the remote
+const MIN_INTERVAL_SECS: u64 = 10;
+
+/// Where to pull content from, read from the `CADEN_BLOG_SYNC_*` environment variables
+#[derive(Debug, Clone)]
+pub struct SyncConfig {
+    /// The content directory the checkout lives in
+    pub dir: PathBuf,
+    pub remote: String,
+    pub branch: String,
+    pub interval: Duration,
+}
+
+impl SyncConfig {
+    /// Returns `None` when no remote is configured, which leaves syncing turned off
+    pub fn from_env(dir: &Path) -> Option<SyncConfig> {
+        let remote = std::env::var("CADEN_BLOG_SYNC_REMOTE").ok().filter(|remote| !remote.is_empty())?;
+        let branch = std::env::var("CADEN_BLOG_SYNC_BRANCH").unwrap_or_else(|_| "main".to_string());
+        let interval = interval(std::env::var("CADEN_BLOG_SYNC_INTERVAL").ok().as_deref());
+
+        Some(SyncConfig { dir: dir.to_path_buf(), remote, branch, interval })
+    }
+}
+
+/// The time between pulls from `CADEN_BLOG_SYNC_INTERVAL` seconds, raised to [`MIN_INTERVAL_SECS`] with a warning
+/// when it's shorter
+fn interval(secs: Option<&str>) -> Duration {
+    let secs = secs.and_then(|secs| secs.trim().parse().ok()).unwrap_or(DEFAULT_INTERVAL_SECS);
+    if secs < MIN_INTERVAL_SECS {
+        println!("Sync interval of {}s is too short, pulling every {}s instead", secs, MIN_INTERVAL_SECS);
+        return Duration::from_secs(MIN_INTERVAL_SECS);
+    }
+    Duration::from_secs(secs)
+}
+
+async fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("couldn't run git {}: {}", args.join(" "), e))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(format!("git {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).trim()))
+    }
+}
+
+/// Makes sure the content directory is a repository pointing at the configured remote
+async fn prepare(config: &SyncConfig) -> Result<(), String> {
+    let dir = &config.dir;
+    std::fs::create_dir_all(dir).map_err(|e| format!("couldn't create {}: {}", dir.display(), e))?;
+
+    if !dir.join(".git").exists() {
+        git(dir, &["init", "--quiet"]).await?;
+    }
+
+    if git(dir, &["remote", "get-url", "origin"]).await.is_ok() {
+        git(dir, &["remote", "set-url", "origin", &config.remote]).await?;
+    } else {
+        git(dir, &["remote", "add", "origin", &config.remote]).await?;
+    }
+    Ok(())
+}
+
+/// Fetches the configured branch and checks it out if it moved, returning whether anything changed
+async fn pull(config: &SyncConfig) -> Result<bool, String> {
+    git(&config.dir, &["fetch", "--quiet", "--depth", "1", "origin", &config.branch]).await?;
+
+    let fetched = git(&config.dir, &["rev-parse", "FETCH_HEAD"]).await?;
+    let current = git(&config.dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).await.unwrap_or_default();
+    if fetched == current {
+        return Ok(false);
+    }
+
+    git(&config.dir, &["reset", "--quiet", "--hard", &fetched]).await?;
+    Ok(true)
+}
+
+/// Periodically pulls the content remote, reloading the posts and notes and dropping cached pages and assets after every change
+pub async fn run(config: SyncConfig, state: AppState) {
+    if let Err(e) = prepare(&config).await {
+        println!("Content sync disabled: {}", e);
+        return;
+    }
+    println!("Syncing content from {} ({}) every {}s", config.remote, config.branch, config.interval.as_secs());
+
+    let mut interval = tokio::time::interval(config.interval);
+    loop {
+        interval.tick().await;
+
+        match pull(&config).await {
+            Ok(true) => match state.reload_index().await {
+                Ok((posts, notes)) => println!("Content updated, loaded {} posts and {} notes", posts, notes),
+                // A broken post or note keeps the previous content serving until the next push fixes it
+                Err(e) => println!("Content updated but failed to load: {}", e),
+            },
+            Ok(false) => {}
+            Err(e) => println!("Content sync failed: {}", e),
+        }
+    }
+}
+
+#[test]
+fn sync_intervals_never_drop_below_the_minimum() {
+    assert_eq!(interval(None), Duration::from_secs(DEFAULT_INTERVAL_SECS));
+    assert_eq!(interval(Some("60")), Duration::from_secs(60));
+    assert_eq!(interval(Some("0")), Duration::from_secs(MIN_INTERVAL_SECS));
+    assert_eq!(interval(Some("3")), Duration::from_secs(MIN_INTERVAL_SECS));
+    assert_eq!(interval(Some("soon")), Duration::from_secs(DEFAULT_INTERVAL_SECS));
+}
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+/// Counters grouped by key, like reactions per post or votes per poll, saved as json in the state directory after every change
+pub struct Tally {
+    path: PathBuf,
+    counts: RwLock<HashMap<String, HashMap<String, u64>>>,
+}
+
+impl Tally {
+    pub fn load(path: impl Into<PathBuf>) -> Tally {
+        let path = path.into();
+        let counts = match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+                println!("Couldn't parse {}, starting from zero: {}", path.display(), e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        Tally { path, counts: RwLock::new(counts) }
+    }
+
+    pub fn counts(&self, key: &str) -> HashMap<String, u64> {
+        self.counts.read().expect("failed to lock the tally").get(key).cloned().unwrap_or_default()
+    }
+
+    pub async fn add(&self, key: &str, item: &str) {
+        let json = {
+            let mut counts = self.counts.write().expect("failed to lock the tally");
+            *counts.entry(key.to_string()).or_default().entry(item.to_string()).or_default() += 1;
+            serde_json::to_string_pretty(&*counts).expect("counts serialize")
+        };
+
+        let path = self.path.as_path();
+        let saved = match path.parent() {
+            Some(dir) => tokio::fs::create_dir_all(dir).await,
+            None => Ok(()),
+        };
+        if let Err(e) = saved.and(tokio::fs::write(path, json).await) {
+            println!("Couldn't save {}: {}", path.display(), e);
+        }
+    }
+}
+
+#[tokio::test]
+async fn tally_counts_survive_a_reload() {
+    let path = std::env::temp_dir().join(format!("caden-blog-tally-{}", std::process::id())).join("counts.json");
+
+    let tally = Tally::load(&path);
+    tally.add("post", "like").await;
+    tally.add("post", "like").await;
+    tally.add("other", "clap").await;
+
+    let reloaded = Tally::load(&path);
+    assert_eq!(reloaded.counts("post").get("like"), Some(&2));
+    assert_eq!(reloaded.counts("other").get("clap"), Some(&1));
+    assert!(reloaded.counts("missing").is_empty());
+
+    std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
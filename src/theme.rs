@@ -0,0 +1,140 @@
+use std::path::Path;
+
+use axum::body::Body;
+use axum::extract::{self, State};
+use axum::http::{Response, StatusCode};
+use maud::{html, Markup};
+
+use crate::assets::AssetStore;
+use crate::defaults;
+use crate::fingerprint::{self, AssetManifest, Resolved};
+use crate::routes::assets::cache_asset;
+use crate::state::AppState;
+use crate::store::FileCache;
+
+pub const DEFAULT_THEME: &str = "github-dark";
+
+/// Where post pages link the code block stylesheet from
+pub const STYLESHEET_PATH: &str = "/theme/code.css";
+
+/// Where post pages load the code block copy button from
+pub const SCRIPT_PATH: &str = "/theme/code.js";
+
+/// Places code block labels and copy buttons, whatever the theme
+pub const CODE_CSS: &str = r#"
+    .code-title {
+        display: inline-block;
+        padding: 0.2em 0.8em;
+        border-radius: 6px 6px 0 0;
+        background-color: #343a40;
+        color: #f0f0f0;
+        font-family: monospace;
+        font-size: 0.85em;
+    }
+    .code-title + pre {
+        margin-top: 0;
+    }
+    pre.code-block {
+        position: relative;
+    }
+    .code-copy {
+        position: absolute;
+        top: 0.4em;
+        right: 0.4em;
+        padding: 0.1em 0.6em;
+        border: 1px solid #555;
+        border-radius: 4px;
+        background-color: #343a40;
+        color: #f0f0f0;
+        font-size: 0.8em;
+    }
+"#;
+
+/// Code block theme name, set with `CADEN_BLOG_CODE_THEME`
+pub fn code_theme_from_env() -> String {
+    std::env::var("CADEN_BLOG_CODE_THEME")
+        .ok()
+        .filter(|name| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or_else(|| DEFAULT_THEME.to_string())
+}
+
+/// A code block theme compiled in under `src/defaults/assets/themes/`; `themes/<name>.css` in the asset store adds
+/// more or overrides these
+fn builtin(name: &str) -> Option<Vec<u8>> {
+    defaults::embedded(&format!("assets/themes/{}.css", name))
+}
+
+/// The css of the theme called `name`, from the asset store first and then the builtin themes, cached with the
+/// assets and noted in the manifest
+pub async fn load(name: &str, store: &dyn AssetStore, cache: &FileCache, manifest: &AssetManifest) -> Vec<u8> {
+    if let Some(css) = cache.lock().expect("cdn failed to lock the cache").get(STYLESHEET_PATH) {
+        return css.clone();
+    }
+
+    let stored = match store.stream(&format!("themes/{}.css", name), None).await {
+        // A stylesheet has no metadata to strip
+        Ok(asset) => cache_asset(STYLESHEET_PATH.to_string(), asset, cache, false).await,
+        Err(_) => None,
+    };
+    let css = stored.unwrap_or_else(|| {
+        let css = builtin(name).unwrap_or_else(|| {
+            println!("Unknown code theme {}, falling back to {}", name, DEFAULT_THEME);
+            builtin(DEFAULT_THEME).expect("the default theme is builtin")
+        });
+        cache.lock().expect("cdn failed to lock the cache").insert(STYLESHEET_PATH.to_string(), css.clone());
+        css
+    });
+    manifest.record(STYLESHEET_PATH, &css);
+    css
+}
+
+/// Adds copy buttons to the page's code blocks, labelled in the reader's language
+pub fn code_script(manifest: &AssetManifest, copy: &str, copied: &str) -> Markup {
+    html! {
+        script src=(manifest.url(SCRIPT_PATH)) defer data-copy=(copy) data-copied=(copied) {}
+    }
+}
+
+/// Reads the copy button script from `templates/code.js` in the `content` directory, or the compiled in copy, into the
+/// file cache and notes it in the manifest
+pub async fn load_script(content: &Path, cache: &FileCache, manifest: &AssetManifest) -> Vec<u8> {
+    if let Some(script) = cache.lock().expect("cdn failed to lock the cache").get(SCRIPT_PATH) {
+        return script.clone();
+    }
+
+    let script = defaults::read_async(content, "templates/code.js").await.expect("the copy button script is compiled in");
+    cache.lock().expect("cdn failed to lock the cache").insert(SCRIPT_PATH.to_string(), script.clone());
+    manifest.record(SCRIPT_PATH, &script);
+    script
+}
+
+/// `GET /theme/:file`: the code block stylesheet or copy button script, by the fingerprinted name pages link or the
+/// plain one
+pub async fn serve_theme_file(State(AppState { config, store, cache, manifest, .. }): State<AppState>, extract::Path(file): extract::Path<String>) -> Result<Response<Body>, StatusCode> {
+    let path = format!("/theme/{}", file);
+    let (path, cache_control) = match manifest.resolve(&path) {
+        Resolved::Plain => (path, fingerprint::UNVERSIONED),
+        Resolved::Current(plain) => (plain, fingerprint::IMMUTABLE),
+        Resolved::Stale(current) => return Ok(fingerprint::redirect(&current)),
+    };
+    let (content_type, contents) = match path.as_str() {
+        STYLESHEET_PATH => ("text/css; charset=utf-8", load(&config.code_theme, store.as_ref(), &cache, &manifest).await),
+        SCRIPT_PATH => ("application/javascript", load_script(&config.content_dir, &cache, &manifest).await),
+        _ => return Err(StatusCode::NOT_FOUND),
+    };
+
+    Ok(Response::builder()
+        .header("Content-Type", content_type)
+        .header("Cache-Control", cache_control)
+        .body(Body::from(contents))
+        .unwrap())
+}
+
+#[test]
+fn builtin_themes_style_code_blocks() {
+    for name in ["github-dark", "github-light"] {
+        let css = String::from_utf8(builtin(name).unwrap()).unwrap();
+        assert!(css.contains("--color-prettylights-syntax-keyword"), "{} is missing syntax colors", name);
+    }
+    assert!(builtin("missing").is_none());
+}
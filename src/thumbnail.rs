@@ -0,0 +1,101 @@
+use std::io::Cursor;
+
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, Response, StatusCode};
+use axum::response::{IntoResponse, Redirect};
+use image::{DynamicImage, ImageFormat};
+
+use crate::state::AppState;
+
+/// Where resized copies of the assets are served, as `/thumb/<width>/<asset>`
+pub const THUMB_PATH: &str = "/thumb/:width/*filename";
+
+/// The widths thumbnails come in. Only these are made, so nobody can fill the cache with every width there is.
+pub const THUMB_WIDTHS: [u32; 3] = [240, 480, 960];
+
+/// Biggest asset that gets decoded for a thumbnail, anything larger is sent as it is
+const MAX_SOURCE_SIZE: u64 = 32 * 1024 * 1024;
+
+/// The URL of an asset's thumbnail at `width`, one of [`THUMB_WIDTHS`]
+pub fn url(filename: &str, width: u32) -> String {
+    format!("/thumb/{}/{}", width, filename)
+}
+
+/// The image scaled down to `width` as a JPEG, or a PNG when it has transparency to keep. Images already that
+/// narrow keep their size. `None` when it isn't an image we can read.
+pub fn resize(source: &[u8], width: u32) -> Option<(Vec<u8>, &'static str)> {
+    let image = image::load_from_memory(source).ok()?;
+    let image = if image.width() > width { image.thumbnail(width, u32::MAX) } else { image };
+    let mut encoded = Cursor::new(Vec::new());
+    if image.color().has_alpha() {
+        image.write_to(&mut encoded, ImageFormat::Png).ok()?;
+        Some((encoded.into_inner(), "image/png"))
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8()).write_to(&mut encoded, ImageFormat::Jpeg).ok()?;
+        Some((encoded.into_inner(), "image/jpeg"))
+    }
+}
+
+/// Serves `/thumb/:width/*filename`, resizing the asset on first request and caching it with the assets. Assets
+/// that aren't images we can read are sent to their full-size URL instead.
+pub async fn serve_thumbnail(State(AppState { cache, store, hotlinks, .. }): State<AppState>, Path((width, filename)): Path<(u32, String)>, headers: HeaderMap) -> Result<Response<Body>, StatusCode> {
+    if !THUMB_WIDTHS.contains(&width) || !crate::assets::valid_name(&filename) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if let Some(refusal) = hotlinks.check(&headers) {
+        return Ok(hotlinks.refuse(refusal, &headers));
+    }
+    let key = url(&filename, width);
+    let original = || Ok(Redirect::temporary(&format!("/asset/{}", filename)).into_response());
+
+    let cached = cache.lock().expect("cdn failed to lock the cache").get(&key).cloned();
+    let (thumbnail, content_type) = match cached {
+        Some(thumbnail) => {
+            let content_type = if thumbnail.starts_with(b"\x89PNG") { "image/png" } else { "image/jpeg" };
+            (thumbnail, content_type)
+        }
+        None => {
+            let asset = store.stream(&filename, None).await.map_err(|_| StatusCode::NOT_FOUND)?;
+            if asset.len.is_some_and(|len| len > MAX_SOURCE_SIZE) {
+                return original();
+            }
+            let Ok(source) = axum::body::to_bytes(asset.body, MAX_SOURCE_SIZE as usize).await else { return original() };
+            let Some((thumbnail, content_type)) = tokio::task::spawn_blocking(move || resize(&source, width)).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? else {
+                return original();
+            };
+            cache.lock().expect("cdn failed to lock the cache").insert(key, thumbnail.clone());
+            (thumbnail, content_type)
+        }
+    };
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, content_type)
+        .header(header::CACHE_CONTROL, crate::fingerprint::UNVERSIONED)
+        .body(Body::from(thumbnail))
+        .unwrap();
+    if hotlinks.enabled() {
+        crate::hotlink::vary(&mut response);
+    }
+    Ok(response)
+}
+
+#[test]
+fn thumbnails_shrink_to_the_width_and_keep_transparency() {
+    let encode = |image: DynamicImage| {
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    };
+
+    let (jpeg, content_type) = resize(&encode(DynamicImage::new_rgb8(1200, 600)), 480).unwrap();
+    assert_eq!(content_type, "image/jpeg");
+    let thumbnail = image::load_from_memory(&jpeg).unwrap();
+    assert_eq!((thumbnail.width(), thumbnail.height()), (480, 240));
+
+    let (png, content_type) = resize(&encode(DynamicImage::new_rgba8(100, 50)), 480).unwrap();
+    assert_eq!(content_type, "image/png");
+    assert_eq!(image::load_from_memory(&png).unwrap().width(), 100);
+
+    assert!(resize(b"not an image", 480).is_none());
+}
@@ -0,0 +1,122 @@
+use axum::body::Body;
+use axum::extract::Path;
+use axum::http::{Response, StatusCode};
+use maud::{html, Markup};
+
+use crate::fingerprint;
+
+/// A third-party client library pages load
+pub struct Library {
+    /// The name it's served under, in `/assets/vendor/`
+    pub name: &'static str,
+    /// Where it's loaded from when it isn't embedded
+    pub upstream: &'static str,
+    /// The hash pinned in `build.rs`, which both the embedded copy and the upstream file have
+    pub integrity: Option<&'static str>,
+    /// The copy compiled into the binary, when the build could fetch and check it
+    pub embedded: Option<Embedded>,
+}
+
+pub struct Embedded {
+    pub bytes: &'static [u8],
+    /// The start of the content hash, put in the file name pages link to
+    pub version: &'static str,
+}
+
+include!(concat!(env!("OUT_DIR"), "/vendored.rs"));
+
+/// The library a requested file name is for, and the version in the name if it has one
+fn parse_name(name: &str) -> Option<(&'static Library, Option<&str>)> {
+    if let Some(library) = LIBRARIES.iter().find(|library| library.name == name) {
+        return Some((library, None));
+    }
+    let (plain, version) = fingerprint::parse(name)?;
+    let library = LIBRARIES.iter().find(|library| library.name == plain)?;
+    Some((library, Some(version)))
+}
+
+fn library(name: &str) -> &'static Library {
+    LIBRARIES.iter().find(|library| library.name == name).expect("only known libraries are linked")
+}
+
+/// Where pages load a library from: the embedded copy by its fingerprinted name, otherwise the upstream CDN. Both
+/// are checked against the pinned hash.
+pub(crate) fn source(name: &str) -> (String, Option<&'static str>) {
+    let library = library(name);
+    match &library.embedded {
+        Some(embedded) => (format!("/assets/vendor/{}", fingerprint::name(name, embedded.version)), library.integrity),
+        None => (library.upstream.to_string(), library.integrity),
+    }
+}
+
+pub fn stylesheet(name: &str) -> Markup {
+    let (href, integrity) = source(name);
+    html! {
+        link rel="stylesheet" href=(href) integrity=[integrity] crossorigin=[integrity.map(|_| "anonymous")];
+    }
+}
+
+pub fn script(name: &str) -> Markup {
+    let (src, integrity) = source(name);
+    html! {
+        script src=(src) integrity=[integrity] crossorigin=[integrity.map(|_| "anonymous")] {}
+    }
+}
+
+/// Local URLs of the embedded libraries, for the service worker to precache
+pub fn local_urls() -> Vec<String> {
+    LIBRARIES.iter().filter(|library| library.embedded.is_some()).map(|library| source(library.name).0).collect()
+}
+
+/// Logs which libraries are still loaded from a CDN, so a binary built without them is noticed
+pub fn report() {
+    let missing: Vec<&str> = LIBRARIES.iter().filter(|library| library.embedded.is_none()).map(|library| library.name).collect();
+    if !missing.is_empty() {
+        println!("Loading {} from upstream CDNs since the build didn't embed them", missing.join(", "));
+    }
+}
+
+/// `GET /assets/vendor/:name`: a library by its fingerprinted name, which never changes, or by its plain name, which
+/// browsers check again after an hour. A stale fingerprint from a cached page redirects to the current copy.
+pub async fn serve_vendor(Path(name): Path<String>) -> Result<Response<Body>, StatusCode> {
+    let (library, version) = parse_name(&name).ok_or(StatusCode::NOT_FOUND)?;
+    let file = library.embedded.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let Some(cache_control) = fingerprint::cache_control(version, file.version) else {
+        return Ok(fingerprint::redirect(&source(library.name).0));
+    };
+    let content_type = if library.name.ends_with(".css") { "text/css; charset=utf-8" } else { "text/javascript; charset=utf-8" };
+
+    Ok(Response::builder()
+        .header("Content-Type", content_type)
+        .header("Cache-Control", cache_control)
+        .body(Body::from(file.bytes))
+        .unwrap())
+}
+
+#[test]
+fn libraries_link_with_their_pinned_hash_wherever_they_load_from() {
+    let names: std::collections::HashSet<&str> = LIBRARIES.iter().map(|library| library.name).collect();
+    assert_eq!(names.len(), LIBRARIES.len());
+
+    let bootstrap = library("bootstrap.min.css");
+    let link = stylesheet("bootstrap.min.css").into_string();
+    assert!(link.contains(&format!(r#"integrity="{}" crossorigin="anonymous""#, bootstrap.integrity.unwrap())), "{}", link);
+    match &bootstrap.embedded {
+        Some(embedded) => assert!(link.contains(&format!(r#"href="/assets/vendor/bootstrap.min.{}.css""#, embedded.version))),
+        None => assert!(link.contains(bootstrap.upstream)),
+    }
+    // Only pinned libraries are ever embedded
+    assert!(LIBRARIES.iter().all(|library| library.embedded.is_none() || library.integrity.is_some()));
+}
+
+#[test]
+fn fingerprinted_names_find_their_library() {
+    let parsed = |name| parse_name(name).map(|(library, version)| (library.name, version));
+    assert_eq!(parsed("htmx.min.ab12cd34ef56.js"), Some(("htmx.min.js", Some("ab12cd34ef56"))));
+    assert_eq!(parsed("bootstrap.bundle.min.000000000f0f.js"), Some(("bootstrap.bundle.min.js", Some("000000000f0f"))));
+    assert_eq!(parsed("htmx.min.js"), Some(("htmx.min.js", None)));
+    // Not hex, or not a library
+    assert_eq!(parsed("htmx.min.latest.js"), None);
+    assert_eq!(parsed("evil.ab12cd34ef56.js"), None);
+    assert_eq!(parsed("htmx.min.ab12cd34ef56.css"), None);
+}
@@ -0,0 +1,68 @@
+use std::path::Path;
+use std::time::Instant;
+
+use crate::config::Config;
+use crate::fingerprint::AssetManifest;
+use crate::i18n::Locales;
+use crate::prefs::ClientHints;
+use crate::tally::Tally;
+use crate::render::home::render_home;
+use crate::render::listing::Cards;
+use crate::routes::assets::{cache_asset, load_favicon};
+use crate::routes::home::home_cache_key;
+use crate::store::posts::{list_files_in_directory, localized_listing};
+use crate::state::AppState;
+use crate::store::{PageCache, PostIndex};
+
+/// Assets to preload, from the comma separated `CADEN_BLOG_PRELOAD_ASSETS`, defaulting to everything in the assets directory
+fn preload_list(content: &Path) -> Vec<String> {
+    match std::env::var("CADEN_BLOG_PRELOAD_ASSETS") {
+        Ok(list) => list.split(',').map(str::trim).filter(|name| !name.is_empty()).map(String::from).collect(),
+        Err(_) => list_files_in_directory(content.join("assets")),
+    }
+}
+
+/// Pre-renders the cached pages in every language from the current post index, in the configured layout and
+/// background speed and for readers not saving data, since readers who picked otherwise are few
+pub fn warm_pages(posts: &PostIndex, pages: &PageCache, locales: &Locales, reactions: &Tally, manifest: &AssetManifest, config: &Config) {
+    let posts = posts.read().expect("failed to lock the post index");
+    let layout = config.layout;
+    for lang in locales.languages() {
+        let (hints, speed) = (ClientHints::default(), config.background_speed);
+        let cards = Cards { text: locales.text(lang), reactions, manifest, layout, hints, config };
+        let home = render_home(&localized_listing(&posts, lang), cards, speed);
+        pages.write().expect("failed to lock the page cache").insert(home_cache_key(lang, layout, hints, speed), home);
+    }
+}
+
+/// Fills the page and asset caches so the first visitors after a deploy don't pay for the cold path
+pub async fn warm_caches(AppState { posts, pages, locales, reactions, cache, store, config, manifest, .. }: &AppState) {
+    let started = Instant::now();
+    warm_pages(posts, pages, locales, reactions, manifest, config);
+
+    if let Err(status) = load_favicon(&config.content_dir, cache).await {
+        println!("Couldn't preload the favicon: {}", status);
+    }
+
+    let mut preloaded = 0;
+    if store.cache_in_memory() {
+        for name in preload_list(&config.content_dir) {
+            match store.stream(&name, None).await {
+                Ok(asset) if asset.len.is_some_and(|len| len <= config.max_cached_size) => {
+                    if cache_asset(name, asset, cache, config.strip_metadata).await.is_some() {
+                        preloaded += 1;
+                    }
+                }
+                Ok(_) => {}
+                Err(_) => println!("Couldn't preload asset {}", name),
+            }
+        }
+    }
+
+    println!(
+        "Warmed caches with {} posts and {} assets in {}ms",
+        posts.read().expect("failed to lock the post index").len(),
+        preloaded,
+        started.elapsed().as_millis(),
+    );
+}
@@ -0,0 +1,18 @@
+//! The app embedded through the library, serving the repository's own `caden-blog` directory without a listener
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use tower::util::ServiceExt;
+
+use caden_blog::{build_app, AppState, Config};
+
+#[tokio::test]
+async fn embedded_app_serves_the_site() {
+    let state = AppState::load(Config { addr: String::new(), max_cached_size: 1024, ..Default::default() }).await.unwrap();
+    let app = build_app(&state);
+
+    for (uri, status) in [("/", StatusCode::OK), ("/site.webmanifest", StatusCode::OK), ("/post/does-not-exist", StatusCode::NOT_FOUND)] {
+        let response = app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), status, "{}", uri);
+    }
+}
@@ -0,0 +1,71 @@
+//! Load test against the real binary, left out of the normal test run:
+//! `cargo test --release --test load -- --ignored --nocapture`
+
+use std::time::{Duration, Instant};
+
+mod support;
+
+/// Clients hammering the server at once
+const CONCURRENCY: usize = 32;
+const DURATION: Duration = Duration::from_secs(10);
+const PATHS: [&str; 4] = ["/", "/post/test", "/post/test/plain", "/fragment/card/test"];
+
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    sorted[(sorted.len() * percent / 100).min(sorted.len() - 1)]
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "takes a while, run it explicitly with --ignored"]
+async fn sustained_load() {
+    let server = support::Server::start();
+    let client = reqwest::Client::new();
+    let deadline = Instant::now() + DURATION;
+
+    let workers: Vec<_> = (0..CONCURRENCY)
+        .map(|worker| {
+            let client = client.clone();
+            let urls: Vec<String> = PATHS.iter().map(|path| server.url(path)).collect();
+            tokio::spawn(async move {
+                let mut latencies = Vec::new();
+                let mut errors = 0;
+                let mut next = worker;
+                while Instant::now() < deadline {
+                    let started = Instant::now();
+                    let served = match client.get(&urls[next % urls.len()]).send().await {
+                        Ok(response) if response.status().is_success() => response.bytes().await.is_ok(),
+                        _ => false,
+                    };
+                    if served {
+                        latencies.push(started.elapsed());
+                    } else {
+                        errors += 1;
+                    }
+                    next += 1;
+                }
+                (latencies, errors)
+            })
+        })
+        .collect();
+
+    let mut latencies = Vec::new();
+    let mut errors = 0;
+    for worker in workers {
+        let (worker_latencies, worker_errors) = worker.await.unwrap();
+        latencies.extend(worker_latencies);
+        errors += worker_errors;
+    }
+    latencies.sort();
+
+    println!(
+        "{} requests from {} clients in {}s: {:.0} req/s, p50 {:?}, p99 {:?}, max {:?}, {} errors",
+        latencies.len(),
+        CONCURRENCY,
+        DURATION.as_secs(),
+        latencies.len() as f64 / DURATION.as_secs_f64(),
+        percentile(&latencies, 50),
+        percentile(&latencies, 99),
+        latencies.last().unwrap(),
+        errors,
+    );
+    assert_eq!(errors, 0);
+}
@@ -0,0 +1,40 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// The blog binary running against the repository's own `caden-blog` directory on a free local port,
+/// killed when dropped
+pub struct Server {
+    child: Child,
+    pub addr: SocketAddr,
+}
+
+impl Server {
+    pub fn start() -> Server {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let child = Command::new(env!("CARGO_BIN_EXE_caden-blog"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .env("CADEN_BLOG_ADDR", addr.to_string())
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("failed to start caden-blog");
+
+        let started = Instant::now();
+        while TcpStream::connect(addr).is_err() {
+            assert!(started.elapsed() < Duration::from_secs(10), "caden-blog didn't start listening on {}", addr);
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Server { child, addr }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}